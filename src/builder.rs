@@ -0,0 +1,1414 @@
+use std::borrow::Cow;
+
+use ahash::{HashMap, HashMapExt};
+use indextree::NodeId;
+use xmlparser::{ElementEnd, ExternalId, StrSpan, Token, Tokenizer};
+
+use crate::declaration::{XmlDeclaration, XmlVersion};
+use crate::dtd::parser::{internal_subset_end, DtdParser};
+use crate::dtd::{AttributeType, Dtd};
+use crate::entity::{
+    entity_references, is_restricted_char, normalize_tokenized_attribute, parse_attribute,
+    parse_attribute_leniently, parse_text, parse_text_leniently, references, Entities,
+};
+use crate::error::ParseError;
+use crate::id::{Name, NameId, PrefixId};
+use crate::output::xml::DocType;
+use crate::parseoptions::{exceeds, ParseLimits, ParseOptions};
+use crate::span::{Span, SpanInfo, SpanInfoKey, StartTagSpans};
+use crate::spanmapping::SpanMapping;
+use crate::unpretty::insignificant_whitespace_children;
+use crate::xmlvalue::{Attribute, Comment, Element, Namespace, ProcessingInstruction, Text, Value};
+use crate::xotdata::{Node, Xot};
+use crate::NamespaceId;
+
+type Namespaces = Vec<(PrefixId, NamespaceId)>;
+pub(crate) type NamespaceSpans = Vec<(PrefixId, Span, Span)>;
+
+struct AttributeBuilder {
+    prefix: String,
+    name: String,
+    value: String,
+    name_span: Span,
+    value_span: Span,
+    prefix_span: Span,
+    // false if the attribute was defaulted from the DTD
+    specified: bool,
+    is_id: bool,
+}
+
+struct ElementBuilder {
+    prefix: String,
+    name: String,
+    namespaces: Namespaces,
+    // the spans of the namespace declarations in the start tag
+    namespace_spans: NamespaceSpans,
+    attributes: Vec<AttributeBuilder>,
+    prefix_span: Span,
+    span: Span,
+}
+
+impl ElementBuilder {
+    fn new(prefix: StrSpan<'_>, name: StrSpan<'_>) -> Self {
+        ElementBuilder {
+            prefix: prefix.to_string(),
+            name: name.to_string(),
+            namespaces: Namespaces::new(),
+            namespace_spans: NamespaceSpans::new(),
+            attributes: Vec::new(),
+            prefix_span: prefix.into(),
+            span: Span::from_prefix_name(prefix, name),
+        }
+    }
+
+    // Apply the attribute list declarations for this element: normalize the
+    // values of attributes of tokenized types, and add the attributes with a
+    // default value that weren't specified.
+    // https://www.w3.org/TR/xml/#attdecls
+    fn apply_attribute_declarations(&mut self, dtd: &Dtd, xot: &mut Xot) {
+        let declarations = if self.prefix.is_empty() {
+            dtd.attribute_declarations(&self.name)
+        } else {
+            dtd.attribute_declarations(&format!("{}:{}", self.prefix, self.name))
+        };
+        for declaration in declarations {
+            let (prefix, name) = declaration
+                .name()
+                .split_once(':')
+                .unwrap_or(("", declaration.name()));
+            // a defaulted namespace declaration declares a namespace
+            let namespace_prefix = match (prefix, name) {
+                ("", "xmlns") => Some(""),
+                ("xmlns", _) => Some(name),
+                _ => None,
+            };
+            if let Some(namespace_prefix) = namespace_prefix {
+                let prefix_id = xot.prefix_lookup.get_id_mut(namespace_prefix);
+                let is_declared = self.namespaces.iter().any(|(p, _)| *p == prefix_id);
+                if let (false, Some(value)) = (is_declared, declaration.default().value()) {
+                    let namespace_id = xot.namespace_lookup.get_id_mut(value);
+                    self.namespaces.push((prefix_id, namespace_id));
+                }
+                continue;
+            }
+            let is_id = matches!(declaration.attribute_type(), AttributeType::Id);
+            let attribute = self
+                .attributes
+                .iter_mut()
+                .find(|attribute| attribute.prefix == prefix && attribute.name == name);
+            if let Some(attribute) = attribute {
+                if declaration.attribute_type().is_tokenized() {
+                    attribute.value = normalize_tokenized_attribute(&attribute.value);
+                }
+                attribute.is_id |= is_id;
+            } else if let Some(value) = declaration.default().value() {
+                // defaulted attributes have no place in the source, so we
+                // use the span of the element
+                self.attributes.push(AttributeBuilder {
+                    prefix: prefix.to_string(),
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    name_span: self.span,
+                    value_span: self.span,
+                    prefix_span: self.span,
+                    specified: false,
+                    is_id,
+                });
+            }
+        }
+    }
+}
+
+// What the builder built, in order. We only keep track of this when parsing
+// in a streaming fashion.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Built {
+    // an element was opened
+    Opened(NodeId),
+    // an element was closed
+    Closed(NodeId),
+    // a text, comment or processing instruction node was added. Text may be
+    // consolidated with the previous text node, so this may be a node that
+    // was added before.
+    Added(NodeId),
+}
+
+pub(crate) struct DocumentBuilder {
+    pub(crate) tree: NodeId,
+    pub(crate) current_node_id: NodeId,
+    name_id_builder: NameIdBuilder,
+    element_builder: Option<ElementBuilder>,
+    pub(crate) declaration: Option<XmlDeclaration>,
+    pub(crate) dtd: Option<Dtd>,
+    // the span of the system id of the external subset, if any
+    system_id_span: Option<Span>,
+    internal_subset_start: usize,
+    // the internal subset and its position, if we found it before parsing
+    pub(crate) internal_subset: Option<(usize, String)>,
+    entities: Entities,
+    // the elements that were open when we started expanding an entity. The
+    // replacement text of an entity may not close these.
+    entity_boundaries: Vec<NodeId>,
+    // elements by the value of their ID attributes, unless elements don't
+    // stay around
+    pub(crate) ids: Option<HashMap<String, (Node, NameId)>>,
+    pub(crate) built: Option<Vec<Built>>,
+    // whether we keep CDATA sections as CDATA text
+    pub(crate) preserve_cdata: bool,
+    normalize_xml_id: bool,
+    pub(crate) remove_insignificant_whitespace: bool,
+    limits: ParseLimits,
+    // the size of the text and the number of nodes so far, to enforce the
+    // limits
+    text_size: usize,
+    nodes: usize,
+    // the errors we recovered from, if we parse leniently
+    pub(crate) errors: Option<Vec<ParseError>>,
+    // how the spans of the token we're parsing relate to the source, so we
+    // can record the errors we recover from with the right spans
+    mapping: SpanMapping,
+    // whether we tokenize what comes after the prolog as a fragment, and
+    // whether we've seen the document element there
+    content_as_fragment: bool,
+    has_document_element: bool,
+}
+
+impl DocumentBuilder {
+    pub(crate) fn new(xot: &mut Xot, options: &ParseOptions) -> Self {
+        let document = xot.arena.new_node(Value::Document);
+        let mut name_id_builder = NameIdBuilder::new(xot.base_prefixes().into_iter().collect());
+        let base_prefixes = vec![(xot.empty_prefix_id, xot.no_namespace_id)];
+        name_id_builder.push(base_prefixes);
+        let mut entities = Entities::new(xot.resolver.clone());
+        entities.expansion = options.entity_expansion;
+        entities.limits = options.limits;
+        DocumentBuilder {
+            tree: document,
+            current_node_id: document,
+            name_id_builder,
+            element_builder: None,
+            declaration: None,
+            dtd: None,
+            system_id_span: None,
+            internal_subset_start: 0,
+            internal_subset: None,
+            entities,
+            entity_boundaries: Vec::new(),
+            ids: Some(HashMap::new()),
+            built: None,
+            preserve_cdata: options.preserve_cdata,
+            normalize_xml_id: options.normalize_xml_id,
+            remove_insignificant_whitespace: options.remove_insignificant_whitespace,
+            limits: options.limits,
+            text_size: 0,
+            nodes: 0,
+            errors: None,
+            mapping: SpanMapping::Source,
+            content_as_fragment: false,
+            has_document_element: false,
+        }
+    }
+
+    fn check_name_length(&self, span: Span) -> Result<(), ParseError> {
+        if exceeds(self.limits.max_name_length, span.end - span.start) {
+            return Err(ParseError::NameLengthLimit(span));
+        }
+        Ok(())
+    }
+
+    fn check_attribute_count(&self, name_span: Span) -> Result<(), ParseError> {
+        let element_builder = self.element_builder.as_ref().unwrap();
+        let count = element_builder.namespaces.len() + element_builder.attributes.len() + 1;
+        if exceeds(self.limits.max_attributes, count) {
+            return Err(ParseError::AttributeLimit(name_span));
+        }
+        Ok(())
+    }
+
+    fn add_text_size(&mut self, size: usize, span: Span) -> Result<(), ParseError> {
+        self.text_size += size;
+        if exceeds(self.limits.max_text_size, self.text_size) {
+            return Err(ParseError::TextSizeLimit(span));
+        }
+        Ok(())
+    }
+
+    fn add_nodes(&mut self, count: usize, span: Span) -> Result<(), ParseError> {
+        self.nodes += count;
+        if exceeds(self.limits.max_nodes, self.nodes) {
+            return Err(ParseError::NodeLimit(span));
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, built: Built) {
+        if let Some(log) = &mut self.built {
+            log.push(built);
+        }
+    }
+
+    // If we parse leniently, record an error we recover from. Otherwise we
+    // can't recover and return the error.
+    fn recover(&mut self, error: ParseError) -> Result<(), ParseError> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(self.mapping.map_error(error));
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    // Like `recover`, for an error that's located in the source already.
+    fn recover_mapped(&mut self, error: ParseError) -> Result<(), ParseError> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    // When we parse leniently, a name with an unknown prefix is taken to be
+    // in no namespace.
+    fn element_name_id(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        prefix_span: Span,
+        xot: &mut Xot,
+    ) -> Result<NameId, ParseError> {
+        match self
+            .name_id_builder
+            .element_name_id(prefix, name, prefix_span, xot)
+        {
+            Err(error @ ParseError::UnknownPrefix(..)) => {
+                self.recover(error)?;
+                Ok(xot.add_name(name))
+            }
+            result => result,
+        }
+    }
+
+    fn attribute_name_id(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        prefix_span: Span,
+        xot: &mut Xot,
+    ) -> Result<NameId, ParseError> {
+        match self
+            .name_id_builder
+            .attribute_name_id(prefix, name, prefix_span, xot)
+        {
+            Err(error @ ParseError::UnknownPrefix(..)) => {
+                self.recover(error)?;
+                Ok(xot.add_name(name))
+            }
+            result => result,
+        }
+    }
+
+    fn element(&mut self, prefix: StrSpan<'_>, name: StrSpan<'_>) -> Result<(), ParseError> {
+        let span = Span::from_prefix_name(prefix, name);
+        if exceeds(self.limits.max_depth, self.name_id_builder.depth() + 1) {
+            return Err(ParseError::DepthLimit(span));
+        }
+        self.check_name_length(span)?;
+        self.element_builder = Some(ElementBuilder::new(prefix, name));
+        Ok(())
+    }
+
+    fn prefix(
+        &mut self,
+        prefix: &str,
+        namespace_uri: StrSpan<'_>,
+        name_span: Span,
+        xot: &mut Xot,
+    ) -> Result<(), ParseError> {
+        self.check_attribute_count(name_span)?;
+        self.check_name_length(Span::new(name_span.end - prefix.len(), name_span.end))?;
+        // https://www.w3.org/TR/xml-names11/#nsc-NSDeclared
+        if !prefix.is_empty()
+            && namespace_uri.is_empty()
+            && self.entities.version == XmlVersion::Xml10
+        {
+            self.recover(ParseError::PrefixUndeclaration(
+                prefix.to_string(),
+                name_span,
+            ))?;
+        }
+        self.add_text_size(namespace_uri.as_str().len(), namespace_uri.into())?;
+        let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
+        let namespace_id = xot.namespace_lookup.get_id_mut(namespace_uri.as_str());
+        let element_builder = self.element_builder.as_mut().unwrap();
+        element_builder.namespaces.push((prefix_id, namespace_id));
+        element_builder
+            .namespace_spans
+            .push((prefix_id, name_span, namespace_uri.into()));
+        Ok(())
+    }
+
+    fn attribute(
+        &mut self,
+        prefix: StrSpan<'_>,
+        name: StrSpan<'_>,
+        value: StrSpan<'_>,
+    ) -> Result<(), ParseError> {
+        let attributes = &self.element_builder.as_ref().unwrap().attributes;
+        let is_duplicate = attributes.iter().any(|attribute_builder| {
+            attribute_builder.prefix == prefix.as_str() && attribute_builder.name == name.as_str()
+        });
+        if is_duplicate {
+            let attr_name = if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}:{}", prefix, name)
+            };
+            let span = Span::from_prefix_name(prefix, name);
+            // when we parse leniently, the first attribute wins
+            return self.recover(ParseError::DuplicateAttribute(attr_name, span));
+        }
+        let name_span = Span::from_prefix_name(prefix, name);
+        self.check_attribute_count(name_span)?;
+        self.check_name_length(name_span)?;
+        let value_span = value.into();
+        let value = if self.errors.is_some() {
+            let mut errors = Vec::new();
+            let value = parse_attribute_leniently(
+                value.as_str().into(),
+                value.start(),
+                &mut self.entities,
+                &mut errors,
+            )
+            .to_string();
+            for error in errors {
+                self.recover(error)?;
+            }
+            value
+        } else {
+            parse_attribute(value.as_str().into(), value.start(), &mut self.entities)?.to_string()
+        };
+        // if this is an xml:id we want to apply xml:id normalization as described here
+        // https://www.w3.org/TR/xml-id/#id-avn
+        let is_id = name == "id" && prefix == "xml";
+        let value = if is_id && self.normalize_xml_id {
+            normalize_tokenized_attribute(&value)
+        } else {
+            value
+        };
+        self.add_text_size(value.len(), value_span)?;
+        let attributes = &mut self.element_builder.as_mut().unwrap().attributes;
+        attributes.push(AttributeBuilder {
+            prefix: prefix.to_string(),
+            name: name.to_string(),
+            value,
+            name_span,
+            value_span,
+            prefix_span: prefix.into(),
+            specified: true,
+            is_id,
+        });
+        Ok(())
+    }
+
+    fn add(&mut self, value: Value, xot: &mut Xot) -> NodeId {
+        let node_id = xot.arena.new_node(value);
+        self.current_node_id.append(node_id, &mut xot.arena);
+        node_id
+    }
+
+    fn open_element(&mut self, xot: &mut Xot) -> Result<(NodeId, StartTagSpans), ParseError> {
+        let mut element_builder = self.element_builder.take().unwrap();
+        let span = element_builder.span;
+        if let Some(dtd) = &self.dtd {
+            element_builder.apply_attribute_declarations(dtd, xot);
+            let count = element_builder.namespaces.len() + element_builder.attributes.len();
+            if exceeds(self.limits.max_attributes, count) {
+                return Err(ParseError::AttributeLimit(span));
+            }
+        }
+        self.add_nodes(
+            1 + element_builder.namespaces.len() + element_builder.attributes.len(),
+            span,
+        )?;
+
+        self.name_id_builder
+            .push(element_builder.namespaces.clone());
+
+        let name_id = self.element_name_id(
+            &element_builder.prefix,
+            &element_builder.name,
+            element_builder.prefix_span,
+            xot,
+        )?;
+        let element_value = Value::Element(Element { name_id });
+        let node_id = self.add(element_value, xot);
+        self.current_node_id = node_id;
+
+        // add namespace nodes
+        for (prefix_id, namespace_id) in &element_builder.namespaces {
+            let namespace_node = xot.arena.new_node(Value::Namespace(Namespace {
+                prefix_id: *prefix_id,
+                namespace_id: *namespace_id,
+            }));
+            self.current_node_id.append(namespace_node, &mut xot.arena);
+        }
+        // add attribute nodes
+        let mut attribute_spans = Vec::new();
+        for attribute_builder in element_builder.attributes {
+            let name_id = self.attribute_name_id(
+                &attribute_builder.prefix,
+                &attribute_builder.name,
+                attribute_builder.prefix_span,
+                xot,
+            )?;
+            // when we parse leniently, an attribute with an unknown prefix
+            // may end up with the name of another attribute
+            let is_duplicate = self.errors.is_some()
+                && attribute_spans
+                    .iter()
+                    .any(|(other, _, _)| *other == name_id);
+            if is_duplicate {
+                let attr_name = if attribute_builder.prefix.is_empty() {
+                    attribute_builder.name.clone()
+                } else {
+                    format!("{}:{}", attribute_builder.prefix, attribute_builder.name)
+                };
+                self.recover(ParseError::DuplicateAttribute(
+                    attr_name,
+                    attribute_builder.name_span,
+                ))?;
+                continue;
+            }
+            if let Some(ids) = self.ids.as_mut().filter(|_| attribute_builder.is_id) {
+                ids.entry(attribute_builder.value.clone())
+                    .or_insert((Node::new(node_id), name_id));
+            }
+            let attribute_node = xot.arena.new_node(Value::Attribute(Attribute {
+                name_id,
+                value: attribute_builder.value,
+                specified: attribute_builder.specified,
+            }));
+            if attribute_builder.specified {
+                attribute_spans.push((
+                    name_id,
+                    attribute_builder.name_span,
+                    attribute_builder.value_span,
+                ));
+            }
+            self.current_node_id.append(attribute_node, &mut xot.arena);
+        }
+        self.record(Built::Opened(node_id));
+
+        Ok((
+            node_id,
+            StartTagSpans {
+                name: span,
+                attributes: attribute_spans,
+                namespaces: element_builder.namespace_spans,
+            },
+        ))
+    }
+
+    // consolidates a text node with previous node if possible. If consolidation
+    // took place returns the node id , otherwise none.
+    fn consolidate_text(&mut self, content: &str, cdata: bool, xot: &mut Xot) -> Option<NodeId> {
+        // let's look at the last node we added
+        let last = xot.arena[self.current_node_id].last_child();
+        if let Some(last) = last {
+            let value = xot.arena.get_mut(last).unwrap().get_mut();
+            // CDATA text is only consolidated with CDATA text
+            if let Value::Text(last_text) = value {
+                if last_text.is_cdata() == cdata {
+                    last_text.get_mut().push_str(content);
+                    return Some(last);
+                }
+            }
+        }
+        None
+    }
+
+    fn text(&mut self, content: &str, start: usize, xot: &mut Xot) -> Result<NodeId, ParseError> {
+        let span = Span::new(start, start + content.len());
+        let content = if self.errors.is_some() {
+            let mut errors = Vec::new();
+            let content =
+                parse_text_leniently(content.into(), start, self.entities.version, &mut errors);
+            for error in errors {
+                self.recover(error)?;
+            }
+            content
+        } else {
+            parse_text(content.into(), start, self.entities.version)?
+        };
+        self.add_text(&content, false, span, xot)
+    }
+
+    fn cdata_text(
+        &mut self,
+        content: &str,
+        span: Span,
+        xot: &mut Xot,
+    ) -> Result<NodeId, ParseError> {
+        self.add_text(content, self.preserve_cdata, span, xot)
+    }
+
+    fn add_text(
+        &mut self,
+        content: &str,
+        cdata: bool,
+        span: Span,
+        xot: &mut Xot,
+    ) -> Result<NodeId, ParseError> {
+        self.add_text_size(content.len(), span)?;
+        let node_id = match self.consolidate_text(content, cdata, xot) {
+            Some(last) => last,
+            None => {
+                self.add_nodes(1, span)?;
+                if cdata {
+                    self.add(Value::Text(Text::new_cdata(content.to_string())), xot)
+                } else {
+                    self.add(Value::Text(Text::new(content.to_string())), xot)
+                }
+            }
+        };
+        self.record(Built::Added(node_id));
+        Ok(node_id)
+    }
+
+    // When we parse leniently and the element that a close tag closes is
+    // open further up, the elements inside it are unclosed. Returns how many
+    // there are.
+    fn unclosed_inside(&mut self, prefix: StrSpan, name: StrSpan, xot: &mut Xot) -> usize {
+        if self.errors.is_none() {
+            return 0;
+        }
+        // unknown prefixes are reported when we close the element
+        let name_id = self
+            .name_id_builder
+            .element_name_id(&prefix, &name, prefix.into(), xot)
+            .unwrap_or_else(|_| xot.add_name(&name));
+        let mut node_id = self.current_node_id;
+        let mut count = 0;
+        // we can't close elements that were open before the entity we are
+        // expanding
+        while self.entity_boundaries.last() != Some(&node_id) {
+            match xot.arena[node_id].get() {
+                Value::Element(element) if element.name_id == name_id => return count,
+                Value::Element(_) => {}
+                _ => return 0,
+            }
+            count += 1;
+            node_id = xot.arena[node_id].parent().unwrap();
+        }
+        0
+    }
+
+    pub(crate) fn close_element_immediate(&mut self, xot: &mut Xot) -> NodeId {
+        let current_node = xot.arena.get(self.current_node_id).unwrap();
+        if matches!(current_node.get(), Value::Element(_)) {
+            self.name_id_builder.pop();
+        }
+        let closed_node_id = self.current_node_id;
+        self.current_node_id = current_node.parent().expect("Cannot close document node");
+        self.record(Built::Closed(closed_node_id));
+        closed_node_id
+    }
+
+    fn close_element(
+        &mut self,
+        prefix: StrSpan,
+        name: StrSpan,
+        xot: &mut Xot,
+    ) -> Result<NodeId, ParseError> {
+        let name_id = self.element_name_id(&prefix, &name, prefix.into(), xot)?;
+        let current_node = xot.arena.get(self.current_node_id).unwrap();
+        // we can only close the element that is currently open, and not
+        // an element that was open before the entity we are expanding
+        let is_open = matches!(current_node.get(), Value::Element(element) if element.name_id == name_id)
+            && self.entity_boundaries.last() != Some(&self.current_node_id);
+        if !is_open {
+            return Err(ParseError::InvalidCloseTag(
+                prefix.to_string(),
+                name.to_string(),
+                Span::from_prefix_name(prefix, name),
+            ));
+        }
+        self.name_id_builder.pop();
+        let closed_node_id = self.current_node_id;
+        self.current_node_id = current_node.parent().expect("Cannot close document node");
+        self.record(Built::Closed(closed_node_id));
+        Ok(closed_node_id)
+    }
+
+    fn comment(&mut self, content: StrSpan<'_>, xot: &mut Xot) -> Result<NodeId, ParseError> {
+        let span = content.into();
+        let content = content.as_str();
+        self.add_text_size(content.len(), span)?;
+        self.add_nodes(1, span)?;
+        // XXX are there illegal comments, like those with -- inside? or
+        // won't they pass the parser?
+        let node_id = self.add(Value::Comment(Comment::new(content.to_string())), xot);
+        self.record(Built::Added(node_id));
+        Ok(node_id)
+    }
+
+    fn processing_instruction(
+        &mut self,
+        target: StrSpan<'_>,
+        content: Option<StrSpan<'_>>,
+        xot: &mut Xot,
+    ) -> Result<NodeId, ParseError> {
+        let span = target.into();
+        self.check_name_length(span)?;
+        if let Some(content) = content {
+            self.add_text_size(content.as_str().len(), content.into())?;
+        }
+        self.add_nodes(1, span)?;
+        let target = target.as_str();
+        let content = content.map(|content| content.as_str());
+        // XXX are there illegal processing instructions, like those with
+        // ?> inside? or won't they pass the parser? What about those with xml?
+        let target = xot.add_name(target);
+        let node_id = self.add(
+            Value::ProcessingInstruction(ProcessingInstruction::new(
+                target,
+                content.map(|s| s.to_string()),
+            )),
+            xot,
+        );
+        self.record(Built::Added(node_id));
+        Ok(node_id)
+    }
+
+    fn doctype(&mut self, name: &str, external_id: Option<ExternalId>) {
+        let external_id = external_id.map(|external_id| match external_id {
+            ExternalId::System(system) => {
+                self.system_id_span = Some(system.into());
+                DocType::System {
+                    system: system.to_string(),
+                }
+            }
+            ExternalId::Public(public, system) => {
+                self.system_id_span = Some(system.into());
+                DocType::Public {
+                    public: public.to_string(),
+                    system: system.to_string(),
+                }
+            }
+        });
+        self.dtd = Some(Dtd::new(name.to_string(), external_id));
+    }
+
+    // xmlparser doesn't support everything that can appear in the internal
+    // subset, such as parameter entity references. We parse the internal
+    // subset ourselves, and blank it out for xmlparser.
+    pub(crate) fn blank_internal_subset<'a>(&mut self, xml: &'a str) -> Cow<'a, str> {
+        let Some(range) = find_internal_subset(xml) else {
+            return Cow::Borrowed(xml);
+        };
+        let blanked = format!(
+            "{}{}{}",
+            &xml[..range.start],
+            " ".repeat(range.len()),
+            &xml[range.end..]
+        );
+        self.internal_subset = Some((range.start, xml[range].to_string()));
+        Cow::Owned(blanked)
+    }
+
+    // Process the declarations in the internal subset, if any, and then those
+    // in the external subset, if the resolver lets us read it.
+    fn dtd_declarations(
+        &mut self,
+        internal_subset: Option<(usize, String)>,
+    ) -> Result<(), ParseError> {
+        let Some(dtd) = &mut self.dtd else {
+            return Ok(());
+        };
+        let mut parser = DtdParser::new(self.entities.resolver(), self.limits);
+        if let Some((start, internal_subset)) = internal_subset {
+            parser.parse_internal_subset(&internal_subset, start, dtd, &mut self.entities)?;
+            dtd.set_internal_subset(internal_subset);
+        }
+        if let (Some(system_id), Some(span)) = (dtd.system_id(), self.system_id_span) {
+            let public_id = dtd.public_id().map(|public_id| public_id.to_string());
+            let system_id = system_id.to_string();
+            parser.parse_external_subset(
+                public_id.as_deref(),
+                &system_id,
+                span,
+                dtd,
+                &mut self.entities,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self, xot: &mut Xot) {
+        if let Some(declaration) = self.declaration.take() {
+            xot.document_info_mut(Node::new(self.tree)).declaration = Some(declaration);
+        }
+        if let Some(dtd) = self.dtd.take() {
+            xot.document_info_mut(Node::new(self.tree)).dtd = Some(dtd);
+        }
+        if let Some(ids) = self.ids.take().filter(|ids| !ids.is_empty()) {
+            xot.document_info_mut(Node::new(self.tree)).ids = ids;
+        }
+    }
+
+    // Whether the document element has started, and we're not in the
+    // middle of a start tag.
+    pub(crate) fn has_started_content(&self, xot: &Xot) -> bool {
+        self.element_builder.is_none()
+            && self
+                .tree
+                .children(&xot.arena)
+                .any(|child| matches!(xot.arena[child].get(), Value::Element(_)))
+    }
+
+    pub(crate) fn is_current_node_document(&self, xot: &Xot) -> bool {
+        matches!(xot.arena[self.current_node_id].get(), Value::Document)
+    }
+
+    // Tokenized as a fragment, the content outside of the document element
+    // can have whitespace, text and multiple elements, which xmlparser
+    // doesn't accept in a document. Returns whether to skip the token.
+    fn outside_document_element(
+        &mut self,
+        token: &Token<'_>,
+        xot: &Xot,
+    ) -> Result<bool, ParseError> {
+        if !self.content_as_fragment || !self.is_current_node_document(xot) {
+            return Ok(false);
+        }
+        match token {
+            Token::Text { text } if text.as_str().chars().all(is_xml_space) => Ok(true),
+            Token::Text { text: span } | Token::Cdata { span, .. } => {
+                Err(ParseError::TextAtTopLevel((*span).into()))
+            }
+            Token::ElementStart { prefix, local, .. } => {
+                if self.has_document_element {
+                    return Err(ParseError::MultipleElementsAtTopLevel(
+                        Span::from_prefix_name(*prefix, *local),
+                    ));
+                }
+                self.has_document_element = true;
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+struct NameIdBuilder {
+    namespace_stack: Vec<Namespaces>,
+}
+
+impl NameIdBuilder {
+    fn new(prefixes: Namespaces) -> Self {
+        let namespace_stack = vec![prefixes];
+        Self { namespace_stack }
+    }
+
+    fn push(&mut self, namespaces: Namespaces) {
+        self.namespace_stack.push(namespaces);
+    }
+
+    fn pop(&mut self) {
+        // should always be able to pop as there's a bottom entry
+        self.namespace_stack.pop();
+    }
+
+    // The number of open elements. The bottom two entries are the base
+    // prefixes.
+    fn depth(&self) -> usize {
+        self.namespace_stack.len() - 2
+    }
+
+    fn element_name_id(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        prefix_span: Span,
+        xot: &mut Xot,
+    ) -> Result<NameId, ParseError> {
+        let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
+        if let Ok(name_id) = self.name_id_with_prefix_id(prefix_id, name, xot) {
+            Ok(name_id)
+        } else {
+            Err(ParseError::UnknownPrefix(prefix.to_string(), prefix_span))
+        }
+    }
+
+    fn attribute_name_id(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        prefix_span: Span,
+        xot: &mut Xot,
+    ) -> Result<NameId, ParseError> {
+        // an unprefixed attribute is in no namespace, not
+        // in the default namespace
+        // https://stackoverflow.com/questions/3312390/xml-default-namespaces-for-unqualified-attribute-names
+        let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
+        if prefix_id == xot.empty_prefix_id {
+            let name = Name::new(name.to_string(), xot.no_namespace_id);
+            return Ok(xot.name_lookup.get_id_mut(&name));
+        }
+        if let Ok(name_id) = self.name_id_with_prefix_id(prefix_id, name, xot) {
+            Ok(name_id)
+        } else {
+            Err(ParseError::UnknownPrefix(prefix.to_string(), prefix_span))
+        }
+    }
+
+    fn name_id_with_prefix_id(
+        &mut self,
+        prefix_id: PrefixId,
+        name: &str,
+        xot: &mut Xot,
+    ) -> Result<NameId, ()> {
+        // go through namespace stack backwards, find the first namespace
+        // that matches this prefix
+        let namespace_id = self.namespace_stack.iter().rev().find_map(|ns| {
+            ns.iter()
+                .rev()
+                .find_map(|(p, ns)| if *p == prefix_id { Some(*ns) } else { None })
+        });
+        let namespace_id = namespace_id.ok_or(())?;
+        // in XML 1.1 `xmlns:p=""` undeclares the prefix
+        if namespace_id == xot.no_namespace_id && prefix_id != xot.empty_prefix_id {
+            return Err(());
+        }
+        let name = Name::new(name.to_string(), namespace_id);
+        Ok(xot.name_lookup.get_id_mut(&name))
+    }
+}
+
+// Find the span of the value of the standalone declaration in an XML
+// declaration, as xmlparser doesn't give us this.
+fn standalone_span(declaration: StrSpan<'_>) -> Option<Span> {
+    let text = declaration.as_str();
+    let after_name = text.find("standalone")? + "standalone".len();
+    let rest = text[after_name..].trim_start_matches(is_xml_space);
+    let rest = rest.strip_prefix('=')?.trim_start_matches(is_xml_space);
+    let quote = rest.chars().next()?;
+    let value_start = text.len() - rest.len() + 1;
+    let value_end = value_start + rest[1..].find(quote)?;
+    Some(Span::new(
+        declaration.start() + value_start,
+        declaration.start() + value_end,
+    ))
+}
+
+// In XML 1.1, control characters may only appear as character references.
+// https://www.w3.org/TR/xml11/#NT-RestrictedChar
+fn restricted_char(token: &Token<'_>) -> Option<ParseError> {
+    let text = match token {
+        Token::Text { text } | Token::Cdata { text, .. } | Token::Comment { text, .. } => *text,
+        Token::Attribute { value, .. } => *value,
+        Token::ProcessingInstruction {
+            content: Some(content),
+            ..
+        } => *content,
+        _ => return None,
+    };
+    let (position, c) = text
+        .as_str()
+        .char_indices()
+        .find(|(_, c)| is_restricted_char(*c))?;
+    let start = text.start() + position;
+    Some(ParseError::RestrictedChar(
+        c,
+        Span::new(start, start + c.len_utf8()),
+    ))
+}
+
+fn is_xml_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+impl Xot {
+    // We expect both a document as the current node (everything else being
+    // closed) *and* the content of this node containing a single element
+    // if not, we have a problem. We want to produce a parse error for
+    // this, as this is the parser.
+    pub(crate) fn check_document(
+        &self,
+        builder: &DocumentBuilder,
+        span_info: SpanInfo,
+        len: usize,
+    ) -> Result<(Node, SpanInfo), ParseError> {
+        if builder.is_current_node_document(self) {
+            let document_node = Node::new(builder.tree);
+            let mut element_nodes = Vec::new();
+
+            for child in self.children(document_node) {
+                match self.value(child) {
+                    Value::Element(_) => element_nodes.push(child),
+                    Value::Text(_) => {
+                        return Err(ParseError::TextAtTopLevel(
+                            *span_info.get(SpanInfoKey::Text(child)).unwrap(),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            if element_nodes.is_empty() {
+                return Err(ParseError::NoElementAtTopLevel(len));
+            }
+            if element_nodes.len() > 1 {
+                return Err(ParseError::MultipleElementsAtTopLevel(
+                    *span_info
+                        .get(SpanInfoKey::ElementStart(element_nodes[1]))
+                        .unwrap(),
+                ));
+            }
+            Ok((document_node, span_info))
+        } else {
+            let current_node = Node::new(builder.current_node_id);
+
+            // the top level node's span is the problem
+            Err(ParseError::UnclosedTag(
+                *span_info
+                    .get(SpanInfoKey::ElementStart(current_node))
+                    .unwrap(),
+            ))
+        }
+    }
+
+    pub(crate) fn _parse(
+        &mut self,
+        tokenizer: Tokenizer<'_>,
+        builder: &mut DocumentBuilder,
+        mut span_info: SpanInfo,
+    ) -> Result<SpanInfo, ParseError> {
+        self.parse_tokens(tokenizer, builder, &mut span_info, SpanMapping::Source)?;
+        builder.finish(self);
+        Ok(span_info)
+    }
+
+    // xmlparser doesn't support everything that can appear in the internal
+    // subset, so we blank it out. Rather than copying the whole source, we
+    // parse the prolog up to the end of the document type declaration right
+    // away, and give back a tokenizer for the rest of the source as a
+    // fragment.
+    pub(crate) fn document_tokenizer<'a>(
+        &mut self,
+        xml: &'a str,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+    ) -> Result<Tokenizer<'a>, ParseError> {
+        let Some(range) = find_internal_subset(xml) else {
+            return Ok(Tokenizer::from(xml));
+        };
+        let doctype_end = xml[range.end..]
+            .find('>')
+            .map(|index| range.end + index + 1)
+            .unwrap_or(xml.len());
+        let prolog = builder.blank_internal_subset(&xml[..doctype_end]);
+        let tokenizer = Tokenizer::from(prolog.as_ref());
+        self.parse_tokens(tokenizer, builder, span_info, SpanMapping::Source)?;
+        builder.content_as_fragment = true;
+        Ok(Tokenizer::from_fragment(xml, doctype_end..xml.len()))
+    }
+
+    pub(crate) fn parse_tokens(
+        &mut self,
+        mut tokenizer: Tokenizer<'_>,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<(), ParseError> {
+        while self.parse_next_token(&mut tokenizer, builder, span_info, mapping)? {}
+        Ok(())
+    }
+
+    // Parse the next token from the tokenizer into the builder. Returns
+    // `false` if there are no more tokens.
+    pub(crate) fn parse_next_token(
+        &mut self,
+        tokenizer: &mut Tokenizer<'_>,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<bool, ParseError> {
+        let source = tokenizer.stream().span().as_str();
+        // getting the position before the token is required to get the
+        // right one for the error handling, which is a bit unfortunate
+        // https://github.com/RazrFalcon/xmlparser/issues/30
+        let position = tokenizer.stream().pos();
+        let Some(token) = tokenizer.next() else {
+            return Ok(false);
+        };
+        let token = match token {
+            Ok(token) => token,
+            Err(e) => {
+                return Err(mapping.map_error(ParseError::XmlParser(e, position)));
+            }
+        };
+        // the replacement text of an entity may contain the characters its
+        // character references refer to
+        if builder.entities.version == XmlVersion::Xml11
+            && !matches!(mapping, SpanMapping::Entity(_))
+        {
+            if let Some(error) = restricted_char(&token) {
+                builder.mapping = mapping;
+                builder.recover(error).map_err(|e| mapping.map_error(e))?;
+            }
+        }
+        if builder
+            .outside_document_element(&token, self)
+            .map_err(|e| mapping.map_error(e))?
+        {
+            return Ok(true);
+        }
+        if let Token::Text { text } = token {
+            self.parse_text(text, builder, span_info, mapping)?;
+        } else {
+            self.parse_token(token, source, builder, span_info, mapping)
+                .map_err(|e| mapping.map_error(e))?;
+        }
+        Ok(true)
+    }
+
+    fn parse_token(
+        &mut self,
+        token: Token<'_>,
+        source: &str,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<(), ParseError> {
+        use Token::*;
+
+        builder.mapping = mapping;
+        match token {
+            Attribute {
+                prefix,
+                local,
+                value,
+                span: _,
+            } => {
+                if prefix.as_str() == "xmlns" {
+                    let name_span = Span::from_prefix_name(prefix, local);
+                    builder.prefix(local.as_str(), value, name_span, self)?;
+                } else if local.as_str() == "xmlns" {
+                    builder.prefix("", value, local.into(), self)?;
+                } else {
+                    builder.attribute(prefix, local, value)?;
+                }
+            }
+            // text is handled by `parse_text`
+            Text { .. } => unreachable!(),
+            Cdata { text, span: _ } => {
+                let node_id = builder.cdata_text(text.as_str(), text.into(), self)?;
+                span_info.extend_text_span(node_id.into(), mapping.map(text.into()));
+            }
+            ElementStart {
+                prefix,
+                local,
+                span: _,
+            } => {
+                builder.element(prefix, local)?;
+            }
+
+            ElementEnd {
+                end,
+                span: end_span,
+            } => {
+                use self::ElementEnd::*;
+
+                match end {
+                    Open => {
+                        let (node_id, spans) = builder.open_element(self)?;
+                        span_info.add_start_tag_spans(node_id, spans, mapping);
+                    }
+                    Close(prefix, local) => {
+                        let end_span = mapping.map(end_span.into());
+                        // when we parse leniently, we close the elements
+                        // that are still open inside the element first
+                        for _ in 0..builder.unclosed_inside(prefix, local, self) {
+                            let node = Node::new(builder.current_node_id);
+                            builder.recover_mapped(ParseError::UnclosedTag(
+                                *span_info.get(SpanInfoKey::ElementStart(node)).unwrap(),
+                            ))?;
+                            builder.close_element_immediate(self);
+                            span_info.add(
+                                SpanInfoKey::ElementEnd(node),
+                                Span::new(end_span.start, end_span.start),
+                            );
+                        }
+                        match builder.close_element(prefix, local, self) {
+                            Ok(node_id) => {
+                                span_info.add(SpanInfoKey::ElementEnd(node_id.into()), end_span);
+                                self.closed(node_id.into(), builder, span_info);
+                            }
+                            // when we parse leniently, we skip a close tag
+                            // without an open element
+                            Err(error @ ParseError::InvalidCloseTag(..)) => {
+                                builder.recover(error)?;
+                            }
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Empty => {
+                        let (node_id, spans) = builder.open_element(self)?;
+                        span_info.add_start_tag_spans(node_id, spans, mapping);
+                        let node_id = builder.close_element_immediate(self);
+                        span_info.add(
+                            SpanInfoKey::ElementEnd(node_id.into()),
+                            mapping.map(end_span.into()),
+                        );
+                    }
+                }
+            }
+            Comment { text, span: _ } => {
+                let node_id = builder.comment(text, self)?;
+                span_info.add(
+                    SpanInfoKey::Comment(node_id.into()),
+                    mapping.map(text.into()),
+                );
+            }
+            ProcessingInstruction {
+                target,
+                content,
+                span: _,
+            } => {
+                let node_id = builder.processing_instruction(target, content, self)?;
+                span_info.add(
+                    SpanInfoKey::PiTarget(node_id.into()),
+                    mapping.map(target.into()),
+                );
+                if let Some(content) = content {
+                    span_info.add(
+                        SpanInfoKey::PiContent(node_id.into()),
+                        mapping.map(content.into()),
+                    );
+                }
+            }
+            Declaration {
+                version,
+                encoding,
+                standalone,
+                span,
+            } => {
+                let document = Node::new(builder.tree);
+                span_info.add(
+                    SpanInfoKey::DeclarationVersion(document),
+                    mapping.map(version.into()),
+                );
+                if let Some(encoding) = encoding {
+                    span_info.add(
+                        SpanInfoKey::DeclarationEncoding(document),
+                        mapping.map(encoding.into()),
+                    );
+                }
+                if let Some(standalone_span) = standalone.and_then(|_| standalone_span(span)) {
+                    span_info.add(
+                        SpanInfoKey::DeclarationStandalone(document),
+                        mapping.map(standalone_span),
+                    );
+                }
+                builder.entities.version = match version.as_str() {
+                    "1.0" => XmlVersion::Xml10,
+                    "1.1" => XmlVersion::Xml11,
+                    _ => {
+                        return Err(ParseError::UnsupportedVersion(
+                            version.to_string(),
+                            version.into(),
+                        ))
+                    }
+                };
+                builder.declaration = Some(XmlDeclaration::new(
+                    version.to_string(),
+                    encoding.map(|encoding| encoding.to_string()),
+                    standalone,
+                ));
+            }
+            DtdStart {
+                name,
+                external_id,
+                span,
+            } => {
+                builder.doctype(name.as_str(), external_id);
+                // the span of the doctype start includes the opening `[`
+                builder.internal_subset_start = span.end();
+            }
+            DtdEnd { span } => {
+                let internal_subset = builder.internal_subset.take().unwrap_or_else(|| {
+                    let start = builder.internal_subset_start;
+                    (start, source[start..span.start()].to_string())
+                });
+                builder.dtd_declarations(Some(internal_subset))?;
+            }
+            EmptyDtd {
+                name,
+                external_id,
+                span: _,
+            } => {
+                builder.doctype(name.as_str(), external_id);
+                builder.dtd_declarations(None)?;
+            }
+            // we parse the declarations in the internal subset ourselves
+            EntityDeclaration { .. } => {}
+        }
+        Ok(())
+    }
+
+    // Once we have all the children of a node, we remove its insignificant
+    // whitespace if we're asked to.
+    pub(crate) fn closed(
+        &mut self,
+        node: Node,
+        builder: &DocumentBuilder,
+        span_info: &mut SpanInfo,
+    ) {
+        if !builder.remove_insignificant_whitespace {
+            return;
+        }
+        for text in insignificant_whitespace_children(self, node) {
+            span_info.remove(SpanInfoKey::Text(text));
+            span_info.remove_references(text);
+            // this doesn't consolidate text, as there's no text left
+            text.get().remove(&mut self.arena);
+        }
+    }
+
+    // Text may contain references to user-defined entities. As these can
+    // contain markup, we expand them by parsing their replacement text.
+    //
+    // Errors that result from the expansion are located at the entity
+    // reference already, so we only map the errors in the text itself.
+    fn parse_text(
+        &mut self,
+        text: StrSpan<'_>,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<(), ParseError> {
+        let content = text.as_str();
+        let mut position = 0;
+        for (range, name) in entity_references(content) {
+            if range.start > position {
+                self.parse_text_segment(text, position..range.start, builder, span_info, mapping)
+                    .map_err(|e| mapping.map_error(e))?;
+            }
+            let span = mapping.map(Span::new(
+                text.start() + range.start,
+                text.start() + range.end,
+            ));
+            self.expand_entity(name, span, builder, span_info, mapping)?;
+            position = range.end;
+        }
+        if position < content.len() {
+            self.parse_text_segment(text, position..content.len(), builder, span_info, mapping)
+                .map_err(|e| mapping.map_error(e))?;
+        }
+        Ok(())
+    }
+
+    fn parse_text_segment(
+        &mut self,
+        text: StrSpan<'_>,
+        range: std::ops::Range<usize>,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<(), ParseError> {
+        let start = text.start() + range.start;
+        builder.mapping = mapping;
+        let content = &text.as_str()[range.clone()];
+        let node_id = builder.text(content, start, self)?;
+        span_info.extend_text_span(
+            node_id.into(),
+            mapping.map(Span::new(start, text.start() + range.end)),
+        );
+        // the references in the replacement text of an entity are all at
+        // the entity reference
+        if !matches!(mapping, SpanMapping::Entity(_)) {
+            for reference in references(content) {
+                span_info.add_reference(
+                    node_id.into(),
+                    mapping.map(Span::new(start + reference.start, start + reference.end)),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_entity(
+        &mut self,
+        name: &str,
+        span: Span,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        mapping: SpanMapping,
+    ) -> Result<(), ParseError> {
+        let replacement_text = match builder.entities.enter(name, span) {
+            Ok(replacement_text) => replacement_text,
+            Err(error) => {
+                // when we parse leniently, we keep the reference as it is
+                builder.recover_mapped(error)?;
+                let node_id = builder.add_text(&format!("&{};", name), false, span, self)?;
+                span_info.extend_text_span(node_id.into(), span);
+                return Ok(());
+            }
+        };
+        if !replacement_text.contains(['<', '&']) {
+            // fast path: the replacement text is plain text
+            let node_id = builder
+                .text(&replacement_text, 0, self)
+                .map_err(|error| error.with_span(span))?;
+            span_info.extend_text_span(node_id.into(), span);
+            if !matches!(mapping, SpanMapping::Entity(_)) {
+                span_info.add_reference(node_id.into(), span);
+            }
+        } else {
+            let tokenizer = Tokenizer::from_fragment(&replacement_text, 0..replacement_text.len());
+            let boundary = builder.current_node_id;
+            builder.entity_boundaries.push(boundary);
+            if let Err(error) =
+                self.parse_tokens(tokenizer, builder, span_info, SpanMapping::Entity(span))
+            {
+                builder.recover_mapped(error)?;
+            }
+            // the replacement text has to be balanced
+            if builder.current_node_id != boundary {
+                builder.recover_mapped(ParseError::UnclosedTag(span))?;
+                while builder.current_node_id != boundary {
+                    let node_id = builder.close_element_immediate(self);
+                    span_info.add(
+                        SpanInfoKey::ElementEnd(node_id.into()),
+                        Span::new(span.end, span.end),
+                    );
+                }
+            }
+            builder.entity_boundaries.pop();
+        }
+        builder.entities.exit();
+        Ok(())
+    }
+}
+
+// Find the range of the internal subset of the document type declaration,
+// if there is one.
+pub(crate) fn find_internal_subset(xml: &str) -> Option<std::ops::Range<usize>> {
+    for token in Tokenizer::from(xml) {
+        match token {
+            Ok(Token::DtdStart { span, .. }) => {
+                // the span of the doctype start includes the opening `[`
+                let start = span.end();
+                return internal_subset_end(xml, start).map(|end| start..end);
+            }
+            Ok(Token::Declaration { .. })
+            | Ok(Token::Comment { .. })
+            | Ok(Token::ProcessingInstruction { .. }) => {}
+            _ => return None,
+        }
+    }
+    None
+}
@@ -1,5 +1,6 @@
 use crate::error::Error;
 use crate::id::NameId;
+use crate::xmlchar::check_chars;
 use crate::xmlvalue::{Attribute, Comment, Element, Namespace, ProcessingInstruction, Text, Value};
 use crate::xotdata::{Node, Xot};
 use crate::{NamespaceId, PrefixId};
@@ -164,6 +165,31 @@ impl Xot {
         self.new_node(text)
     }
 
+    /// Create a new, unattached text node, checking that XML allows its
+    /// characters.
+    ///
+    /// This is like [`Xot::new_text`], but if the text contains a character
+    /// that XML doesn't allow, such as NUL, you get an
+    /// [`Error::InvalidCharacter`].
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc/>"#)?;
+    /// let doc_el = xot.document_element(root)?;
+    /// let txt = xot.try_new_text("Hello, world!")?;
+    /// xot.append(doc_el, txt)?;
+    /// assert_eq!(xot.to_string(root)?, "<doc>Hello, world!</doc>");
+    ///
+    /// assert!(matches!(xot.try_new_text("\0"), Err(Error::InvalidCharacter('\0'))));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn try_new_text(&mut self, text: &str) -> Result<Node, Error> {
+        check_chars(text)?;
+        Ok(self.new_text(text))
+    }
+
     /// Create a new, unattached comment node given comment text.
     ///
     /// ```rust
@@ -232,6 +258,7 @@ impl Xot {
         let attr = Value::Attribute(Attribute {
             name_id: name.into(),
             value,
+            specified: true,
         });
         self.new_node(attr)
     }
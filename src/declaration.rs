@@ -0,0 +1,100 @@
+use crate::output::xml::Declaration;
+
+/// The XML declaration of a parsed document.
+///
+/// You can access it using [`Xot::xml_declaration`](crate::Xot::xml_declaration).
+///
+/// ```rust
+/// use xot::Xot;
+///
+/// let mut xot = Xot::new();
+/// let root = xot.parse(r#"<?xml version="1.0" encoding="ISO-8859-1" standalone="yes"?><doc/>"#)?;
+///
+/// let declaration = xot.xml_declaration(root).unwrap();
+/// assert_eq!(declaration.version(), "1.0");
+/// assert_eq!(declaration.encoding(), Some("ISO-8859-1"));
+/// assert_eq!(declaration.standalone(), Some(true));
+/// # Ok::<(), xot::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlDeclaration {
+    version: String,
+    encoding: Option<String>,
+    standalone: Option<bool>,
+}
+
+impl XmlDeclaration {
+    pub(crate) fn new(version: String, encoding: Option<String>, standalone: Option<bool>) -> Self {
+        XmlDeclaration {
+            version,
+            encoding,
+            standalone,
+        }
+    }
+
+    /// The version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The encoding, as it was declared.
+    pub fn encoding(&self) -> Option<&str> {
+        self.encoding.as_deref()
+    }
+
+    /// The standalone declaration: `Some(true)` for `standalone="yes"`.
+    pub fn standalone(&self) -> Option<bool> {
+        self.standalone
+    }
+
+    /// The version as an [`XmlVersion`].
+    pub fn xml_version(&self) -> XmlVersion {
+        if self.version == "1.1" {
+            XmlVersion::Xml11
+        } else {
+            XmlVersion::Xml10
+        }
+    }
+
+    /// The declaration for serialization, so that you can reproduce this
+    /// XML declaration.
+    pub fn declaration(&self) -> Declaration {
+        Declaration {
+            version: self.xml_version(),
+            encoding: self.encoding.clone(),
+            standalone: self.standalone,
+        }
+    }
+}
+
+/// The version of XML.
+///
+/// A document is parsed as XML 1.1 if its XML declaration says so, and
+/// serialized as XML 1.1 if the [`Declaration`] you serialize it with says
+/// so. XML 1.1 differs from XML 1.0 in that:
+///
+/// * NEL (U+0085) and LS (U+2028) are line endings, which the parser
+///   normalizes to a line feed like carriage returns.
+///
+/// * You can refer to control characters with character references, such
+///   as `&#x1;`. Most of these can't appear in the document otherwise.
+///
+/// * You can undeclare a namespace prefix with `xmlns:p=""`, so that it's
+///   no longer in scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum XmlVersion {
+    /// XML 1.0
+    #[default]
+    Xml10,
+    /// XML 1.1
+    Xml11,
+}
+
+impl XmlVersion {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            XmlVersion::Xml10 => "1.0",
+            XmlVersion::Xml11 => "1.1",
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+use crate::builder::find_internal_subset;
+use crate::error::ParseError;
+use crate::sourceindex::{LineColumn, SourceIndex};
+use crate::span::{Span, SpanInfo, SpanInfoKey};
+
+/// A report of a [`ParseError`] for humans or tools.
+///
+/// You get this with [`ParseError::diagnostic`]. It describes the problem
+/// and where it is in the source text. Its `Display` implementation renders
+/// a report that shows the line with the problem and underlines the span,
+/// like this:
+///
+/// ```text
+/// error: close tag `</b>` doesn't match the open element
+///  --> 2:8
+///   |
+/// 2 |   <a></b>
+///   |        ^ expected `</a>`
+///   |
+///   = help: expected `</a>` here; `<a>` was opened at 2:4
+/// ```
+///
+/// ```rust
+/// use xot::Xot;
+///
+/// let mut xot = Xot::new();
+/// let xml = "<doc>\n  <a></b>\n</doc>";
+/// let error = xot.parse(xml).unwrap_err();
+/// let diagnostic = error.diagnostic(xml);
+///
+/// assert_eq!(diagnostic.label, "expected `</a>`");
+/// assert_eq!(diagnostic.start.line, 2);
+/// println!("{}", diagnostic);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// What the problem is.
+    pub message: String,
+    /// Where the problem is in the source text.
+    pub span: Span,
+    /// The line and column of the start of the span.
+    pub start: LineColumn,
+    /// The line and column of the end of the span.
+    pub end: LineColumn,
+    /// A short description of what's wrong at the span.
+    pub label: String,
+    /// Suggestions on how to fix the problem.
+    pub help: Vec<String>,
+    /// The text of the line the span starts on, without the line break.
+    pub source_line: String,
+}
+
+impl ParseError {
+    /// Describe this error as a [`Diagnostic`], given the source text that
+    /// was parsed.
+    ///
+    /// If you have the [`SpanInfo`] of the parse, as you do with
+    /// [`Xot::parse_lenient`](crate::Xot::parse_lenient), use
+    /// [`ParseError::diagnostic_with_span_info`] instead, which doesn't have
+    /// to scan the source to describe some errors.
+    pub fn diagnostic(&self, source: &str) -> Diagnostic {
+        self.describe_diagnostic(source, |name_span| expected_close_tag(source, name_span))
+    }
+
+    /// Describe this error as a [`Diagnostic`], given the source text that
+    /// was parsed and its span information.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<doc>\n  <a></b>\n</doc>";
+    /// let (_, span_info, errors) = xot.parse_lenient(xml);
+    /// // `<a>` is never closed, and `</b>` doesn't match it
+    /// let diagnostic = errors[1].diagnostic_with_span_info(xml, &span_info);
+    ///
+    /// assert_eq!(diagnostic.label, "expected `</a>`");
+    /// assert_eq!(
+    ///     diagnostic.help,
+    ///     vec!["expected `</a>` here; `<a>` was opened at 2:4"]
+    /// );
+    /// ```
+    pub fn diagnostic_with_span_info(&self, source: &str, span_info: &SpanInfo) -> Diagnostic {
+        self.describe_diagnostic(source, |name_span| open_element_span(span_info, name_span))
+    }
+
+    // `open_element` finds the span of the name of the element that's open
+    // at the close tag with the given name span.
+    fn describe_diagnostic(
+        &self,
+        source: &str,
+        open_element: impl Fn(Span) -> Option<Span>,
+    ) -> Diagnostic {
+        let span = match self {
+            ParseError::UnclosedEntity(_, position) => Span::new(*position, *position + 1),
+            // xmlparser reports the position before the whitespace in front
+            // of the token
+            ParseError::XmlParser(_, position) => {
+                let rest = source.get(*position..).unwrap_or_default();
+                let position = source.len() - rest.trim_start_matches(is_whitespace).len();
+                Span::new(position, position)
+            }
+            _ => self.span(),
+        };
+        let span = Span::new(span.start.min(source.len()), span.end.min(source.len()));
+        let text = source.get(span.range()).unwrap_or_default();
+        let index = SourceIndex::new(source);
+        let (message, label, help) = describe(self, source, text, &index, open_element);
+        let line_start = source[..span.start]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |index| span.start + index);
+        let source_line = source[line_start..line_end].trim_end_matches('\r');
+        Diagnostic {
+            message,
+            span,
+            start: index.line_column(span.start),
+            end: index.line_column(span.end),
+            label,
+            help,
+            source_line: source_line.to_string(),
+        }
+    }
+}
+
+// The message, label and help for an error. `text` is the text of the span.
+fn describe(
+    error: &ParseError,
+    source: &str,
+    text: &str,
+    index: &SourceIndex,
+    open_element: impl Fn(Span) -> Option<Span>,
+) -> (String, String, Vec<String>) {
+    match error {
+        ParseError::UnclosedTag(_) if text.starts_with('&') => (
+            format!("the replacement text of `{}` leaves an element open", text),
+            "entity referenced here".to_string(),
+            vec!["close the elements in the replacement text of the entity".to_string()],
+        ),
+        ParseError::UnclosedTag(_) => (
+            format!("element `{}` is never closed", text),
+            "opened here".to_string(),
+            vec![format!("close it with `</{}>`", text)],
+        ),
+        ParseError::InvalidCloseTag(prefix, name, span) => {
+            let name = qualified_name(prefix, name);
+            let message = format!("close tag `</{}>` doesn't match the open element", name);
+            let expected = open_element(*span)
+                .and_then(|open_span| Some((source.get(open_span.range())?, open_span)))
+                .filter(|(expected, _)| *expected != name);
+            match expected {
+                Some((expected, open_span)) => {
+                    let opened_at = index.line_column(open_span.start);
+                    (
+                        message,
+                        format!("expected `</{}>`", expected),
+                        vec![format!(
+                            "expected `</{}>` here; `<{}>` was opened at {}:{}",
+                            expected, expected, opened_at.line, opened_at.column
+                        )],
+                    )
+                }
+                None => (message, format!("no element `{}` is open", name), vec![]),
+            }
+        }
+        ParseError::UnclosedEntity(name, _) => (
+            // the name is the rest of the text
+            format!(
+                "reference `&{}` is not closed",
+                name.split(|c: char| c.is_whitespace() || c == '<' || c == '&')
+                    .next()
+                    .unwrap_or_default()
+            ),
+            "missing `;`".to_string(),
+            vec!["write `&amp;` for a literal `&`".to_string()],
+        ),
+        ParseError::InvalidEntity(name, _) => (
+            format!("cannot expand reference `{}`", text),
+            format!("`{}` is not a known entity or a valid character", name),
+            vec!["declare the entity in the DTD, or write `&amp;` for a literal `&`".to_string()],
+        ),
+        ParseError::RecursiveEntity(name, _) => (
+            format!("entity `{}` refers to itself", name),
+            "recursive reference".to_string(),
+            vec![],
+        ),
+        ParseError::EntityNotAllowed(name, _) => (
+            format!("entity `{}` may not be expanded", name),
+            "referenced here".to_string(),
+            vec!["allow its expansion in the parse options".to_string()],
+        ),
+        ParseError::EncodingMismatch(encoding, _) => (
+            format!(
+                "the encoding declaration `{}` doesn't match the data",
+                encoding
+            ),
+            "declared here".to_string(),
+            vec!["declare the encoding the data is in".to_string()],
+        ),
+        ParseError::MalformedEncoding(encoding, _) => (
+            format!("the data is not valid {}", encoding),
+            "malformed bytes here".to_string(),
+            vec![],
+        ),
+        ParseError::DepthLimit(_) if text.starts_with('&') => (
+            "the replacement text of the entity nests elements too deeply".to_string(),
+            "entity referenced here".to_string(),
+            vec![],
+        ),
+        ParseError::DepthLimit(_) => (
+            format!("element `{}` is nested too deeply", text),
+            "exceeds the maximum depth".to_string(),
+            vec![],
+        ),
+        ParseError::AttributeLimit(_) => (
+            "the element has too many attributes".to_string(),
+            "exceeds the maximum number of attributes".to_string(),
+            vec![],
+        ),
+        ParseError::NameLengthLimit(_) => (
+            "the name is too long".to_string(),
+            "exceeds the maximum name length".to_string(),
+            vec![],
+        ),
+        ParseError::TextSizeLimit(_) => (
+            "the document has too much text".to_string(),
+            "exceeds the maximum text size".to_string(),
+            vec![],
+        ),
+        ParseError::NodeLimit(_) => (
+            "the document has too many nodes".to_string(),
+            "exceeds the maximum number of nodes".to_string(),
+            vec![],
+        ),
+        ParseError::EntityExpansionLimit(name, _) => (
+            format!("expanding entity `{}` exceeds the limits", name),
+            "expanded here".to_string(),
+            vec![],
+        ),
+        ParseError::UnresolvedEntity(name, error, _) => (
+            format!("cannot resolve external entity `{}`", name),
+            "referenced here".to_string(),
+            vec![error.to_string()],
+        ),
+        ParseError::UnresolvedDtd(system_id, error, _) => (
+            format!("cannot resolve external DTD subset `{}`", system_id),
+            "referenced here".to_string(),
+            vec![error.to_string()],
+        ),
+        ParseError::InvalidDtd(_) => (
+            "the DTD is not well-formed".to_string(),
+            "invalid declaration".to_string(),
+            vec![],
+        ),
+        ParseError::UnknownPrefix(prefix, _) => (
+            format!("namespace prefix `{}` is not declared", prefix),
+            "unknown prefix".to_string(),
+            vec![format!("declare it with `xmlns:{}=\"...\"`", prefix)],
+        ),
+        ParseError::DuplicateAttribute(name, _) => (
+            format!("attribute `{}` is specified more than once", name),
+            "duplicate attribute".to_string(),
+            vec![],
+        ),
+        ParseError::UnsupportedVersion(version, _) => (
+            format!("XML version `{}` is not supported", version),
+            "unsupported version".to_string(),
+            vec!["use version 1.0 or 1.1".to_string()],
+        ),
+        ParseError::PrefixUndeclaration(prefix, _) => (
+            format!("namespace prefix `{}` is undeclared", prefix),
+            "empty namespace".to_string(),
+            vec!["prefixes can only be undeclared in XML 1.1".to_string()],
+        ),
+        ParseError::RestrictedChar(c, _) => (
+            format!("control character {:?} is not allowed", c),
+            "restricted character".to_string(),
+            vec![format!("use the character reference `&#x{:X};`", *c as u32)],
+        ),
+        ParseError::NoElementAtTopLevel(_) => (
+            "the document has no document element".to_string(),
+            "expected an element".to_string(),
+            vec![],
+        ),
+        ParseError::MultipleElementsAtTopLevel(_) => (
+            "the document has more than one document element".to_string(),
+            "another element at the top level".to_string(),
+            vec!["wrap the elements in a single document element".to_string()],
+        ),
+        ParseError::TextAtTopLevel(_) => (
+            "text outside of the document element".to_string(),
+            "text at the top level".to_string(),
+            vec![],
+        ),
+        ParseError::XmlParser(error, _) => (error.to_string(), "here".to_string(), vec![]),
+        #[allow(deprecated)]
+        ParseError::UnsupportedNotStandalone(_) | ParseError::DtdUnsupported(_) => {
+            (error.to_string(), "here".to_string(), vec![])
+        }
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn qualified_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}:{}", prefix, name)
+    }
+}
+
+// Find the span of the name of the element that's open at the close tag
+// with the given name span in the span information. This is the innermost
+// element that starts before the close tag and isn't closed before it.
+fn open_element_span(span_info: &SpanInfo, name_span: Span) -> Option<Span> {
+    span_info
+        .map
+        .iter()
+        .filter_map(|(key, (span, _))| match key {
+            SpanInfoKey::ElementStart(node) if span.end <= name_span.start => {
+                let is_open = span_info
+                    .get(SpanInfoKey::ElementEnd(*node))
+                    .is_none_or(|end| end.start >= name_span.start);
+                is_open.then_some(*span)
+            }
+            _ => None,
+        })
+        .max_by_key(|span| span.start)
+}
+
+// Find the span of the name of the element that's open in the source at the
+// close tag with the given name span, so we can tell what close tag was
+// expected there. We only need this if we don't have span information.
+fn expected_close_tag(source: &str, name_span: Span) -> Option<Span> {
+    // xmlparser can't handle the internal subset, so we blank it out
+    let source = match find_internal_subset(source) {
+        Some(range) => {
+            let mut blanked = source.to_string();
+            blanked.replace_range(range.clone(), &" ".repeat(range.len()));
+            Cow::Owned(blanked)
+        }
+        None => Cow::Borrowed(source),
+    };
+    let mut open = Vec::new();
+    let mut pending = None;
+    for token in Tokenizer::from(source.as_ref()) {
+        let Ok(token) = token else {
+            break;
+        };
+        match token {
+            Token::ElementStart { prefix, local, .. } => {
+                pending = Some(Span::from_prefix_name(prefix, local));
+            }
+            Token::ElementEnd { end, span } => {
+                // this is the close tag itself
+                if span.end() > name_span.start {
+                    break;
+                }
+                match end {
+                    ElementEnd::Open => open.extend(pending.take()),
+                    ElementEnd::Empty => pending = None,
+                    ElementEnd::Close(..) => {
+                        open.pop();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    open.pop()
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line_number = self.start.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{}--> {}:{}", gutter, self.start.line, self.start.column)?;
+        writeln!(f, "{} |", gutter)?;
+        // tabs would throw off the underline
+        writeln!(
+            f,
+            "{} | {}",
+            line_number,
+            self.source_line.replace('\t', " ")
+        )?;
+        // we underline up to the end of the line the span starts on
+        let line_length = self.source_line.chars().count();
+        let start = self.start.column - 1;
+        let end = if self.end.line == self.start.line {
+            self.end.column - 1
+        } else {
+            line_length
+        };
+        let width = end.saturating_sub(start).max(1);
+        write!(
+            f,
+            "{} | {}{} {}",
+            gutter,
+            " ".repeat(start),
+            "^".repeat(width),
+            self.label
+        )?;
+        if !self.help.is_empty() {
+            write!(f, "\n{} |", gutter)?;
+            for help in &self.help {
+                write!(f, "\n{} = help: {}", gutter, help)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_close_tag() {
+        let source = "<doc><p:a xmlns:p='x'><b/></x></p:a></doc>";
+        assert_eq!(
+            expected_close_tag(source, Span::new(28, 29)),
+            Some(Span::new(6, 9))
+        );
+    }
+
+    #[test]
+    fn test_expected_close_tag_internal_subset() {
+        let source = "<!DOCTYPE doc [<!ENTITY e '<x>'>]><doc></x></doc>";
+        assert_eq!(
+            expected_close_tag(source, Span::new(41, 42)),
+            Some(Span::new(35, 38))
+        );
+    }
+}
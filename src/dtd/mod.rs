@@ -0,0 +1,406 @@
+//! Document type declarations.
+//!
+//! When Xot parses a document that has a `<!DOCTYPE ...>` declaration, it
+//! retains it as a [`Dtd`]. You can obtain it with [`Xot::dtd`] given the
+//! document node.
+//!
+//! ```rust
+//! use xot::Xot;
+//!
+//! let mut xot = Xot::new();
+//! let root = xot.parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)?;
+//! let dtd = xot.dtd(root).unwrap();
+//! assert_eq!(dtd.name(), "doc");
+//! assert_eq!(dtd.system_id(), Some("doc.dtd"));
+//! # Ok::<(), xot::Error>(())
+//! ```
+
+pub(crate) mod parser;
+mod validate;
+
+pub use validate::{ValidationError, ValidationErrorKind};
+
+use ahash::{HashMap, HashMapExt};
+
+#[cfg(doc)]
+use crate::Xot;
+
+use crate::output::xml::DocType;
+
+/// A document type declaration.
+///
+/// This describes the `<!DOCTYPE ...>` declaration of a parsed document: the
+/// name of the root element, the external identifier (public and system id),
+/// if any, and the internal subset, if any. It also holds the element and
+/// attribute list declarations that were read from the internal and external
+/// subsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dtd {
+    name: String,
+    external_id: Option<DocType>,
+    internal_subset: Option<String>,
+    content_models: HashMap<String, ContentModel>,
+    attribute_declarations: HashMap<String, Vec<AttributeDeclaration>>,
+}
+
+impl Dtd {
+    pub(crate) fn new(name: String, external_id: Option<DocType>) -> Self {
+        Dtd {
+            name,
+            external_id,
+            internal_subset: None,
+            content_models: HashMap::new(),
+            attribute_declarations: HashMap::new(),
+        }
+    }
+
+    // An element type may only be declared once; we keep the first
+    // declaration.
+    pub(crate) fn add_content_model(&mut self, element: &str, content_model: ContentModel) {
+        self.content_models
+            .entry(element.to_string())
+            .or_insert(content_model);
+    }
+
+    // If an attribute is declared more than once for the same element, the
+    // first declaration is binding.
+    pub(crate) fn add_attribute_declaration(
+        &mut self,
+        element: &str,
+        declaration: AttributeDeclaration,
+    ) {
+        let declarations = self
+            .attribute_declarations
+            .entry(element.to_string())
+            .or_default();
+        if !declarations
+            .iter()
+            .any(|existing| existing.name == declaration.name)
+        {
+            declarations.push(declaration);
+        }
+    }
+
+    pub(crate) fn set_internal_subset(&mut self, internal_subset: String) {
+        self.internal_subset = Some(internal_subset);
+    }
+
+    /// The name of the root element as declared in the doctype.
+    ///
+    /// This is the name as it appears in the source, including any prefix.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The public identifier, if any.
+    pub fn public_id(&self) -> Option<&str> {
+        match &self.external_id {
+            Some(DocType::Public { public, .. }) => Some(public),
+            _ => None,
+        }
+    }
+
+    /// The system identifier, if any.
+    pub fn system_id(&self) -> Option<&str> {
+        match &self.external_id {
+            Some(DocType::Public { system, .. }) => Some(system),
+            Some(DocType::System { system }) => Some(system),
+            None => None,
+        }
+    }
+
+    /// The internal subset, if any.
+    ///
+    /// This is the literal text between `[` and `]` in the doctype
+    /// declaration.
+    pub fn internal_subset(&self) -> Option<&str> {
+        self.internal_subset.as_deref()
+    }
+
+    /// The external identifier as a [`DocType`].
+    ///
+    /// You can pass this into
+    /// [`output::xml::Parameters`](crate::output::xml::Parameters) to
+    /// reproduce the doctype declaration during serialization. If the doctype
+    /// declaration has no external identifier this returns `None`.
+    ///
+    /// ```rust
+    /// use xot::{Xot, output};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = r#"<!DOCTYPE doc PUBLIC "-//Example//DTD Doc//EN" "doc.dtd">
+    /// <doc/>"#;
+    /// let root = xot.parse(xml)?;
+    /// let doc_type = xot.dtd(root).unwrap().doc_type();
+    ///
+    /// let serialized = xot.serialize_xml_string(output::xml::Parameters {
+    ///     doctype: doc_type,
+    ///     ..Default::default()
+    /// }, root)?;
+    /// assert_eq!(serialized, xml);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn doc_type(&self) -> Option<DocType> {
+        self.external_id.clone()
+    }
+
+    /// The content model of an element as declared in its element type
+    /// declaration (`<!ELEMENT ...>`), if any.
+    ///
+    /// DTDs are not aware of namespaces, so the element name is the name as
+    /// it appears in the source, including any prefix.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<!DOCTYPE doc [
+    /// <!ELEMENT doc (title, (p | list)*)>
+    /// <!ELEMENT p (#PCDATA | em)*>
+    /// ]><doc><title/></doc>"#)?;
+    /// let dtd = xot.dtd(root).unwrap();
+    /// assert_eq!(dtd.content_model("doc").unwrap().to_string(), "(title,(p|list)*)");
+    /// assert_eq!(dtd.content_model("p").unwrap().to_string(), "(#PCDATA|em)*");
+    /// assert_eq!(dtd.content_model("title"), None);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn content_model(&self, element: &str) -> Option<&ContentModel> {
+        self.content_models.get(element)
+    }
+
+    /// The attributes declared for an element in attribute list
+    /// declarations (`<!ATTLIST ...>`).
+    ///
+    /// DTDs are not aware of namespaces, so the element name is the name as
+    /// it appears in the source, including any prefix.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    /// use xot::dtd::{AttributeDefault, AttributeType};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<!DOCTYPE doc [
+    /// <!ATTLIST doc id ID #IMPLIED kind (a|b) "a">
+    /// ]><doc/>"#)?;
+    /// let dtd = xot.dtd(root).unwrap();
+    /// let declarations = dtd.attribute_declarations("doc");
+    /// assert_eq!(declarations.len(), 2);
+    /// assert_eq!(declarations[0].name(), "id");
+    /// assert_eq!(declarations[0].attribute_type(), &AttributeType::Id);
+    /// assert_eq!(declarations[1].default(), &AttributeDefault::Value("a".to_string()));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn attribute_declarations(&self, element: &str) -> &[AttributeDeclaration] {
+        self.attribute_declarations
+            .get(element)
+            .map(|declarations| declarations.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// The declaration of an attribute in an attribute list declaration.
+///
+/// <https://www.w3.org/TR/xml/#attdecls>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeDeclaration {
+    name: String,
+    attribute_type: AttributeType,
+    default: AttributeDefault,
+}
+
+impl AttributeDeclaration {
+    pub(crate) fn new(
+        name: String,
+        attribute_type: AttributeType,
+        default: AttributeDefault,
+    ) -> Self {
+        AttributeDeclaration {
+            name,
+            attribute_type,
+            default,
+        }
+    }
+
+    /// The name of the attribute, as it appears in the declaration.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type of the attribute.
+    pub fn attribute_type(&self) -> &AttributeType {
+        &self.attribute_type
+    }
+
+    /// The default of the attribute.
+    pub fn default(&self) -> &AttributeDefault {
+        &self.default
+    }
+}
+
+/// The type of a declared attribute.
+///
+/// <https://www.w3.org/TR/xml/#sec-attribute-types>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeType {
+    /// `CDATA`: any string.
+    Cdata,
+    /// `ID`: a name that identifies the element.
+    Id,
+    /// `IDREF`: a reference to an ID.
+    IdRef,
+    /// `IDREFS`: references to IDs, separated by spaces.
+    IdRefs,
+    /// `ENTITY`: the name of an unparsed entity.
+    Entity,
+    /// `ENTITIES`: names of unparsed entities, separated by spaces.
+    Entities,
+    /// `NMTOKEN`: a name token.
+    NmToken,
+    /// `NMTOKENS`: name tokens, separated by spaces.
+    NmTokens,
+    /// `NOTATION (...)`: one of the given notation names.
+    Notation(Vec<String>),
+    /// `(...)`: one of the given name tokens.
+    Enumeration(Vec<String>),
+}
+
+impl AttributeType {
+    /// Whether the type is a tokenized type, which is every type except
+    /// `CDATA`.
+    ///
+    /// The values of attributes of tokenized types are normalized further
+    /// than those of `CDATA` attributes: leading and trailing spaces are
+    /// removed, and sequences of spaces are replaced by a single space.
+    pub fn is_tokenized(&self) -> bool {
+        !matches!(self, AttributeType::Cdata)
+    }
+}
+
+/// The default of a declared attribute.
+///
+/// <https://www.w3.org/TR/xml/#sec-attr-defaults>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeDefault {
+    /// `#REQUIRED`: the attribute must always be specified.
+    Required,
+    /// `#IMPLIED`: there is no default value.
+    Implied,
+    /// A default value which is used if the attribute is not specified.
+    Value(String),
+    /// `#FIXED`: the attribute always has this value.
+    Fixed(String),
+}
+
+impl AttributeDefault {
+    /// The default value, if any.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            AttributeDefault::Value(value) | AttributeDefault::Fixed(value) => Some(value),
+            AttributeDefault::Required | AttributeDefault::Implied => None,
+        }
+    }
+}
+
+/// The content model of an element type declaration.
+///
+/// Its `Display` implementation gives the content model in DTD syntax.
+///
+/// <https://www.w3.org/TR/xml/#elemdecls>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentModel {
+    /// `EMPTY`: the element has no content.
+    Empty,
+    /// `ANY`: the element may contain any declared elements and text.
+    Any,
+    /// `(#PCDATA | ...)*`: text mixed with any of the given elements, in
+    /// any order.
+    Mixed(Vec<String>),
+    /// Element content: child elements as described by the content particle,
+    /// optionally separated by whitespace.
+    Children(ContentParticle),
+}
+
+impl std::fmt::Display for ContentModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ContentModel::Empty => write!(f, "EMPTY"),
+            ContentModel::Any => write!(f, "ANY"),
+            ContentModel::Mixed(names) if names.is_empty() => write!(f, "(#PCDATA)"),
+            ContentModel::Mixed(names) => write!(f, "(#PCDATA|{})*", names.join("|")),
+            ContentModel::Children(particle) => write!(f, "{}", particle),
+        }
+    }
+}
+
+/// A content particle in element content: a name, a choice or a sequence,
+/// along with how often it may occur.
+///
+/// <https://www.w3.org/TR/xml/#sec-element-content>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentParticle {
+    kind: ParticleKind,
+    occurrence: Occurrence,
+}
+
+impl ContentParticle {
+    pub(crate) fn new(kind: ParticleKind, occurrence: Occurrence) -> Self {
+        ContentParticle { kind, occurrence }
+    }
+
+    /// What the particle consists of.
+    pub fn kind(&self) -> &ParticleKind {
+        &self.kind
+    }
+
+    /// How often the particle may occur.
+    pub fn occurrence(&self) -> Occurrence {
+        self.occurrence
+    }
+}
+
+impl std::fmt::Display for ContentParticle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |f: &mut std::fmt::Formatter, particles: &[ContentParticle], separator| {
+            let particles = particles
+                .iter()
+                .map(|particle| particle.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "({})", particles.join(separator))
+        };
+        match &self.kind {
+            ParticleKind::Name(name) => write!(f, "{}", name)?,
+            ParticleKind::Choice(particles) => join(f, particles, "|")?,
+            ParticleKind::Sequence(particles) => join(f, particles, ",")?,
+        }
+        match self.occurrence {
+            Occurrence::Once => Ok(()),
+            Occurrence::Optional => write!(f, "?"),
+            Occurrence::ZeroOrMore => write!(f, "*"),
+            Occurrence::OneOrMore => write!(f, "+"),
+        }
+    }
+}
+
+/// What a content particle consists of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticleKind {
+    /// An element with the given name.
+    Name(String),
+    /// One of the given particles: `(a | b)`.
+    Choice(Vec<ContentParticle>),
+    /// The given particles in order: `(a, b)`.
+    Sequence(Vec<ContentParticle>),
+}
+
+/// How often a content particle may occur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occurrence {
+    /// Exactly once.
+    Once,
+    /// `?`: zero or one times.
+    Optional,
+    /// `*`: zero or more times.
+    ZeroOrMore,
+    /// `+`: one or more times.
+    OneOrMore,
+}
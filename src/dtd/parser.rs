@@ -0,0 +1,1168 @@
+// A parser for the declarations in the internal and external DTD subsets.
+//
+// xmlparser only handles a limited form of the internal subset, so we parse
+// DTD subsets ourselves. This supports parameter entities, both internal and
+// external, conditional sections, and element type and attribute list
+// declarations.
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use ahash::{HashMap, HashMapExt};
+
+use crate::entity::{
+    normalize_tokenized_attribute, parse_attribute, replacement_text, strip_text_declaration,
+    Entities, ExternalEntity,
+};
+use crate::error::ParseError;
+use crate::parseoptions::{exceeds, ParseLimits};
+use crate::resolver::{ResolveError, Resolver};
+use crate::Span;
+
+use super::{
+    AttributeDeclaration, AttributeDefault, AttributeType, ContentModel, ContentParticle, Dtd,
+    Occurrence, ParticleKind,
+};
+
+// Where the text we parse is located.
+#[derive(Debug, Clone, Copy)]
+enum Origin {
+    // The text is in the source, starting at this position.
+    Source(usize),
+    // The text is the replacement text of an entity, or an external
+    // subset. Everything in it is located at the reference.
+    Reference(Span),
+}
+
+impl Origin {
+    fn span(&self, start: usize, end: usize) -> Span {
+        match self {
+            Origin::Source(offset) => Span::new(offset + start, offset + end),
+            Origin::Reference(span) => *span,
+        }
+    }
+
+    fn position(&self, position: usize) -> Option<usize> {
+        match self {
+            Origin::Source(offset) => Some(offset + position),
+            Origin::Reference(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Terminator {
+    // the end of the text
+    End,
+    // the end of a conditional section, `]]>`
+    ConditionalSection,
+}
+
+enum ParameterEntity {
+    Internal(Rc<str>),
+    External(ExternalEntity),
+}
+
+// The replacement text of a parameter entity, along with the URI it was
+// loaded from if it's external.
+struct Replacement {
+    text: Rc<str>,
+    base: Option<String>,
+}
+
+// The text of a DTD subset, or of the replacement text of a parameter entity.
+struct Subset<'a> {
+    text: &'a str,
+    position: usize,
+    origin: Origin,
+    // the URI of the resource this text is in, if known
+    base: Option<&'a str>,
+}
+
+impl<'a> Subset<'a> {
+    fn new(text: &'a str, origin: Origin, base: Option<&'a str>) -> Self {
+        Subset {
+            text,
+            position: 0,
+            origin,
+            base,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches(is_whitespace).len();
+    }
+
+    fn here(&self) -> Span {
+        self.origin.span(self.position, self.position)
+    }
+
+    fn span(&self, start: usize) -> Span {
+        self.origin.span(start, self.position)
+    }
+
+    // skip past the given delimiter
+    fn skip_past(&mut self, delimiter: &str) -> Result<(), ParseError> {
+        let index = self
+            .rest()
+            .find(delimiter)
+            .ok_or_else(|| ParseError::InvalidDtd(self.here()))?;
+        self.position += index + delimiter.len();
+        Ok(())
+    }
+
+    // a markup declaration, from `<!` up to and including the closing `>`,
+    // returning its start position
+    fn declaration(&mut self) -> Result<usize, ParseError> {
+        let start = self.position;
+        let mut quote = None;
+        for (index, c) in self.rest().char_indices() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                (None, '>') => {
+                    self.position += index + 1;
+                    return Ok(start);
+                }
+                _ => {}
+            }
+        }
+        Err(ParseError::InvalidDtd(self.here()))
+    }
+
+    // a parameter entity reference `%name;`
+    fn reference(&mut self) -> Result<(&'a str, Span), ParseError> {
+        let start = self.position;
+        let rest = &self.rest()[1..];
+        let length = name_length(rest);
+        if length == 0 || !rest[length..].starts_with(';') {
+            return Err(ParseError::InvalidDtd(self.here()));
+        }
+        self.position += length + 2;
+        Ok((&rest[..length], self.span(start)))
+    }
+
+    // skip the content of an ignored conditional section, including nested
+    // conditional sections, up to and including the closing `]]>`
+    fn skip_ignored_section(&mut self) -> Result<(), ParseError> {
+        let mut depth = 1;
+        while depth > 0 {
+            let rest = self.rest();
+            let open = rest.find("<![");
+            let close = rest
+                .find("]]>")
+                .ok_or_else(|| ParseError::InvalidDtd(self.here()))?;
+            match open {
+                Some(open) if open < close => {
+                    depth += 1;
+                    self.position += open + 3;
+                }
+                _ => {
+                    depth -= 1;
+                    self.position += close + 3;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses DTD subsets, declaring the general entities, element types and
+/// attribute lists it finds.
+pub(crate) struct DtdParser {
+    resolver: Arc<dyn Resolver>,
+    parameter_entities: HashMap<String, ParameterEntity>,
+    expanding: Vec<String>,
+    expanded_size: usize,
+    limits: ParseLimits,
+    // If we cannot read a parameter entity, we must not process any
+    // declarations after it, as the entity could have changed their meaning.
+    // https://www.w3.org/TR/xml/#proc-types
+    skip_declarations: bool,
+}
+
+impl DtdParser {
+    pub(crate) fn new(resolver: Arc<dyn Resolver>, limits: ParseLimits) -> Self {
+        DtdParser {
+            resolver,
+            parameter_entities: HashMap::new(),
+            expanding: Vec::new(),
+            expanded_size: 0,
+            limits,
+            skip_declarations: false,
+        }
+    }
+
+    /// Parse the internal subset, which is located at `start` in the source.
+    pub(crate) fn parse_internal_subset(
+        &mut self,
+        internal_subset: &str,
+        start: usize,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let mut subset = Subset::new(internal_subset, Origin::Source(start), None);
+        self.parse_declarations(&mut subset, Terminator::End, dtd, entities)
+    }
+
+    /// Resolve and parse the external subset. The span is that of the system
+    /// id in the document type declaration.
+    ///
+    /// If the resolver refuses to fetch the external subset, it's skipped.
+    pub(crate) fn parse_external_subset(
+        &mut self,
+        public_id: Option<&str>,
+        system_id: &str,
+        span: Span,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let resource = match self.resolver.resolve(public_id, system_id, None) {
+            Ok(resource) => resource,
+            Err(ResolveError::Refused) => return Ok(()),
+            Err(e) => return Err(ParseError::UnresolvedDtd(system_id.to_string(), e, span)),
+        };
+        let text = strip_text_declaration(resource.text());
+        let mut subset = Subset::new(text, Origin::Reference(span), Some(resource.uri()));
+        self.parse_declarations(&mut subset, Terminator::End, dtd, entities)
+    }
+
+    fn parse_declarations(
+        &mut self,
+        subset: &mut Subset,
+        terminator: Terminator,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        loop {
+            subset.skip_whitespace();
+            let rest = subset.rest();
+            if rest.is_empty() {
+                return match terminator {
+                    Terminator::End => Ok(()),
+                    Terminator::ConditionalSection => Err(ParseError::InvalidDtd(subset.here())),
+                };
+            }
+            if rest.starts_with("]]>") && terminator == Terminator::ConditionalSection {
+                subset.position += 3;
+                return Ok(());
+            }
+            if rest.starts_with("<!--") {
+                subset.skip_past("-->")?;
+            } else if rest.starts_with("<?") {
+                subset.skip_past("?>")?;
+            } else if rest.starts_with("<![") {
+                self.conditional_section(subset, dtd, entities)?;
+            } else if rest.starts_with("<!") {
+                let start = subset.declaration()?;
+                self.declaration(subset, start, dtd, entities)?;
+            } else if rest.starts_with('%') {
+                let (name, span) = subset.reference()?;
+                self.include_parameter_entity(name, span, subset.base, dtd, entities)?;
+            } else {
+                return Err(ParseError::InvalidDtd(subset.here()));
+            }
+        }
+    }
+
+    // https://www.w3.org/TR/xml/#sec-condition-sect
+    fn conditional_section(
+        &mut self,
+        subset: &mut Subset,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let start = subset.position;
+        subset.position += 3;
+        let Some(keyword_length) = subset.rest().find('[') else {
+            return Err(ParseError::InvalidDtd(subset.here()));
+        };
+        let keyword = &subset.rest()[..keyword_length];
+        let span = subset.origin.span(start, subset.position + keyword_length);
+        subset.position += keyword_length + 1;
+        // the keyword may be given by a parameter entity reference
+        let Some(keyword) = self.expand_references(keyword, span)? else {
+            // we cannot tell whether to include this section
+            subset.skip_ignored_section()?;
+            return Ok(());
+        };
+        match keyword.trim_matches(is_whitespace) {
+            "INCLUDE" => {
+                self.parse_declarations(subset, Terminator::ConditionalSection, dtd, entities)
+            }
+            "IGNORE" => subset.skip_ignored_section(),
+            _ => Err(ParseError::InvalidDtd(span)),
+        }
+    }
+
+    fn declaration(
+        &mut self,
+        subset: &Subset,
+        start: usize,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let text = &subset.text[start..subset.position];
+        let span = subset.span(start);
+        let keyword_length = text[2..]
+            .find(|c: char| !c.is_ascii_uppercase())
+            .unwrap_or(text.len() - 2);
+        let keyword = &text[2..2 + keyword_length];
+        match keyword {
+            "ENTITY" | "ATTLIST" | "ELEMENT" => {}
+            // notation declarations are not used
+            "NOTATION" => return Ok(()),
+            _ => return Err(ParseError::InvalidDtd(span)),
+        }
+        if self.skip_declarations {
+            return Ok(());
+        }
+        // parameter entity references in the declaration are replaced by
+        // their replacement text
+        let Some(expanded) = self.expand_references(text, span)? else {
+            self.skip_declarations = true;
+            return Ok(());
+        };
+        // we can only locate positions in the declaration precisely if it's
+        // in the source and wasn't changed by expansion
+        let base_position = match expanded {
+            Cow::Borrowed(_) => subset.origin.position(start),
+            Cow::Owned(_) => None,
+        };
+        match keyword {
+            "ENTITY" => {
+                self.entity_declaration(&expanded, span, base_position, subset.base, entities)
+            }
+            "ATTLIST" => attribute_list_declaration(&expanded, span, base_position, dtd, entities),
+            _ => element_declaration(&expanded, span, dtd),
+        }
+    }
+
+    // https://www.w3.org/TR/xml/#sec-entity-decl
+    fn entity_declaration(
+        &mut self,
+        text: &str,
+        span: Span,
+        base_position: Option<usize>,
+        base: Option<&str>,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let invalid = || ParseError::InvalidDtd(span);
+        let mut tokens = Tokens::new(text, "<!ENTITY".len());
+        tokens.required_whitespace().ok_or_else(invalid)?;
+        let is_parameter_entity = tokens.symbol('%');
+        if is_parameter_entity {
+            tokens.required_whitespace().ok_or_else(invalid)?;
+        }
+        let name = tokens.name().ok_or_else(invalid)?;
+        tokens.required_whitespace().ok_or_else(invalid)?;
+        if let Some((value_start, value)) = tokens.literal() {
+            tokens.end().ok_or_else(invalid)?;
+            let Some(value) = self.expand_literal_references(value, span)? else {
+                self.skip_declarations = true;
+                return Ok(());
+            };
+            // we can only report precise positions if the value wasn't changed
+            let value_position = match value {
+                Cow::Borrowed(_) => base_position.map(|position| position + value_start),
+                Cow::Owned(_) => None,
+            };
+            let relocate = |e: ParseError| match value_position {
+                Some(_) => e,
+                None => e.with_span(span),
+            };
+            if is_parameter_entity {
+                if !self.parameter_entities.contains_key(name) {
+                    let replacement_text =
+                        replacement_text(&value, value_position.unwrap_or(0), entities.version)
+                            .map_err(relocate)?;
+                    self.parameter_entities.insert(
+                        name.to_string(),
+                        ParameterEntity::Internal(replacement_text.into()),
+                    );
+                }
+            } else {
+                entities
+                    .declare(name, &value, value_position.unwrap_or(0))
+                    .map_err(relocate)?;
+            }
+        } else {
+            let (public_id, system_id) = tokens.external_id().ok_or_else(invalid)?;
+            let external_entity = ExternalEntity {
+                public_id: public_id.map(|public_id| public_id.to_string()),
+                system_id: system_id.to_string(),
+                base: base.map(|base| base.to_string()),
+            };
+            if is_parameter_entity {
+                tokens.end().ok_or_else(invalid)?;
+                self.parameter_entities
+                    .entry(name.to_string())
+                    .or_insert(ParameterEntity::External(external_entity));
+            } else {
+                // https://www.w3.org/TR/xml/#NT-NDataDecl
+                let has_whitespace = tokens.whitespace();
+                if has_whitespace && tokens.keyword("NDATA") {
+                    tokens.required_whitespace().ok_or_else(invalid)?;
+                    tokens.name().ok_or_else(invalid)?;
+                    tokens.end().ok_or_else(invalid)?;
+                    entities.declare_unparsed(name);
+                } else {
+                    tokens.end().ok_or_else(invalid)?;
+                    entities.declare_external(name, external_entity);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn include_parameter_entity(
+        &mut self,
+        name: &str,
+        span: Span,
+        base: Option<&str>,
+        dtd: &mut Dtd,
+        entities: &mut Entities,
+    ) -> Result<(), ParseError> {
+        let Some(replacement) = self.enter(name, span)? else {
+            self.skip_declarations = true;
+            return Ok(());
+        };
+        let mut subset = Subset::new(
+            &replacement.text,
+            Origin::Reference(span),
+            replacement.base.as_deref().or(base),
+        );
+        self.parse_declarations(&mut subset, Terminator::End, dtd, entities)?;
+        self.exit();
+        Ok(())
+    }
+
+    // Replace parameter entity references in markup declarations by their
+    // replacement text, padded with spaces.
+    // https://www.w3.org/TR/xml/#as-PE
+    //
+    // Returns `None` if a parameter entity cannot be read.
+    fn expand_references<'a>(
+        &mut self,
+        text: &'a str,
+        span: Span,
+    ) -> Result<Option<Cow<'a, str>>, ParseError> {
+        self.expand(text, span, false)
+    }
+
+    // Replace parameter entity references in an entity value by their
+    // replacement text.
+    // https://www.w3.org/TR/xml/#inliteral
+    fn expand_literal_references<'a>(
+        &mut self,
+        text: &'a str,
+        span: Span,
+    ) -> Result<Option<Cow<'a, str>>, ParseError> {
+        self.expand(text, span, true)
+    }
+
+    fn expand<'a>(
+        &mut self,
+        text: &'a str,
+        span: Span,
+        in_literal: bool,
+    ) -> Result<Option<Cow<'a, str>>, ParseError> {
+        let mut result = String::new();
+        let mut quote = None;
+        let mut copied = 0;
+        let mut chars = text.char_indices();
+        while let Some((index, c)) = chars.next() {
+            if !in_literal {
+                match (quote, c) {
+                    (None, '"' | '\'') => {
+                        quote = Some(c);
+                        continue;
+                    }
+                    (Some(q), c) if q == c => {
+                        quote = None;
+                        continue;
+                    }
+                    (Some(_), _) => continue,
+                    _ => {}
+                }
+            }
+            if c != '%' {
+                continue;
+            }
+            let rest = &text[index + 1..];
+            let length = name_length(rest);
+            if length == 0 || !rest[length..].starts_with(';') {
+                // a `%` that is not a reference, as in `<!ENTITY % name ...>`
+                continue;
+            }
+            let name = &rest[..length];
+            let Some(replacement) = self.enter(name, span)? else {
+                return Ok(None);
+            };
+            let expanded = self.expand(&replacement.text, span, in_literal);
+            self.exit();
+            let Some(expanded) = expanded? else {
+                return Ok(None);
+            };
+            result.push_str(&text[copied..index]);
+            if !in_literal {
+                result.push(' ');
+            }
+            result.push_str(&expanded);
+            if !in_literal {
+                result.push(' ');
+            }
+            copied = index + length + 2;
+            // skip past the reference
+            for _ in 0..length + 1 {
+                chars.next();
+            }
+        }
+        if copied == 0 {
+            return Ok(Some(Cow::Borrowed(text)));
+        }
+        result.push_str(&text[copied..]);
+        Ok(Some(Cow::Owned(result)))
+    }
+
+    // Start the expansion of a parameter entity, returning its replacement.
+    // Returns `None` if the entity cannot be read. You must call `exit` when
+    // done.
+    fn enter(&mut self, name: &str, span: Span) -> Result<Option<Replacement>, ParseError> {
+        let (replacement_text, base) = match self.parameter_entities.get(name) {
+            Some(ParameterEntity::Internal(replacement_text)) => (replacement_text.clone(), None),
+            Some(ParameterEntity::External(external_entity)) => {
+                match self.resolver.resolve(
+                    external_entity.public_id.as_deref(),
+                    &external_entity.system_id,
+                    external_entity.base.as_deref(),
+                ) {
+                    Ok(resource) => (
+                        strip_text_declaration(resource.text()).into(),
+                        Some(resource.uri().to_string()),
+                    ),
+                    Err(ResolveError::Refused) => return Ok(None),
+                    Err(e) => return Err(ParseError::UnresolvedEntity(name.to_string(), e, span)),
+                }
+            }
+            // a parameter entity that isn't declared, which may be because
+            // its declaration wasn't read
+            None => return Ok(None),
+        };
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return Err(ParseError::RecursiveEntity(name.to_string(), span));
+        }
+        self.expanded_size += replacement_text.len();
+        if exceeds(self.limits.max_entity_depth, self.expanding.len() + 1)
+            || exceeds(self.limits.max_entity_expansion_size, self.expanded_size)
+        {
+            return Err(ParseError::EntityExpansionLimit(name.to_string(), span));
+        }
+        self.expanding.push(name.to_string());
+        Ok(Some(Replacement {
+            text: replacement_text,
+            base,
+        }))
+    }
+
+    fn exit(&mut self) {
+        self.expanding.pop();
+    }
+}
+
+// https://www.w3.org/TR/xml/#elemdecls
+fn element_declaration(text: &str, span: Span, dtd: &mut Dtd) -> Result<(), ParseError> {
+    let invalid = || ParseError::InvalidDtd(span);
+    let mut tokens = Tokens::new(text, "<!ELEMENT".len());
+    tokens.required_whitespace().ok_or_else(invalid)?;
+    let element = tokens.name().ok_or_else(invalid)?;
+    tokens.required_whitespace().ok_or_else(invalid)?;
+    let content_model = tokens.content_model().ok_or_else(invalid)?;
+    tokens.whitespace();
+    if tokens.rest() != ">" {
+        return Err(invalid());
+    }
+    dtd.add_content_model(element, content_model);
+    Ok(())
+}
+
+// https://www.w3.org/TR/xml/#attdecls
+fn attribute_list_declaration(
+    text: &str,
+    span: Span,
+    base_position: Option<usize>,
+    dtd: &mut Dtd,
+    entities: &mut Entities,
+) -> Result<(), ParseError> {
+    let invalid = || ParseError::InvalidDtd(span);
+    let mut tokens = Tokens::new(text, "<!ATTLIST".len());
+    tokens.required_whitespace().ok_or_else(invalid)?;
+    let element = tokens.name().ok_or_else(invalid)?;
+    loop {
+        let has_whitespace = tokens.whitespace();
+        if tokens.rest() == ">" {
+            return Ok(());
+        }
+        if !has_whitespace {
+            return Err(invalid());
+        }
+        let name = tokens.name().ok_or_else(invalid)?;
+        tokens.required_whitespace().ok_or_else(invalid)?;
+        let attribute_type = tokens.attribute_type().ok_or_else(invalid)?;
+        tokens.required_whitespace().ok_or_else(invalid)?;
+        // https://www.w3.org/TR/xml/#NT-DefaultDecl
+        let fixed = if tokens.symbol('#') {
+            match tokens.name().ok_or_else(invalid)? {
+                "REQUIRED" => {
+                    let declaration = AttributeDeclaration::new(
+                        name.to_string(),
+                        attribute_type,
+                        AttributeDefault::Required,
+                    );
+                    dtd.add_attribute_declaration(element, declaration);
+                    continue;
+                }
+                "IMPLIED" => {
+                    let declaration = AttributeDeclaration::new(
+                        name.to_string(),
+                        attribute_type,
+                        AttributeDefault::Implied,
+                    );
+                    dtd.add_attribute_declaration(element, declaration);
+                    continue;
+                }
+                "FIXED" => {
+                    tokens.required_whitespace().ok_or_else(invalid)?;
+                    true
+                }
+                _ => return Err(invalid()),
+            }
+        } else {
+            false
+        };
+        let (value_start, value) = tokens.literal().ok_or_else(invalid)?;
+        // https://www.w3.org/TR/xml/#CleanAttrVals
+        if value.contains('<') {
+            return Err(invalid());
+        }
+        let value_position = base_position.map(|position| position + value_start);
+        let value =
+            parse_attribute(value.into(), value_position.unwrap_or(0), entities).map_err(|e| {
+                match value_position {
+                    Some(_) => e,
+                    None => e.with_span(span),
+                }
+            })?;
+        let value = if attribute_type.is_tokenized() {
+            normalize_tokenized_attribute(&value)
+        } else {
+            value.into_owned()
+        };
+        let default = if fixed {
+            AttributeDefault::Fixed(value)
+        } else {
+            AttributeDefault::Value(value)
+        };
+        dtd.add_attribute_declaration(
+            element,
+            AttributeDeclaration::new(name.to_string(), attribute_type, default),
+        );
+    }
+}
+
+/// Find the end of the internal subset that starts at `start` in `xml`. This
+/// is the position of the closing `]`.
+///
+/// Returns `None` if the internal subset is not well-formed.
+pub(crate) fn internal_subset_end(xml: &str, start: usize) -> Option<usize> {
+    let mut subset = Subset::new(xml, Origin::Source(0), None);
+    subset.position = start;
+    loop {
+        subset.skip_whitespace();
+        let rest = subset.rest();
+        if rest.starts_with(']') {
+            return Some(subset.position);
+        } else if rest.starts_with("<!--") {
+            subset.skip_past("-->").ok()?;
+        } else if rest.starts_with("<?") {
+            subset.skip_past("?>").ok()?;
+        } else if rest.starts_with("<!") {
+            subset.declaration().ok()?;
+        } else if rest.starts_with('%') {
+            subset.reference().ok()?;
+        } else {
+            return None;
+        }
+    }
+}
+
+// The tokens in a markup declaration.
+struct Tokens<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str, position: usize) -> Self {
+        Tokens { text, position }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn whitespace(&mut self) -> bool {
+        let rest = self.rest();
+        let length = rest.len() - rest.trim_start_matches(is_whitespace).len();
+        self.position += length;
+        length > 0
+    }
+
+    fn required_whitespace(&mut self) -> Option<()> {
+        self.whitespace().then_some(())
+    }
+
+    fn symbol(&mut self, symbol: char) -> bool {
+        if self.rest().starts_with(symbol) {
+            self.position += symbol.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let rest = self.rest();
+        if rest.starts_with(keyword) && name_length(rest) == keyword.len() {
+            self.position += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let length = name_length(rest);
+        if length == 0 {
+            return None;
+        }
+        self.position += length;
+        Some(&rest[..length])
+    }
+
+    fn name_token(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        let length = name_token_length(rest);
+        if length == 0 {
+            return None;
+        }
+        self.position += length;
+        Some(&rest[..length])
+    }
+
+    // https://www.w3.org/TR/xml/#NT-AttType
+    fn attribute_type(&mut self) -> Option<AttributeType> {
+        if self.symbol('(') {
+            return Some(AttributeType::Enumeration(self.name_token_group()?));
+        }
+        Some(match self.name()? {
+            "CDATA" => AttributeType::Cdata,
+            "ID" => AttributeType::Id,
+            "IDREF" => AttributeType::IdRef,
+            "IDREFS" => AttributeType::IdRefs,
+            "ENTITY" => AttributeType::Entity,
+            "ENTITIES" => AttributeType::Entities,
+            "NMTOKEN" => AttributeType::NmToken,
+            "NMTOKENS" => AttributeType::NmTokens,
+            "NOTATION" => {
+                self.required_whitespace()?;
+                if !self.symbol('(') {
+                    return None;
+                }
+                AttributeType::Notation(self.name_token_group()?)
+            }
+            _ => return None,
+        })
+    }
+
+    // The alternatives of an enumerated type, after the opening `(`. As
+    // names are name tokens as well, we use this for notation types too.
+    fn name_token_group(&mut self) -> Option<Vec<String>> {
+        let mut name_tokens = Vec::new();
+        loop {
+            self.whitespace();
+            name_tokens.push(self.name_token()?.to_string());
+            self.whitespace();
+            if self.symbol(')') {
+                return Some(name_tokens);
+            }
+            if !self.symbol('|') {
+                return None;
+            }
+        }
+    }
+
+    // https://www.w3.org/TR/xml/#NT-contentspec
+    fn content_model(&mut self) -> Option<ContentModel> {
+        if !self.symbol('(') {
+            return match self.name()? {
+                "EMPTY" => Some(ContentModel::Empty),
+                "ANY" => Some(ContentModel::Any),
+                _ => None,
+            };
+        }
+        self.whitespace();
+        if !self.symbol('#') {
+            let kind = self.particle_group()?;
+            return Some(ContentModel::Children(ContentParticle::new(
+                kind,
+                self.occurrence(),
+            )));
+        }
+        // https://www.w3.org/TR/xml/#NT-Mixed
+        if self.name()? != "PCDATA" {
+            return None;
+        }
+        let mut names = Vec::new();
+        loop {
+            self.whitespace();
+            if self.symbol(')') {
+                // with element names, the group has to repeat
+                if !self.symbol('*') && !names.is_empty() {
+                    return None;
+                }
+                return Some(ContentModel::Mixed(names));
+            }
+            if !self.symbol('|') {
+                return None;
+            }
+            self.whitespace();
+            names.push(self.name()?.to_string());
+        }
+    }
+
+    // https://www.w3.org/TR/xml/#NT-cp
+    fn particle(&mut self) -> Option<ContentParticle> {
+        let kind = if self.symbol('(') {
+            self.whitespace();
+            self.particle_group()?
+        } else {
+            ParticleKind::Name(self.name()?.to_string())
+        };
+        Some(ContentParticle::new(kind, self.occurrence()))
+    }
+
+    // A choice or a sequence, after the opening `(`. A group with a single
+    // particle is a sequence.
+    fn particle_group(&mut self) -> Option<ParticleKind> {
+        let mut particles = vec![self.particle()?];
+        let mut separator = None;
+        loop {
+            self.whitespace();
+            if self.symbol(')') {
+                break;
+            }
+            let next = self.rest().chars().next()?;
+            if !matches!(next, '|' | ',') || separator.is_some_and(|s| s != next) {
+                return None;
+            }
+            self.symbol(next);
+            separator = Some(next);
+            self.whitespace();
+            particles.push(self.particle()?);
+        }
+        Some(match separator {
+            Some('|') => ParticleKind::Choice(particles),
+            _ => ParticleKind::Sequence(particles),
+        })
+    }
+
+    fn occurrence(&mut self) -> Occurrence {
+        if self.symbol('?') {
+            Occurrence::Optional
+        } else if self.symbol('*') {
+            Occurrence::ZeroOrMore
+        } else if self.symbol('+') {
+            Occurrence::OneOrMore
+        } else {
+            Occurrence::Once
+        }
+    }
+
+    // a quoted literal, returning the position of its content along with it
+    fn literal(&mut self) -> Option<(usize, &'a str)> {
+        let rest = self.rest();
+        let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let length = rest[1..].find(quote)?;
+        let start = self.position + 1;
+        self.position += length + 2;
+        Some((start, &rest[1..length + 1]))
+    }
+
+    // https://www.w3.org/TR/xml/#NT-ExternalID
+    fn external_id(&mut self) -> Option<(Option<&'a str>, &'a str)> {
+        if self.keyword("SYSTEM") {
+            self.required_whitespace()?;
+            let (_, system_id) = self.literal()?;
+            Some((None, system_id))
+        } else if self.keyword("PUBLIC") {
+            self.required_whitespace()?;
+            let (_, public_id) = self.literal()?;
+            self.required_whitespace()?;
+            let (_, system_id) = self.literal()?;
+            Some((Some(public_id), system_id))
+        } else {
+            None
+        }
+    }
+
+    // the end of the declaration
+    fn end(&mut self) -> Option<()> {
+        self.whitespace();
+        (self.rest() == ">").then_some(())
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+// The length of the XML name at the start of the text, if any.
+// https://www.w3.org/TR/xml/#NT-Name
+pub(crate) fn name_length(text: &str) -> usize {
+    match text.chars().next() {
+        Some(c) if is_name_start_char(c) => name_token_length(text),
+        _ => 0,
+    }
+}
+
+// The length of the name token at the start of the text, if any.
+// https://www.w3.org/TR/xml/#NT-Nmtoken
+pub(crate) fn name_token_length(text: &str) -> usize {
+    text.find(|c: char| !(is_name_start_char(c) || c.is_ascii_digit() || matches!(c, '-' | '.')))
+        .unwrap_or(text.len())
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_alphabetic() || matches!(c, '_' | ':') || (!c.is_ascii() && !c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::NoResolver;
+
+    fn parse(internal_subset: &str) -> Result<Entities, ParseError> {
+        parse_dtd(internal_subset).map(|(entities, _)| entities)
+    }
+
+    fn parse_dtd(internal_subset: &str) -> Result<(Entities, Dtd), ParseError> {
+        let mut entities = Entities::new(Arc::new(NoResolver));
+        let mut dtd = Dtd::new("doc".to_string(), None);
+        let mut parser = DtdParser::new(Arc::new(NoResolver), ParseLimits::default());
+        parser.parse_internal_subset(internal_subset, 0, &mut dtd, &mut entities)?;
+        Ok((entities, dtd))
+    }
+
+    fn expand(entities: &mut Entities, name: &str) -> String {
+        let replacement_text = entities.enter(name, Span::new(0, 0)).unwrap();
+        entities.exit();
+        replacement_text.to_string()
+    }
+
+    #[test]
+    fn test_entity_declaration() {
+        let mut entities = parse(r#"<!ENTITY e "E">"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "E");
+    }
+
+    #[test]
+    fn test_parameter_entity() {
+        let mut entities = parse(r#"<!ENTITY % p "<!ENTITY e 'E'>"> %p;"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "E");
+    }
+
+    #[test]
+    fn test_parameter_entity_in_declaration() {
+        let mut entities = parse(r#"<!ENTITY % name "e"><!ENTITY %name; "E">"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "E");
+    }
+
+    #[test]
+    fn test_parameter_entity_in_entity_value() {
+        let mut entities = parse(r#"<!ENTITY % p "P"><!ENTITY e "[%p;]">"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "[P]");
+    }
+
+    #[test]
+    fn test_percent_character_reference_in_entity_value() {
+        let mut entities = parse(r#"<!ENTITY % p "P"><!ENTITY e "&#37;p;">"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "%p;");
+    }
+
+    #[test]
+    fn test_conditional_sections() {
+        let mut entities = parse(
+            r#"<!ENTITY % draft "INCLUDE"><!ENTITY % final "IGNORE">
+            <![%draft;[<!ENTITY e "draft">]]>
+            <![%final;[<!ENTITY e "final"><![INCLUDE[<!ENTITY f "F">]]>]]>
+            <!ENTITY e "default"><!ENTITY f "default">"#,
+        )
+        .unwrap();
+        assert_eq!(expand(&mut entities, "e"), "draft");
+        assert_eq!(expand(&mut entities, "f"), "default");
+    }
+
+    #[test]
+    fn test_unknown_parameter_entity_skips_declarations() {
+        let mut entities = parse(r#"<!ENTITY e "E"> %unknown; <!ENTITY f "F">"#).unwrap();
+        assert_eq!(expand(&mut entities, "e"), "E");
+        assert!(entities.enter("f", Span::new(0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_recursive_parameter_entity() {
+        let err = parse(r#"<!ENTITY % p "&#37;p;"> %p;"#);
+        assert!(matches!(err, Err(ParseError::RecursiveEntity(_, _))));
+    }
+
+    #[test]
+    fn test_attribute_list_declaration() {
+        let (_, dtd) = parse_dtd(
+            r#"<!ATTLIST doc
+                id ID #REQUIRED
+                tokens NMTOKENS "  a   b "
+                text CDATA " a&#32;&#32;b "
+                format NOTATION (gif | png) #IMPLIED
+                kind ( 1a|b ) #FIXED 'b'>
+            <!ATTLIST doc text CDATA "ignored" extra IDREF #IMPLIED>"#,
+        )
+        .unwrap();
+        let declarations = dtd.attribute_declarations("doc");
+        let summary = declarations
+            .iter()
+            .map(|d| (d.name(), d.attribute_type().clone(), d.default().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("id", AttributeType::Id, AttributeDefault::Required),
+                (
+                    "tokens",
+                    AttributeType::NmTokens,
+                    AttributeDefault::Value("a b".to_string())
+                ),
+                (
+                    "text",
+                    AttributeType::Cdata,
+                    AttributeDefault::Value(" a  b ".to_string())
+                ),
+                (
+                    "format",
+                    AttributeType::Notation(vec!["gif".to_string(), "png".to_string()]),
+                    AttributeDefault::Implied
+                ),
+                (
+                    "kind",
+                    AttributeType::Enumeration(vec!["1a".to_string(), "b".to_string()]),
+                    AttributeDefault::Fixed("b".to_string())
+                ),
+                ("extra", AttributeType::IdRef, AttributeDefault::Implied),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_element_declaration() {
+        let (_, dtd) = parse_dtd(
+            r#"<!ELEMENT doc ( head , ( p | list )* , foot? )+>
+            <!ELEMENT head (#PCDATA)>
+            <!ELEMENT p ( #PCDATA | em | strong )* >
+            <!ELEMENT list (item)>
+            <!ELEMENT br EMPTY>
+            <!ELEMENT foot ANY>
+            <!ELEMENT br ANY>"#,
+        )
+        .unwrap();
+        let content_model = |name| dtd.content_model(name).unwrap().to_string();
+        assert_eq!(content_model("doc"), "(head,(p|list)*,foot?)+");
+        assert_eq!(content_model("head"), "(#PCDATA)");
+        assert_eq!(content_model("p"), "(#PCDATA|em|strong)*");
+        assert_eq!(content_model("list"), "(item)");
+        assert_eq!(content_model("br"), "EMPTY");
+        assert_eq!(content_model("foot"), "ANY");
+    }
+
+    #[test]
+    fn test_invalid_element_declaration() {
+        for declaration in [
+            "<!ELEMENT doc>",
+            "<!ELEMENT doc (a|b,c)>",
+            "<!ELEMENT doc (#PCDATA|a)>",
+            "<!ELEMENT doc (a,#PCDATA)>",
+            "<!ELEMENT doc NOTHING>",
+            "<!ELEMENT doc (a) b>",
+        ] {
+            let err = parse(declaration);
+            assert!(
+                matches!(err, Err(ParseError::InvalidDtd(_))),
+                "{}",
+                declaration
+            );
+        }
+    }
+
+    #[test]
+    fn test_attribute_list_declaration_with_parameter_entity() {
+        let (_, dtd) =
+            parse_dtd(r#"<!ENTITY % common "lang CDATA 'en'"><!ATTLIST doc %common;>"#).unwrap();
+        let declarations = dtd.attribute_declarations("doc");
+        assert_eq!(declarations.len(), 1);
+        assert_eq!(declarations[0].default().value(), Some("en"));
+    }
+
+    #[test]
+    fn test_invalid_attribute_list_declaration() {
+        let err = parse(r#"<!ATTLIST doc a CDATA>"#);
+        assert!(matches!(err, Err(ParseError::InvalidDtd(span)) if span == Span::new(0, 22)));
+        let err = parse(r#"<!ATTLIST doc a CDATA "<">"#);
+        assert!(matches!(err, Err(ParseError::InvalidDtd(_))));
+        let err = parse(r#"<!ATTLIST doc a CDATA "&unknown;">"#);
+        assert!(
+            matches!(err, Err(ParseError::InvalidEntity(_, span)) if span == Span::new(23, 32))
+        );
+    }
+
+    #[test]
+    fn test_declarations_ignored() {
+        let mut entities = parse(
+            r#"<!-- comment --><?pi content?>
+            <!ATTLIST doc a CDATA "x>y">
+            <!NOTATION n SYSTEM "n">
+            <!ENTITY e "E">"#,
+        )
+        .unwrap();
+        assert_eq!(expand(&mut entities, "e"), "E");
+    }
+
+    #[test]
+    fn test_invalid_declaration() {
+        let err = parse(r#"<!ENTITY e "E"> <!ENTITY>"#);
+        assert!(matches!(err, Err(ParseError::InvalidDtd(span)) if span == Span::new(16, 25)));
+        let err = parse(r#"<!ENTITY e "E"> garbage"#);
+        assert!(matches!(err, Err(ParseError::InvalidDtd(span)) if span == Span::new(16, 16)));
+    }
+
+    #[test]
+    fn test_internal_subset_end() {
+        let xml = r#"<!DOCTYPE doc [<!ENTITY e "]"> %p; <!-- ] --> ]><doc/>"#;
+        assert_eq!(internal_subset_end(xml, 15), Some(46));
+        assert_eq!(internal_subset_end("<!DOCTYPE doc [<!ENTITY e ", 15), None);
+    }
+}
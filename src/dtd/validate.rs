@@ -0,0 +1,523 @@
+// Validation of a document against the element type and attribute list
+// declarations of its DTD.
+// https://www.w3.org/TR/xml/#dt-valid
+use std::collections::BTreeSet;
+
+use ahash::{HashSet, HashSetExt};
+
+use crate::xmlvalue::Value;
+use crate::{NameId, Node, Span, SpanInfo, SpanInfoKey, Xot};
+
+use super::parser::{name_length, name_token_length};
+use super::{
+    AttributeDefault, AttributeType, ContentModel, ContentParticle, Dtd, Occurrence, ParticleKind,
+};
+
+/// A violation of a validity constraint of the DTD.
+///
+/// You get these from [`Xot::validate_dtd`] and
+/// [`Xot::validate_dtd_with_span_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    kind: ValidationErrorKind,
+    node: Node,
+    span: Option<Span>,
+}
+
+impl ValidationError {
+    /// What is wrong.
+    pub fn kind(&self) -> &ValidationErrorKind {
+        &self.kind
+    }
+
+    /// The offending node.
+    ///
+    /// This is the attribute node for problems with an attribute value, the
+    /// document node if the document has no DTD, and the element otherwise.
+    pub fn node(&self) -> Node {
+        self.node
+    }
+
+    /// The span of the offending node in the source, if the document was
+    /// validated with span information.
+    ///
+    /// For an attribute this is the span of its value. Attributes that were
+    /// defaulted from the DTD have no place in the source, so for these it
+    /// is the span of the name of their element.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// The kind of a [`ValidationError`].
+///
+/// Element and attribute names are given as they would appear in the
+/// source, including any prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// The document has no document type declaration.
+    NoDtd,
+    /// The name of the document element doesn't match the name in the
+    /// document type declaration. Holds the declared name and the actual
+    /// name.
+    RootElementMismatch(String, String),
+    /// The element type isn't declared.
+    UndeclaredElement(String),
+    /// The content of the element doesn't match its declared content model.
+    InvalidContent(String, ContentModel),
+    /// The attribute (second) isn't declared for the element (first).
+    UndeclaredAttribute(String, String),
+    /// The required attribute (second) is missing on the element (first).
+    MissingAttribute(String, String),
+    /// The value (second) isn't valid for the declared type of the
+    /// attribute (first), or doesn't match its fixed value.
+    InvalidAttributeValue(String, String),
+    /// The ID value occurs more than once in the document.
+    DuplicateId(String),
+    /// The ID referred to by an `IDREF` or `IDREFS` attribute doesn't occur
+    /// in the document.
+    UnknownIdRef(String),
+}
+
+impl std::fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationErrorKind::NoDtd => write!(f, "Document has no DTD"),
+            ValidationErrorKind::RootElementMismatch(declared, actual) => write!(
+                f,
+                "Document element {} doesn't match declared name {}",
+                actual, declared
+            ),
+            ValidationErrorKind::UndeclaredElement(name) => {
+                write!(f, "Element {} is not declared", name)
+            }
+            ValidationErrorKind::InvalidContent(name, content_model) => write!(
+                f,
+                "Content of element {} doesn't match {}",
+                name, content_model
+            ),
+            ValidationErrorKind::UndeclaredAttribute(element, name) => write!(
+                f,
+                "Attribute {} is not declared for element {}",
+                name, element
+            ),
+            ValidationErrorKind::MissingAttribute(element, name) => write!(
+                f,
+                "Required attribute {} is missing on element {}",
+                name, element
+            ),
+            ValidationErrorKind::InvalidAttributeValue(name, value) => {
+                write!(f, "Invalid value for attribute {}: {}", name, value)
+            }
+            ValidationErrorKind::DuplicateId(id) => write!(f, "Duplicate ID: {}", id),
+            ValidationErrorKind::UnknownIdRef(id) => write!(f, "Reference to unknown ID: {}", id),
+        }
+    }
+}
+
+/// ## DTD validation
+impl Xot {
+    /// Validate a document against its DTD.
+    ///
+    /// This checks the validity constraints of the XML specification that
+    /// concern the document itself: the name of the document element,
+    /// element content against the declared content models (including mixed
+    /// content, `EMPTY` and `ANY`), declared and required attributes,
+    /// attribute values against their declared types (including enumerations
+    /// and fixed values), and the uniqueness of IDs and the targets of
+    /// IDREFs. It returns all the errors it finds; if the list is empty, the
+    /// document is valid.
+    ///
+    /// Text in element content that consists of whitespace only is
+    /// allowed, whether or not it came from a CDATA section. `ENTITY` and
+    /// `ENTITIES` attributes are only checked to be names.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    /// use xot::dtd::ValidationErrorKind;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<!DOCTYPE doc [
+    /// <!ELEMENT doc (title, p*)>
+    /// <!ELEMENT title (#PCDATA)>
+    /// <!ELEMENT p (#PCDATA)>
+    /// ]><doc><p/><title/></doc>"#)?;
+    ///
+    /// let errors = xot.validate_dtd(root);
+    /// assert_eq!(errors.len(), 1);
+    /// assert!(matches!(errors[0].kind(), ValidationErrorKind::InvalidContent(name, _) if name == "doc"));
+    /// assert_eq!(errors[0].to_string(), "Content of element doc doesn't match (title,p*)");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn validate_dtd(&self, document: Node) -> Vec<ValidationError> {
+        self.validate_dtd_spans(document, None)
+    }
+
+    /// Validate a document against its DTD, with span information.
+    ///
+    /// This is like [`Xot::validate_dtd`], but the errors have spans that
+    /// point to the offending nodes in the source, using the [`SpanInfo`]
+    /// obtained with [`Xot::parse_with_span_info`].
+    pub fn validate_dtd_with_span_info(
+        &self,
+        document: Node,
+        span_info: &SpanInfo,
+    ) -> Vec<ValidationError> {
+        self.validate_dtd_spans(document, Some(span_info))
+    }
+
+    fn validate_dtd_spans(
+        &self,
+        document: Node,
+        span_info: Option<&SpanInfo>,
+    ) -> Vec<ValidationError> {
+        let Some(dtd) = self.dtd(document) else {
+            return vec![ValidationError {
+                kind: ValidationErrorKind::NoDtd,
+                node: document,
+                span: None,
+            }];
+        };
+        let mut validator = Validator {
+            xot: self,
+            dtd,
+            span_info,
+            errors: Vec::new(),
+            ids: HashSet::new(),
+            references: Vec::new(),
+        };
+        validator.validate(document);
+        validator.errors
+    }
+}
+
+struct Validator<'a> {
+    xot: &'a Xot,
+    dtd: &'a Dtd,
+    span_info: Option<&'a SpanInfo>,
+    errors: Vec<ValidationError>,
+    ids: HashSet<String>,
+    // the IDREF values we've seen, as errors in case their ID doesn't turn
+    // up
+    references: Vec<(String, ValidationError)>,
+}
+
+impl Validator<'_> {
+    fn validate(&mut self, document: Node) {
+        let Ok(document_element) = self.xot.document_element(document) else {
+            return;
+        };
+        let name = self.element_name(document_element);
+        if name != self.dtd.name() {
+            self.error(
+                ValidationErrorKind::RootElementMismatch(self.dtd.name().to_string(), name),
+                document_element,
+            );
+        }
+        for node in self.xot.descendants(document_element) {
+            if self.xot.is_element(node) {
+                self.validate_element(node);
+            }
+        }
+        let references = std::mem::take(&mut self.references);
+        for (id, error) in references {
+            if !self.ids.contains(&id) {
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn validate_element(&mut self, element: Node) {
+        let name = self.element_name(element);
+        match self.dtd.content_model(&name) {
+            Some(content_model) => {
+                if !self.matches_content_model(element, content_model) {
+                    self.error(
+                        ValidationErrorKind::InvalidContent(name.clone(), content_model.clone()),
+                        element,
+                    );
+                }
+            }
+            None => self.error(
+                ValidationErrorKind::UndeclaredElement(name.clone()),
+                element,
+            ),
+        }
+        self.validate_attributes(element, &name);
+    }
+
+    fn matches_content_model(&self, element: Node, content_model: &ContentModel) -> bool {
+        match content_model {
+            ContentModel::Empty => self.xot.first_child(element).is_none(),
+            ContentModel::Any => true,
+            ContentModel::Mixed(names) => self.xot.children(element).all(|child| {
+                !self.xot.is_element(child) || names.contains(&self.element_name(child))
+            }),
+            ContentModel::Children(particle) => {
+                let mut names = Vec::new();
+                for child in self.xot.children(element) {
+                    match self.xot.value(child) {
+                        Value::Element(_) => names.push(self.element_name(child)),
+                        Value::Text(text) if !is_whitespace(text.get()) => return false,
+                        _ => {}
+                    }
+                }
+                let starts = BTreeSet::from([0]);
+                matching_ends(particle, &names, &starts).contains(&names.len())
+            }
+        }
+    }
+
+    fn validate_attributes(&mut self, element: Node, element_name: &str) {
+        let declarations = self.dtd.attribute_declarations(element_name);
+        let mut names = Vec::new();
+        for node in self.xot.attribute_nodes(element) {
+            let Some(attribute) = self.xot.attribute_node(node) else {
+                continue;
+            };
+            let name = self.name(element, attribute.name());
+            let span = self.attribute_span(element, attribute.name());
+            let value = attribute.value();
+            let declaration = declarations
+                .iter()
+                .find(|declaration| declaration.name() == name);
+            names.push(name.clone());
+            let Some(declaration) = declaration else {
+                self.error_with_span(
+                    ValidationErrorKind::UndeclaredAttribute(element_name.to_string(), name),
+                    node,
+                    span,
+                );
+                continue;
+            };
+            let is_valid = match declaration.attribute_type() {
+                AttributeType::Cdata => true,
+                AttributeType::Id | AttributeType::IdRef | AttributeType::Entity => is_name(value),
+                AttributeType::IdRefs | AttributeType::Entities => {
+                    !value.is_empty() && value.split(' ').all(is_name)
+                }
+                AttributeType::NmToken => is_name_token(value),
+                AttributeType::NmTokens => !value.is_empty() && value.split(' ').all(is_name_token),
+                AttributeType::Notation(names) | AttributeType::Enumeration(names) => {
+                    names.iter().any(|name| name == value)
+                }
+            };
+            let is_fixed_value = match declaration.default() {
+                AttributeDefault::Fixed(fixed) => fixed == value,
+                _ => true,
+            };
+            if !is_valid || !is_fixed_value {
+                self.error_with_span(
+                    ValidationErrorKind::InvalidAttributeValue(name, value.to_string()),
+                    node,
+                    span,
+                );
+                continue;
+            }
+            match declaration.attribute_type() {
+                AttributeType::Id if !self.ids.insert(value.to_string()) => {
+                    self.error_with_span(
+                        ValidationErrorKind::DuplicateId(value.to_string()),
+                        node,
+                        span,
+                    );
+                }
+                AttributeType::IdRef | AttributeType::IdRefs => {
+                    for id in value.split(' ') {
+                        let error = ValidationError {
+                            kind: ValidationErrorKind::UnknownIdRef(id.to_string()),
+                            node,
+                            span,
+                        };
+                        self.references.push((id.to_string(), error));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for declaration in declarations {
+            let is_missing = matches!(declaration.default(), AttributeDefault::Required)
+                && !names.iter().any(|name| name == declaration.name());
+            if is_missing {
+                self.error(
+                    ValidationErrorKind::MissingAttribute(
+                        element_name.to_string(),
+                        declaration.name().to_string(),
+                    ),
+                    element,
+                );
+            }
+        }
+    }
+
+    // DTDs are not aware of namespaces, so we compare names as they would
+    // appear in the source
+    fn name(&self, element: Node, name: NameId) -> String {
+        self.xot
+            .full_name(element, name)
+            .unwrap_or_else(|_| self.xot.local_name_str(name).to_string())
+    }
+
+    fn element_name(&self, element: Node) -> String {
+        let name = self.xot.element(element).unwrap().name();
+        self.name(element, name)
+    }
+
+    fn attribute_span(&self, element: Node, name: NameId) -> Option<Span> {
+        let span_info = self.span_info?;
+        span_info
+            .get(SpanInfoKey::AttributeValue(element, name))
+            .or_else(|| span_info.get(SpanInfoKey::ElementStart(element)))
+            .copied()
+    }
+
+    fn error(&mut self, kind: ValidationErrorKind, element: Node) {
+        let span = self
+            .span_info
+            .and_then(|span_info| span_info.get(SpanInfoKey::ElementStart(element)))
+            .copied();
+        self.error_with_span(kind, element, span);
+    }
+
+    fn error_with_span(&mut self, kind: ValidationErrorKind, node: Node, span: Option<Span>) {
+        self.errors.push(ValidationError { kind, node, span });
+    }
+}
+
+// The positions in `names` at which a match of the particle can end, given
+// the positions at which it can start.
+fn matching_ends(
+    particle: &ContentParticle,
+    names: &[String],
+    starts: &BTreeSet<usize>,
+) -> BTreeSet<usize> {
+    match particle.occurrence() {
+        Occurrence::Once => matching_kind_ends(particle.kind(), names, starts),
+        Occurrence::Optional => {
+            let mut ends = matching_kind_ends(particle.kind(), names, starts);
+            ends.extend(starts);
+            ends
+        }
+        Occurrence::ZeroOrMore => repeated_ends(particle.kind(), names, starts.clone()),
+        Occurrence::OneOrMore => {
+            let once = matching_kind_ends(particle.kind(), names, starts);
+            repeated_ends(particle.kind(), names, once)
+        }
+    }
+}
+
+fn matching_kind_ends(
+    kind: &ParticleKind,
+    names: &[String],
+    starts: &BTreeSet<usize>,
+) -> BTreeSet<usize> {
+    match kind {
+        ParticleKind::Name(name) => starts
+            .iter()
+            .filter(|start| names.get(**start) == Some(name))
+            .map(|start| start + 1)
+            .collect(),
+        ParticleKind::Choice(particles) => particles
+            .iter()
+            .flat_map(|particle| matching_ends(particle, names, starts))
+            .collect(),
+        ParticleKind::Sequence(particles) => particles
+            .iter()
+            .fold(starts.clone(), |positions, particle| {
+                matching_ends(particle, names, &positions)
+            }),
+    }
+}
+
+// Repeat the particle for as long as that reaches new positions.
+fn repeated_ends(
+    kind: &ParticleKind,
+    names: &[String],
+    starts: BTreeSet<usize>,
+) -> BTreeSet<usize> {
+    let mut ends = starts.clone();
+    let mut frontier = starts;
+    while !frontier.is_empty() {
+        let next = matching_kind_ends(kind, names, &frontier);
+        frontier = next.difference(&ends).copied().collect();
+        ends.extend(&frontier);
+    }
+    ends
+}
+
+fn is_whitespace(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n'))
+}
+
+fn is_name(value: &str) -> bool {
+    !value.is_empty() && name_length(value) == value.len()
+}
+
+fn is_name_token(value: &str) -> bool {
+    !value.is_empty() && name_token_length(value) == value.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ends(content_model: &str, names: &[&str]) -> bool {
+        let mut xot = Xot::new();
+        let root = xot
+            .parse(&format!(
+                "<!DOCTYPE doc [<!ELEMENT doc {}>]><doc/>",
+                content_model
+            ))
+            .unwrap();
+        let Some(ContentModel::Children(particle)) = xot.dtd(root).unwrap().content_model("doc")
+        else {
+            unreachable!()
+        };
+        let names = names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        matching_ends(particle, &names, &BTreeSet::from([0])).contains(&names.len())
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert!(ends("(a,b)", &["a", "b"]));
+        assert!(!ends("(a,b)", &["b", "a"]));
+        assert!(!ends("(a,b)", &["a"]));
+    }
+
+    #[test]
+    fn test_choice() {
+        assert!(ends("(a|b)", &["b"]));
+        assert!(!ends("(a|b)", &["a", "b"]));
+        assert!(ends("(a|b)*", &["a", "b", "a"]));
+    }
+
+    #[test]
+    fn test_occurrence() {
+        assert!(ends("(a?,b+,c*)", &["b"]));
+        assert!(ends("(a?,b+,c*)", &["a", "b", "b", "c", "c"]));
+        assert!(!ends("(a?,b+,c*)", &["a", "c"]));
+        assert!(!ends("(a?,b)", &["a", "a", "b"]));
+    }
+
+    #[test]
+    fn test_nested() {
+        assert!(ends(
+            "(head,(p|(list,note?))*)",
+            &["head", "list", "p", "list", "note"]
+        ));
+        assert!(!ends("(head,(p|(list,note?))*)", &["head", "note"]));
+        // a repeated group that can match nothing terminates
+        assert!(ends("((a?)*,b)", &["a", "a", "b"]));
+    }
+}
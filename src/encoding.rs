@@ -1,6 +1,28 @@
-use encoding_rs::Encoding;
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use xhtmlchardet::detect;
 
+use crate::error::ParseError;
+use crate::span::Span;
+
+/// How bytes were decoded into text before they were parsed.
+///
+/// You get this with [`Xot::parse_bytes_with_report`](crate::Xot::parse_bytes_with_report).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingReport {
+    /// The name of the encoding, such as `UTF-8`, `UTF-16LE` or
+    /// `windows-1252`.
+    ///
+    /// These are the names of the [Encoding
+    /// Standard](https://encoding.spec.whatwg.org/), which treats
+    /// `ISO-8859-1` and `US-ASCII` as `windows-1252`.
+    pub encoding: &'static str,
+    /// Whether the bytes start with a byte order mark.
+    pub bom: bool,
+    /// Whether bytes that are malformed in the encoding were replaced by
+    /// U+FFFD REPLACEMENT CHARACTER.
+    pub replaced: bool,
+}
+
 pub fn encoding(data: &[u8], hint: Option<String>) -> Option<&'static Encoding> {
     let mut cursor = std::io::Cursor::new(data);
     let charsets = detect(&mut cursor, hint).ok()?;
@@ -13,12 +35,153 @@ pub fn encoding(data: &[u8], hint: Option<String>) -> Option<&'static Encoding>
     Encoding::for_label(label.as_bytes())
 }
 
+// The number of bytes at the start of the data that `encoding` looks at.
+pub(crate) const DETECTION_LENGTH: usize = 4 + 512;
+
+// The encoding of data that starts with `head`, along with the length of its
+// byte order mark. A byte order mark takes precedence over everything else.
+fn detect_encoding(head: &[u8], hint: Option<String>) -> (&'static Encoding, usize) {
+    Encoding::for_bom(head).unwrap_or_else(|| (encoding(head, hint).unwrap_or(UTF_8), 0))
+}
+
 pub(crate) fn decode(data: &[u8], hint: Option<String>) -> String {
-    let enc = encoding(data, hint).unwrap();
+    let enc = encoding(data, hint).unwrap_or(UTF_8);
     let (s, _, _) = enc.decode(data);
     s.into_owned()
 }
 
+// Decode the data, and report how. In strict mode, an encoding declaration
+// that contradicts the data and malformed bytes are errors.
+pub(crate) fn decode_with_report(
+    data: &[u8],
+    hint: Option<String>,
+    strict: bool,
+) -> Result<(String, EncodingReport), ParseError> {
+    let (encoding, bom_length) = detect_encoding(data, hint);
+    let report = |replaced| EncodingReport {
+        encoding: encoding.name(),
+        bom: bom_length > 0,
+        replaced,
+    };
+    let data = &data[bom_length..];
+    if !strict {
+        let (text, replaced) = encoding.decode_without_bom_handling(data);
+        return Ok((text.into_owned(), report(replaced)));
+    }
+    check_declared_encoding(data, encoding)?;
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::new();
+    decode_strictly(&mut decoder, data, &mut text, 0, true)?;
+    Ok((text, report(false)))
+}
+
+// A decoder for data that starts with `head`, which should contain at least
+// `DETECTION_LENGTH` bytes unless the data is shorter. This decodes in the
+// same way as `decode`. In strict mode, an encoding declaration in `head`
+// that contradicts the data is an error.
+pub(crate) fn decoder(
+    head: &[u8],
+    hint: Option<String>,
+    strict: bool,
+) -> Result<Decoder, ParseError> {
+    let (encoding, bom_length) = detect_encoding(head, hint);
+    if strict {
+        check_declared_encoding(&head[bom_length..], encoding)?;
+    }
+    Ok(encoding.new_decoder())
+}
+
+// Decode the bytes, appending them to `text`, without replacing malformed
+// bytes. `position` is where `text` starts in the source, to locate the
+// malformed bytes.
+pub(crate) fn decode_strictly(
+    decoder: &mut Decoder,
+    mut bytes: &[u8],
+    text: &mut String,
+    position: usize,
+    last: bool,
+) -> Result<(), ParseError> {
+    loop {
+        // the worst case length is only unknown for absurdly large chunks
+        let length = decoder
+            .max_utf8_buffer_length_without_replacement(bytes.len())
+            .unwrap_or(bytes.len());
+        text.reserve(length);
+        let (result, read) = decoder.decode_to_string_without_replacement(bytes, text, last);
+        match result {
+            DecoderResult::InputEmpty => return Ok(()),
+            DecoderResult::OutputFull => bytes = &bytes[read..],
+            DecoderResult::Malformed(_, _) => {
+                let position = position + text.len();
+                return Err(ParseError::MalformedEncoding(
+                    decoder.encoding().name().to_string(),
+                    Span::new(position, position),
+                ));
+            }
+        }
+    }
+}
+
+// An encoding declaration that contradicts the encoding we decode the data
+// with, or how the declaration itself is encoded, is an error. The data
+// doesn't include the byte order mark.
+fn check_declared_encoding(data: &[u8], encoding: &'static Encoding) -> Result<(), ParseError> {
+    let Some((label, span, is_utf16)) = declared_encoding(data) else {
+        return Ok(());
+    };
+    let is_utf16_encoding = |encoding| encoding == UTF_16LE || encoding == UTF_16BE;
+    let matches = match Encoding::for_label(label.as_bytes()) {
+        // `UTF-16` is either byte order
+        Some(_) if label.eq_ignore_ascii_case("utf-16") => is_utf16 && is_utf16_encoding(encoding),
+        Some(declared) => is_utf16 == is_utf16_encoding(declared) && declared == encoding,
+        None => false,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(ParseError::EncodingMismatch(label, span))
+    }
+}
+
+// The encoding name in the XML declaration at the start of the data, along
+// with its span in the text, and whether the declaration is in UTF-16.
+fn declared_encoding(data: &[u8]) -> Option<(String, Span, bool)> {
+    let data = &data[..data.len().min(DETECTION_LENGTH)];
+    // the declaration is ASCII, in one or two bytes per character
+    let (declaration, is_utf16): (String, bool) = if data.starts_with(b"<?xml") {
+        (data.iter().map(|b| *b as char).collect(), false)
+    } else if data.starts_with(b"<\0?\0") {
+        (data.iter().step_by(2).map(|b| *b as char).collect(), true)
+    } else if data.starts_with(b"\0<\0?") {
+        (
+            data.iter().skip(1).step_by(2).map(|b| *b as char).collect(),
+            true,
+        )
+    } else {
+        return None;
+    };
+    let declaration = &declaration[..declaration.find("?>")?];
+    if !declaration[5..].starts_with(is_xml_space) {
+        return None;
+    }
+    let index = declaration.find("encoding")?;
+    let rest = declaration[index + "encoding".len()..].trim_start_matches(is_xml_space);
+    let rest = rest.strip_prefix('=')?.trim_start_matches(is_xml_space);
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &rest[1..];
+    let value = &value[..value.find(quote)?];
+    let start = declaration.len() - rest.len() + 1;
+    Some((
+        value.to_string(),
+        Span::new(start, start + value.len()),
+        is_utf16,
+    ))
+}
+
+fn is_xml_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -52,4 +215,9 @@ mod tests {
         // windows-1252 is a superset of 8859-1
         assert_eq!(enc.name(), "windows-1252");
     }
+
+    #[test]
+    fn test_decode_short() {
+        assert_eq!(decode(b"<a", None), "<a");
+    }
 }
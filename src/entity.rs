@@ -1,35 +1,382 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
+use ahash::{HashMap, HashMapExt};
+
+use crate::declaration::XmlVersion;
 use crate::error::ParseError;
 use crate::output::Normalizer;
+use crate::parseoptions::{exceeds, EntityExpansion, ParseLimits};
+use crate::resolver::Resolver;
+use crate::xmlchar::is_char;
 use crate::Span;
 
-pub(crate) fn parse_text(content: Cow<str>, base_position: usize) -> Result<Cow<str>, ParseError> {
-    parse_content(content, false, base_position)
+/// The identifiers of an external entity, along with the URI of the
+/// resource in which it was declared, if any.
+#[derive(Debug, Clone)]
+pub(crate) struct ExternalEntity {
+    pub(crate) public_id: Option<String>,
+    pub(crate) system_id: String,
+    pub(crate) base: Option<String>,
+}
+
+#[derive(Debug)]
+enum Entity {
+    Internal(Arc<str>),
+    External(ExternalEntity),
+    // an unparsed entity, which can only be used in attributes of type
+    // ENTITY or ENTITIES and never referenced
+    Unparsed,
+}
+
+/// The user-defined general entities declared in the DTD, and the
+/// bookkeeping needed to expand them safely.
+#[derive(Debug)]
+pub(crate) struct Entities {
+    declared: HashMap<String, Entity>,
+    resolver: Arc<dyn Resolver>,
+    // the replacement text of external entities that were already fetched
+    fetched: HashMap<String, Arc<str>>,
+    expanding: Vec<String>,
+    expanded_size: usize,
+    // which of the declared entities we may expand
+    pub(crate) expansion: EntityExpansion,
+    // how deeply we may nest expansions, and how much text they may produce
+    pub(crate) limits: ParseLimits,
+    // the XML version of the document, which determines the characters
+    // we may refer to
+    pub(crate) version: XmlVersion,
+}
+
+impl Entities {
+    pub(crate) fn new(resolver: Arc<dyn Resolver>) -> Self {
+        Entities {
+            declared: HashMap::new(),
+            resolver,
+            fetched: HashMap::new(),
+            expanding: Vec::new(),
+            expanded_size: 0,
+            expansion: EntityExpansion::All,
+            limits: ParseLimits::default(),
+            version: XmlVersion::Xml10,
+        }
+    }
+
+    pub(crate) fn resolver(&self) -> Arc<dyn Resolver> {
+        self.resolver.clone()
+    }
+
+    /// Declare an internal general entity. The value is the literal entity
+    /// value as it appears in the declaration.
+    ///
+    /// If an entity is declared multiple times, the first declaration is
+    /// binding. The predefined entities cannot be redeclared.
+    pub(crate) fn declare(
+        &mut self,
+        name: &str,
+        value: &str,
+        base_position: usize,
+    ) -> Result<(), ParseError> {
+        if is_predefined(name) || self.declared.contains_key(name) {
+            return Ok(());
+        }
+        let replacement_text = replacement_text(value, base_position, self.version)?;
+        self.declared
+            .insert(name.to_string(), Entity::Internal(replacement_text.into()));
+        Ok(())
+    }
+
+    /// Declare an external parsed general entity.
+    pub(crate) fn declare_external(&mut self, name: &str, external_entity: ExternalEntity) {
+        if is_predefined(name) || self.declared.contains_key(name) {
+            return;
+        }
+        self.declared
+            .insert(name.to_string(), Entity::External(external_entity));
+    }
+
+    /// Declare an unparsed entity.
+    pub(crate) fn declare_unparsed(&mut self, name: &str) {
+        if is_predefined(name) || self.declared.contains_key(name) {
+            return;
+        }
+        self.declared.insert(name.to_string(), Entity::Unparsed);
+    }
+
+    fn is_external(&self, name: &str) -> bool {
+        matches!(self.declared.get(name), Some(Entity::External(_)))
+    }
+
+    /// Start the expansion of an entity reference, returning its replacement
+    /// text. You must call [`Entities::exit`] when done expanding.
+    pub(crate) fn enter(&mut self, name: &str, span: Span) -> Result<Arc<str>, ParseError> {
+        let is_allowed = match self.declared.get(name) {
+            Some(Entity::Internal(_)) => self.expansion != EntityExpansion::PredefinedOnly,
+            Some(Entity::External(_)) => self.expansion == EntityExpansion::All,
+            Some(Entity::Unparsed) | None => true,
+        };
+        if !is_allowed {
+            return Err(ParseError::EntityNotAllowed(name.to_string(), span));
+        }
+        let replacement_text = match self.declared.get(name) {
+            Some(Entity::Internal(replacement_text)) => replacement_text.clone(),
+            Some(Entity::External(external_entity)) => {
+                if let Some(replacement_text) = self.fetched.get(name) {
+                    replacement_text.clone()
+                } else {
+                    let resource = self
+                        .resolver
+                        .resolve(
+                            external_entity.public_id.as_deref(),
+                            &external_entity.system_id,
+                            external_entity.base.as_deref(),
+                        )
+                        .map_err(|e| ParseError::UnresolvedEntity(name.to_string(), e, span))?;
+                    let replacement_text: Arc<str> = strip_text_declaration(resource.text()).into();
+                    self.fetched
+                        .insert(name.to_string(), replacement_text.clone());
+                    replacement_text
+                }
+            }
+            Some(Entity::Unparsed) | None => {
+                return Err(ParseError::InvalidEntity(name.to_string(), span))
+            }
+        };
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return Err(ParseError::RecursiveEntity(name.to_string(), span));
+        }
+        self.expanded_size += replacement_text.len();
+        if exceeds(self.limits.max_entity_depth, self.expanding.len() + 1)
+            || exceeds(self.limits.max_entity_expansion_size, self.expanded_size)
+        {
+            return Err(ParseError::EntityExpansionLimit(name.to_string(), span));
+        }
+        self.expanding.push(name.to_string());
+        Ok(replacement_text)
+    }
+
+    /// Finish the expansion of the entity most recently entered.
+    pub(crate) fn exit(&mut self) {
+        self.expanding.pop();
+    }
+}
+
+// An external parsed entity may start with a text declaration, which is not
+// part of its replacement text.
+// https://www.w3.org/TR/xml/#sec-TextDecl
+pub(crate) fn strip_text_declaration(text: &str) -> &str {
+    if text.starts_with("<?xml") && text[5..].starts_with(|c: char| c.is_ascii_whitespace()) {
+        if let Some(end) = text.find("?>") {
+            return &text[end + 2..];
+        }
+    }
+    text
+}
+
+fn is_predefined(name: &str) -> bool {
+    matches!(name, "amp" | "apos" | "gt" | "lt" | "quot")
+}
+
+// Construct the replacement text for an entity value literal.
+// https://www.w3.org/TR/xml/#intern-replacement
+// Character references are expanded when the entity is declared, but
+// references to other general entities are bypassed and only expanded when
+// the entity itself is referenced.
+pub(crate) fn replacement_text(
+    value: &str,
+    base_position: usize,
+    version: XmlVersion,
+) -> Result<String, ParseError> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut position = base_position;
+    while let Some(index) = rest.find(|c| c == '&' || is_line_end(c, version)) {
+        result.push_str(&rest[..index]);
+        position += index;
+        rest = &rest[index..];
+        let c = rest.chars().next().unwrap();
+        if c != '&' {
+            // https://www.w3.org/TR/xml/#sec-line-ends
+            result.push('\n');
+            let after = &rest[c.len_utf8()..];
+            let after = match c {
+                '\r' => after
+                    .strip_prefix('\n')
+                    .or_else(|| {
+                        after
+                            .strip_prefix('\u{85}')
+                            .filter(|_| version == XmlVersion::Xml11)
+                    })
+                    .unwrap_or(after),
+                _ => after,
+            };
+            position += rest.len() - after.len();
+            rest = after;
+            continue;
+        }
+        let end = rest
+            .find(';')
+            .ok_or_else(|| ParseError::UnclosedEntity(rest[1..].to_string(), position))?;
+        let reference = &rest[..end + 1];
+        if reference.starts_with("&#") {
+            let expanded = parse_content(reference.into(), false, position, version, None, None)?;
+            result.push_str(&expanded);
+        } else {
+            result.push_str(reference);
+        }
+        position += reference.len();
+        rest = &rest[reference.len()..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Find the references to entities that are not predefined in character data.
+///
+/// Returns the ranges of the references along with the entity names.
+pub(crate) fn entity_references(content: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut references = Vec::new();
+    let mut start = 0;
+    while let Some(index) = content[start..].find('&') {
+        let reference_start = start + index;
+        let Some(length) = content[reference_start..].find(';') else {
+            // this is reported as an unclosed entity later
+            break;
+        };
+        let reference_end = reference_start + length + 1;
+        let name = &content[reference_start + 1..reference_end - 1];
+        if !name.starts_with('#') && !is_predefined(name) {
+            references.push((reference_start..reference_end, name));
+        }
+        start = reference_end;
+    }
+    references
+}
+
+/// Find the entity and character references in character data.
+///
+/// Returns the ranges of the references. Text that doesn't form a
+/// reference, like an `&` without a `;` after the name, is skipped.
+pub(crate) fn references(content: &str) -> Vec<std::ops::Range<usize>> {
+    let mut references = Vec::new();
+    let mut start = 0;
+    while let Some(index) = content[start..].find('&') {
+        let reference_start = start + index;
+        start = reference_start + 1;
+        let Some(length) = content[start..].find(|c: char| !is_reference_char(c)) else {
+            break;
+        };
+        let end = start + length;
+        if length > 0 && content[end..].starts_with(';') {
+            references.push(reference_start..end + 1);
+            start = end + 1;
+        }
+    }
+    references
+}
+
+// Whether a character can be part of the name of a reference. This is
+// looser than the XML name production, which we check when expanding.
+fn is_reference_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '&' | ';' | '<' | '>' | '"' | '\'')
 }
 
-pub(crate) fn parse_attribute(
+pub(crate) fn parse_text(
     content: Cow<str>,
     base_position: usize,
+    version: XmlVersion,
 ) -> Result<Cow<str>, ParseError> {
-    parse_content(content, true, base_position)
+    parse_content(content, false, base_position, version, None, None)
 }
 
-fn parse_content(
-    content: Cow<str>,
+pub(crate) fn parse_text_leniently<'a>(
+    content: Cow<'a, str>,
+    base_position: usize,
+    version: XmlVersion,
+    errors: &mut Vec<ParseError>,
+) -> Cow<'a, str> {
+    // as we recover from all errors, this cannot fail
+    parse_content(content, false, base_position, version, None, Some(errors)).unwrap()
+}
+
+pub(crate) fn parse_attribute<'a>(
+    content: Cow<'a, str>,
+    base_position: usize,
+    entities: &mut Entities,
+) -> Result<Cow<'a, str>, ParseError> {
+    let version = entities.version;
+    parse_content(content, true, base_position, version, Some(entities), None)
+}
+
+pub(crate) fn parse_attribute_leniently<'a>(
+    content: Cow<'a, str>,
+    base_position: usize,
+    entities: &mut Entities,
+    errors: &mut Vec<ParseError>,
+) -> Cow<'a, str> {
+    let version = entities.version;
+    // as we recover from all errors, this cannot fail
+    parse_content(
+        content,
+        true,
+        base_position,
+        version,
+        Some(entities),
+        Some(errors),
+    )
+    .unwrap()
+}
+
+// Characters that XML 1.1 only allows as character references.
+// https://www.w3.org/TR/xml11/#NT-RestrictedChar
+pub(crate) fn is_restricted_char(c: char) -> bool {
+    matches!(c, '\u{1}'..='\u{8}' | '\u{B}' | '\u{C}' | '\u{E}'..='\u{1F}' | '\u{7F}'..='\u{84}' | '\u{86}'..='\u{9F}')
+}
+
+// Whether the character ends a line, besides the line feed.
+// https://www.w3.org/TR/xml11/#sec-line-ends
+fn is_line_end(c: char, version: XmlVersion) -> bool {
+    match c {
+        '\r' => true,
+        '\u{85}' | '\u{2028}' => version == XmlVersion::Xml11,
+        _ => false,
+    }
+}
+
+// Normalize the value of an attribute of a tokenized type, by discarding
+// leading and trailing spaces and replacing sequences of spaces by a single
+// space.
+// https://www.w3.org/TR/xml/#AVNormalize
+pub(crate) fn normalize_tokenized_attribute(value: &str) -> String {
+    value
+        .split(' ')
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// When `errors` is given, we parse leniently: we record the errors in
+// references and keep these references as they are.
+fn parse_content<'a>(
+    content: Cow<'a, str>,
     attribute: bool,
     base_position: usize,
-) -> Result<Cow<str>, ParseError> {
+    version: XmlVersion,
+    mut entities: Option<&mut Entities>,
+    mut errors: Option<&mut Vec<ParseError>>,
+) -> Result<Cow<'a, str>, ParseError> {
     let mut result = String::new();
     let mut chars = content.char_indices().peekable();
     let mut change = false;
     while let Some((position, c)) = chars.next() {
         // https://www.w3.org/TR/xml/#sec-line-ends
-        if c == '\r' {
-            if let Some((_, peeked)) = chars.peek() {
-                if peeked == &'\n' {
-                    // consume next char
-                    chars.next();
+        if is_line_end(c, version) {
+            if c == '\r' {
+                if let Some((_, peeked)) = chars.peek() {
+                    if peeked == &'\n' || (peeked == &'\u{85}' && version == XmlVersion::Xml11) {
+                        // consume next char
+                        chars.next();
+                    }
                 }
             }
             if !attribute {
@@ -51,50 +398,28 @@ fn parse_content(
                 }
                 entity.push(c);
             }
-            if !is_complete {
-                return Err(ParseError::UnclosedEntity(entity, base_position + position));
-            }
             change = true;
-
-            if let Some(entity) = entity.strip_prefix('#') {
-                let first_char = entity.chars().next().ok_or_else(|| {
-                    ParseError::InvalidEntity(
-                        entity.to_string(),
-                        Span::new(base_position + position, base_position + end_position),
-                    )
-                })?;
-                let code = if first_char == 'x' {
-                    u32::from_str_radix(&entity[1..], 16)
-                } else {
-                    entity.parse::<u32>()
-                };
-                let code = code.map_err(|_| {
-                    ParseError::InvalidEntity(
-                        entity.to_string(),
-                        Span::new(base_position + position, base_position + end_position),
-                    )
-                })?;
-                let c = std::char::from_u32(code).ok_or_else(|| {
-                    ParseError::InvalidEntity(
-                        entity.to_string(),
-                        Span::new(base_position + position, base_position + end_position),
-                    )
-                })?;
-                result.push(c);
+            let expanded = if is_complete {
+                let span = Span::new(base_position + position, base_position + end_position);
+                expand_reference(&entity, attribute, span, version, entities.as_deref_mut())
             } else {
-                match entity.as_str() {
-                    "amp" => result.push('&'),
-                    "apos" => result.push('\''),
-                    "gt" => result.push('>'),
-                    "lt" => result.push('<'),
-                    "quot" => result.push('"'),
-                    _ => {
-                        return Err(ParseError::InvalidEntity(
-                            entity,
-                            Span::new(base_position + position, base_position + end_position),
-                        ))
+                Err(ParseError::UnclosedEntity(
+                    entity.clone(),
+                    base_position + position,
+                ))
+            };
+            match (expanded, errors.as_deref_mut()) {
+                (Ok(expanded), _) => result.push_str(&expanded),
+                // when parsing leniently, we keep the reference as it is
+                (Err(error), Some(errors)) => {
+                    errors.push(error);
+                    result.push('&');
+                    result.push_str(&entity);
+                    if is_complete {
+                        result.push(';');
                     }
                 }
+                (Err(error), None) => return Err(error),
             }
         } else if attribute && (c == '\t' || c == '\n') {
             // https://www.w3.org/TR/xml/#AVNormalize
@@ -113,6 +438,65 @@ fn parse_content(
     }
 }
 
+// Expand a character or entity reference in text or an attribute value.
+fn expand_reference(
+    entity: &str,
+    attribute: bool,
+    span: Span,
+    version: XmlVersion,
+    entities: Option<&mut Entities>,
+) -> Result<Cow<'static, str>, ParseError> {
+    if let Some(entity) = entity.strip_prefix('#') {
+        let first_char = entity
+            .chars()
+            .next()
+            .ok_or_else(|| ParseError::InvalidEntity(entity.to_string(), span))?;
+        let code = if first_char == 'x' {
+            u32::from_str_radix(&entity[1..], 16)
+        } else {
+            entity.parse::<u32>()
+        };
+        let code = code.map_err(|_| ParseError::InvalidEntity(entity.to_string(), span))?;
+        let c = std::char::from_u32(code)
+            .filter(|c| is_char(*c, version))
+            .ok_or_else(|| ParseError::InvalidEntity(entity.to_string(), span))?;
+        return Ok(c.to_string().into());
+    }
+    match entity {
+        "amp" => Ok("&".into()),
+        "apos" => Ok("'".into()),
+        "gt" => Ok(">".into()),
+        "lt" => Ok("<".into()),
+        "quot" => Ok("\"".into()),
+        _ => {
+            let entities =
+                entities.ok_or_else(|| ParseError::InvalidEntity(entity.to_string(), span))?;
+            // https://www.w3.org/TR/xml/#NoExternalRefs
+            if entities.is_external(entity) {
+                return Err(ParseError::InvalidEntity(entity.to_string(), span));
+            }
+            let replacement_text = entities.enter(entity, span)?;
+            // https://www.w3.org/TR/xml/#CleanAttrVals
+            let expanded = if replacement_text.contains('<') {
+                Err(ParseError::InvalidEntity(entity.to_string(), span))
+            } else {
+                parse_content(
+                    replacement_text.as_ref().into(),
+                    attribute,
+                    0,
+                    version,
+                    Some(entities),
+                    None,
+                )
+                .map(|expanded| expanded.into_owned().into())
+                .map_err(|e| e.with_span(span))
+            };
+            entities.exit();
+            expanded
+        }
+    }
+}
+
 pub(crate) fn serialize_text<'a, N: Normalizer>(
     content: Cow<'a, str>,
     normalizer: &N,
@@ -257,23 +641,40 @@ mod tests {
     use crate::output::NoopNormalizer;
 
     use super::*;
+    use crate::resolver::NoResolver;
+
+    fn no_entities() -> Entities {
+        Entities::new(Arc::new(NoResolver))
+    }
 
     #[test]
     fn test_parse() {
         let text = "A &amp; B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A & B"
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let text = "a &amp; &#x21;&e; & b &c d; &;";
+        assert_eq!(references(text), vec![2..7, 8..14, 14..17]);
     }
 
     #[test]
     fn test_parse_multiple() {
         let text = "&amp;&apos;&gt;&lt;&quot;";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "&'><\"");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "&'><\""
+        );
     }
 
     #[test]
     fn test_parse_unknown_entity() {
         let text = "&unknown;";
-        let err = parse_text(text.into(), 0);
+        let err = parse_text(text.into(), 0, XmlVersion::Xml10);
         if let Err(ParseError::InvalidEntity(entity, span)) = err {
             assert_eq!(entity, "unknown");
             assert_eq!(span, Span::new(0, 9));
@@ -285,7 +686,7 @@ mod tests {
     #[test]
     fn test_parse_unfinished_entity() {
         let text = "&amp";
-        let err = parse_text(text.into(), 0);
+        let err = parse_text(text.into(), 0, XmlVersion::Xml10);
         if let Err(ParseError::UnclosedEntity(entity, position)) = err {
             assert_eq!(entity, "amp");
             assert_eq!(position, 0);
@@ -297,7 +698,7 @@ mod tests {
     #[test]
     fn test_parse_no_entities() {
         let text = "hello";
-        let result = parse_text(text.into(), 0).unwrap();
+        let result = parse_text(text.into(), 0, XmlVersion::Xml10).unwrap();
         // this is the same slice
         assert!(std::ptr::eq(text, result.as_ref()));
     }
@@ -305,49 +706,151 @@ mod tests {
     #[test]
     fn test_parse_newline_r() {
         let text = "A \r B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_parse_newline_rn() {
         let text = "A \r\n B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_do_not_normalize_text_tab() {
         let text = "A \t B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A \t B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A \t B"
+        );
     }
 
     #[test]
     fn test_do_not_normalize_text_newline() {
         let text = "A \n B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_tab() {
         let text = "A \t B";
-        assert_eq!(parse_attribute(text.into(), 0).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), 0, &mut no_entities()).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_r_newline() {
         let text = "A \r B";
-        assert_eq!(parse_attribute(text.into(), 0).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), 0, &mut no_entities()).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_rn_newline() {
         let text = "A \r\n B";
-        assert_eq!(parse_attribute(text.into(), 0).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), 0, &mut no_entities()).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_newline() {
         let text = "A \n B";
-        assert_eq!(parse_attribute(text.into(), 0).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), 0, &mut no_entities()).unwrap(),
+            "A   B"
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_declared_entity() {
+        let mut entities = Entities::new(Arc::new(NoResolver));
+        entities.declare("e", "E\tF", 0).unwrap();
+        assert_eq!(
+            parse_attribute("A &e; B".into(), 0, &mut entities).unwrap(),
+            "A E F B"
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_nested_entity() {
+        let mut entities = Entities::new(Arc::new(NoResolver));
+        entities.declare("e", "[&f;]", 0).unwrap();
+        entities.declare("f", "F", 0).unwrap();
+        assert_eq!(
+            parse_attribute("&e;".into(), 0, &mut entities).unwrap(),
+            "[F]"
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_recursive_entity() {
+        let mut entities = Entities::new(Arc::new(NoResolver));
+        entities.declare("e", "&f;", 0).unwrap();
+        entities.declare("f", "&e;", 0).unwrap();
+        let err = parse_attribute("A &e;".into(), 0, &mut entities).unwrap_err();
+        assert!(matches!(err, ParseError::RecursiveEntity(_, _)));
+        assert_eq!(err.span(), Span::new(2, 5));
+    }
+
+    #[test]
+    fn test_replacement_text_expands_character_references() {
+        assert_eq!(
+            replacement_text("&#60;&lt;&#x41;", 0, XmlVersion::Xml10).unwrap(),
+            "<&lt;A"
+        );
+    }
+
+    #[test]
+    fn test_replacement_text_normalizes_newlines() {
+        assert_eq!(
+            replacement_text("a\r\nb\rc", 0, XmlVersion::Xml10).unwrap(),
+            "a\nb\nc"
+        );
+    }
+
+    #[test]
+    fn test_replacement_text_xml11() {
+        assert_eq!(
+            replacement_text("a\r\u{85}b\u{85}c\u{2028}&#x1;", 0, XmlVersion::Xml11).unwrap(),
+            "a\nb\nc\n\u{1}"
+        );
+        assert_eq!(
+            replacement_text("a\r\u{85}", 0, XmlVersion::Xml10).unwrap(),
+            "a\n\u{85}"
+        );
+        assert!(replacement_text("&#x1;", 0, XmlVersion::Xml10).is_err());
+    }
+
+    #[test]
+    fn test_entity_declared_first_is_binding() {
+        let mut entities = Entities::new(Arc::new(NoResolver));
+        entities.declare("e", "first", 0).unwrap();
+        entities.declare("e", "second", 0).unwrap();
+        entities.declare("lt", "&#38;#60;", 0).unwrap();
+        assert_eq!(
+            parse_attribute("&e;&lt;".into(), 0, &mut entities).unwrap(),
+            "first<"
+        );
+    }
+
+    #[test]
+    fn test_entity_references() {
+        let references = entity_references("a &amp; &foo; &#32; &bar;");
+        assert_eq!(references, vec![(8..13, "foo"), (20..25, "bar")]);
     }
 
     #[test]
@@ -468,31 +971,37 @@ mod tests {
     #[test]
     fn test_parse_character_hex_entity() {
         let text = "A &#x26; B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A & B"
+        );
     }
 
     #[test]
     fn test_parse_character_decimal_entity() {
         let text = "A &#38; B";
-        assert_eq!(parse_text(text.into(), 0).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), 0, XmlVersion::Xml10).unwrap(),
+            "A & B"
+        );
     }
 
     #[test]
     fn test_parse_character_empty_entity() {
         let text = "A &#; B";
-        assert!(parse_text(text.into(), 0).is_err());
+        assert!(parse_text(text.into(), 0, XmlVersion::Xml10).is_err());
     }
 
     #[test]
     fn test_parse_character_empty_hex_entity() {
         let text = "A &x#; B";
-        assert!(parse_text(text.into(), 0).is_err());
+        assert!(parse_text(text.into(), 0, XmlVersion::Xml10).is_err());
     }
 
     #[test]
     fn test_parse_character_broken_hex_entity() {
         let text = "A &xflub#; B";
-        assert!(parse_text(text.into(), 0).is_err());
+        assert!(parse_text(text.into(), 0, XmlVersion::Xml10).is_err());
     }
 
     #[test]
@@ -1,3 +1,4 @@
+use crate::resolver::ResolveError;
 use crate::{xotdata::Node, Span};
 
 /// An error that occurred during parsing.
@@ -10,20 +11,84 @@ pub enum ParseError {
     /// The XML is not well-formed - you use `&` to open an entity without
     /// closing it with `;`.
     UnclosedEntity(String, usize),
-    /// The entity is not known. Only the basic entities are supported
-    /// right now, not any user defined ones.
+    /// The entity is not known, or it cannot be used here. Only the
+    /// predefined entities and entities declared in the DTD are known.
+    /// External entities and unparsed entities cannot be referenced in
+    /// attribute values, and unparsed entities cannot be referenced in
+    /// content.
     InvalidEntity(String, Span),
+    /// The entity refers to itself, directly or indirectly.
+    RecursiveEntity(String, Span),
+    /// An element is nested deeper than the
+    /// [`ParseLimits`](crate::ParseLimits) allow. The span is that of its
+    /// name.
+    DepthLimit(Span),
+    /// An element has more attributes than the
+    /// [`ParseLimits`](crate::ParseLimits) allow. The span is that of the
+    /// name of the first attribute too many.
+    AttributeLimit(Span),
+    /// A name is longer than the [`ParseLimits`](crate::ParseLimits) allow.
+    NameLengthLimit(Span),
+    /// The document has more text than the
+    /// [`ParseLimits`](crate::ParseLimits) allow. The span is that of the
+    /// text that exceeds it.
+    TextSizeLimit(Span),
+    /// The document has more nodes than the
+    /// [`ParseLimits`](crate::ParseLimits) allow. The span is that of the
+    /// node that exceeds it.
+    NodeLimit(Span),
+    /// The encoding declaration contradicts the encoding of the bytes, or
+    /// names an unknown encoding. The span is that of the encoding name in
+    /// the declaration. This is only an error if you ask for
+    /// [`strict_encoding`](crate::ParseOptions::strict_encoding).
+    EncodingMismatch(String, Span),
+    /// The bytes are malformed in the encoding with the given name. The
+    /// span is where the text decoded so far ends. This is only an error if
+    /// you ask for [`strict_encoding`](crate::ParseOptions::strict_encoding).
+    MalformedEncoding(String, Span),
+    /// The entity is declared, but the
+    /// [`EntityExpansion`](crate::EntityExpansion) of the
+    /// [`ParseOptions`](crate::ParseOptions) doesn't allow its expansion.
+    EntityNotAllowed(String, Span),
+    /// Expanding the entity exceeds the limits on entity expansion, either
+    /// because entity references are nested too deeply or because too much
+    /// text is produced. This protects against "billion laughs" attacks.
+    EntityExpansionLimit(String, Span),
+    /// The external entity could not be resolved by the
+    /// [`Resolver`](crate::resolver::Resolver). The span is that of the
+    /// entity reference.
+    UnresolvedEntity(String, ResolveError, Span),
+    /// The external DTD subset with the given system id could not be
+    /// resolved by the [`Resolver`](crate::resolver::Resolver). The span is
+    /// that of the system id in the document type declaration.
+    ///
+    /// If the resolver refuses to fetch the external DTD subset, it's
+    /// not read and this error does not occur.
+    UnresolvedDtd(String, ResolveError, Span),
+    /// The DTD is not well-formed.
+    InvalidDtd(Span),
     /// You used a namespace prefix that is not declared during parsing.
     UnknownPrefix(String, Span),
     /// You declared an attribute of the same name twice.
     DuplicateAttribute(String, Span),
-    /// Unsupported XML version. Only 1.0 is supported.
+    /// Unsupported XML version. Only 1.0 and 1.1 are supported.
     UnsupportedVersion(String, Span),
+    /// A namespace prefix is undeclared with `xmlns:p=""`, which is only
+    /// allowed in XML 1.1.
+    PrefixUndeclaration(String, Span),
+    /// A control character that XML 1.1 only allows as a character
+    /// reference.
+    RestrictedChar(char, Span),
     /// Unsupported standalone declaration. This error is deprecated since version 0.29, and both
     /// "yes" and "no" values are accepted for the standalone declaration.
-    #[deprecated(since = "0.2.9", note = "The value of the standalone declaration is now ignored")]
+    #[deprecated(
+        since = "0.2.9",
+        note = "The value of the standalone declaration is now ignored"
+    )]
     UnsupportedNotStandalone(Span),
-    /// XML DTD is not supported.
+    /// XML DTD is not supported. This error is deprecated since version 0.29, as
+    /// document type declarations are now accepted by the parser.
+    #[deprecated(since = "0.29.0", note = "Document type declarations are now parsed")]
     DtdUnsupported(Span),
     /// No top-level element in the document.
     NoElementAtTopLevel(usize),
@@ -43,11 +108,27 @@ impl ParseError {
             ParseError::InvalidCloseTag(_, _, span) => *span,
             ParseError::UnclosedEntity(_, position) => Span::new(*position, *position),
             ParseError::InvalidEntity(_, span) => *span,
+            ParseError::RecursiveEntity(_, span) => *span,
+            ParseError::EntityNotAllowed(_, span) => *span,
+            ParseError::EncodingMismatch(_, span) => *span,
+            ParseError::MalformedEncoding(_, span) => *span,
+            ParseError::DepthLimit(span) => *span,
+            ParseError::AttributeLimit(span) => *span,
+            ParseError::NameLengthLimit(span) => *span,
+            ParseError::TextSizeLimit(span) => *span,
+            ParseError::NodeLimit(span) => *span,
+            ParseError::EntityExpansionLimit(_, span) => *span,
+            ParseError::UnresolvedEntity(_, _, span) => *span,
+            ParseError::UnresolvedDtd(_, _, span) => *span,
+            ParseError::InvalidDtd(span) => *span,
             ParseError::UnknownPrefix(_, span) => *span,
             ParseError::DuplicateAttribute(_, span) => *span,
             ParseError::UnsupportedVersion(_, span) => *span,
+            ParseError::PrefixUndeclaration(_, span) => *span,
+            ParseError::RestrictedChar(_, span) => *span,
             #[allow(deprecated)]
             ParseError::UnsupportedNotStandalone(span) => *span,
+            #[allow(deprecated)]
             ParseError::DtdUnsupported(span) => *span,
             ParseError::NoElementAtTopLevel(position) => Span::new(*position, *position),
             ParseError::MultipleElementsAtTopLevel(span) => *span,
@@ -55,6 +136,61 @@ impl ParseError {
             ParseError::XmlParser(_, position) => Span::new(*position, *position),
         }
     }
+
+    // Relocate an error to the given span. This is used for errors in the
+    // replacement text of an entity, which we report at the entity reference.
+    pub(crate) fn with_span(self, span: Span) -> Self {
+        match self {
+            ParseError::UnclosedTag(_) => ParseError::UnclosedTag(span),
+            ParseError::InvalidCloseTag(prefix, name, _) => {
+                ParseError::InvalidCloseTag(prefix, name, span)
+            }
+            ParseError::UnclosedEntity(entity, _) => ParseError::UnclosedEntity(entity, span.start),
+            ParseError::InvalidEntity(entity, _) => ParseError::InvalidEntity(entity, span),
+            ParseError::RecursiveEntity(entity, _) => ParseError::RecursiveEntity(entity, span),
+            ParseError::EntityNotAllowed(entity, _) => ParseError::EntityNotAllowed(entity, span),
+            ParseError::EncodingMismatch(encoding, _) => {
+                ParseError::EncodingMismatch(encoding, span)
+            }
+            ParseError::MalformedEncoding(encoding, _) => {
+                ParseError::MalformedEncoding(encoding, span)
+            }
+            ParseError::DepthLimit(_) => ParseError::DepthLimit(span),
+            ParseError::AttributeLimit(_) => ParseError::AttributeLimit(span),
+            ParseError::NameLengthLimit(_) => ParseError::NameLengthLimit(span),
+            ParseError::TextSizeLimit(_) => ParseError::TextSizeLimit(span),
+            ParseError::NodeLimit(_) => ParseError::NodeLimit(span),
+            ParseError::EntityExpansionLimit(entity, _) => {
+                ParseError::EntityExpansionLimit(entity, span)
+            }
+            ParseError::UnresolvedEntity(entity, e, _) => {
+                ParseError::UnresolvedEntity(entity, e, span)
+            }
+            ParseError::UnresolvedDtd(system_id, e, _) => {
+                ParseError::UnresolvedDtd(system_id, e, span)
+            }
+            ParseError::InvalidDtd(_) => ParseError::InvalidDtd(span),
+            ParseError::UnknownPrefix(prefix, _) => ParseError::UnknownPrefix(prefix, span),
+            ParseError::DuplicateAttribute(name, _) => ParseError::DuplicateAttribute(name, span),
+            ParseError::UnsupportedVersion(version, _) => {
+                ParseError::UnsupportedVersion(version, span)
+            }
+            ParseError::PrefixUndeclaration(prefix, _) => {
+                ParseError::PrefixUndeclaration(prefix, span)
+            }
+            ParseError::RestrictedChar(c, _) => ParseError::RestrictedChar(c, span),
+            #[allow(deprecated)]
+            ParseError::UnsupportedNotStandalone(_) => ParseError::UnsupportedNotStandalone(span),
+            #[allow(deprecated)]
+            ParseError::DtdUnsupported(_) => ParseError::DtdUnsupported(span),
+            ParseError::NoElementAtTopLevel(_) => ParseError::NoElementAtTopLevel(span.start),
+            ParseError::MultipleElementsAtTopLevel(_) => {
+                ParseError::MultipleElementsAtTopLevel(span)
+            }
+            ParseError::TextAtTopLevel(_) => ParseError::TextAtTopLevel(span),
+            ParseError::XmlParser(e, _) => ParseError::XmlParser(e, span.start),
+        }
+    }
 }
 
 /// Xot errors
@@ -76,6 +212,17 @@ pub enum Error {
     /// You aren't allowed to use this string as a processing instruction
     /// target. Happens if you use `XML` or any case variation of this.
     InvalidTarget(String),
+    /// You aren't allowed to use this string as a name without a prefix,
+    /// such as a local name or a prefix. It has to be an
+    /// [NCName](https://www.w3.org/TR/xml-names/#NT-NCName).
+    InvalidNcName(String),
+    /// You aren't allowed to use this string as a name with an optional
+    /// prefix. It has to be a
+    /// [QName](https://www.w3.org/TR/xml-names/#NT-QName).
+    InvalidQName(String),
+    /// XML doesn't allow this character, such as NUL. It has to be a
+    /// [Char](https://www.w3.org/TR/xml/#NT-Char).
+    InvalidCharacter(char),
     /// The node you tried to act on is not an element.
     NotElement(Node),
     /// Indextree error that can happen during manipulation.
@@ -94,6 +241,13 @@ pub enum Error {
     /// target name.
     NamespaceInProcessingInstruction,
 
+    /// The encoding in the XML declaration isn't one we can write XML in.
+    UnsupportedEncoding(String),
+    /// A character in a name, comment or processing instruction can't be
+    /// represented in the encoding of the output. Text and attribute values
+    /// use character references for such characters instead.
+    UnrepresentableCharacter(char, String),
+
     /// An error during parsing
     Parse(ParseError),
 
@@ -149,6 +303,9 @@ impl std::fmt::Display for Error {
             Error::InvalidOperation(s) => write!(f, "Invalid operation: {}", s),
             Error::InvalidComment(s) => write!(f, "Invalid comment: {}", s),
             Error::InvalidTarget(s) => write!(f, "Invalid target: {}", s),
+            Error::InvalidNcName(s) => write!(f, "Invalid NCName: {:?}", s),
+            Error::InvalidQName(s) => write!(f, "Invalid QName: {:?}", s),
+            Error::InvalidCharacter(c) => write!(f, "Invalid character: {:?}", c),
             Error::NotElement(_) => write!(f, "Not an element"),
             Error::NodeError(e) => write!(f, "Node error: {}", e),
             Error::MissingPrefix(_) => write!(f, "Missing prefix"),
@@ -158,7 +315,11 @@ impl std::fmt::Display for Error {
             Error::NamespaceInProcessingInstruction => {
                 write!(f, "Namespace in processing instruction target")
             }
-            Error::Parse(e) => write!(f, "Parse error: {:?}", e),
+            Error::UnsupportedEncoding(s) => write!(f, "Unsupported encoding: {}", s),
+            Error::UnrepresentableCharacter(c, encoding) => {
+                write!(f, "Cannot represent {:?} in {}", c, encoding)
+            }
+            Error::Parse(e) => write!(f, "Parse error: {}", e),
             Error::UnknownPrefix(s) => write!(f, "Unknown prefix: {}", s),
             Error::IllegalAtTopLevel(_) => write!(f, "Illegal content under document node (attribute, namespace or document node"),
             Error::TextAtTopLevel(_) => write!(f, "Text node under document not. Not allowed in a well-formed document, but allowed in a fragment"),
@@ -176,11 +337,35 @@ impl std::fmt::Display for ParseError {
             ParseError::InvalidCloseTag(s, s2, _) => write!(f, "Invalid close tag: {} {}", s, s2),
             ParseError::UnclosedEntity(s, _) => write!(f, "Unclosed entity: {}", s),
             ParseError::InvalidEntity(s, _) => write!(f, "Invalid entity: {}", s),
+            ParseError::RecursiveEntity(s, _) => write!(f, "Recursive entity: {}", s),
+            ParseError::EntityNotAllowed(s, _) => write!(f, "Entity not allowed: {}", s),
+            ParseError::EncodingMismatch(s, _) => write!(f, "Encoding mismatch: {}", s),
+            ParseError::MalformedEncoding(s, _) => write!(f, "Malformed {}", s),
+            ParseError::DepthLimit(_) => write!(f, "Depth limit exceeded"),
+            ParseError::AttributeLimit(_) => write!(f, "Attribute limit exceeded"),
+            ParseError::NameLengthLimit(_) => write!(f, "Name length limit exceeded"),
+            ParseError::TextSizeLimit(_) => write!(f, "Text size limit exceeded"),
+            ParseError::NodeLimit(_) => write!(f, "Node limit exceeded"),
+            ParseError::EntityExpansionLimit(s, _) => {
+                write!(f, "Entity expansion limit exceeded: {}", s)
+            }
+            ParseError::UnresolvedEntity(s, e, _) => {
+                write!(f, "Cannot resolve external entity {}: {}", s, e)
+            }
+            ParseError::UnresolvedDtd(s, e, _) => {
+                write!(f, "Cannot resolve external DTD subset {}: {}", s, e)
+            }
+            ParseError::InvalidDtd(_) => write!(f, "Invalid DTD"),
             ParseError::UnknownPrefix(s, _) => write!(f, "Unknown prefix: {}", s),
             ParseError::DuplicateAttribute(s, _) => write!(f, "Duplicate attribute: {}", s),
             ParseError::UnsupportedVersion(s, _) => write!(f, "Unsupported version: {}", s),
+            ParseError::PrefixUndeclaration(s, _) => {
+                write!(f, "Prefix undeclaration outside of XML 1.1: {}", s)
+            }
+            ParseError::RestrictedChar(c, _) => write!(f, "Restricted character: {:?}", c),
             #[allow(deprecated)]
             ParseError::UnsupportedNotStandalone(_) => write!(f, "Unsupported standalone"),
+            #[allow(deprecated)]
             ParseError::DtdUnsupported(_) => write!(f, "DTD is not supported"),
             ParseError::NoElementAtTopLevel(_) => write!(f, "No element at top level"),
             ParseError::MultipleElementsAtTopLevel(_) => {
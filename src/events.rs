@@ -0,0 +1,513 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+use indextree::NodeId;
+use xmlparser::Tokenizer;
+
+use crate::builder::{Built, DocumentBuilder};
+use crate::dtd::Dtd;
+use crate::error::{Error, ParseError};
+use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::incremental::IncrementalParser;
+use crate::parse::READ_BUFFER_SIZE;
+use crate::parseoptions::ParseOptions;
+use crate::spanmapping::SpanMapping;
+use crate::xmlvalue::Value;
+use crate::xotdata::{Node, Xot};
+use crate::{Span, SpanInfo, SpanInfoKey};
+
+/// An event produced by [`ParseEvents`].
+///
+/// Names are resolved to [`NameId`]s using the namespaces in scope, and
+/// entities are expanded, just like when you parse a document into a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEvent {
+    /// The start of an element.
+    StartElement {
+        /// The name of the element.
+        name: NameId,
+        /// The namespaces declared on the element.
+        namespaces: Vec<(PrefixId, NamespaceId)>,
+        /// The attributes of the element with their values, including any
+        /// attributes defaulted from the DTD.
+        attributes: Vec<(NameId, String)>,
+        /// The span of the element name in the start tag.
+        span: Span,
+    },
+    /// The end of an element.
+    EndElement {
+        /// The name of the element.
+        name: NameId,
+        /// The span of the end tag, or of the `/>` of an empty element.
+        span: Span,
+    },
+    /// Text, including the text of CDATA sections. Adjacent text and CDATA
+    /// sections are combined into a single event.
+    Text {
+        /// The text.
+        text: String,
+        /// The span of the text.
+        span: Span,
+    },
+    /// A comment.
+    Comment {
+        /// The text of the comment.
+        text: String,
+        /// The span of the text of the comment.
+        span: Span,
+    },
+    /// A processing instruction.
+    ProcessingInstruction {
+        /// The target of the processing instruction.
+        target: NameId,
+        /// The content of the processing instruction, if any.
+        content: Option<String>,
+        /// The span of the target.
+        span: Span,
+    },
+}
+
+impl ParseEvent {
+    /// The span of the event in the source.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseEvent::StartElement { span, .. }
+            | ParseEvent::EndElement { span, .. }
+            | ParseEvent::Text { span, .. }
+            | ParseEvent::Comment { span, .. }
+            | ParseEvent::ProcessingInstruction { span, .. } => *span,
+        }
+    }
+}
+
+/// An iterator over the parse events of an XML document.
+///
+/// You obtain this with [`Xot::parse_events`]. It produces events without
+/// building a tree, so memory use doesn't grow with the size of the
+/// document, apart from the source, which you can read chunk by chunk with
+/// [`ReaderParseEvents`] instead. If the document is not well-formed, the iterator produces the
+/// events up to the problem, then a [`ParseError`], and then stops.
+///
+/// As the iterator holds on to the [`Xot`] to resolve names, you can access it
+/// during iteration through [`ParseEvents::xot`].
+pub struct ParseEvents<'a> {
+    xot: &'a mut Xot,
+    xml: &'a str,
+    tokenizer: Tokenizer<'a>,
+    builder: DocumentBuilder,
+    span_info: SpanInfo,
+    events: EventQueue,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> ParseEvents<'a> {
+    pub(crate) fn new(xot: &'a mut Xot, xml: &'a str, options: &ParseOptions) -> Self {
+        let mut builder = DocumentBuilder::new(xot, options);
+        EventQueue::prepare(&mut builder);
+        ParseEvents {
+            xot,
+            xml,
+            tokenizer: Tokenizer::from(xml),
+            builder,
+            span_info: SpanInfo::new(),
+            events: EventQueue::default(),
+            started: false,
+            done: false,
+        }
+    }
+
+    /// The Xot used to resolve names.
+    pub fn xot(&self) -> &Xot {
+        self.xot
+    }
+
+    /// The document type declaration, if the document has one and it has
+    /// been parsed.
+    pub fn dtd(&self) -> Option<&Dtd> {
+        self.builder.dtd.as_ref()
+    }
+
+    fn advance(&mut self) -> Result<(), ParseError> {
+        if !self.started {
+            self.started = true;
+            self.start()?;
+        } else if !self.xot.parse_next_token(
+            &mut self.tokenizer,
+            &mut self.builder,
+            &mut self.span_info,
+            SpanMapping::Source,
+        )? {
+            self.done = true;
+            return self.events.finish(
+                self.xot,
+                &mut self.builder,
+                &mut self.span_info,
+                self.xml.len(),
+            );
+        }
+        self.events
+            .process_built(self.xot, &mut self.builder, &mut self.span_info)
+    }
+
+    fn start(&mut self) -> Result<(), ParseError> {
+        self.tokenizer =
+            self.xot
+                .document_tokenizer(self.xml, &mut self.builder, &mut self.span_info)?;
+        Ok(())
+    }
+}
+
+impl Iterator for ParseEvents<'_> {
+    type Item = Result<ParseEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.advance() {
+                self.done = true;
+                self.events.events.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl Drop for ParseEvents<'_> {
+    fn drop(&mut self) {
+        // get rid of whatever is left of the nodes we built
+        self.builder.tree.remove_subtree(&mut self.xot.arena);
+    }
+}
+
+/// An iterator over the parse events of an XML document read from a
+/// reader.
+///
+/// You obtain this with [`Xot::parse_events_reader`]. This is like
+/// [`ParseEvents`], but it reads and decodes the data chunk by chunk as you
+/// iterate, so that you don't need the whole document in memory at once. As
+/// reading can fail, it produces [`Error`]s rather than just
+/// [`ParseError`]s.
+pub struct ReaderParseEvents<'a, R> {
+    xot: &'a mut Xot,
+    reader: R,
+    buffer: Vec<u8>,
+    parser: IncrementalParser,
+    events: EventQueue,
+    done: bool,
+}
+
+impl<'a, R: Read> ReaderParseEvents<'a, R> {
+    pub(crate) fn new(xot: &'a mut Xot, reader: R, options: &ParseOptions) -> Self {
+        let mut parser = IncrementalParser::new(xot, options);
+        EventQueue::prepare(&mut parser.builder);
+        ReaderParseEvents {
+            xot,
+            reader,
+            buffer: vec![0; READ_BUFFER_SIZE],
+            parser,
+            events: EventQueue::default(),
+            done: false,
+        }
+    }
+
+    /// The Xot used to resolve names.
+    pub fn xot(&self) -> &Xot {
+        self.xot
+    }
+
+    /// The document type declaration, if the document has one and it has
+    /// been parsed.
+    pub fn dtd(&self) -> Option<&Dtd> {
+        self.parser.builder.dtd.as_ref()
+    }
+
+    fn advance(&mut self) -> Result<(), Error> {
+        let read = match self.reader.read(&mut self.buffer) {
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if read == 0 {
+            self.done = true;
+            self.parser.finish_parsing(self.xot)?;
+        } else {
+            self.parser.feed(self.xot, &self.buffer[..read])?;
+        }
+        let parser = &mut self.parser;
+        self.events
+            .process_built(self.xot, &mut parser.builder, &mut parser.span_info)?;
+        if self.done {
+            let length = parser.position();
+            self.events
+                .finish(self.xot, &mut parser.builder, &mut parser.span_info, length)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for ReaderParseEvents<'_, R> {
+    type Item = Result<ParseEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.advance() {
+                self.done = true;
+                self.events.events.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl<R> Drop for ReaderParseEvents<'_, R> {
+    fn drop(&mut self) {
+        // get rid of whatever is left of the nodes we built
+        self.parser.builder.tree.remove_subtree(&mut self.xot.arena);
+    }
+}
+
+// Turns what a builder built into events, removing the nodes as it goes.
+#[derive(Default)]
+struct EventQueue {
+    events: VecDeque<ParseEvent>,
+    // Text is only complete once something else comes along, as adjacent
+    // text and CDATA sections are consolidated.
+    pending_text: Option<NodeId>,
+    has_document_element: bool,
+}
+
+impl EventQueue {
+    // Set up the builder to record what it builds, and to build nothing
+    // that events don't need.
+    fn prepare(builder: &mut DocumentBuilder) {
+        builder.built = Some(Vec::new());
+        // elements don't stay around, so there's no point in recording
+        // their ids
+        builder.ids = None;
+        // text events don't tell CDATA sections apart
+        builder.preserve_cdata = false;
+        // whitespace is only insignificant once its element is closed, by
+        // which time we've produced its events
+        builder.remove_insignificant_whitespace = false;
+    }
+
+    fn process_built(
+        &mut self,
+        xot: &mut Xot,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+    ) -> Result<(), ParseError> {
+        let built = builder.built.replace(Vec::new()).unwrap_or_default();
+        for built in built {
+            match built {
+                Built::Added(node_id) if self.pending_text == Some(node_id) => {}
+                Built::Added(node_id) => {
+                    self.flush_text(xot, span_info);
+                    self.added(xot, node_id, span_info);
+                }
+                Built::Opened(node_id) => {
+                    self.flush_text(xot, span_info);
+                    self.opened(xot, builder, node_id, span_info)?;
+                }
+                Built::Closed(node_id) => {
+                    self.flush_text(xot, span_info);
+                    self.closed(xot, node_id, span_info);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check the end of the document, which is `length` long.
+    fn finish(
+        &mut self,
+        xot: &mut Xot,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+        length: usize,
+    ) -> Result<(), ParseError> {
+        self.flush_text(xot, span_info);
+        if !builder.is_current_node_document(xot) {
+            let current = Node::new(builder.current_node_id);
+            let span = span(span_info, SpanInfoKey::ElementStart(current));
+            return Err(ParseError::UnclosedTag(span));
+        }
+        if !self.has_document_element {
+            return Err(ParseError::NoElementAtTopLevel(length));
+        }
+        Ok(())
+    }
+
+    fn opened(
+        &mut self,
+        xot: &mut Xot,
+        builder: &DocumentBuilder,
+        node_id: NodeId,
+        span_info: &mut SpanInfo,
+    ) -> Result<(), ParseError> {
+        let node = Node::new(node_id);
+        let span = span(span_info, SpanInfoKey::ElementStart(node));
+        if xot.arena[node_id].parent() == Some(builder.tree) {
+            if self.has_document_element {
+                return Err(ParseError::MultipleElementsAtTopLevel(span));
+            }
+            self.has_document_element = true;
+        }
+        let mut namespaces = Vec::new();
+        let mut attributes = Vec::new();
+        // when we process what was built for a whole chunk at once, the
+        // element may have content already, which we leave for later
+        let children = node_id.children(&xot.arena).collect::<Vec<_>>();
+        for child in children {
+            match xot.arena[child].get_mut() {
+                Value::Namespace(namespace) => {
+                    let prefix = namespace.prefix();
+                    namespaces.push((prefix, namespace.namespace()));
+                    span_info.remove(SpanInfoKey::NamespacePrefix(node, prefix));
+                    span_info.remove(SpanInfoKey::NamespaceUri(node, prefix));
+                }
+                Value::Attribute(attribute) => {
+                    let name = attribute.name();
+                    attributes.push((name, std::mem::take(&mut attribute.value)));
+                    span_info.remove(SpanInfoKey::AttributeName(node, name));
+                    span_info.remove(SpanInfoKey::AttributeValue(node, name));
+                }
+                _ => continue,
+            }
+            child.remove(&mut xot.arena);
+        }
+        let name = xot.element(node).unwrap().name();
+        self.events.push_back(ParseEvent::StartElement {
+            name,
+            namespaces,
+            attributes,
+            span,
+        });
+        Ok(())
+    }
+
+    fn closed(&mut self, xot: &mut Xot, node_id: NodeId, span_info: &mut SpanInfo) {
+        let node = Node::new(node_id);
+        let name = xot.element(node).unwrap().name();
+        span_info.remove(SpanInfoKey::ElementStart(node));
+        let span = span(span_info, SpanInfoKey::ElementEnd(node));
+        span_info.remove(SpanInfoKey::ElementEnd(node));
+        node_id.remove_subtree(&mut xot.arena);
+        self.events.push_back(ParseEvent::EndElement { name, span });
+    }
+
+    fn added(&mut self, xot: &mut Xot, node_id: NodeId, span_info: &mut SpanInfo) {
+        let node = Node::new(node_id);
+        let event = match xot.arena[node_id].get_mut() {
+            Value::Text(_) => {
+                self.pending_text = Some(node_id);
+                return;
+            }
+            Value::Comment(comment) => {
+                let text = comment.get().to_string();
+                let span = span(span_info, SpanInfoKey::Comment(node));
+                span_info.remove(SpanInfoKey::Comment(node));
+                ParseEvent::Comment { text, span }
+            }
+            Value::ProcessingInstruction(processing_instruction) => {
+                let target = processing_instruction.target();
+                let content = processing_instruction.data().map(|data| data.to_string());
+                let span = span(span_info, SpanInfoKey::PiTarget(node));
+                span_info.remove(SpanInfoKey::PiTarget(node));
+                span_info.remove(SpanInfoKey::PiContent(node));
+                ParseEvent::ProcessingInstruction {
+                    target,
+                    content,
+                    span,
+                }
+            }
+            _ => unreachable!(),
+        };
+        node_id.remove(&mut xot.arena);
+        self.events.push_back(event);
+    }
+
+    fn flush_text(&mut self, xot: &mut Xot, span_info: &mut SpanInfo) {
+        let Some(node_id) = self.pending_text.take() else {
+            return;
+        };
+        let node = Node::new(node_id);
+        let span = span(span_info, SpanInfoKey::Text(node));
+        span_info.remove(SpanInfoKey::Text(node));
+        span_info.remove_references(node);
+        let text = match xot.arena[node_id].get_mut() {
+            Value::Text(text) => std::mem::take(text.get_mut()),
+            _ => unreachable!(),
+        };
+        node_id.remove(&mut xot.arena);
+        self.events.push_back(ParseEvent::Text { text, span });
+    }
+}
+
+fn span(span_info: &SpanInfo, key: SpanInfoKey) -> Span {
+    // all nodes we build have spans
+    span_info.get(key).copied().unwrap_or(Span::new(0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_does_not_grow() {
+        let mut xot = Xot::new();
+        let mut xml = "<doc>".to_string();
+        for i in 0..1000 {
+            xml.push_str(&format!(
+                r#"<item id="{}" xmlns:x="u">a &amp; b<!--c--><?pi?></item>"#,
+                i
+            ));
+        }
+        xml.push_str("</doc>");
+        let before = xot.arena.count();
+        let mut events = xot.parse_events(&xml);
+        for event in events.by_ref() {
+            event.unwrap();
+        }
+        assert_eq!(events.span_info.map.len(), 0);
+        drop(events);
+        assert!(xot.arena.count() - before < 10);
+    }
+
+    #[test]
+    fn test_reader_arena_does_not_grow() {
+        let mut xot = Xot::new();
+        let mut xml = "<doc>".to_string();
+        for i in 0..10000 {
+            xml.push_str(&format!(
+                r#"<item id="{}" xmlns:x="u">a &amp; b<!--c--><?pi?></item>"#,
+                i
+            ));
+        }
+        xml.push_str("</doc>");
+        let before = xot.arena.count();
+        let mut events = xot.parse_events_reader(xml.as_bytes());
+        for event in events.by_ref() {
+            event.unwrap();
+        }
+        assert_eq!(events.parser.span_info.map.len(), 0);
+        drop(events);
+        // we build the nodes of a whole chunk before we turn them into
+        // events, so the arena grows with the size of a chunk, but not with
+        // the size of the document, which has several nodes per item
+        assert!(xml.len() > 4 * READ_BUFFER_SIZE);
+        assert!(xot.arena.count() - before < 10000);
+    }
+}
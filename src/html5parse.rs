@@ -0,0 +1,370 @@
+use std::borrow::Cow;
+use std::cell::{Ref, RefCell};
+use std::fmt::Write as _;
+
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use html5ever::interface::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
+use html5ever::tendril::{StrTendril, TendrilSink};
+use html5ever::{ns, parse_document, Attribute, QualName};
+
+use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::xmlchar::{is_char, is_name_char, is_name_start_char};
+use crate::xotdata::{Node, Xot};
+use crate::XmlVersion;
+
+// Builds a Xot tree for the html5ever tree builder, which implements the
+// WHATWG parsing algorithm.
+//
+// The tree builder gives us names and text that XML doesn't allow, so we
+// coerce them into XML the way the HTML specification describes:
+// https://html.spec.whatwg.org/multipage/parsing.html#coercing-an-html-dom-into-an-infoset
+struct XotSink<'a> {
+    // the tree builder only gets shared references to us
+    xot: RefCell<&'a mut Xot>,
+    document: Node,
+    // the tree builder asks for element names the way it gave them to us
+    names: RefCell<HashMap<Node, QualName>>,
+    integration_points: RefCell<HashSet<Node>>,
+    // the prefixes of namespaced attributes such as `xlink:href`
+    attribute_prefixes: RefCell<HashMap<NamespaceId, PrefixId>>,
+}
+
+impl<'a> XotSink<'a> {
+    fn new(xot: &'a mut Xot) -> Self {
+        let document = xot.new_document();
+        XotSink {
+            xot: RefCell::new(xot),
+            document,
+            names: RefCell::new(HashMap::new()),
+            integration_points: RefCell::new(HashSet::new()),
+            attribute_prefixes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn name_id(&self, xot: &mut Xot, name: &QualName) -> NameId {
+        let namespace_id = xot.add_namespace(&name.ns);
+        xot.add_name_ns(&coerce_name(&name.local), namespace_id)
+    }
+
+    // Add the attributes the element doesn't have yet.
+    fn add_attributes(&self, xot: &mut Xot, element: Node, attributes: Vec<Attribute>) {
+        for attribute in attributes {
+            // Xot has namespace declarations of its own
+            if is_namespace_declaration(&attribute.name) {
+                continue;
+            }
+            let name_id = self.name_id(xot, &attribute.name);
+            if let Some(prefix) = &attribute.name.prefix {
+                let prefix_id = xot.add_prefix(prefix);
+                let namespace_id = xot.namespace_for_name(name_id);
+                self.attribute_prefixes
+                    .borrow_mut()
+                    .insert(namespace_id, prefix_id);
+            }
+            let mut attributes = xot.attributes_mut(element);
+            if attributes.get(name_id).is_none() {
+                attributes.insert(name_id, coerce_text(&attribute.value).into_owned());
+            }
+        }
+    }
+
+    fn node_or_text(&self, xot: &mut Xot, child: NodeOrText<Node>) -> Node {
+        match child {
+            NodeOrText::AppendNode(node) => node,
+            NodeOrText::AppendText(text) => xot.new_text(&coerce_text(&text)),
+        }
+    }
+
+    // Declare the namespaces the tree uses, so it can be serialized as XML.
+    // Elements declare their namespace as the default namespace where it
+    // changes.
+    fn declare_namespaces(&self, xot: &mut Xot) {
+        let elements = xot
+            .descendants(self.document)
+            .filter(|node| xot.is_element(*node))
+            .collect::<Vec<_>>();
+        let attribute_prefixes = self.attribute_prefixes.borrow();
+        for element in elements {
+            let namespace_id = xot.namespace_for_name(xot.node_name(element).unwrap());
+            let parent_namespace_id = xot
+                .parent(element)
+                .and_then(|parent| xot.node_name(parent))
+                .map(|name_id| xot.namespace_for_name(name_id));
+            if parent_namespace_id != Some(namespace_id) {
+                let empty_prefix = xot.empty_prefix();
+                xot.namespaces_mut(element)
+                    .insert(empty_prefix, namespace_id);
+            }
+            let attribute_namespace_ids = xot
+                .attributes(element)
+                .keys()
+                .map(|name_id| xot.namespace_for_name(name_id))
+                .filter(|namespace_id| {
+                    *namespace_id != xot.no_namespace() && *namespace_id != xot.xml_namespace()
+                })
+                .collect::<Vec<_>>();
+            for namespace_id in attribute_namespace_ids {
+                let Some(prefix_id) = attribute_prefixes.get(&namespace_id).copied() else {
+                    continue;
+                };
+                if xot.namespace_for_prefix(element, prefix_id) != Some(namespace_id) {
+                    xot.namespaces_mut(element).insert(prefix_id, namespace_id);
+                }
+            }
+        }
+    }
+}
+
+impl TreeSink for XotSink<'_> {
+    type Handle = Node;
+    type Output = Node;
+    type ElemName<'b>
+        = Ref<'b, QualName>
+    where
+        Self: 'b;
+
+    fn finish(self) -> Node {
+        self.declare_namespaces(&mut self.xot.borrow_mut());
+        self.document
+    }
+
+    // the parsing algorithm recovers from all errors
+    fn parse_error(&self, _message: Cow<'static, str>) {}
+
+    fn get_document(&self) -> Node {
+        self.document
+    }
+
+    fn elem_name<'b>(&'b self, target: &'b Node) -> Ref<'b, QualName> {
+        Ref::map(self.names.borrow(), |names| &names[target])
+    }
+
+    fn create_element(&self, name: QualName, attrs: Vec<Attribute>, flags: ElementFlags) -> Node {
+        let mut xot = self.xot.borrow_mut();
+        let name_id = self.name_id(&mut xot, &name);
+        let element = xot.new_element(name_id);
+        self.add_attributes(&mut xot, element, attrs);
+        self.names.borrow_mut().insert(element, name);
+        if flags.mathml_annotation_xml_integration_point {
+            self.integration_points.borrow_mut().insert(element);
+        }
+        element
+    }
+
+    fn create_comment(&self, text: StrTendril) -> Node {
+        self.xot.borrow_mut().new_comment(&coerce_comment(&text))
+    }
+
+    fn create_pi(&self, target: StrTendril, data: StrTendril) -> Node {
+        let mut xot = self.xot.borrow_mut();
+        let target = xot.add_name(&coerce_name(&target));
+        let data = (!data.is_empty()).then(|| coerce_text(&data));
+        xot.new_processing_instruction(target, data.as_deref())
+    }
+
+    fn append(&self, parent: &Node, child: NodeOrText<Node>) {
+        let mut xot = self.xot.borrow_mut();
+        let child = self.node_or_text(&mut xot, child);
+        xot.append(*parent, child).unwrap();
+    }
+
+    fn append_based_on_parent_node(
+        &self,
+        element: &Node,
+        prev_element: &Node,
+        child: NodeOrText<Node>,
+    ) {
+        let has_parent = self.xot.borrow().parent(*element).is_some();
+        if has_parent {
+            self.append_before_sibling(element, child);
+        } else {
+            self.append(prev_element, child);
+        }
+    }
+
+    // the HTML5 serializer always writes `<!DOCTYPE html>`
+    fn append_doctype_to_document(
+        &self,
+        _name: StrTendril,
+        _public_id: StrTendril,
+        _system_id: StrTendril,
+    ) {
+    }
+
+    // Xot has no document fragments, so the content of a template is its
+    // children
+    fn get_template_contents(&self, target: &Node) -> Node {
+        *target
+    }
+
+    fn same_node(&self, x: &Node, y: &Node) -> bool {
+        x == y
+    }
+
+    fn set_quirks_mode(&self, _mode: QuirksMode) {}
+
+    fn append_before_sibling(&self, sibling: &Node, new_node: NodeOrText<Node>) {
+        let mut xot = self.xot.borrow_mut();
+        let new_node = self.node_or_text(&mut xot, new_node);
+        xot.insert_before(*sibling, new_node).unwrap();
+    }
+
+    fn add_attrs_if_missing(&self, target: &Node, attrs: Vec<Attribute>) {
+        self.add_attributes(&mut self.xot.borrow_mut(), *target, attrs);
+    }
+
+    fn remove_from_parent(&self, target: &Node) {
+        self.xot.borrow_mut().detach(*target).unwrap();
+    }
+
+    fn reparent_children(&self, node: &Node, new_parent: &Node) {
+        let mut xot = self.xot.borrow_mut();
+        let children = xot.children(*node).collect::<Vec<_>>();
+        for child in children {
+            xot.detach(child).unwrap();
+            xot.append(*new_parent, child).unwrap();
+        }
+    }
+
+    fn is_mathml_annotation_xml_integration_point(&self, handle: &Node) -> bool {
+        self.integration_points.borrow().contains(handle)
+    }
+}
+
+impl Xot {
+    /// Parse an HTML document.
+    ///
+    /// This follows the [WHATWG parsing
+    /// algorithm](https://html.spec.whatwg.org/multipage/parsing.html), like
+    /// a browser does: it adds the elements that tags imply, such as `html`,
+    /// `head`, `body` and `tbody`, closes elements that end implicitly, knows
+    /// about void elements such as `br`, repairs misnested formatting
+    /// elements and expands named character references. It recovers from all
+    /// errors, so this always gives you a document.
+    ///
+    /// HTML elements are in the XHTML namespace
+    /// (`http://www.w3.org/1999/xhtml`), and MathML and SVG elements in their
+    /// own namespaces, which are declared as default namespaces. This means
+    /// you can serialize the document with [`Xot::html5`] as well as with
+    /// [`Xot::to_string`].
+    ///
+    /// The result is coerced into XML the way the HTML specification
+    /// describes: characters that aren't allowed in a name are written as
+    /// `U` followed by six hexadecimal digits of their code point, characters
+    /// that XML doesn't allow become U+FFFD REPLACEMENT CHARACTER, and `--`
+    /// in a comment becomes `- -`. Any `xmlns` attributes and the document
+    /// type declaration are dropped, and the content of a `template` element
+    /// becomes its children.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse_html5("<title>Example</title><p>One<p>Two &amp; three<br>");
+    ///
+    /// assert_eq!(
+    ///     xot.html5().to_string(root)?,
+    ///     concat!(
+    ///         r#"<!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml"><head><title>Example</title></head>"#,
+    ///         r#"<body><p>One</p><p>Two &amp; three<br></p></body></html>"#
+    ///     )
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    ///
+    /// This requires the `html5` feature.
+    #[cfg(feature = "html5")]
+    pub fn parse_html5(&mut self, html: &str) -> Node {
+        parse_document(XotSink::new(self), Default::default()).one(html)
+    }
+}
+
+fn is_namespace_declaration(name: &QualName) -> bool {
+    name.ns == ns!(xmlns)
+        || (name.ns == ns!() && (&*name.local == "xmlns" || name.local.starts_with("xmlns:")))
+}
+
+// Replace each character that isn't allowed in an XML name (without a
+// namespace prefix) by `U` and the six hexadecimal digits of its code point.
+fn coerce_name(name: &str) -> Cow<'_, str> {
+    let is_allowed = |index, c| {
+        c != ':'
+            && if index == 0 {
+                is_name_start_char(c)
+            } else {
+                is_name_char(c)
+            }
+    };
+    if name
+        .chars()
+        .enumerate()
+        .all(|(index, c)| is_allowed(index, c))
+    {
+        return Cow::Borrowed(name);
+    }
+    let mut coerced = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if is_allowed(index, c) {
+            coerced.push(c);
+        } else {
+            write!(coerced, "U{:06X}", c as u32).unwrap();
+        }
+    }
+    Cow::Owned(coerced)
+}
+
+// Replace form feeds by spaces, and other characters that XML doesn't allow by
+// U+FFFD REPLACEMENT CHARACTER.
+fn coerce_text(text: &str) -> Cow<'_, str> {
+    if text.chars().all(|c| is_char(c, XmlVersion::Xml10)) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.chars()
+            .map(|c| match c {
+                '\u{C}' => ' ',
+                c if is_char(c, XmlVersion::Xml10) => c,
+                _ => '\u{FFFD}',
+            })
+            .collect(),
+    )
+}
+
+// A comment can't contain `--`, or end with `-`.
+fn coerce_comment(text: &str) -> String {
+    let mut coerced = String::new();
+    for c in coerce_text(text).chars() {
+        if c == '-' && coerced.ends_with('-') {
+            coerced.push(' ');
+        }
+        coerced.push(c);
+    }
+    if coerced.ends_with('-') {
+        coerced.push(' ');
+    }
+    coerced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coerce_name() {
+        assert_eq!(coerce_name("a-b.c"), "a-b.c");
+        assert_eq!(coerce_name("a:b"), "aU00003Ab");
+        assert_eq!(coerce_name("1a\""), "U000031aU000022");
+    }
+
+    #[test]
+    fn test_coerce_text() {
+        assert_eq!(coerce_text("a\tb"), "a\tb");
+        assert_eq!(coerce_text("a\u{C}b\u{1}\u{FFFF}"), "a b\u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn test_coerce_comment() {
+        assert_eq!(coerce_comment("a-b"), "a-b");
+        assert_eq!(coerce_comment("a--b---"), "a- -b- - - ");
+    }
+}
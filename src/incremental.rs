@@ -0,0 +1,485 @@
+use encoding_rs::Decoder;
+use xmlparser::Tokenizer;
+
+use crate::builder::DocumentBuilder;
+use crate::dtd::parser::internal_subset_end;
+use crate::encoding::{decode_strictly, decoder, DETECTION_LENGTH};
+use crate::error::ParseError;
+use crate::parseoptions::ParseOptions;
+use crate::spanmapping::{Chunk, SpanMapping};
+use crate::xotdata::{Node, Xot};
+use crate::SpanInfo;
+
+// Where we are in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    // Before the end of the start tag of the document element.
+    Prolog,
+    // Inside the document element, at the given depth.
+    Content(usize),
+    // After the document element.
+    Epilog,
+}
+
+// What markup starts some text, as far as we need to know to find the
+// boundaries of chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Markup {
+    StartTag,
+    EmptyTag,
+    EndTag,
+    // text, comments, processing instructions, CDATA sections and the
+    // document type declaration
+    Other,
+}
+
+// A document element on its own puts the tokenizer in the state after the
+// document element.
+const EPILOG_PREFIX: &str = "<_/>";
+
+/// Parses XML from chunks of bytes as they arrive.
+///
+/// xmlparser needs all of its input at once, so we decode the bytes into a
+/// buffer, and tokenize complete bits of markup from it as soon as we have
+/// them. The prolog up to the start tag of the document element is tokenized
+/// as a document, the content of the document element as a fragment, and
+/// the epilog as a document after an empty document element. This way the
+/// tokenizer sees exactly what it would see if it tokenized the whole
+/// source at once, and we get the same tokens and errors.
+pub(crate) struct IncrementalParser {
+    pub(crate) builder: DocumentBuilder,
+    pub(crate) span_info: SpanInfo,
+    // bytes we hold on to until we have enough to detect the encoding
+    head: Vec<u8>,
+    decoder: Option<Decoder>,
+    // the encoding we were told the bytes are in, if any
+    encoding: Option<String>,
+    // whether an encoding declaration that contradicts the data and
+    // malformed bytes are errors
+    strict_encoding: bool,
+    // decoded text that we haven't parsed yet
+    text: String,
+    // the length of the complete markup at the start of `text`
+    scanned: usize,
+    // how far we searched for the end of the incomplete markup after that
+    searched: usize,
+    // the quote of the attribute value the search for the end of a tag
+    // stopped in, if any
+    quote: Option<u8>,
+    phase: Phase,
+    // where `text` starts in the source
+    position: usize,
+    row: u32,
+    col: u32,
+}
+
+impl IncrementalParser {
+    pub(crate) fn new(xot: &mut Xot, options: &ParseOptions) -> Self {
+        IncrementalParser {
+            builder: DocumentBuilder::new(xot, options),
+            span_info: SpanInfo::new(),
+            head: Vec::new(),
+            decoder: None,
+            encoding: options.encoding.clone(),
+            strict_encoding: options.strict_encoding,
+            text: String::new(),
+            scanned: 0,
+            searched: 0,
+            quote: None,
+            phase: Phase::Prolog,
+            position: 0,
+            row: 1,
+            col: 1,
+        }
+    }
+
+    /// Parse the next chunk of bytes.
+    pub(crate) fn feed(&mut self, xot: &mut Xot, bytes: &[u8]) -> Result<(), ParseError> {
+        if self.decoder.is_none() {
+            self.head.extend_from_slice(bytes);
+            if self.head.len() < DETECTION_LENGTH {
+                return Ok(());
+            }
+            let head = std::mem::take(&mut self.head);
+            self.decoder = Some(decoder(&head, self.encoding.clone(), self.strict_encoding)?);
+            self.decode(&head, false)?;
+        } else {
+            self.decode(bytes, false)?;
+        }
+        self.parse(xot, false)
+    }
+
+    /// Parse what remains once all bytes have been fed, and check the
+    /// document.
+    pub(crate) fn finish(&mut self, xot: &mut Xot) -> Result<(Node, SpanInfo), ParseError> {
+        self.finish_parsing(xot)?;
+        self.builder.finish(xot);
+        let span_info = std::mem::replace(&mut self.span_info, SpanInfo::new());
+        xot.check_document(&self.builder, span_info, self.position)
+    }
+
+    /// Parse what remains once all bytes have been fed, without checking
+    /// the document.
+    pub(crate) fn finish_parsing(&mut self, xot: &mut Xot) -> Result<(), ParseError> {
+        if self.decoder.is_none() {
+            let head = std::mem::take(&mut self.head);
+            self.decoder = Some(decoder(&head, self.encoding.clone(), self.strict_encoding)?);
+            self.decode(&head, true)?;
+        } else {
+            self.decode(&[], true)?;
+        }
+        self.parse(xot, true)
+    }
+
+    /// How much of the source we parsed, which is all of it once we're
+    /// finished.
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    fn decode(&mut self, bytes: &[u8], last: bool) -> Result<(), ParseError> {
+        let decoder = self.decoder.as_mut().unwrap();
+        if self.strict_encoding {
+            return decode_strictly(decoder, bytes, &mut self.text, self.position, last);
+        }
+        // the worst case length is only unknown for absurdly large chunks
+        let length = decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len());
+        self.text.reserve(length);
+        // as we reserved enough, this decodes all bytes
+        let _ = decoder.decode_to_string(bytes, &mut self.text, last);
+        Ok(())
+    }
+
+    // Parse as much of the text as we can.
+    fn parse(&mut self, xot: &mut Xot, last: bool) -> Result<(), ParseError> {
+        while let Some((end, markup)) = markup_end(
+            &self.text[self.scanned..],
+            self.searched,
+            &mut self.quote,
+            last,
+        ) {
+            self.scanned += end;
+            self.searched = 0;
+            self.quote = None;
+            match (self.phase, markup) {
+                (Phase::Prolog, Markup::StartTag) => {
+                    self.parse_prolog(xot)?;
+                    self.phase = Phase::Content(1);
+                }
+                (Phase::Prolog, Markup::EmptyTag | Markup::EndTag) => {
+                    self.parse_prolog(xot)?;
+                    self.phase = Phase::Epilog;
+                }
+                (Phase::Content(depth), Markup::StartTag) => {
+                    self.phase = Phase::Content(depth + 1);
+                }
+                (Phase::Content(1), Markup::EndTag) => {
+                    self.parse_content(xot)?;
+                    self.phase = Phase::Epilog;
+                }
+                (Phase::Content(depth), Markup::EndTag) => {
+                    self.phase = Phase::Content(depth - 1);
+                }
+                _ => {}
+            }
+        }
+        self.searched = self.text.len() - self.scanned;
+        match self.phase {
+            // the prolog is tokenized as a whole
+            Phase::Prolog if last => self.parse_prolog(xot),
+            Phase::Prolog => Ok(()),
+            Phase::Content(_) => self.parse_content(xot),
+            Phase::Epilog => self.parse_epilog(xot, last),
+        }
+    }
+
+    fn parse_prolog(&mut self, xot: &mut Xot) -> Result<(), ParseError> {
+        let text = &self.text[..self.scanned];
+        let source = self.builder.blank_internal_subset(text);
+        let mut tokenizer = Tokenizer::from(source.as_ref());
+        while xot.parse_next_token(
+            &mut tokenizer,
+            &mut self.builder,
+            &mut self.span_info,
+            SpanMapping::Source,
+        )? {}
+        // the tokenizer sees the blanked out internal subset, so rows and
+        // columns are relative to that
+        let (row, col) = advance_text_pos(self.row, self.col, &source);
+        self.row = row;
+        self.col = col;
+        self.drain(self.scanned);
+        Ok(())
+    }
+
+    fn parse_content(&mut self, xot: &mut Xot) -> Result<(), ParseError> {
+        if self.scanned == 0 {
+            return Ok(());
+        }
+        let text = &self.text[..self.scanned];
+        let mut tokenizer = Tokenizer::from_fragment(text, 0..text.len());
+        let mapping = SpanMapping::Chunk(self.chunk(0));
+        while xot.parse_next_token(
+            &mut tokenizer,
+            &mut self.builder,
+            &mut self.span_info,
+            mapping,
+        )? {}
+        self.consume(self.scanned);
+        Ok(())
+    }
+
+    fn parse_epilog(&mut self, xot: &mut Xot, last: bool) -> Result<(), ParseError> {
+        // The tokenizer skips whitespace after the document element as part
+        // of the next token, so we keep it with what comes after it.
+        let length = if last {
+            self.scanned
+        } else {
+            self.text[..self.scanned]
+                .trim_end_matches([' ', '\t', '\r', '\n'])
+                .len()
+        };
+        if length == 0 {
+            return Ok(());
+        }
+        let source = format!("{}{}", EPILOG_PREFIX, &self.text[..length]);
+        let mut tokenizer = Tokenizer::from(source.as_str());
+        // skip the start and end of the empty element
+        tokenizer.next();
+        tokenizer.next();
+        let mapping = SpanMapping::Chunk(self.chunk(EPILOG_PREFIX.len()));
+        while xot.parse_next_token(
+            &mut tokenizer,
+            &mut self.builder,
+            &mut self.span_info,
+            mapping,
+        )? {}
+        self.consume(length);
+        Ok(())
+    }
+
+    fn chunk(&self, prefix: usize) -> Chunk {
+        Chunk {
+            position: self.position,
+            prefix,
+            row: self.row,
+            col: self.col,
+        }
+    }
+
+    // Drop the text we parsed, keeping track of where we are in the source.
+    fn consume(&mut self, length: usize) {
+        let (row, col) = advance_text_pos(self.row, self.col, &self.text[..length]);
+        self.row = row;
+        self.col = col;
+        self.drain(length);
+    }
+
+    fn drain(&mut self, length: usize) {
+        self.position += length;
+        self.text.drain(..length);
+        self.scanned -= length;
+    }
+}
+
+// The row and column after `text`, given those before it, counted the way
+// xmlparser does.
+fn advance_text_pos(row: u32, col: u32, text: &str) -> (u32, u32) {
+    match text.rfind('\n') {
+        Some(newline) => (
+            row + text.bytes().filter(|b| *b == b'\n').count() as u32,
+            1 + text[newline + 1..].chars().count() as u32,
+        ),
+        None => (row, col + text.chars().count() as u32),
+    }
+}
+
+// Find the end of the markup or text at the start of `text`. We already
+// searched the first `searched` bytes without finding it, and if it's a tag,
+// that search stopped inside an attribute value with `quote`. Returns `None`
+// if we need more text to find it. If this is the last text, the end of the
+// text ends any markup.
+fn markup_end(
+    text: &str,
+    searched: usize,
+    quote: &mut Option<u8>,
+    last: bool,
+) -> Option<(usize, Markup)> {
+    if text.is_empty() {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let or_last = |end: Option<usize>| end.or(last.then_some(text.len()));
+    let markup = |end: Option<usize>, markup| or_last(end).map(|end| (end, markup));
+    if bytes[0] != b'<' {
+        return markup(find(bytes, b"<", 0, searched), Markup::Other);
+    }
+    if starts_with(bytes, b"<!--", last)? {
+        markup(find_end(bytes, b"-->", 4, searched), Markup::Other)
+    } else if starts_with(bytes, b"<![CDATA[", last)? {
+        markup(find_end(bytes, b"]]>", 9, searched), Markup::Other)
+    } else if starts_with(bytes, b"<!DOCTYPE", last)? {
+        markup(doctype_end(text), Markup::Other)
+    } else if starts_with(bytes, b"<?", last)? {
+        markup(find_end(bytes, b"?>", 2, searched), Markup::Other)
+    } else if starts_with(bytes, b"</", last)? {
+        markup(find_end(bytes, b">", 2, searched), Markup::EndTag)
+    } else if starts_with(bytes, b"<!", last)? {
+        markup(find_end(bytes, b">", 2, searched), Markup::Other)
+    } else {
+        let end = or_last(tag_end(bytes, searched, quote))?;
+        if bytes[..end].ends_with(b"/>") {
+            Some((end, Markup::EmptyTag))
+        } else {
+            Some((end, Markup::StartTag))
+        }
+    }
+}
+
+// Whether `bytes` starts with `prefix`, or `None` if we need more to know.
+fn starts_with(bytes: &[u8], prefix: &[u8], last: bool) -> Option<bool> {
+    if bytes.len() >= prefix.len() || last {
+        Some(bytes.starts_with(prefix))
+    } else if prefix.starts_with(bytes) {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+// Find `needle` from `start`, resuming after what we searched already.
+fn find(bytes: &[u8], needle: &[u8], start: usize, searched: usize) -> Option<usize> {
+    let start = start.max(searched.saturating_sub(needle.len() - 1));
+    bytes
+        .get(start..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| start + index)
+}
+
+// Find the end of `needle` from `start`.
+fn find_end(bytes: &[u8], needle: &[u8], start: usize, searched: usize) -> Option<usize> {
+    find(bytes, needle, start, searched).map(|index| index + needle.len())
+}
+
+// Find the end of a tag, skipping over quoted attribute values. We resume
+// the search after what we searched already, inside the attribute value
+// with `quote` if there is one, and leave the quote we stop in there.
+fn tag_end(bytes: &[u8], searched: usize, quote: &mut Option<u8>) -> Option<usize> {
+    for (index, byte) in bytes.iter().enumerate().skip(searched.max(1)) {
+        match (*quote, byte) {
+            (Some(q), b) if q == *b => *quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => *quote = Some(*byte),
+            (None, b'>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Find the end of the document type declaration, which may have an internal
+// subset.
+fn doctype_end(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut quote = None;
+    let mut index = "<!DOCTYPE".len();
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match quote {
+            Some(q) if q == byte => quote = None,
+            Some(_) => {}
+            None => match byte {
+                b'"' | b'\'' => quote = Some(byte),
+                b'>' => return Some(index + 1),
+                b'[' => {
+                    // the position of the closing `]`
+                    index = internal_subset_end(text, index + 1)?;
+                }
+                _ => {}
+            },
+        }
+        index += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markup_end() {
+        assert_eq!(
+            markup_end("text<a>", 0, &mut None, false),
+            Some((4, Markup::Other))
+        );
+        assert_eq!(markup_end("text", 0, &mut None, false), None);
+        assert_eq!(
+            markup_end("text", 0, &mut None, true),
+            Some((4, Markup::Other))
+        );
+        assert_eq!(
+            markup_end("<a b='>'>", 0, &mut None, false),
+            Some((9, Markup::StartTag))
+        );
+        assert_eq!(
+            markup_end("<a/>text", 0, &mut None, false),
+            Some((4, Markup::EmptyTag))
+        );
+        assert_eq!(
+            markup_end("</a>", 0, &mut None, false),
+            Some((4, Markup::EndTag))
+        );
+        assert_eq!(
+            markup_end("<!-- > -->", 0, &mut None, false),
+            Some((10, Markup::Other))
+        );
+        assert_eq!(markup_end("<!-- > --", 0, &mut None, false), None);
+        assert_eq!(
+            markup_end("<![CDATA[>]]>", 0, &mut None, false),
+            Some((13, Markup::Other))
+        );
+        assert_eq!(
+            markup_end("<? > ?>", 0, &mut None, false),
+            Some((7, Markup::Other))
+        );
+        assert_eq!(markup_end("<!", 0, &mut None, false), None);
+        assert_eq!(markup_end("<", 0, &mut None, false), None);
+        assert_eq!(
+            markup_end(
+                r#"<!DOCTYPE a SYSTEM ">" [<!ENTITY e "]>">]>"#,
+                0,
+                &mut None,
+                false
+            ),
+            Some((42, Markup::Other))
+        );
+        assert_eq!(
+            markup_end("<!DOCTYPE a [<!ENTITY e 'x'>", 0, &mut None, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_markup_end_resumed() {
+        assert_eq!(
+            markup_end("<!-- - -->", 8, &mut None, false),
+            Some((10, Markup::Other))
+        );
+        assert_eq!(markup_end("<!-- - ->", 8, &mut None, false), None);
+        assert_eq!(
+            markup_end("long text<", 9, &mut None, false),
+            Some((9, Markup::Other))
+        );
+        let mut quote = None;
+        assert_eq!(markup_end("<a b='>", 0, &mut quote, false), None);
+        assert_eq!(quote, Some(b'\''));
+        assert_eq!(
+            markup_end("<a b='>' c='x'/>", 7, &mut quote, false),
+            Some((16, Markup::EmptyTag))
+        );
+    }
+}
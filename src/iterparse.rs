@@ -0,0 +1,178 @@
+use crate::builder::{Built, DocumentBuilder};
+use crate::id::NameId;
+use crate::xmlvalue::Value;
+use crate::xotdata::{Node, Xot};
+use crate::{SpanInfo, SpanInfoKey};
+
+/// Decides which elements [`Xot::iterparse`] hands to you.
+///
+/// This is implemented for [`NameId`], which matches elements with that
+/// name, and for closures `FnMut(&Xot, Node) -> bool`, which get the
+/// completely parsed element.
+pub trait ElementMatcher {
+    /// Whether the element `node` matches.
+    fn matches(&mut self, xot: &Xot, node: Node) -> bool;
+}
+
+impl ElementMatcher for NameId {
+    fn matches(&mut self, xot: &Xot, node: Node) -> bool {
+        xot.node_name(node) == Some(*self)
+    }
+}
+
+impl<F> ElementMatcher for F
+where
+    F: FnMut(&Xot, Node) -> bool,
+{
+    fn matches(&mut self, xot: &Xot, node: Node) -> bool {
+        self(xot, node)
+    }
+}
+
+/// Hands the matching elements the builder closed to a callback, and
+/// removes them from the tree afterward.
+pub(crate) struct IterParser<M, F> {
+    matcher: M,
+    callback: F,
+}
+
+impl<M, F> IterParser<M, F>
+where
+    M: ElementMatcher,
+    F: FnMut(&Xot, Node),
+{
+    pub(crate) fn new(builder: &mut DocumentBuilder, matcher: M, callback: F) -> Self {
+        builder.built = Some(Vec::new());
+        // elements don't stay around, so there's no point in recording
+        // their ids
+        builder.ids = None;
+        IterParser { matcher, callback }
+    }
+
+    pub(crate) fn elements_closed(
+        &mut self,
+        xot: &mut Xot,
+        builder: &mut DocumentBuilder,
+        span_info: &mut SpanInfo,
+    ) {
+        let Some(built) = builder.built.as_mut() else {
+            return;
+        };
+        for built in std::mem::take(built) {
+            let Built::Closed(node_id) = built else {
+                continue;
+            };
+            let node = Node::new(node_id);
+            if !self.matcher.matches(xot, node) {
+                continue;
+            }
+            (self.callback)(xot, node);
+            // we need the document element to check the document
+            if xot.arena[node_id].parent() != Some(builder.tree) {
+                remove(xot, node, span_info);
+            }
+        }
+    }
+}
+
+// Remove an element along with the span information for its subtree.
+//
+// The whitespace in front of the element goes too. Otherwise the whitespace
+// between the elements we remove would be consolidated into a single text
+// node that grows with the size of the document.
+fn remove(xot: &mut Xot, node: Node, span_info: &mut SpanInfo) {
+    let previous = xot.previous_sibling(node).filter(|previous| {
+        xot.text_str(*previous)
+            .is_some_and(|text| text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n')))
+    });
+    if let Some(previous) = previous {
+        span_info.remove(SpanInfoKey::Text(previous));
+        span_info.remove_references(previous);
+        // removing text never fails
+        xot.remove(previous).unwrap();
+    }
+    for descendant in xot.all_descendants(node) {
+        match xot.value(descendant) {
+            Value::Element(_) => {
+                span_info.remove(SpanInfoKey::ElementStart(descendant));
+                span_info.remove(SpanInfoKey::ElementEnd(descendant));
+            }
+            Value::Attribute(attribute) => {
+                let element = Node::new(xot.arena[descendant.get()].parent().unwrap());
+                span_info.remove(SpanInfoKey::AttributeName(element, attribute.name()));
+                span_info.remove(SpanInfoKey::AttributeValue(element, attribute.name()));
+            }
+            Value::Namespace(namespace) => {
+                let element = Node::new(xot.arena[descendant.get()].parent().unwrap());
+                span_info.remove(SpanInfoKey::NamespacePrefix(element, namespace.prefix()));
+                span_info.remove(SpanInfoKey::NamespaceUri(element, namespace.prefix()));
+            }
+            Value::Text(_) => {
+                span_info.remove(SpanInfoKey::Text(descendant));
+                span_info.remove_references(descendant);
+            }
+            Value::Comment(_) => {
+                span_info.remove(SpanInfoKey::Comment(descendant));
+            }
+            Value::ProcessingInstruction(_) => {
+                span_info.remove(SpanInfoKey::PiTarget(descendant));
+                span_info.remove(SpanInfoKey::PiContent(descendant));
+            }
+            Value::Document => {}
+        }
+    }
+    let next = xot.next_sibling(node);
+    // removing an element never fails
+    xot.remove(node).unwrap();
+    // the text after the element may have been consolidated with the text
+    // before it
+    if let Some(next) = next.filter(|next| xot.arena[next.get()].is_removed()) {
+        span_info.remove(SpanInfoKey::Text(next));
+        span_info.remove_references(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_does_not_grow() {
+        let mut xml = "<doc>".to_string();
+        for i in 0..1000 {
+            xml.push_str(&format!(
+                r#"<item id="{}">text<!--c--><?pi?><sub a="1"/></item>"#,
+                i
+            ));
+        }
+        xml.push_str("</doc>");
+        let mut xot = Xot::new();
+        let item = xot.add_name("item");
+        let before = xot.arena.count();
+        let mut count = 0;
+        xot.iterparse(&xml, item, |_, _| count += 1).unwrap();
+        assert_eq!(count, 1000);
+        assert!(xot.arena.count() - before < 20);
+    }
+
+    #[test]
+    fn test_text_does_not_grow() {
+        let mut xml = "<doc>\n".to_string();
+        for i in 0..1000 {
+            xml.push_str(&format!("  <item id=\"{}\"><sub/></item>\n", i));
+        }
+        xml.push_str("</doc>\n");
+        let mut xot = Xot::new();
+        let item = xot.add_name("item");
+
+        let root = xot.iterparse(&xml, item, |_, _| {}).unwrap();
+        let doc = xot.document_element(root).unwrap();
+        assert_eq!(xot.text_content_str(doc), Some("\n"));
+
+        let root = xot
+            .iterparse_reader(xml.as_bytes(), item, |_, _| {})
+            .unwrap();
+        let doc = xot.document_element(root).unwrap();
+        assert_eq!(xot.text_content_str(doc), Some("\n"));
+    }
+}
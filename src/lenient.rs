@@ -0,0 +1,195 @@
+use xmlparser::Tokenizer;
+
+use crate::builder::DocumentBuilder;
+use crate::error::ParseError;
+use crate::parseoptions::ParseOptions;
+use crate::span::{Span, SpanInfo, SpanInfoKey};
+use crate::spanmapping::SpanMapping;
+use crate::xmlvalue::Value;
+use crate::xotdata::{Node, Xot};
+
+impl Xot {
+    /// Parse a string containing XML leniently, recovering from errors.
+    ///
+    /// Instead of stopping at the first error like
+    /// [`Xot::parse_with_span_info`], this fixes up common problems, and
+    /// records a [`ParseError`] for each one:
+    ///
+    /// - Elements that are still open when their parent is closed or the
+    ///   document ends are closed.
+    ///
+    /// - A close tag without an open element is skipped.
+    ///
+    /// - A name with an unknown prefix is taken to be in no namespace.
+    ///
+    /// - An entity or character reference that can't be expanded is kept as
+    ///   text.
+    ///
+    /// - Of duplicate attributes, the first one is kept.
+    ///
+    /// - After markup that isn't well-formed, parsing continues with the
+    ///   next `<`.
+    ///
+    /// Text and multiple elements at the top level are recorded as errors
+    /// too, but they are kept in the document.
+    ///
+    /// This returns the document node of the best-effort tree along with its
+    /// span information, and the errors ordered by where they occur. The
+    /// list of errors is empty for a well-formed document.
+    ///
+    /// ```rust
+    /// use xot::{ParseError, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let (document, _, errors) = xot.parse_lenient("<doc><p>unclosed</doc>");
+    ///
+    /// assert_eq!(xot.to_string(document).unwrap(), "<doc><p>unclosed</p></doc>");
+    /// assert_eq!(errors.len(), 1);
+    /// assert!(matches!(errors[0], ParseError::UnclosedTag(_)));
+    /// ```
+    pub fn parse_lenient(&mut self, xml: &str) -> (Node, SpanInfo, Vec<ParseError>) {
+        self.parse_lenient_with_options(xml, &ParseOptions::default())
+    }
+
+    /// Parse a string containing XML leniently, recovering from errors, with
+    /// options.
+    ///
+    /// This is like [`Xot::parse_lenient`], configured by the
+    /// [`ParseOptions`]. The source is always parsed as a document, so the
+    /// [`fragment`](ParseOptions::fragment) option doesn't apply.
+    ///
+    /// Exceeding one of the [`limits`](ParseOptions::limits) isn't recovered
+    /// from: it is recorded as an error and parsing stops there, closing the
+    /// elements that are still open.
+    ///
+    /// ```rust
+    /// use xot::{ParseError, ParseOptions, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<a>".repeat(1000) + &"</a>".repeat(1000);
+    /// let (_, _, errors) = xot.parse_lenient_with_options(&xml, &ParseOptions::hardened());
+    /// assert!(errors.iter().any(|error| matches!(error, ParseError::DepthLimit(_))));
+    /// ```
+    pub fn parse_lenient_with_options(
+        &mut self,
+        xml: &str,
+        options: &ParseOptions,
+    ) -> (Node, SpanInfo, Vec<ParseError>) {
+        let mut builder = DocumentBuilder::new(self, options);
+        builder.errors = Some(Vec::new());
+        let source = builder.blank_internal_subset(xml);
+        let mut tokenizer = Tokenizer::from(source.as_ref());
+        let mut is_fragment = false;
+        let mut span_info = SpanInfo::new();
+        loop {
+            match self.parse_next_token(
+                &mut tokenizer,
+                &mut builder,
+                &mut span_info,
+                SpanMapping::Source,
+            ) {
+                Ok(true) => {
+                    // xmlparser keeps track of the depth of elements, and
+                    // would take the document element to be closed if we
+                    // skip a close tag. It also doesn't accept text and
+                    // elements after the document element. So once the
+                    // document element has started, we continue parsing as
+                    // a fragment.
+                    if !is_fragment && builder.has_started_content(self) {
+                        let position = tokenizer.stream().pos();
+                        tokenizer = Tokenizer::from_fragment(source.as_ref(), position..xml.len());
+                        is_fragment = true;
+                    }
+                }
+                Ok(false) => break,
+                Err(error) => {
+                    // the limits guard against malicious input, so we
+                    // don't recover from exceeding them
+                    let is_limit = matches!(
+                        error,
+                        ParseError::DepthLimit(_)
+                            | ParseError::AttributeLimit(_)
+                            | ParseError::NameLengthLimit(_)
+                            | ParseError::TextSizeLimit(_)
+                            | ParseError::NodeLimit(_)
+                            | ParseError::EntityExpansionLimit(_, _)
+                    );
+                    if is_limit {
+                        builder.errors.as_mut().unwrap().push(error);
+                        break;
+                    }
+                    let resume = match error {
+                        ParseError::XmlParser(_, position) => Some(position),
+                        _ => None,
+                    };
+                    builder.errors.as_mut().unwrap().push(error);
+                    // xmlparser stops at an error, so we continue with the
+                    // next markup after it
+                    if let Some(position) = resume {
+                        let next = source[position..]
+                            .char_indices()
+                            .skip(1)
+                            .find(|(_, c)| *c == '<');
+                        let Some((offset, _)) = next else {
+                            break;
+                        };
+                        tokenizer =
+                            Tokenizer::from_fragment(source.as_ref(), position + offset..xml.len());
+                        is_fragment = true;
+                    }
+                }
+            }
+        }
+        let mut errors = builder.errors.take().unwrap();
+        while !builder.is_current_node_document(self) {
+            let node = Node::new(builder.current_node_id);
+            errors.push(ParseError::UnclosedTag(
+                *span_info.get(SpanInfoKey::ElementStart(node)).unwrap(),
+            ));
+            builder.close_element_immediate(self);
+            span_info.add(
+                SpanInfoKey::ElementEnd(node),
+                Span::new(xml.len(), xml.len()),
+            );
+        }
+        builder.finish(self);
+
+        let document = Node::new(builder.tree);
+        let mut has_element = false;
+        for child in self.children(document).collect::<Vec<_>>() {
+            match self.value(child) {
+                Value::Element(_) => {
+                    if has_element {
+                        errors.push(ParseError::MultipleElementsAtTopLevel(
+                            *span_info.get(SpanInfoKey::ElementStart(child)).unwrap(),
+                        ));
+                    }
+                    has_element = true;
+                }
+                Value::Text(text) => {
+                    // when we continue after an error, we may see whitespace
+                    // outside of the document element
+                    let is_whitespace = text
+                        .get()
+                        .chars()
+                        .all(|c| matches!(c, ' ' | '\t' | '\r' | '\n'));
+                    if is_whitespace {
+                        span_info.remove(SpanInfoKey::Text(child));
+                        self.remove(child).unwrap();
+                    } else {
+                        errors.push(ParseError::TextAtTopLevel(
+                            *span_info.get(SpanInfoKey::Text(child)).unwrap(),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !has_element {
+            errors.push(ParseError::NoElementAtTopLevel(xml.len()));
+        }
+        errors.sort_by_key(|error| error.span().start);
+        span_info.index_source(xml);
+        (document, span_info, errors)
+    }
+}
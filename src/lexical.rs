@@ -0,0 +1,391 @@
+use ahash::{HashMap, HashMapExt};
+
+use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::span::{Span, SpanInfo, SpanInfoKey};
+use crate::xmlvalue::Value;
+use crate::xotdata::{Node, Xot};
+
+/// Lexical details of a parsed document, to write it back the way it was.
+///
+/// You obtain this by using [`Xot::parse_lossless`]. It records how each
+/// node was written in the source text: the quotes and whitespace in start
+/// tags, whether an element without content was written as `<a/>` or
+/// `<a></a>`, CDATA sections and references in text, references to entities
+/// with markup in their replacement text, and the XML declaration, doctype
+/// and whitespace around the document element.
+///
+/// [`Xot::to_string_lossless`] and [`Xot::write_lossless`] use this to write
+/// the original text for everything that's unchanged, and
+/// [`Xot::text_edits`] uses it to describe your changes as edits of the
+/// source text.
+pub struct LexicalInfo {
+    document: Node,
+    source: String,
+    span_info: SpanInfo,
+    records: HashMap<Node, Record>,
+    // the entity runs, by their first node
+    entity_runs: HashMap<Node, EntityRun>,
+}
+
+/// A change to the source text: replace the text in the span with the
+/// replacement.
+///
+/// You get these with [`Xot::text_edits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The span of the source text to replace.
+    pub span: Span,
+    /// The text to replace it with.
+    pub replacement: String,
+}
+
+// How a node was written in the source.
+pub(crate) enum Record {
+    // The text between the top-level nodes of a document, along with the
+    // node that follows it, if any.
+    Document(Vec<(Span, Option<Node>)>),
+    Element(ElementRecord),
+    Text {
+        value: String,
+        span: Span,
+    },
+    Comment {
+        value: String,
+        span: Span,
+    },
+    ProcessingInstruction {
+        target: NameId,
+        data: Option<String>,
+        span: Span,
+    },
+}
+
+pub(crate) struct ElementRecord {
+    // `<name`
+    pub(crate) start: Span,
+    // the namespace declarations and attributes in the start tag
+    pub(crate) items: Vec<Item>,
+    // the rest of the start tag: whitespace and `>` or `/>`
+    pub(crate) tail: Span,
+    // `</name>`, unless the element was written as an empty element
+    pub(crate) end: Option<Span>,
+}
+
+// A namespace declaration or attribute in a start tag.
+pub(crate) struct Item {
+    pub(crate) kind: ItemKind,
+    // the whitespace in front of it along with the item itself
+    pub(crate) span: Span,
+    pub(crate) name: Span,
+    // the value, without its quotes
+    pub(crate) value: Span,
+    pub(crate) quote: char,
+}
+
+// The sibling nodes that result from references to entities with markup in
+// their replacement text, along with the text around them, which may have
+// been consolidated with text from the replacement text.
+pub(crate) struct EntityRun {
+    pub(crate) nodes: Vec<Node>,
+    // the source text between the siblings around the run
+    pub(crate) span: Span,
+    // the nodes serialized, to tell whether they changed
+    pub(crate) value: String,
+}
+
+#[derive(PartialEq, Eq)]
+pub(crate) enum ItemKind {
+    Namespace(PrefixId, NamespaceId),
+    Attribute(NameId, String),
+}
+
+impl LexicalInfo {
+    pub(crate) fn new(xot: &Xot, document: Node, source: &str, span_info: SpanInfo) -> Self {
+        let mut lexical_info = LexicalInfo {
+            document,
+            source: source.to_string(),
+            span_info,
+            records: HashMap::new(),
+            entity_runs: HashMap::new(),
+        };
+        for node in xot.descendants(document) {
+            // the descendants of an element from an entity have the span of
+            // the reference too
+            let parent = xot.parent(node);
+            if parent.is_some_and(|parent| {
+                xot.is_element(parent) && !lexical_info.records.contains_key(&parent)
+            }) {
+                continue;
+            }
+            if let Some(record) = lexical_info.record(xot, node) {
+                lexical_info.records.insert(node, record);
+            }
+        }
+        lexical_info.entity_runs = lexical_info.entity_runs(xot, document);
+        lexical_info.drop_text_around_entities(xot, document);
+        let gaps = lexical_info.gaps(xot, document);
+        lexical_info
+            .records
+            .insert(document, Record::Document(gaps));
+        lexical_info
+    }
+
+    /// The source text.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The span information of the parsed document.
+    pub fn span_info(&self) -> &SpanInfo {
+        &self.span_info
+    }
+
+    pub(crate) fn document(&self) -> Node {
+        self.document
+    }
+
+    pub(crate) fn get(&self, node: Node) -> Option<&Record> {
+        self.records.get(&node)
+    }
+
+    pub(crate) fn entity_run(&self, node: Node) -> Option<&EntityRun> {
+        self.entity_runs.get(&node)
+    }
+
+    pub(crate) fn text(&self, span: Span) -> &str {
+        &self.source[span.range()]
+    }
+
+    fn span(&self, key: SpanInfoKey) -> Option<Span> {
+        self.span_info.get(key).copied()
+    }
+
+    fn record(&self, xot: &Xot, node: Node) -> Option<Record> {
+        let source = self.source.as_str();
+        let record = match xot.value(node) {
+            Value::Element(_) => Record::Element(self.element_record(xot, node)?),
+            Value::Text(text) => {
+                let span = self.span(SpanInfoKey::Text(node))?;
+                // the span of text leaves out the delimiters of CDATA
+                // sections at its start and end
+                let start = if source[..span.start].ends_with("<![CDATA[") {
+                    span.start - "<![CDATA[".len()
+                } else {
+                    span.start
+                };
+                let end = if source[span.end..].starts_with("]]>") {
+                    span.end + "]]>".len()
+                } else {
+                    span.end
+                };
+                Record::Text {
+                    value: text.get().to_string(),
+                    span: Span::new(start, end),
+                }
+            }
+            Value::Comment(comment) => {
+                let span = self.span(SpanInfoKey::Comment(node))?;
+                // a comment from an entity has the span of the reference
+                if !source[..span.start].ends_with("<!--") {
+                    return None;
+                }
+                Record::Comment {
+                    value: comment.get().to_string(),
+                    span: Span::new(span.start - "<!--".len(), span.end + "-->".len()),
+                }
+            }
+            Value::ProcessingInstruction(processing_instruction) => {
+                let target = self.span(SpanInfoKey::PiTarget(node))?;
+                if !source[..target.start].ends_with("<?") {
+                    return None;
+                }
+                let after = self
+                    .span(SpanInfoKey::PiContent(node))
+                    .map_or(target.end, |content| content.end);
+                let end = after + source[after..].find("?>")? + "?>".len();
+                Record::ProcessingInstruction {
+                    target: processing_instruction.target(),
+                    data: processing_instruction.data().map(|data| data.to_string()),
+                    span: Span::new(target.start - "<?".len(), end),
+                }
+            }
+            Value::Document | Value::Attribute(_) | Value::Namespace(_) => return None,
+        };
+        Some(record)
+    }
+
+    fn element_record(&self, xot: &Xot, node: Node) -> Option<ElementRecord> {
+        let source = self.source.as_str();
+        let name = self.span(SpanInfoKey::ElementStart(node))?;
+        // an element from an entity has the span of the reference
+        if !source[..name.start].ends_with('<') {
+            return None;
+        }
+        let mut items = Vec::new();
+        for (prefix, namespace) in xot.namespaces(node).iter() {
+            // a namespace declaration from the DTD has no spans
+            if let (Some(name), Some(value)) = (
+                self.span(SpanInfoKey::NamespacePrefix(node, prefix)),
+                self.span(SpanInfoKey::NamespaceUri(node, prefix)),
+            ) {
+                let kind = ItemKind::Namespace(prefix, *namespace);
+                items.push((kind, name, value));
+            }
+        }
+        for attribute_node in xot.attribute_nodes(node) {
+            let attribute = xot.attribute_node(attribute_node).unwrap();
+            if !attribute.specified() {
+                continue;
+            }
+            let name_id = attribute.name();
+            if let (Some(name), Some(value)) = (
+                self.span(SpanInfoKey::AttributeName(node, name_id)),
+                self.span(SpanInfoKey::AttributeValue(node, name_id)),
+            ) {
+                let kind = ItemKind::Attribute(name_id, attribute.value().to_string());
+                items.push((kind, name, value));
+            }
+        }
+        items.sort_by_key(|(_, name, _)| name.start);
+        let mut position = name.end;
+        let items = items
+            .into_iter()
+            .map(|(kind, name, value)| {
+                let start = position;
+                // the value is followed by its closing quote
+                position = value.end + 1;
+                Item {
+                    kind,
+                    span: Span::new(start, position),
+                    name,
+                    value,
+                    quote: source[value.end..].chars().next().unwrap(),
+                }
+            })
+            .collect();
+        let rest = &source[position..];
+        let close = rest.len() - rest.trim_start().len();
+        let (tail, end) = if rest[close..].starts_with("/>") {
+            (Span::new(position, position + close + "/>".len()), None)
+        } else {
+            let end = self.span(SpanInfoKey::ElementEnd(node))?;
+            (Span::new(position, position + close + ">".len()), Some(end))
+        };
+        Some(ElementRecord {
+            start: Span::new(name.start - 1, name.end),
+            items,
+            tail,
+            end,
+        })
+    }
+
+    // Nodes from an entity reference with markup in its replacement text
+    // have no records, and the text next to them may have been consolidated
+    // with text from the replacement text. We record these nodes together
+    // in entity runs, so that we can write their source text as long as
+    // none of them changes.
+    fn entity_runs(&self, xot: &Xot, document: Node) -> HashMap<Node, EntityRun> {
+        let mut entity_runs = HashMap::new();
+        for parent in xot.descendants(document) {
+            let Some(Record::Element(element_record)) = self.records.get(&parent) else {
+                continue;
+            };
+            let Some(end) = element_record.end else {
+                continue;
+            };
+            let mut start = element_record.tail.end;
+            let mut nodes = Vec::new();
+            for child in xot.children(parent) {
+                let span = (!xot.is_text(child))
+                    .then(|| self.records.get(&child).and_then(Record::span))
+                    .flatten();
+                match span {
+                    Some(span) => {
+                        self.add_entity_run(xot, &mut entity_runs, nodes, start, span.start);
+                        nodes = Vec::new();
+                        start = span.end;
+                    }
+                    None => nodes.push(child),
+                }
+            }
+            self.add_entity_run(xot, &mut entity_runs, nodes, start, end.start);
+        }
+        entity_runs
+    }
+
+    fn add_entity_run(
+        &self,
+        xot: &Xot,
+        entity_runs: &mut HashMap<Node, EntityRun>,
+        nodes: Vec<Node>,
+        start: usize,
+        end: usize,
+    ) {
+        // without nodes from an entity, there's only text
+        if nodes.iter().all(|node| xot.is_text(*node)) {
+            return;
+        }
+        let value = nodes
+            .iter()
+            .map(|node| xot.to_string(*node))
+            .collect::<Result<String, _>>();
+        if let Ok(value) = value {
+            let run = EntityRun {
+                nodes,
+                span: Span::new(start, end),
+                value,
+            };
+            entity_runs.insert(run.nodes[0], run);
+        }
+    }
+
+    // Once nodes from an entity change, we write them like new nodes, so
+    // we don't record the text next to them either. As text is
+    // consolidated, that is all text that is part of an entity run.
+    fn drop_text_around_entities(&mut self, xot: &Xot, document: Node) {
+        let is_from_entity = |node: Option<Node>| {
+            node.is_some_and(|node| !xot.is_text(node) && !self.records.contains_key(&node))
+        };
+        let texts = xot
+            .descendants(document)
+            .filter(|node| {
+                xot.is_text(*node)
+                    && (is_from_entity(xot.previous_sibling(*node))
+                        || is_from_entity(xot.next_sibling(*node)))
+            })
+            .collect::<Vec<_>>();
+        for text in texts {
+            self.records.remove(&text);
+        }
+    }
+
+    // The text between the top-level nodes of the document.
+    fn gaps(&self, xot: &Xot, document: Node) -> Vec<(Span, Option<Node>)> {
+        let mut gaps = Vec::new();
+        let mut position = 0;
+        for child in xot.children(document) {
+            if let Some(span) = self.records.get(&child).and_then(Record::span) {
+                gaps.push((Span::new(position, span.start), Some(child)));
+                position = span.end;
+            }
+        }
+        gaps.push((Span::new(position, self.source.len()), None));
+        gaps
+    }
+}
+
+impl Record {
+    // The span of the whole node in the source.
+    fn span(&self) -> Option<Span> {
+        match self {
+            Record::Document(_) => None,
+            Record::Element(element) => Some(Span::new(
+                element.start.start,
+                element.end.map_or(element.tail.end, |end| end.end),
+            )),
+            Record::Text { span, .. }
+            | Record::Comment { span, .. }
+            | Record::ProcessingInstruction { span, .. } => Some(*span),
+        }
+    }
+}
@@ -58,39 +58,103 @@
 //!
 //! You can also create Xot nodes from a fixed structure, the [`fixed`]
 //! submodule.
+//!
+//! If a parsed document has a document type declaration, you can access it
+//! using [`Xot::dtd`]; see the [`dtd`] submodule. You can validate a document
+//! against its DTD using [`Xot::validate_dtd`]. [`Xot::xml_declaration`]
+//! gives you the XML declaration, and [`Xot::xml_parameters`] the
+//! serialization parameters to write both of them again.
+//!
+//! You can parse XML from a reader with [`Xot::parse_reader`], or push data
+//! into a [`PushParser`] as it arrives. To process documents too large to
+//! hold in memory, you can parse them into a stream of events with
+//! [`Xot::parse_events`] or [`Xot::parse_events_reader`], or have matching
+//! elements handed to you one by one with [`Xot::iterparse`].
+//!
+//! [`Xot::parse_with_options`] lets you configure parsing with
+//! [`ParseOptions`], for instance to remove insignificant whitespace or to
+//! restrict the expansion of entities.
+//!
+//! To get a tree out of a document that isn't well-formed, along with all
+//! its errors, use [`Xot::parse_lenient`]. [`ParseError::diagnostic`]
+//! describes an error as a [`Diagnostic`] that you can show to people.
+//!
+//! You can serialize a document as HTML with [`Xot::html5`].
+#![cfg_attr(
+    feature = "html5",
+    doc = r##"
+If you enable the `html5` feature, [`Xot::parse_html5`] parses HTML the way
+a browser does, into a tree with elements in the XHTML namespace."##
+)]
+//!
+//! To write a document back the way it was written, apart from your
+//! changes, parse it with [`Xot::parse_lossless`] and serialize it with
+//! [`Xot::to_string_lossless`]. [`Xot::text_edits`] gives you your changes
+//! as edits of the source text instead.
+//!
+//! Xot doesn't fetch external entities or external DTD subsets unless you
+//! configure a resolver using [`Xot::set_resolver`]; see the [`resolver`]
+//! submodule.
 
 mod access;
+mod builder;
 mod creation;
+mod declaration;
+mod diagnostic;
+pub mod dtd;
 mod encoding;
 mod entity;
 mod error;
+mod events;
 pub mod fixed;
+#[cfg(feature = "html5")]
+mod html5parse;
 mod id;
+mod incremental;
+mod iterparse;
+mod lenient;
 mod levelorder;
+mod lexical;
 mod manipulation;
 mod nameaccess;
 mod nodemap;
 pub mod output;
 mod parse;
+mod parseoptions;
 #[cfg(feature = "proptest")]
 pub mod proptest;
+mod push;
+pub mod resolver;
 mod serialize;
+mod sourceindex;
+mod span;
+mod spanmapping;
 
 mod unpretty;
 mod valueaccess;
+mod xmlchar;
 pub mod xmlname;
 mod xmlvalue;
 mod xotdata;
 
 pub use access::{Axis, NodeEdge};
+pub use declaration::{XmlDeclaration, XmlVersion};
+pub use diagnostic::Diagnostic;
+pub use encoding::EncodingReport;
 pub use error::{Error, ParseError};
+pub use events::{ParseEvent, ParseEvents, ReaderParseEvents};
 pub use id::{NameId, NamespaceId, PrefixId};
+pub use iterparse::ElementMatcher;
 pub use levelorder::LevelOrder;
+pub use lexical::{LexicalInfo, TextEdit};
 pub use nodemap::{
     Attributes, Entry, MutableAttributes, MutableNamespaces, MutableNodeMap, Namespaces, NodeMap,
 };
-pub use parse::{Span, SpanInfo, SpanInfoKey};
+pub use parseoptions::{EntityExpansion, ParseLimits, ParseOptions};
+pub use push::PushParser;
 pub use serialize::Html5;
+pub use sourceindex::{LineColumn, SourceIndex};
+pub use span::{Span, SpanInfo, SpanInfoKey};
 pub use xmlvalue::{
     Attribute, Comment, Element, Namespace, Prefixes, ProcessingInstruction, Text, Value, ValueType,
 };
@@ -5,6 +5,7 @@ use crate::{xmlname, MutableAttributes, MutableNamespaces, NamespaceId, PrefixId
 use crate::access::NodeEdge;
 use crate::error::Error;
 use crate::id::NameId;
+use crate::xmlchar::check_chars;
 use crate::xmlvalue::{Value, ValueCategory, ValueType};
 
 /// ## Manipulation
@@ -29,7 +30,12 @@ use crate::xmlvalue::{Value, ValueCategory, ValueType};
 /// second text node is removed.
 ///
 /// During parsing it's also guaranteed that text and CDATA content that is
-/// adjacent is consolidated into a single node.
+/// adjacent is consolidated into a single node, unless you keep CDATA
+/// sections with [`ParseOptions::preserve_cdata`](crate::ParseOptions::preserve_cdata).
+///
+/// Text that is a CDATA section (see [`Text::is_cdata`](crate::Text::is_cdata))
+/// is only consolidated with other CDATA text, and other text only with text
+/// that isn't a CDATA section.
 ///
 /// You can disable and enable text consolidation using
 /// [`Xot::set_text_consolidation`].
@@ -465,6 +471,9 @@ impl Xot {
     /// # Ok::<(), xot::Error>(())
     /// ```
     pub fn remove(&mut self, node: Node) -> Result<(), Error> {
+        if self.is_document(node) {
+            self.documents.remove(&node);
+        }
         let prev_node = self.previous_sibling(node);
         let next_node = self.next_sibling(node);
         node.get().remove_subtree(self.arena_mut());
@@ -502,7 +511,7 @@ impl Xot {
     ///
     /// assert_eq!(xot.to_string(root).unwrap(), r#"<p xmlns:foo="FOO">Example</p>"#);
     /// ```
-    pub fn namespaces_mut(&mut self, node: Node) -> MutableNamespaces {
+    pub fn namespaces_mut(&mut self, node: Node) -> MutableNamespaces<'_> {
         if !self.is_element(node) {
             panic!("Node is not an element, so cannot set namespaces");
         }
@@ -541,7 +550,7 @@ impl Xot {
     ///
     /// assert_eq!(xot.to_string(root).unwrap(), r#"<p a="A">Example</p>"#);
     /// ```
-    pub fn attributes_mut(&mut self, node: Node) -> MutableAttributes {
+    pub fn attributes_mut(&mut self, node: Node) -> MutableAttributes<'_> {
         if !self.is_element(node) {
             panic!("Node is not an element, so cannot set attributes");
         }
@@ -555,6 +564,26 @@ impl Xot {
         self.attributes_mut(node).insert(name, value.into());
     }
 
+    /// Set attribute on an element, checking that XML allows the characters
+    /// of its value.
+    ///
+    /// This is like [`Xot::set_attribute`], but if the value contains a
+    /// character that XML doesn't allow, such as NUL, you get an
+    /// [`Error::InvalidCharacter`] and the attribute isn't set.
+    ///
+    /// Note that if this is invoked on a non-element it's going to panic.
+    pub fn try_set_attribute(
+        &mut self,
+        node: Node,
+        name: NameId,
+        value: impl Into<String>,
+    ) -> Result<(), Error> {
+        let value = value.into();
+        check_chars(&value)?;
+        self.set_attribute(node, name, value);
+        Ok(())
+    }
+
     /// Remove attribute from an element, if it exists
     ///
     /// Note that if this is invoked on a non-element it's going to panic.
@@ -935,28 +964,30 @@ impl Xot {
             return false;
         }
         let added_text = if let Value::Text(t) = self.value(node) {
-            Some(t.get().to_string())
+            Some((t.get().to_string(), t.is_cdata()))
         } else {
             None
         };
         if added_text.is_none() {
             return false;
         }
-        let added_text = added_text.unwrap();
+        // CDATA text is only consolidated with CDATA text
+        let (added_text, cdata) = added_text.unwrap();
 
         // if consolidation is turned off, then we could have two adjacent
         // text nodes. Prefer to consolidate with the previous node.
         let consolidated = if let Some(prev_node) = prev_node {
-            if let Value::Text(prev) = self.value_mut(prev_node) {
-                let mut s = prev.get().to_string();
-                s.push_str(&added_text);
-                prev.set(s);
-                // remove the text node we wanted to insert as it's now consolidated
-                // we can always remove text nodes safely.
-                self.remove_dangerously(node);
-                true
-            } else {
-                false
+            match self.value_mut(prev_node) {
+                Value::Text(prev) if prev.is_cdata() == cdata => {
+                    let mut s = prev.get().to_string();
+                    s.push_str(&added_text);
+                    prev.set(s);
+                    // remove the text node we wanted to insert as it's now consolidated
+                    // we can always remove text nodes safely.
+                    self.remove_dangerously(node);
+                    true
+                }
+                _ => false,
             }
         } else {
             false
@@ -967,16 +998,17 @@ impl Xot {
         // we couldn't consolidate with the previous node, try to consolidate
         // with the next node
         if let Some(next_node) = next_node {
-            if let Value::Text(next) = self.value_mut(next_node) {
-                let mut s = added_text;
-                s.push_str(next.get());
-                next.set(s);
-                // remove the text node we wanted to insert as it's now consolidated
-                // we can always remove text nodes safely.
-                self.remove_dangerously(node);
-                true
-            } else {
-                false
+            match self.value_mut(next_node) {
+                Value::Text(next) if next.is_cdata() == cdata => {
+                    let mut s = added_text;
+                    s.push_str(next.get());
+                    next.set(s);
+                    // remove the text node we wanted to insert as it's now consolidated
+                    // we can always remove text nodes safely.
+                    self.remove_dangerously(node);
+                    true
+                }
+                _ => false,
             }
         } else {
             false
@@ -1004,6 +1036,10 @@ impl Xot {
         if prev_text.is_none() || next_text.is_none() {
             return false;
         }
+        // CDATA text is only consolidated with CDATA text
+        if prev_text.unwrap().is_cdata() != next_text.unwrap().is_cdata() {
+            return false;
+        }
         let to_add = next_text.unwrap().get().to_string();
 
         let prev_text_mut = self.text_mut(prev_node).unwrap();
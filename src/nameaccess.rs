@@ -6,6 +6,7 @@ use crate::access::NodeEdge;
 use crate::error::Error;
 use crate::id::{Name, NameId, NamespaceId, PrefixId};
 use crate::output::FullnameSerializer;
+use crate::xmlchar::check_ncname;
 use crate::xmlvalue::Prefixes;
 use crate::xotdata::{Node, Xot};
 use crate::{xmlname, Value};
@@ -84,6 +85,30 @@ impl Xot {
         self.add_name_ns(name, self.no_namespace_id)
     }
 
+    /// Add name without a namespace, checking that XML allows it.
+    ///
+    /// This is like [`Xot::add_name`], but if the name isn't an
+    /// [NCName](https://www.w3.org/TR/xml-names/#NT-NCName), for instance
+    /// because it contains a space or a colon, you get an
+    /// [`Error::InvalidNcName`].
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    ///
+    /// let name = xot.try_add_name("a")?;
+    /// assert_eq!(xot.name_ns_str(name), ("a", ""));
+    ///
+    /// assert!(matches!(xot.try_add_name("a b"), Err(Error::InvalidNcName(_))));
+    /// assert!(matches!(xot.try_add_name("p:a"), Err(Error::InvalidNcName(_))));
+    ///
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn try_add_name(&mut self, name: &str) -> Result<NameId, Error> {
+        self.try_add_name_ns(name, self.no_namespace_id)
+    }
+
     /// Look up name with a namespace.
     ///
     /// ```rust
@@ -147,6 +172,20 @@ impl Xot {
         self.name_lookup.get_id_mut(&Name::new(name, namespace_id))
     }
 
+    /// Add name with a namespace, checking that XML allows it.
+    ///
+    /// This is like [`Xot::add_name_ns`], but if the name isn't an
+    /// [NCName](https://www.w3.org/TR/xml-names/#NT-NCName) you get an
+    /// [`Error::InvalidNcName`].
+    pub fn try_add_name_ns(
+        &mut self,
+        name: &str,
+        namespace_id: NamespaceId,
+    ) -> Result<NameId, Error> {
+        check_ncname(name)?;
+        Ok(self.add_name_ns(name, namespace_id))
+    }
+
     /// Look up namespace.
     ///
     /// This is the immutable version of [`Xot::add_namespace`]; it returns
@@ -177,6 +216,18 @@ impl Xot {
         self.prefix_lookup.get_id_mut(prefix)
     }
 
+    /// Add prefix, checking that XML allows it.
+    ///
+    /// This is like [`Xot::add_prefix`], but if the prefix is neither empty
+    /// nor an [NCName](https://www.w3.org/TR/xml-names/#NT-NCName) you get an
+    /// [`Error::InvalidNcName`].
+    pub fn try_add_prefix(&mut self, prefix: &str) -> Result<PrefixId, Error> {
+        if !prefix.is_empty() {
+            check_ncname(prefix)?;
+        }
+        Ok(self.add_prefix(prefix))
+    }
+
     /// No namespace
     ///
     /// Returns the namespace id used when an element or attribute
@@ -258,7 +309,7 @@ impl Xot {
     ///
     /// # Ok::<(), xot::Error>(())
     /// ```
-    pub fn name_ref(&self, name_id: NameId, context: Node) -> Result<xmlname::RefName, Error> {
+    pub fn name_ref(&self, name_id: NameId, context: Node) -> Result<xmlname::RefName<'_>, Error> {
         xmlname::RefName::from_node(self, context, name_id)
     }
 
@@ -472,7 +523,7 @@ impl Xot {
     ///
     /// # Ok::<(), xot::Error>(())
     /// ```
-    pub fn node_name_ref(&self, node: Node) -> Result<Option<xmlname::RefName>, Error> {
+    pub fn node_name_ref(&self, node: Node) -> Result<Option<xmlname::RefName<'_>>, Error> {
         if let Some(name) = self.node_name(node) {
             Ok(Some(self.name_ref(name, node)?))
         } else {
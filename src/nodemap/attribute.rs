@@ -49,7 +49,13 @@ impl ValueAdapter<NameId, String> for AttributeAdapter {
 
     fn value_mut(value: &mut Value) -> &mut String {
         match value {
-            Value::Attribute(Attribute { value, .. }) => value,
+            // the value may be changed, so it's no longer a default
+            Value::Attribute(Attribute {
+                value, specified, ..
+            }) => {
+                *specified = true;
+                value
+            }
             _ => unreachable!(),
         }
     }
@@ -58,14 +64,18 @@ impl ValueAdapter<NameId, String> for AttributeAdapter {
         Value::Attribute(Attribute {
             name_id: key,
             value,
+            specified: true,
         })
     }
 
     fn update(value: &mut Value, new_value: String) -> Option<String> {
         match value {
             Value::Attribute(Attribute {
-                value: old_value, ..
+                value: old_value,
+                specified,
+                ..
             }) => {
+                *specified = true;
                 let old_value = std::mem::replace(old_value, new_value);
                 Some(old_value)
             }
@@ -0,0 +1,287 @@
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io;
+
+use encoding_rs::{
+    Encoder, EncoderResult, Encoding, REPLACEMENT, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252,
+};
+
+use crate::error::Error;
+
+// The encoding we write XML in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OutputEncoding {
+    encoding: &'static Encoding,
+    // the Encoding Standard decodes ISO-8859-1 and US-ASCII as windows-1252,
+    // but their readers don't expect the rest of windows-1252
+    max: Option<char>,
+}
+
+impl OutputEncoding {
+    pub(crate) fn for_label(label: &str) -> Result<Self, Error> {
+        let label = label.trim();
+        let encoding = match Encoding::for_label(label.as_bytes()) {
+            // the replacement encoding can only be decoded
+            Some(encoding) if encoding != REPLACEMENT => encoding,
+            _ => return Err(Error::UnsupportedEncoding(label.to_string())),
+        };
+        let label = label.to_ascii_lowercase();
+        let max = if encoding != WINDOWS_1252 {
+            None
+        } else if ["ascii", "us-ascii", "ansi_x3.4-1968"].contains(&label.as_str()) {
+            Some('\u{7f}')
+        } else if ["cp1252", "windows-1252", "x-cp1252"].contains(&label.as_str()) {
+            None
+        } else {
+            Some('\u{ff}')
+        };
+        Ok(OutputEncoding { encoding, max })
+    }
+
+    pub(crate) fn is_utf8(&self) -> bool {
+        self.encoding == UTF_8
+    }
+
+    fn is_utf16(&self) -> bool {
+        self.encoding == UTF_16LE || self.encoding == UTF_16BE
+    }
+
+    // The first character in the text we can't write in this encoding, with
+    // its position.
+    fn unrepresentable(&self, text: &str) -> Option<(usize, char)> {
+        // encoding_rs doesn't encode UTF-16; we do that ourselves
+        if self.is_utf8() || self.is_utf16() {
+            return None;
+        }
+        let beyond_max = self
+            .max
+            .and_then(|max| text.char_indices().find(|(_, c)| *c > max));
+        let text = match beyond_max {
+            Some((position, _)) => &text[..position],
+            None => text,
+        };
+        let mut encoder = self.encoding.new_encoder();
+        let mut buffer = [0; 1024];
+        let mut position = 0;
+        loop {
+            let (result, read, _) =
+                encoder.encode_from_utf8_without_replacement(&text[position..], &mut buffer, true);
+            position += read;
+            match result {
+                EncoderResult::InputEmpty => return beyond_max,
+                EncoderResult::OutputFull => {}
+                // what's read includes the unmappable character
+                EncoderResult::Unmappable(c) => return Some((position - c.len_utf8(), c)),
+            }
+        }
+    }
+
+    // Check that we can write text that can't contain character references,
+    // such as a name.
+    pub(crate) fn check(&self, text: &str) -> Result<(), Error> {
+        match self.unrepresentable(text) {
+            Some((_, c)) => Err(Error::UnrepresentableCharacter(
+                c,
+                self.encoding.name().to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Replace the characters we can't write by character references, or,
+    // in a CDATA section, by character references between CDATA sections.
+    pub(crate) fn escape<'a>(&self, text: &'a str, is_cdata: bool) -> Cow<'a, str> {
+        let mut rest = text;
+        let mut escaped = String::new();
+        while let Some((position, c)) = self.unrepresentable(rest) {
+            escaped.push_str(&rest[..position]);
+            if is_cdata {
+                write!(escaped, "]]>&#x{:X};<![CDATA[", c as u32).unwrap();
+            } else {
+                write!(escaped, "&#x{:X};", c as u32).unwrap();
+            }
+            rest = &rest[position + c.len_utf8()..];
+        }
+        if rest.len() == text.len() {
+            return Cow::Borrowed(text);
+        }
+        escaped.push_str(rest);
+        Cow::Owned(escaped)
+    }
+}
+
+// Writes UTF-8 in another encoding. UTF-16 starts with a byte order mark.
+//
+// What we write should only contain characters the encoding can represent;
+// the serializer takes care of that.
+pub(crate) struct EncodingWriter<'a, W: io::Write> {
+    w: &'a mut W,
+    encoding: OutputEncoding,
+    encoder: Encoder,
+    // bytes of a UTF-8 sequence that was split up between writes
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl<'a, W: io::Write> EncodingWriter<'a, W> {
+    pub(crate) fn new(w: &'a mut W, encoding: OutputEncoding) -> Self {
+        EncodingWriter {
+            w,
+            encoding,
+            encoder: encoding.encoding.new_encoder(),
+            pending: Vec::new(),
+            started: false,
+        }
+    }
+
+    // Write what's left of the encoder state, such as the escape sequence
+    // back to ASCII of ISO-2022-JP.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            return Err(invalid_utf8());
+        }
+        self.encode("", true)?;
+        self.w.flush()
+    }
+
+    fn encode(&mut self, text: &str, last: bool) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            if self.encoding.encoding == UTF_16LE {
+                self.w.write_all(&[0xFF, 0xFE])?;
+            } else if self.encoding.encoding == UTF_16BE {
+                self.w.write_all(&[0xFE, 0xFF])?;
+            }
+        }
+        if self.encoding.is_utf16() {
+            let is_le = self.encoding.encoding == UTF_16LE;
+            let bytes = text
+                .encode_utf16()
+                .flat_map(|unit| {
+                    if is_le {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect::<Vec<_>>();
+            return self.w.write_all(&bytes);
+        }
+        let mut buffer = [0; 1024];
+        let mut position = 0;
+        loop {
+            let (result, read, written) = self.encoder.encode_from_utf8_without_replacement(
+                &text[position..],
+                &mut buffer,
+                last,
+            );
+            position += read;
+            self.w.write_all(&buffer[..written])?;
+            match result {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(c) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "cannot represent {:?} in {}",
+                            c,
+                            self.encoding.encoding.name()
+                        ),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for EncodingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let pending = std::mem::take(&mut self.pending);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text,
+            // the rest may be the start of a UTF-8 sequence
+            Err(error) if error.error_len().is_none() => {
+                std::str::from_utf8(&pending[..error.valid_up_to()]).unwrap()
+            }
+            Err(_) => return Err(invalid_utf8()),
+        };
+        self.encode(valid, false)?;
+        self.pending = pending[valid.len()..].to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let encoding = OutputEncoding::for_label("windows-1252").unwrap();
+        assert_eq!(
+            encoding.escape("caf\u{e9} \u{20ac}", false),
+            "caf\u{e9} \u{20ac}"
+        );
+        assert_eq!(
+            encoding.escape("a\u{3b1}b\u{1f600}", false),
+            "a&#x3B1;b&#x1F600;"
+        );
+        assert_eq!(encoding.escape("a\u{3b1}b", true), "a]]>&#x3B1;<![CDATA[b");
+    }
+
+    #[test]
+    fn test_latin1_and_ascii() {
+        let encoding = OutputEncoding::for_label("windows-1252").unwrap();
+        assert_eq!(encoding.escape("\u{20ac}\u{3b1}", false), "\u{20ac}&#x3B1;");
+        let encoding = OutputEncoding::for_label("ISO-8859-1").unwrap();
+        assert_eq!(encoding.escape("\u{e9}\u{20ac}", false), "\u{e9}&#x20AC;");
+        let encoding = OutputEncoding::for_label("US-ASCII").unwrap();
+        assert_eq!(encoding.escape("a\u{e9}", false), "a&#xE9;");
+    }
+
+    #[test]
+    fn test_check() {
+        let encoding = OutputEncoding::for_label("shift_jis").unwrap();
+        assert!(encoding.check("\u{65e5}\u{672c}").is_ok());
+        assert!(matches!(
+            encoding.check("\u{e9}"),
+            Err(Error::UnrepresentableCharacter('\u{e9}', _))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(matches!(
+            OutputEncoding::for_label("no-such-encoding"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+        assert!(matches!(
+            OutputEncoding::for_label("iso-2022-kr"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_writer_split_sequence() {
+        let encoding = OutputEncoding::for_label("utf-16be").unwrap();
+        let mut bytes = Vec::new();
+        let mut w = EncodingWriter::new(&mut bytes, encoding);
+        let text = "a\u{e9}".as_bytes();
+        w.write_all(&text[..2]).unwrap();
+        w.write_all(&text[2..]).unwrap();
+        w.finish().unwrap();
+        assert_eq!(bytes, [0xFE, 0xFF, 0, b'a', 0, 0xE9]);
+    }
+}
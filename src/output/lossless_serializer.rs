@@ -0,0 +1,352 @@
+use std::borrow::Cow;
+use std::io;
+
+use crate::entity::serialize_attribute;
+use crate::error::Error;
+use crate::id::NameId;
+use crate::lexical::{ElementRecord, EntityRun, ItemKind, LexicalInfo, Record, TextEdit};
+use crate::output::NoopNormalizer;
+use crate::span::Span;
+use crate::xotdata::{Node, Xot};
+
+use super::{Output, TokenSerializeParameters, XmlSerializer};
+
+// A piece of serialized XML: either a span of the source text or new text.
+enum Piece<'a> {
+    Source(Span),
+    Text(Cow<'a, str>),
+}
+
+// Serializes XML, writing the source text for what's unchanged since it
+// was parsed.
+pub(crate) struct LosslessSerializer<'a> {
+    xot: &'a Xot,
+    // the node we serialize
+    node: Node,
+    lexical_info: &'a LexicalInfo,
+    serializer: XmlSerializer<'a, NoopNormalizer>,
+    // the text between the top-level nodes of the document we serialize,
+    // and how much of it we wrote
+    gaps: &'a [(Span, Option<Node>)],
+    gaps_written: usize,
+    // the last node of the entity run we wrote the source text of, while
+    // we skip the outputs of its nodes
+    skip_until: Option<Node>,
+}
+
+impl<'a> LosslessSerializer<'a> {
+    pub(crate) fn new(xot: &'a Xot, node: Node, lexical_info: &'a LexicalInfo) -> Self {
+        let gaps = match lexical_info.get(node) {
+            Some(Record::Document(gaps)) => gaps.as_slice(),
+            _ => &[],
+        };
+        let mut serializer = XmlSerializer::new(
+            xot,
+            node,
+            TokenSerializeParameters::default(),
+            NoopNormalizer,
+        );
+        // what we write anew is in the version of the document
+        if let Some(declaration) = xot.xml_declaration(xot.root(node)) {
+            serializer.set_version(declaration.xml_version());
+        }
+        LosslessSerializer {
+            xot,
+            node,
+            lexical_info,
+            serializer,
+            gaps,
+            gaps_written: 0,
+            skip_until: None,
+        }
+    }
+
+    pub(crate) fn serialize<W: io::Write>(
+        &mut self,
+        w: &mut W,
+        outputs: impl Iterator<Item = (Node, Output<'a>)>,
+    ) -> Result<(), Error> {
+        let lexical_info = self.lexical_info;
+        self.pieces(outputs, |piece| {
+            let text = match &piece {
+                Piece::Source(span) => lexical_info.text(*span),
+                Piece::Text(text) => text,
+            };
+            w.write_all(text.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    // The edits that turn the source text into the serialized document.
+    pub(crate) fn edits(
+        &mut self,
+        outputs: impl Iterator<Item = (Node, Output<'a>)>,
+    ) -> Result<Vec<TextEdit>, Error> {
+        let lexical_info = self.lexical_info;
+        let mut edits = Vec::new();
+        // the end of the source text we have kept so far, and what we
+        // write after it
+        let mut position = 0;
+        let mut replacement = String::new();
+        self.pieces(outputs, |piece| {
+            match piece {
+                // a piece of source text in front of what we kept, for
+                // instance of a node that was moved, is written anew
+                Piece::Source(span) if span.start < position => {
+                    replacement.push_str(lexical_info.text(span));
+                }
+                Piece::Source(span) => {
+                    let replaced = Span::new(position, span.start);
+                    // what we write anew may be the same as the source text
+                    if lexical_info.text(replaced) != replacement {
+                        edits.push(TextEdit {
+                            span: replaced,
+                            replacement: std::mem::take(&mut replacement),
+                        });
+                    }
+                    replacement.clear();
+                    position = span.end;
+                }
+                Piece::Text(text) => replacement.push_str(&text),
+            }
+            Ok(())
+        })?;
+        let rest = Span::new(position, lexical_info.source().len());
+        if lexical_info.text(rest) != replacement {
+            edits.push(TextEdit {
+                span: rest,
+                replacement,
+            });
+        }
+        Ok(edits)
+    }
+
+    fn pieces(
+        &mut self,
+        outputs: impl Iterator<Item = (Node, Output<'a>)>,
+        mut f: impl FnMut(Piece<'a>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut pieces = Vec::new();
+        for (node, output) in outputs {
+            if let Some(last) = self.skip_until {
+                // this keeps track of the namespaces in scope
+                self.serializer.render_output(node, &output)?;
+                if node == last && self.is_node_end(node, &output) {
+                    self.skip_until = None;
+                }
+                continue;
+            }
+            if let Some(run) = self.unchanged_entity_run(node, &output) {
+                self.serializer.render_output(node, &output)?;
+                f(Piece::Source(run.span))?;
+                let last = *run.nodes.last().unwrap();
+                if node != last || !self.is_node_end(node, &output) {
+                    self.skip_until = Some(last);
+                }
+                continue;
+            }
+            if matches!(
+                output,
+                Output::StartTagOpen(_)
+                    | Output::Text(_)
+                    | Output::Comment(_)
+                    | Output::ProcessingInstruction(..)
+            ) {
+                self.gaps_before(node, &mut pieces);
+            }
+            self.render_output(node, &output, &mut pieces)?;
+            for piece in pieces.drain(..) {
+                f(piece)?;
+            }
+        }
+        for (gap, _) in &self.gaps[self.gaps_written..] {
+            f(Piece::Source(*gap))?;
+        }
+        Ok(())
+    }
+
+    // The entity run that starts with `node`, if this is the first output of
+    // the node and none of the nodes of the run changed. If we serialize
+    // the node on its own, its siblings aren't ours to write.
+    fn unchanged_entity_run(&self, node: Node, output: &Output<'a>) -> Option<&'a EntityRun> {
+        let is_node_start = matches!(output, Output::StartTagOpen(_)) || !self.xot.is_element(node);
+        if !is_node_start || node == self.node {
+            return None;
+        }
+        let run = self.lexical_info.entity_run(node)?;
+        let is_in_place = run
+            .nodes
+            .windows(2)
+            .all(|pair| self.xot.next_sibling(pair[0]) == Some(pair[1]));
+        if !is_in_place {
+            return None;
+        }
+        let value = run
+            .nodes
+            .iter()
+            .map(|node| self.xot.to_string(*node))
+            .collect::<Result<String, _>>()
+            .ok()?;
+        (value == run.value).then_some(run)
+    }
+
+    // Whether this is the last output of a node.
+    fn is_node_end(&self, node: Node, output: &Output<'a>) -> bool {
+        matches!(output, Output::EndTag(_)) || !self.xot.is_element(node)
+    }
+
+    // The text in front of a top-level node, along with the text in front of
+    // any top-level nodes that were removed before it.
+    fn gaps_before(&mut self, node: Node, pieces: &mut Vec<Piece<'a>>) {
+        let rest = &self.gaps[self.gaps_written..];
+        if let Some(index) = rest.iter().position(|(_, next)| *next == Some(node)) {
+            pieces.extend(rest[..=index].iter().map(|(gap, _)| Piece::Source(*gap)));
+            self.gaps_written += index + 1;
+        }
+    }
+
+    fn render_output(
+        &mut self,
+        node: Node,
+        output: &Output<'a>,
+        pieces: &mut Vec<Piece<'a>>,
+    ) -> Result<(), Error> {
+        let lexical_info = self.lexical_info;
+        let record = lexical_info.get(node);
+        let element_record = match record {
+            Some(Record::Element(element_record)) => Some(element_record),
+            _ => None,
+        };
+        match (output, element_record) {
+            (Output::StartTagOpen(_), Some(element_record)) => {
+                let rendered = self.rendered(node, output)?;
+                if rendered == lexical_info.text(element_record.start) {
+                    pieces.push(Piece::Source(element_record.start));
+                } else {
+                    pieces.push(Piece::Text(rendered));
+                }
+            }
+            (Output::Prefix(prefix, namespace), Some(element_record)) => {
+                let kind = ItemKind::Namespace(*prefix, *namespace);
+                match element_record.items.iter().find(|item| item.kind == kind) {
+                    Some(item) => pieces.push(Piece::Source(item.span)),
+                    None => pieces.push(Piece::Text(self.rendered(node, output)?)),
+                }
+            }
+            (Output::Attribute(name, value), Some(element_record)) => {
+                self.attribute(node, output, element_record, *name, value, pieces)?
+            }
+            (Output::StartTagClose, Some(element_record)) => {
+                let tail = element_record.tail;
+                let has_children = self.xot.first_child(node).is_some();
+                if has_children && lexical_info.text(tail).ends_with("/>") {
+                    pieces.push(Piece::Source(Span::new(tail.start, tail.end - 2)));
+                    pieces.push(Piece::Text(">".into()));
+                } else {
+                    pieces.push(Piece::Source(tail));
+                }
+            }
+            (Output::EndTag(element), Some(element_record)) => {
+                let name = self.serializer.element_fullname(element.name())?;
+                // this keeps track of the namespaces in scope
+                self.serializer.render_output(node, output)?;
+                let has_children = self.xot.first_child(node).is_some();
+                match element_record.end {
+                    Some(end) => {
+                        let original = lexical_info.text(end);
+                        if original[2..].trim_end_matches(['>', ' ', '\t', '\r', '\n']) == name {
+                            pieces.push(Piece::Source(end));
+                        } else {
+                            pieces.push(Piece::Text(format!("</{}>", name).into()));
+                        }
+                    }
+                    None if has_children => {
+                        pieces.push(Piece::Text(format!("</{}>", name).into()));
+                    }
+                    None => {}
+                }
+            }
+            (Output::Text(text), _) => match record {
+                Some(Record::Text { value, span }) if value == text => {
+                    pieces.push(Piece::Source(*span));
+                }
+                _ => pieces.push(Piece::Text(self.rendered(node, output)?)),
+            },
+            (Output::Comment(text), _) => match record {
+                Some(Record::Comment { value, span }) if value == text => {
+                    pieces.push(Piece::Source(*span));
+                }
+                _ => pieces.push(Piece::Text(self.rendered(node, output)?)),
+            },
+            (Output::ProcessingInstruction(target, data), _) => match record {
+                Some(Record::ProcessingInstruction {
+                    target: original_target,
+                    data: original_data,
+                    span,
+                }) if original_target == target && original_data.as_deref() == *data => {
+                    pieces.push(Piece::Source(*span));
+                }
+                _ => pieces.push(Piece::Text(self.rendered(node, output)?)),
+            },
+            _ => pieces.push(Piece::Text(self.rendered(node, output)?)),
+        }
+        Ok(())
+    }
+
+    fn attribute(
+        &mut self,
+        node: Node,
+        output: &Output<'a>,
+        element_record: &'a ElementRecord,
+        name: NameId,
+        value: &str,
+        pieces: &mut Vec<Piece<'a>>,
+    ) -> Result<(), Error> {
+        let lexical_info = self.lexical_info;
+        let item = element_record
+            .items
+            .iter()
+            .find(|item| matches!(&item.kind, ItemKind::Attribute(other, _) if *other == name));
+        let Some(item) = item else {
+            // attributes with a default value from the DTD weren't in the
+            // source text
+            let is_defaulted = self.xot.attribute_nodes(node).any(|attribute_node| {
+                let attribute = self.xot.attribute_node(attribute_node).unwrap();
+                attribute.name() == name && !attribute.specified()
+            });
+            if !is_defaulted {
+                pieces.push(Piece::Text(self.rendered(node, output)?));
+            }
+            return Ok(());
+        };
+        let fullname = self.serializer.attribute_fullname(name)?;
+        if fullname != lexical_info.text(item.name) {
+            pieces.push(Piece::Text(self.rendered(node, output)?));
+            return Ok(());
+        }
+        if item.kind == ItemKind::Attribute(name, value.to_string()) {
+            pieces.push(Piece::Source(item.span));
+            return Ok(());
+        }
+        // we keep everything but the value, including its quotes
+        let value = serialize_attribute(value.into(), &NoopNormalizer);
+        let value = if item.quote == '\'' {
+            value.replace("&quot;", "\"").replace('\'', "&apos;")
+        } else {
+            value.into_owned()
+        };
+        pieces.push(Piece::Source(Span::new(item.span.start, item.value.start)));
+        pieces.push(Piece::Text(value.into()));
+        pieces.push(Piece::Source(Span::new(item.value.end, item.span.end)));
+        Ok(())
+    }
+
+    fn rendered(&mut self, node: Node, output: &Output<'a>) -> Result<Cow<'a, str>, Error> {
+        let token = self.serializer.render_output(node, output)?;
+        if token.space {
+            Ok(format!(" {}", token.text).into())
+        } else {
+            Ok(token.text.into())
+        }
+    }
+}
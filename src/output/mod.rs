@@ -2,12 +2,14 @@
 //!
 //! This module lets you control serialization in various ways.
 mod common;
+mod encoding;
 mod fullname;
 pub mod html5;
 mod html5_serializer;
 mod html5elements;
 #[cfg(feature = "icu")]
 mod icu_normalization;
+mod lossless_serializer;
 mod normalizer;
 mod pretty;
 mod serializer;
@@ -15,10 +17,12 @@ pub mod xml;
 mod xml_serializer;
 
 pub use common::{Indentation, TokenSerializeParameters};
+pub(crate) use encoding::{EncodingWriter, OutputEncoding};
 pub(crate) use fullname::FullnameSerializer;
 pub(crate) use fullname::NamespaceDeclarations;
 pub(crate) use html5_serializer::Html5Serializer;
 pub(crate) use html5elements::Html5Elements;
+pub(crate) use lossless_serializer::LosslessSerializer;
 pub use normalizer::{NoopNormalizer, Normalizer};
 pub(crate) use pretty::Pretty;
 pub use pretty::PrettyOutputToken;
@@ -17,24 +17,26 @@
 // * `normalization-form` is not directly supported, but if you enable the `icu`
 //   feature you can use [`Xot::serialize_xml_string_with_normalizer`] to control
 //   normalization with an ICU normalizer.
-// * There is no way to declare the `version` parameter, as only XML 1.0 is
-//   permitted at this time.
-// * You can only influence encoding parameter of the XML declaration, and
-//   this does not trigger actual encoding; output is always UTF-8 and it's up
-//   to you to do any further re-encoding.
+// * The `version` parameter is the version of the XML declaration; without a
+//   declaration the output is XML 1.0.
+// * The `encoding` parameter is the encoding of the XML declaration. A string
+//   is always UTF-8, but only contains characters the encoding can represent.
+//   We don't support `byte-order-mark`: UTF-16 always has one, and UTF-8
+//   never does.
 // * The `item-separator` parameter is specific to XPath/XSLT sequences and is
 //   not supported directly by Xot.
 // * The `media-type` property is only meaningful in the context of a larger
 //   system and is not supported directly by Xot.
-// * `undeclare-prefixes` is only supported by XML 1.1, which Xot does not
-//   support at present.
+// * `undeclare-prefixes` is not supported as such. Prefixes that are
+//   undeclared in the tree, which you get by parsing XML 1.1, are undeclared
+//   in XML 1.1 output, and left out of XML 1.0 output.
 
 #[cfg(doc)]
 use crate::Xot;
 
 use std::io::Write;
 
-use crate::NameId;
+use crate::{NameId, XmlVersion};
 
 use super::Indentation;
 
@@ -58,6 +60,15 @@ pub struct Parameters {
     /// `>` is not escaped, except for the special case of `]]>` outside of CDATA,
     /// which is mandated by the XML specification to always be escaped.
     pub unescaped_gt: bool,
+    /// Whether to refuse to write characters that XML doesn't allow, such as
+    /// NUL, anywhere in the document. If you set this to true, you get an
+    /// [`Error::InvalidCharacter`](crate::Error::InvalidCharacter) instead.
+    /// By default such characters are written as they are, which gives you
+    /// XML that can't be parsed.
+    ///
+    /// In XML 1.1 control characters in text and attribute values are
+    /// allowed, as they are written as character references.
+    pub check_characters: bool,
     // TODO: character maps
 }
 
@@ -78,20 +89,33 @@ pub struct Parameters {
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Declaration {
+    /// The XML version. In XML 1.1, control characters, NEL (U+0085) and
+    /// LS (U+2028) in text and attribute values are written as character
+    /// references, and undeclared namespace prefixes are written as
+    /// `xmlns:p=""`.
+    pub version: XmlVersion,
     /// This causes an encoding declaration to be included in the XML declaration.
-    /// The text given here is taken literally. It does not affect the encoding of
-    /// the output of serialization; that is always UTF-8.
+    ///
+    /// [`Xot::serialize_xml_write`] writes the output in this encoding, such
+    /// as `ISO-8859-1`, `Shift_JIS` or `UTF-16` (with a byte order mark).
+    /// Characters the encoding can't represent become character references
+    /// in text and attribute values, and cause an
+    /// [`Error::UnrepresentableCharacter`](crate::Error::UnrepresentableCharacter)
+    /// in names, comments and processing instructions. An encoding we don't
+    /// know is an [`Error::UnsupportedEncoding`](crate::Error::UnsupportedEncoding).
+    ///
+    /// [`Xot::serialize_xml_string`] still gives you a UTF-8 string, but one
+    /// with character references in the same way.
     pub encoding: Option<String>,
     /// This causes a standalone declaration to be included in the XML declaration.
     pub standalone: Option<bool>,
-    // TODO: if in the future we add XML 1.1 support, we can add the version here.
-    // This way without a declaration it is automatically an XML 1.0 document. Potentially
-    // also include `undeclare-prefixes` here, as that's only supported in XML 1.1.
 }
 
 impl Declaration {
     pub(crate) fn serialize(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
-        w.write_all(b"<?xml version=\"1.0\"")?;
+        w.write_all(b"<?xml version=\"")?;
+        w.write_all(self.version.as_str().as_bytes())?;
+        w.write_all(b"\"")?;
         if let Some(encoding) = &self.encoding {
             w.write_all(b" encoding=\"")?;
             w.write_all(encoding.as_bytes())?;
@@ -159,7 +183,7 @@ impl DocType {
 
 #[cfg(test)]
 mod tests {
-    use crate::{output::Indentation, Xot};
+    use crate::{output::Indentation, ParseOptions, Xot};
 
     use super::*;
 
@@ -372,4 +396,22 @@ mod tests {
             r#"<doc><p><![CDATA[hello]]]]><![CDATA[> world]]></p></doc>"#
         );
     }
+
+    #[test]
+    fn test_preserved_cdata() {
+        let mut xot = Xot::new();
+        let options = ParseOptions {
+            preserve_cdata: true,
+            ..Default::default()
+        };
+        let doc = xot
+            .parse_with_options("<doc>a &lt; b<![CDATA[<c>]]><p>hello</p></doc>", &options)
+            .unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(Parameters::default(), doc)
+                .unwrap(),
+            "<doc>a &lt; b<![CDATA[<c>]]><p>hello</p></doc>"
+        );
+    }
 }
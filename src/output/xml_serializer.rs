@@ -1,11 +1,15 @@
+use std::borrow::Cow;
 use std::io;
 
-use crate::entity::{serialize_attribute, serialize_cdata, serialize_text};
+use crate::declaration::XmlVersion;
+use crate::entity::{is_restricted_char, serialize_attribute, serialize_cdata, serialize_text};
 use crate::error::Error;
 use crate::id::NameId;
 use crate::output::Normalizer;
+use crate::xmlchar::check_chars;
 use crate::xotdata::{Node, Xot};
 
+use super::encoding::OutputEncoding;
 use super::fullname::FullnameSerializer;
 use super::{Output, OutputToken, Pretty, TokenSerializeParameters};
 
@@ -14,6 +18,9 @@ pub(crate) struct XmlSerializer<'a, N: Normalizer> {
     fullname_serializer: FullnameSerializer<'a>,
     normalizer: N,
     parameters: TokenSerializeParameters,
+    encoding: Option<OutputEncoding>,
+    version: XmlVersion,
+    check_characters: bool,
 }
 
 impl<'a, N: Normalizer> XmlSerializer<'a, N> {
@@ -30,6 +37,51 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
             fullname_serializer,
             normalizer,
             parameters,
+            encoding: None,
+            version: XmlVersion::Xml10,
+            check_characters: false,
+        }
+    }
+
+    pub(crate) fn set_version(&mut self, version: XmlVersion) {
+        self.version = version;
+    }
+
+    // Refuse to write characters XML doesn't allow.
+    pub(crate) fn set_check_characters(&mut self, check_characters: bool) {
+        self.check_characters = check_characters;
+    }
+
+    // Write only characters the encoding can represent. Where we can, we
+    // write character references instead; otherwise it's an error.
+    pub(crate) fn set_encoding(&mut self, encoding: OutputEncoding) {
+        self.encoding = Some(encoding);
+    }
+
+    fn check_encoding(&self, text: &str) -> Result<(), Error> {
+        match &self.encoding {
+            Some(encoding) => encoding.check(text),
+            None => Ok(()),
+        }
+    }
+
+    // Write character references for the characters in text and attribute
+    // values that XML 1.1 requires them for, and for those the encoding
+    // can't represent.
+    fn escape_chars(&self, text: String, is_cdata: bool) -> String {
+        let text = match self.version {
+            XmlVersion::Xml10 => text,
+            XmlVersion::Xml11 => match char_references(&text, is_cdata) {
+                Cow::Borrowed(_) => text,
+                Cow::Owned(escaped) => escaped,
+            },
+        };
+        match &self.encoding {
+            Some(encoding) => match encoding.escape(&text, is_cdata) {
+                Cow::Borrowed(_) => text,
+                Cow::Owned(escaped) => escaped,
+            },
+            None => text,
         }
     }
 
@@ -72,6 +124,10 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         output: Output<'a>,
     ) -> Result<(), Error> {
         let data = self.render_output(node, &output)?;
+        // by now XML 1.1 control characters are character references
+        if self.check_characters {
+            check_chars(&data.text)?;
+        }
         if data.space {
             w.write_all(b" ").unwrap();
         }
@@ -79,6 +135,14 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         Ok(())
     }
 
+    pub(crate) fn element_fullname(&self, name_id: NameId) -> Result<Cow<'a, str>, Error> {
+        self.fullname_serializer.element_fullname(name_id)
+    }
+
+    pub(crate) fn attribute_fullname(&self, name_id: NameId) -> Result<Cow<'a, str>, Error> {
+        self.fullname_serializer.attribute_fullname(name_id)
+    }
+
     pub(crate) fn render_output(
         &mut self,
         node: Node,
@@ -89,12 +153,11 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
             StartTagOpen(element) => {
                 self.fullname_serializer
                     .push(self.xot.namespace_declarations(node));
+                let fullname = self.fullname_serializer.element_fullname(element.name_id)?;
+                self.check_encoding(&fullname)?;
                 OutputToken {
                     space: false,
-                    text: format!(
-                        "<{}",
-                        self.fullname_serializer.element_fullname(element.name_id)?
-                    ),
+                    text: format!("<{}", fullname),
                 }
             }
             StartTagClose => {
@@ -137,7 +200,19 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
                         text: "".to_string(),
                     });
                 }
-                let namespace = self.xot.namespace_str(*namespace_id);
+                // a prefix without a namespace is undeclared, which is
+                // only possible in XML 1.1
+                if *namespace_id == self.xot.no_namespace_id
+                    && *prefix_id != self.xot.empty_prefix_id
+                    && self.version == XmlVersion::Xml10
+                {
+                    return Ok(OutputToken {
+                        space: false,
+                        text: "".to_string(),
+                    });
+                }
+                let namespace =
+                    self.escape_chars(self.xot.namespace_str(*namespace_id).to_string(), false);
                 if *prefix_id == self.xot.empty_prefix_id {
                     OutputToken {
                         space: true,
@@ -145,6 +220,7 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
                     }
                 } else {
                     let prefix = self.xot.prefix_str(*prefix_id);
+                    self.check_encoding(prefix)?;
                     OutputToken {
                         space: true,
                         text: format!("xmlns:{}=\"{}\"", prefix, namespace),
@@ -153,13 +229,11 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
             }
             Attribute(name_id, value) => {
                 let fullname = self.fullname_serializer.attribute_fullname(*name_id)?;
+                self.check_encoding(&fullname)?;
+                let value = serialize_attribute((*value).into(), &self.normalizer).to_string();
                 OutputToken {
                     space: true,
-                    text: format!(
-                        "{}=\"{}\"",
-                        fullname,
-                        serialize_attribute((*value).into(), &self.normalizer)
-                    ),
+                    text: format!("{}=\"{}\"", fullname, self.escape_chars(value, false)),
                 }
             }
             Text(text) => {
@@ -173,34 +247,46 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
                 } else {
                     false
                 };
+                let is_cdata = self.xot.text(node).is_some_and(|text| text.is_cdata());
 
-                if is_cdata_element {
+                if is_cdata_element || is_cdata {
                     OutputToken {
                         space: false,
-                        text: serialize_cdata((*text).into(), &self.normalizer).to_string(),
+                        text: self.escape_chars(
+                            serialize_cdata((*text).into(), &self.normalizer).to_string(),
+                            true,
+                        ),
                     }
                 } else {
                     OutputToken {
                         space: false,
-                        text: serialize_text(
-                            (*text).into(),
-                            &self.normalizer,
-                            self.parameters.unescaped_gt,
-                        )
-                        .to_string(),
+                        text: self.escape_chars(
+                            serialize_text(
+                                (*text).into(),
+                                &self.normalizer,
+                                self.parameters.unescaped_gt,
+                            )
+                            .to_string(),
+                            false,
+                        ),
                     }
                 }
             }
-            Comment(text) => OutputToken {
-                space: false,
-                text: format!("<!--{}-->", text),
-            },
+            Comment(text) => {
+                self.check_encoding(text)?;
+                OutputToken {
+                    space: false,
+                    text: format!("<!--{}-->", text),
+                }
+            }
             ProcessingInstruction(target, data) => {
                 let (target, ns) = self.xot.name_ns_str(*target);
                 if !ns.is_empty() {
                     return Err(Error::NamespaceInProcessingInstruction);
                 }
+                self.check_encoding(target)?;
                 if let Some(data) = data {
+                    self.check_encoding(data)?;
                     OutputToken {
                         space: false,
                         text: format!("<?{} {}?>", target, data),
@@ -216,3 +302,24 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         Ok(r)
     }
 }
+
+// XML 1.1 only allows control characters as character references, and NEL
+// and LS would be taken to be line endings. In a CDATA section, we write the
+// character references between CDATA sections.
+fn char_references(text: &str, is_cdata: bool) -> Cow<'_, str> {
+    let is_referenced = |c: char| is_restricted_char(c) || c == '\u{85}' || c == '\u{2028}';
+    if !text.contains(is_referenced) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !is_referenced(c) {
+            escaped.push(c);
+        } else if is_cdata {
+            escaped.push_str(&format!("]]>&#x{:X};<![CDATA[", c as u32));
+        } else {
+            escaped.push_str(&format!("&#x{:X};", c as u32));
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -0,0 +1,211 @@
+#[cfg(doc)]
+use crate::{ParseError, PushParser, Xot};
+
+/// Options that control how XML is parsed.
+///
+/// You can use these options with [`Xot::parse_with_options`],
+/// [`Xot::parse_bytes_with_options`], [`Xot::parse_reader_with_options`],
+/// [`Xot::parse_lossless_with_options`], [`Xot::parse_lenient_with_options`],
+/// [`Xot::parse_events_with_options`],
+/// [`Xot::parse_events_reader_with_options`], [`Xot::iterparse_with_options`],
+/// [`Xot::iterparse_reader_with_options`] and [`PushParser::with_options`].
+/// The default options parse a document the way [`Xot::parse`] does.
+///
+/// ```rust
+/// use xot::{ParseOptions, Xot};
+///
+/// let mut xot = Xot::new();
+/// let options = ParseOptions {
+///     remove_insignificant_whitespace: true,
+///     ..Default::default()
+/// };
+/// let root = xot.parse_with_options("<doc>\n  <p>Hello <b>world</b></p>\n</doc>", &options)?;
+/// assert_eq!(xot.to_string(root)?, "<doc><p>Hello <b>world</b></p></doc>");
+/// # Ok::<(), xot::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Parse a fragment instead of a document, like
+    /// [`Xot::parse_fragment`]. By default this is false.
+    pub fragment: bool,
+    /// Remove insignificant whitespace while parsing, like
+    /// [`Xot::remove_insignificant_whitespace`] does afterward. By default
+    /// this is false.
+    pub remove_insignificant_whitespace: bool,
+    /// Apply xml:id normalization to the values of `xml:id` attributes,
+    /// as described in <https://www.w3.org/TR/xml-id/#id-avn>. By default
+    /// this is true.
+    pub normalize_xml_id: bool,
+    /// Keep CDATA sections as text nodes of their own, for which
+    /// [`Text::is_cdata`](crate::Text::is_cdata) is true, so that
+    /// serializing them writes a CDATA section again. Adjacent CDATA
+    /// sections are still consolidated into a single text node. By default
+    /// this is false, and the text of a CDATA section is consolidated with
+    /// the text around it.
+    ///
+    /// ```rust
+    /// use xot::{ParseOptions, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let options = ParseOptions {
+    ///     preserve_cdata: true,
+    ///     ..Default::default()
+    /// };
+    /// let root = xot.parse_with_options("<doc>a <![CDATA[<b>]]></doc>", &options)?;
+    /// let doc_el = xot.document_element(root)?;
+    /// let cdata = xot.last_child(doc_el).unwrap();
+    /// assert!(xot.text(cdata).unwrap().is_cdata());
+    /// assert_eq!(xot.text_str(cdata), Some("<b>"));
+    ///
+    /// assert_eq!(xot.to_string(root)?, "<doc>a <![CDATA[<b>]]></doc>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub preserve_cdata: bool,
+    /// Which entities declared in the DTD may be expanded. By default all
+    /// of them are.
+    pub entity_expansion: EntityExpansion,
+    /// The name of the encoding of the bytes, if you know it from elsewhere,
+    /// such as from a HTTP header. An encoding declared in the XML
+    /// declaration takes precedence. This is only used when you parse bytes.
+    pub encoding: Option<String>,
+    /// Fail with a [`ParseError::EncodingMismatch`] if the encoding
+    /// declaration contradicts the encoding of the bytes, and with a
+    /// [`ParseError::MalformedEncoding`] if they have malformed bytes,
+    /// instead of replacing them by U+FFFD REPLACEMENT CHARACTER. By default
+    /// this is false. This is only used when you parse bytes.
+    pub strict_encoding: bool,
+    /// Limits on the size of the document. By default only the expansion of
+    /// entities is limited.
+    pub limits: ParseLimits,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            fragment: false,
+            remove_insignificant_whitespace: false,
+            normalize_xml_id: true,
+            preserve_cdata: false,
+            entity_expansion: EntityExpansion::default(),
+            encoding: None,
+            strict_encoding: false,
+            limits: ParseLimits::default(),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options for parsing untrusted input: the limits of
+    /// [`ParseLimits::hardened`], and only internal entities are expanded.
+    ///
+    /// ```rust
+    /// use xot::{ParseError, ParseOptions, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<a>".repeat(1000) + &"</a>".repeat(1000);
+    /// let error = xot.parse_with_options(&xml, &ParseOptions::hardened()).unwrap_err();
+    /// assert!(matches!(error, ParseError::DepthLimit(_)));
+    /// ```
+    pub fn hardened() -> Self {
+        ParseOptions {
+            entity_expansion: EntityExpansion::InternalOnly,
+            limits: ParseLimits::hardened(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Limits on the size of a parsed document, to protect against documents
+/// that exhaust memory, or the stack when you traverse them recursively.
+///
+/// A limit of `None` means there is no limit. By default only the expansion
+/// of entities is limited, to protect against "billion laughs" attacks, in
+/// which a small document expands to a huge amount of text. Sizes and
+/// lengths are in bytes of UTF-8. Exceeding a limit is a [`ParseError`]
+/// located where it's exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The maximum depth of nested elements. The document element is at
+    /// depth 1. Exceeding it is a [`ParseError::DepthLimit`].
+    pub max_depth: Option<usize>,
+    /// The maximum number of attributes of an element, including namespace
+    /// declarations and attributes defaulted by the DTD. Exceeding it is a
+    /// [`ParseError::AttributeLimit`].
+    pub max_attributes: Option<usize>,
+    /// The maximum length of the name of an element, an attribute, a
+    /// namespace prefix or a processing instruction target, including its
+    /// prefix. Exceeding it is a [`ParseError::NameLengthLimit`].
+    pub max_name_length: Option<usize>,
+    /// The maximum total size of the text of text nodes, attribute values,
+    /// comments and processing instructions in the document. Exceeding it is
+    /// a [`ParseError::TextSizeLimit`].
+    pub max_text_size: Option<usize>,
+    /// The maximum total number of nodes in the document, including
+    /// attribute and namespace nodes. Exceeding it is a
+    /// [`ParseError::NodeLimit`].
+    pub max_nodes: Option<usize>,
+    /// The maximum depth of nested entity references, counting a reference
+    /// in the replacement text of another entity one deeper. This applies to
+    /// general entities and to parameter entities in the DTD. Exceeding it
+    /// is a [`ParseError::EntityExpansionLimit`]. By default this is 16.
+    pub max_entity_depth: Option<usize>,
+    /// The maximum total size of the replacement text of the entities
+    /// expanded in the document, and of the parameter entities expanded in
+    /// its DTD. Exceeding it is a [`ParseError::EntityExpansionLimit`]. By
+    /// default this is 10 MB.
+    pub max_entity_expansion_size: Option<usize>,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_depth: None,
+            max_attributes: None,
+            max_name_length: None,
+            max_text_size: None,
+            max_nodes: None,
+            max_entity_depth: Some(16),
+            max_entity_expansion_size: Some(10_000_000),
+        }
+    }
+}
+
+impl ParseLimits {
+    /// Limits that no reasonable document exceeds.
+    ///
+    /// These are a maximum depth of 256, 256 attributes per element, names
+    /// of 1000 bytes, 100 MB of text and 10 million nodes, and the default
+    /// limits on entity expansion.
+    pub fn hardened() -> Self {
+        ParseLimits {
+            max_depth: Some(256),
+            max_attributes: Some(256),
+            max_name_length: Some(1000),
+            max_text_size: Some(100_000_000),
+            max_nodes: Some(10_000_000),
+            ..Default::default()
+        }
+    }
+}
+
+// Whether a value exceeds a limit, if there is one.
+pub(crate) fn exceeds(limit: Option<usize>, value: usize) -> bool {
+    limit.is_some_and(|limit| value > limit)
+}
+
+/// Which entities declared in the DTD are expanded when they're referenced.
+///
+/// The predefined entities such as `&amp;` and character references are
+/// always expanded. A reference to an entity that may not be expanded is a
+/// [`ParseError::EntityNotAllowed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntityExpansion {
+    /// Expand all entities. External entities are only expanded if the
+    /// resolver fetches them.
+    #[default]
+    All,
+    /// Expand internal entities only.
+    InternalOnly,
+    /// Expand the predefined entities only, and none declared in the DTD.
+    PredefinedOnly,
+}
@@ -0,0 +1,149 @@
+use crate::builder::Built;
+use crate::error::ParseError;
+use crate::incremental::IncrementalParser;
+use crate::parseoptions::ParseOptions;
+use crate::xotdata::{Node, Xot};
+
+type Callback<'a> = Box<dyn FnMut(&Xot, Node) + Send + 'a>;
+
+/// A parser that you push chunks of XML data into as they arrive.
+///
+/// The tree is built as data comes in, and you can inspect it before the
+/// document is complete. You can also have a callback called each time an
+/// element at a given depth has been parsed completely, using
+/// [`PushParser::on_element_closed`].
+///
+/// Once all data has been fed, [`PushParser::finish`] returns the document
+/// node, or the same error that [`Xot::parse_bytes`] would have given for
+/// the complete data.
+///
+/// ```rust
+/// use xot::{PushParser, Xot};
+///
+/// let mut xot = Xot::new();
+/// let mut names = Vec::new();
+/// let mut parser = PushParser::new(&mut xot);
+/// parser.on_element_closed(2, |xot, node| {
+///     names.push(xot.text_content_str(node).unwrap().to_string());
+/// });
+/// parser.feed(b"<people><name>Alice</na")?;
+/// parser.feed(b"me><name>Bob</name></people>")?;
+/// let document = parser.finish()?;
+///
+/// assert_eq!(names, vec!["Alice", "Bob"]);
+/// assert_eq!(
+///     xot.to_string(document).unwrap(),
+///     "<people><name>Alice</name><name>Bob</name></people>"
+/// );
+/// # Ok::<(), xot::ParseError>(())
+/// ```
+pub struct PushParser<'a> {
+    xot: &'a mut Xot,
+    parser: IncrementalParser,
+    on_element_closed: Option<(usize, Callback<'a>)>,
+    error: Option<ParseError>,
+}
+
+impl<'a> PushParser<'a> {
+    /// Create a new push parser that builds a document in `xot`.
+    pub fn new(xot: &'a mut Xot) -> Self {
+        Self::with_options(xot, &ParseOptions::default())
+    }
+
+    /// Create a new push parser that builds a document in `xot`, configured
+    /// by the [`ParseOptions`] like [`Xot::parse_reader_with_options`].
+    ///
+    /// The data is always parsed as a document, so the
+    /// [`fragment`](ParseOptions::fragment) option doesn't apply.
+    ///
+    /// ```rust
+    /// use xot::{ParseError, ParseOptions, PushParser, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let mut parser = PushParser::with_options(&mut xot, &ParseOptions::hardened());
+    /// let error = parser.feed("<a>".repeat(1000).as_bytes()).unwrap_err();
+    /// assert!(matches!(error, ParseError::DepthLimit(_)));
+    /// ```
+    pub fn with_options(xot: &'a mut Xot, options: &ParseOptions) -> Self {
+        let parser = IncrementalParser::new(xot, options);
+        PushParser {
+            xot,
+            parser,
+            on_element_closed: None,
+            error: None,
+        }
+    }
+
+    /// Call `callback` each time an element at `depth` has been parsed
+    /// completely, with the element node.
+    ///
+    /// The document element is at depth 1, its children at depth 2, and so
+    /// on. The callback is called when the chunk of data that closes the
+    /// element has been parsed, so later siblings may already be in the tree.
+    ///
+    /// The callback has to be `Send` so that the parser is too, and you can
+    /// move it to another thread or hold on to it across an `.await`.
+    pub fn on_element_closed(
+        &mut self,
+        depth: usize,
+        callback: impl FnMut(&Xot, Node) + Send + 'a,
+    ) {
+        self.parser.builder.built = Some(Vec::new());
+        self.on_element_closed = Some((depth, Box::new(callback)));
+    }
+
+    /// The Xot the document is built in.
+    pub fn xot(&self) -> &Xot {
+        self.xot
+    }
+
+    /// The document node of the document being built.
+    ///
+    /// Until [`PushParser::finish`] succeeds, the document may be incomplete.
+    pub fn document(&self) -> Node {
+        Node::new(self.parser.builder.tree)
+    }
+
+    /// Parse the next chunk of data.
+    ///
+    /// The encoding of the data is detected from its start, so parsing only
+    /// starts once there's enough data for that. Once this returns an error,
+    /// it returns the same error for any further data.
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        let result = self.parser.feed(self.xot, data);
+        self.elements_closed();
+        result.inspect_err(|error| self.error = Some(error.clone()))
+    }
+
+    /// Finish parsing, once all data has been fed, and return the document
+    /// node.
+    pub fn finish(mut self) -> Result<Node, ParseError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let result = self.parser.finish(self.xot);
+        self.elements_closed();
+        result.map(|(node, _)| node)
+    }
+
+    fn elements_closed(&mut self) {
+        let Some(built) = self.parser.builder.built.as_mut() else {
+            return;
+        };
+        let built = std::mem::take(built);
+        let Some((depth, callback)) = self.on_element_closed.as_mut() else {
+            return;
+        };
+        for built in built {
+            if let Built::Closed(node_id) = built {
+                // the ancestors include the element itself and the document
+                if node_id.ancestors(&self.xot.arena).count() == *depth + 1 {
+                    callback(self.xot, Node::new(node_id));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::path::Path;
+
+use ahash::{HashSet, HashSetExt};
+
+use crate::encoding::decode;
+use crate::error::Error;
+use crate::xotdata::{Node, Xot};
+
+use super::{has_scheme, resolve_uri, ResolveError, Resolver, Resource};
+
+const CATALOG_NAMESPACE: &str = "urn:oasis:names:tc:entity:xmlns:xml:catalog";
+
+/// An [OASIS XML
+/// Catalog](https://www.oasis-open.org/committees/download.php/14809/xml-catalogs.html)
+/// which maps public and system ids to local files.
+///
+/// The catalog is a [`Resolver`]. It never fetches anything from the
+/// network: it only reads local files. A resource is read if its public or
+/// system id is mapped by the catalog to a `file:` URI, or if its system id
+/// is a relative URI and it's referred to from a local file, such as a DTD
+/// that was itself found using the catalog. Everything else is refused.
+///
+/// The following catalog entries are supported: `public`, `system`,
+/// `rewriteSystem`, `systemSuffix`, `delegatePublic`, `delegateSystem`,
+/// `nextCatalog` and `group`, along with the `prefer` and `xml:base`
+/// attributes.
+///
+/// ```rust,no_run
+/// use xot::Xot;
+/// use xot::resolver::Catalog;
+///
+/// let mut xot = Xot::new();
+/// xot.set_resolver(Catalog::from_file("/etc/xml/catalog")?);
+/// let root = xot.parse(r#"<!DOCTYPE article PUBLIC "-//OASIS//DTD DocBook XML V4.5//EN"
+///   "http://www.oasis-open.org/docbook/xml/4.5/docbookx.dtd">
+/// <article><para>&mdash;</para></article>"#)?;
+/// # Ok::<(), xot::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    files: Vec<CatalogFile>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CatalogFile {
+    entries: Vec<Entry>,
+    next: Vec<CatalogFile>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Public {
+        public_id: String,
+        uri: String,
+        prefer_public: bool,
+    },
+    System {
+        system_id: String,
+        uri: String,
+    },
+    RewriteSystem {
+        start: String,
+        rewrite_prefix: String,
+    },
+    SystemSuffix {
+        suffix: String,
+        uri: String,
+    },
+    DelegatePublic {
+        start: String,
+        catalog: CatalogFile,
+        prefer_public: bool,
+    },
+    DelegateSystem {
+        start: String,
+        catalog: CatalogFile,
+    },
+}
+
+impl Catalog {
+    /// Create a new, empty catalog. It doesn't map anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a catalog from a catalog file.
+    ///
+    /// Catalog files referred to by `nextCatalog`, `delegatePublic` and
+    /// `delegateSystem` entries are loaded as well. If these cannot be
+    /// loaded they are ignored.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut catalog = Self::new();
+        catalog.add_file(path)?;
+        Ok(catalog)
+    }
+
+    /// Add a catalog file to this catalog.
+    ///
+    /// Catalog files are consulted in the order in which they were added.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let uri = path_to_uri(path.as_ref())?;
+        let mut loader = Loader {
+            xot: Xot::new(),
+            loaded: HashSet::new(),
+        };
+        let file = loader.load(&uri)?;
+        self.files.push(file);
+        Ok(())
+    }
+
+    /// Look up the URI for an external identifier.
+    ///
+    /// Returns `None` if the catalog doesn't map the identifier.
+    pub fn lookup(&self, public_id: Option<&str>, system_id: Option<&str>) -> Option<String> {
+        let public_id = public_id.map(normalize_public_id);
+        self.files
+            .iter()
+            .find_map(|file| file.lookup(public_id.as_deref(), system_id))
+    }
+}
+
+impl Resolver for Catalog {
+    fn resolve(
+        &self,
+        public_id: Option<&str>,
+        system_id: &str,
+        base: Option<&str>,
+    ) -> Result<Resource, ResolveError> {
+        let uri = match self.lookup(public_id, Some(system_id)) {
+            Some(uri) => uri,
+            None => match base {
+                // a relative reference from a local file
+                Some(base) if base.starts_with("file:") && !has_scheme(system_id) => {
+                    resolve_uri(base, system_id)
+                }
+                _ => return Err(ResolveError::Refused),
+            },
+        };
+        let path = uri_to_path(&uri).ok_or(ResolveError::Refused)?;
+        let bytes = std::fs::read(path)?;
+        Ok(Resource::new(uri, decode(&bytes, None)))
+    }
+}
+
+impl CatalogFile {
+    // https://www.oasis-open.org/committees/download.php/14809/xml-catalogs.html#resolve.external
+    fn lookup(&self, public_id: Option<&str>, system_id: Option<&str>) -> Option<String> {
+        if let Some(system_id) = system_id {
+            for entry in &self.entries {
+                if let Entry::System {
+                    system_id: entry_system_id,
+                    uri,
+                } = entry
+                {
+                    if entry_system_id == system_id {
+                        return Some(uri.clone());
+                    }
+                }
+            }
+            let rewrite = self
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::RewriteSystem {
+                        start,
+                        rewrite_prefix,
+                    } if system_id.starts_with(start.as_str()) => Some((start, rewrite_prefix)),
+                    _ => None,
+                })
+                .max_by_key(|(start, _)| start.len());
+            if let Some((start, rewrite_prefix)) = rewrite {
+                return Some(format!("{}{}", rewrite_prefix, &system_id[start.len()..]));
+            }
+            let suffix = self
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::SystemSuffix { suffix, uri } if system_id.ends_with(suffix.as_str()) => {
+                        Some((suffix, uri))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|(suffix, _)| suffix.len());
+            if let Some((_, uri)) = suffix {
+                return Some(uri.clone());
+            }
+            let mut delegates = self
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::DelegateSystem { start, catalog }
+                        if system_id.starts_with(start.as_str()) =>
+                    {
+                        Some((start, catalog))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !delegates.is_empty() {
+                delegates.sort_by_key(|(start, _)| std::cmp::Reverse(start.len()));
+                return delegates
+                    .iter()
+                    .find_map(|(_, catalog)| catalog.lookup(None, Some(system_id)));
+            }
+        }
+        if let Some(public_id) = public_id {
+            // public entries are only considered if there is no system id,
+            // or if the entry prefers public ids
+            let considered = |prefer_public: bool| system_id.is_none() || prefer_public;
+            for entry in &self.entries {
+                if let Entry::Public {
+                    public_id: entry_public_id,
+                    uri,
+                    prefer_public,
+                } = entry
+                {
+                    if entry_public_id == public_id && considered(*prefer_public) {
+                        return Some(uri.clone());
+                    }
+                }
+            }
+            let mut delegates = self
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::DelegatePublic {
+                        start,
+                        catalog,
+                        prefer_public,
+                    } if public_id.starts_with(start.as_str()) && considered(*prefer_public) => {
+                        Some((start, catalog))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !delegates.is_empty() {
+                delegates.sort_by_key(|(start, _)| std::cmp::Reverse(start.len()));
+                return delegates
+                    .iter()
+                    .find_map(|(_, catalog)| catalog.lookup(Some(public_id), None));
+            }
+        }
+        self.next
+            .iter()
+            .find_map(|next| next.lookup(public_id, system_id))
+    }
+}
+
+struct Loader {
+    xot: Xot,
+    // the catalog files loaded so far, to avoid circular references
+    loaded: HashSet<String>,
+}
+
+impl Loader {
+    fn load(&mut self, uri: &str) -> Result<CatalogFile, Error> {
+        self.loaded.insert(uri.to_string());
+        let path = uri_to_path(uri)
+            .ok_or_else(|| Error::Io(format!("Cannot load catalog from {}", uri)))?;
+        let bytes = std::fs::read(path)?;
+        let root = self.xot.parse(&decode(&bytes, None))?;
+        let document_element = self.xot.document_element(root)?;
+        let mut file = CatalogFile::default();
+        self.load_entries(document_element, uri, true, &mut file);
+        self.xot.remove(root)?;
+        Ok(file)
+    }
+
+    // load a catalog file referred to by another catalog file. As per the
+    // specification, any problems loading it are ignored.
+    fn load_referenced(&mut self, uri: &str) -> Option<CatalogFile> {
+        if self.loaded.contains(uri) {
+            return None;
+        }
+        self.load(uri).ok()
+    }
+
+    fn load_entries(
+        &mut self,
+        node: Node,
+        base: &str,
+        prefer_public: bool,
+        file: &mut CatalogFile,
+    ) {
+        let base = match self.attribute_ns(node, "base", self.xot.xml_namespace()) {
+            Some(xml_base) => resolve_uri(base, &xml_base),
+            None => base.to_string(),
+        };
+        let prefer_public = match self.attribute(node, "prefer").as_deref() {
+            Some("public") => true,
+            Some("system") => false,
+            _ => prefer_public,
+        };
+        let Some(local_name) = self.catalog_element_name(node) else {
+            // elements from other namespaces are ignored
+            return;
+        };
+        let attribute = |name: &str| self.attribute(node, name);
+        let uri = || attribute("uri").map(|uri| resolve_uri(&base, &uri));
+        match local_name.as_str() {
+            "catalog" | "group" => {
+                let children = self.xot.children(node).collect::<Vec<_>>();
+                for child in children {
+                    if self.xot.is_element(child) {
+                        self.load_entries(child, &base, prefer_public, file);
+                    }
+                }
+            }
+            "public" => {
+                if let (Some(public_id), Some(uri)) = (attribute("publicId"), uri()) {
+                    file.entries.push(Entry::Public {
+                        public_id: normalize_public_id(&public_id),
+                        uri,
+                        prefer_public,
+                    });
+                }
+            }
+            "system" => {
+                if let (Some(system_id), Some(uri)) = (attribute("systemId"), uri()) {
+                    file.entries.push(Entry::System { system_id, uri });
+                }
+            }
+            "rewriteSystem" => {
+                if let (Some(start), Some(rewrite_prefix)) =
+                    (attribute("systemIdStartString"), attribute("rewritePrefix"))
+                {
+                    file.entries.push(Entry::RewriteSystem {
+                        start,
+                        rewrite_prefix: resolve_uri(&base, &rewrite_prefix),
+                    });
+                }
+            }
+            "systemSuffix" => {
+                if let (Some(suffix), Some(uri)) = (attribute("systemIdSuffix"), uri()) {
+                    file.entries.push(Entry::SystemSuffix { suffix, uri });
+                }
+            }
+            "delegatePublic" => {
+                if let (Some(start), Some(catalog)) =
+                    (attribute("publicIdStartString"), attribute("catalog"))
+                {
+                    let start = normalize_public_id(&start);
+                    if let Some(catalog) = self.load_referenced(&resolve_uri(&base, &catalog)) {
+                        file.entries.push(Entry::DelegatePublic {
+                            start,
+                            catalog,
+                            prefer_public,
+                        });
+                    }
+                }
+            }
+            "delegateSystem" => {
+                if let (Some(start), Some(catalog)) =
+                    (attribute("systemIdStartString"), attribute("catalog"))
+                {
+                    if let Some(catalog) = self.load_referenced(&resolve_uri(&base, &catalog)) {
+                        file.entries.push(Entry::DelegateSystem { start, catalog });
+                    }
+                }
+            }
+            "nextCatalog" => {
+                if let Some(catalog) = attribute("catalog") {
+                    if let Some(catalog) = self.load_referenced(&resolve_uri(&base, &catalog)) {
+                        file.next.push(catalog);
+                    }
+                }
+            }
+            // other entries are not supported
+            _ => {}
+        }
+    }
+
+    fn catalog_element_name(&self, node: Node) -> Option<String> {
+        let name = self.xot.node_name(node)?;
+        let (local_name, namespace) = self.xot.name_ns_str(name);
+        (namespace == CATALOG_NAMESPACE).then(|| local_name.to_string())
+    }
+
+    fn attribute(&self, node: Node, name: &str) -> Option<String> {
+        self.attribute_ns(node, name, self.xot.no_namespace())
+    }
+
+    fn attribute_ns(
+        &self,
+        node: Node,
+        name: &str,
+        namespace: crate::NamespaceId,
+    ) -> Option<String> {
+        let name = self.xot.name_ns(name, namespace)?;
+        self.xot
+            .get_attribute(node, name)
+            .map(|value| value.to_string())
+    }
+}
+
+// https://www.oasis-open.org/committees/download.php/14809/xml-catalogs.html#normalization
+fn normalize_public_id(public_id: &str) -> String {
+    public_id.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn path_to_uri(path: &Path) -> Result<String, Error> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    let path = path.to_string_lossy().replace('\\', "/");
+    let path = path.replace('%', "%25").replace(' ', "%20");
+    if path.starts_with('/') {
+        Ok(format!("file://{}", path))
+    } else {
+        Ok(format!("file:///{}", path))
+    }
+}
+
+fn uri_to_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file:")?;
+    let path = match path.strip_prefix("//") {
+        Some(path) => path.strip_prefix("localhost").unwrap_or(path),
+        None => path,
+    };
+    Some(percent_decode(path))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_public_id() {
+        assert_eq!(
+            normalize_public_id("  -//OASIS//DTD\n  DocBook XML V4.5//EN "),
+            "-//OASIS//DTD DocBook XML V4.5//EN"
+        );
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///usr/share/my%20dtd.dtd"),
+            Some("/usr/share/my dtd.dtd".to_string())
+        );
+        assert_eq!(
+            uri_to_path("file://localhost/etc/xml/catalog"),
+            Some("/etc/xml/catalog".to_string())
+        );
+        assert_eq!(uri_to_path("http://example.com/doc.dtd"), None);
+    }
+}
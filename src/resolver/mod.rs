@@ -0,0 +1,276 @@
+//! Resolution of external entities and external DTD subsets.
+//!
+//! A document can refer to external resources by public and system id: its
+//! document type declaration can name an external DTD subset, and the DTD
+//! can declare external entities. Xot never fetches these by itself;
+//! instead it asks a [`Resolver`] to produce their text.
+//!
+//! By default Xot uses [`NoResolver`], which refuses all external fetches.
+//! This means that external DTD subsets are not read, and references to
+//! external entities result in a
+//! [`ParseError::UnresolvedEntity`](crate::ParseError::UnresolvedEntity)
+//! error. You can configure another resolver with [`Xot::set_resolver`].
+//!
+//! Xot ships with a [`Catalog`], which implements [OASIS XML
+//! Catalogs](https://www.oasis-open.org/committees/download.php/14809/xml-catalogs.html)
+//! to map public and system ids to local files.
+//!
+//! You can also implement your own resolver:
+//!
+//! ```rust
+//! use xot::Xot;
+//! use xot::resolver::{Resolver, Resource, ResolveError};
+//!
+//! #[derive(Debug)]
+//! struct Greetings;
+//!
+//! impl Resolver for Greetings {
+//!     fn resolve(
+//!         &self,
+//!         _public_id: Option<&str>,
+//!         system_id: &str,
+//!         _base: Option<&str>,
+//!     ) -> Result<Resource, ResolveError> {
+//!         match system_id {
+//!             "greetings.dtd" => Ok(Resource::new(
+//!                 "urn:example:greetings.dtd",
+//!                 r#"<!ENTITY hello "Hello, world!">"#,
+//!             )),
+//!             _ => Err(ResolveError::Refused),
+//!         }
+//!     }
+//! }
+//!
+//! let mut xot = Xot::new();
+//! xot.set_resolver(Greetings);
+//! let root = xot.parse(r#"<!DOCTYPE doc SYSTEM "greetings.dtd"><doc>&hello;</doc>"#)?;
+//! assert_eq!(xot.to_string(root)?, "<doc>Hello, world!</doc>");
+//! # Ok::<(), xot::Error>(())
+//! ```
+
+mod catalog;
+
+pub use catalog::Catalog;
+
+#[cfg(doc)]
+use crate::Xot;
+
+/// A resolver for external entities and external DTD subsets.
+///
+/// The parser calls [`Resolver::resolve`] for the external DTD subset named
+/// by a document type declaration, for external parameter entities that are
+/// referenced in the DTD, and for external general entities that are
+/// referenced in content.
+pub trait Resolver: std::fmt::Debug + Send + Sync {
+    /// Resolve an external resource identified by a public id (if any) and a
+    /// system id.
+    ///
+    /// The system id is given as it appears in the declaration, so it may be
+    /// a relative URI. `base` is the URI of the resource in which the
+    /// declaration appears, if it's known; it's `None` for declarations in
+    /// the document itself.
+    fn resolve(
+        &self,
+        public_id: Option<&str>,
+        system_id: &str,
+        base: Option<&str>,
+    ) -> Result<Resource, ResolveError>;
+}
+
+/// The text of a resolved external resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    uri: String,
+    text: String,
+}
+
+impl Resource {
+    /// Create a new resource from its URI and its text.
+    ///
+    /// The URI is used as the base for resolving any external resources
+    /// that the resource itself refers to.
+    pub fn new(uri: impl Into<String>, text: impl Into<String>) -> Self {
+        Resource {
+            uri: uri.into(),
+            text: text.into(),
+        }
+    }
+
+    /// The URI of the resource.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// The text of the resource.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// A failure to resolve an external resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The resolver refuses to fetch this resource.
+    Refused,
+    /// The resource could not be read.
+    ///
+    /// We take the string version of the IO error so as to keep errors
+    /// comparable.
+    Io(String),
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResolveError::Refused => write!(f, "refused to fetch external resource"),
+            ResolveError::Io(s) => write!(f, "IO error: {}", s),
+        }
+    }
+}
+
+impl From<std::io::Error> for ResolveError {
+    #[inline]
+    fn from(e: std::io::Error) -> Self {
+        ResolveError::Io(e.to_string())
+    }
+}
+
+/// A resolver that refuses to fetch anything.
+///
+/// This is the default resolver of Xot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoResolver;
+
+impl Resolver for NoResolver {
+    fn resolve(
+        &self,
+        _public_id: Option<&str>,
+        _system_id: &str,
+        _base: Option<&str>,
+    ) -> Result<Resource, ResolveError> {
+        Err(ResolveError::Refused)
+    }
+}
+
+// Resolve a URI reference against a base URI.
+// https://www.rfc-editor.org/rfc/rfc3986#section-5.2
+//
+// This is a simplified implementation: query strings and fragments in the
+// base are not treated specially.
+pub(crate) fn resolve_uri(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let (prefix, base_path) = split_authority(base);
+    let path = if let Some(absolute) = reference.strip_prefix('/') {
+        format!("/{}", absolute)
+    } else {
+        match base_path.rfind('/') {
+            Some(index) => format!("{}{}", &base_path[..index + 1], reference),
+            None => reference.to_string(),
+        }
+    };
+    format!("{}{}", prefix, remove_dot_segments(&path))
+}
+
+pub(crate) fn has_scheme(uri: &str) -> bool {
+    match uri.find(':') {
+        Some(index) => {
+            let scheme = &uri[..index];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+// split a URI into the scheme and authority part, and the path
+fn split_authority(uri: &str) -> (&str, &str) {
+    let after_scheme = if has_scheme(uri) {
+        uri.find(':').unwrap() + 1
+    } else {
+        0
+    };
+    let rest = &uri[after_scheme..];
+    if let Some(authority) = rest.strip_prefix("//") {
+        let end = authority.find('/').unwrap_or(authority.len());
+        let index = after_scheme + 2 + end;
+        (&uri[..index], &uri[index..])
+    } else {
+        (&uri[..after_scheme], rest)
+    }
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = path.split('/').collect();
+    for (i, segment) in parts.iter().enumerate() {
+        let is_last = i == parts.len() - 1;
+        match *segment {
+            "." => {
+                if is_last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                if segments.len() > 1 || segments.first().is_some_and(|s| !s.is_empty()) {
+                    segments.pop();
+                }
+                if is_last {
+                    segments.push("");
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_uri_relative() {
+        assert_eq!(
+            resolve_uri("file:///usr/share/xml/catalog.xml", "docbook/docbookx.dtd"),
+            "file:///usr/share/xml/docbook/docbookx.dtd"
+        );
+    }
+
+    #[test]
+    fn test_resolve_uri_dot_segments() {
+        assert_eq!(
+            resolve_uri(
+                "file:///usr/share/xml/docbook/catalog.xml",
+                "../ent/iso-lat1.ent"
+            ),
+            "file:///usr/share/xml/ent/iso-lat1.ent"
+        );
+        assert_eq!(resolve_uri("file:///a/b/c", "./d/../e"), "file:///a/b/e");
+    }
+
+    #[test]
+    fn test_resolve_uri_absolute_path() {
+        assert_eq!(
+            resolve_uri("http://example.com/a/b.dtd", "/c/d.ent"),
+            "http://example.com/c/d.ent"
+        );
+    }
+
+    #[test]
+    fn test_resolve_uri_absolute() {
+        assert_eq!(
+            resolve_uri("file:///a/b.dtd", "http://example.com/c.dtd"),
+            "http://example.com/c.dtd"
+        );
+    }
+
+    #[test]
+    fn test_resolve_uri_relative_base() {
+        assert_eq!(resolve_uri("dtd/doc.dtd", "doc.ent"), "dtd/doc.ent");
+        assert_eq!(resolve_uri("doc.dtd", "doc.ent"), "doc.ent");
+    }
+}
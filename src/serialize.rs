@@ -2,13 +2,13 @@ use std::io::Write;
 
 use crate::error::Error;
 use crate::output::{
-    gen_outputs, Html5Elements, Html5Serializer, Output, OutputToken, TokenSerializeParameters,
-    XmlSerializer,
+    gen_outputs, EncodingWriter, Html5Elements, Html5Serializer, LosslessSerializer, Output,
+    OutputEncoding, OutputToken, TokenSerializeParameters, XmlSerializer,
 };
 use crate::output::{NoopNormalizer, Normalizer};
 use crate::output::{Pretty, PrettyOutputToken};
 use crate::xmlname::NameStrInfo;
-use crate::{output, NameId, Value};
+use crate::{output, LexicalInfo, NameId, TextEdit, Value, XmlVersion};
 
 use crate::xotdata::{Node, Xot};
 
@@ -258,16 +258,93 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         node: Node,
         normalizer: N,
     ) -> Result<String, Error> {
+        // a string is always UTF-8, but it contains only characters the
+        // declared encoding can represent, so you can encode it later
+        let encoding = output_encoding(&parameters)?;
         let mut buf = Vec::new();
-        self.serialize_xml_write_with_normalizer(parameters, node, &mut buf, normalizer)?;
+        self.serialize_xml(parameters, node, &mut buf, normalizer, encoding)?;
         Ok(String::from_utf8(buf).unwrap())
     }
 
+    /// The parameters to serialize a parsed document with its XML
+    /// declaration and doctype declaration.
+    ///
+    /// The parameters have the declaration from
+    /// [`Xot::xml_declaration`] and the doctype from the external identifier
+    /// of [`Xot::dtd`], if the document has them, and the defaults
+    /// otherwise. As the internal subset of the DTD isn't written, a
+    /// doctype declaration is only included if the DTD has an external
+    /// identifier.
+    ///
+    /// Use these to keep the prolog of a document you parse and write:
+    ///
+    /// ```rust
+    /// use xot::{Xot, output};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!DOCTYPE doc SYSTEM "doc.dtd">
+    /// <doc><a/></doc>"#;
+    /// let root = xot.parse(xml)?;
+    ///
+    /// let parameters = xot.xml_parameters(root);
+    /// assert_eq!(xot.serialize_xml_string(parameters, root)?, xml);
+    ///
+    /// // you can combine them with other parameters
+    /// let parameters = output::xml::Parameters {
+    ///     indentation: Some(Default::default()),
+    ///     ..xot.xml_parameters(root)
+    /// };
+    /// assert_eq!(
+    ///     xot.serialize_xml_string(parameters, root)?,
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!DOCTYPE doc SYSTEM "doc.dtd">
+    /// <doc>
+    ///   <a/>
+    /// </doc>
+    /// "#
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn xml_parameters(&self, node: Node) -> output::xml::Parameters {
+        output::xml::Parameters {
+            declaration: self
+                .xml_declaration(node)
+                .map(|declaration| declaration.declaration()),
+            doctype: self.dtd(node).and_then(|dtd| dtd.doc_type()),
+            ..Default::default()
+        }
+    }
+
     /// Serialize to XML via a [`Write`], with options.
     ///
     /// This is like [`Xot::serialize_xml_string`] but writes to a [`Write`]. This
     /// is more efficient if you want to write directly to a file, for
     /// instance, as no string needs to be created in memory.
+    ///
+    /// The output is in the encoding of the XML declaration, or UTF-8 if
+    /// there is none:
+    ///
+    /// ```rust
+    /// use xot::{Xot, output};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse("<p>caf\u{e9} \u{3b1}</p>")?;
+    ///
+    /// let mut bytes = Vec::new();
+    /// xot.serialize_xml_write(output::xml::Parameters {
+    ///     declaration: Some(output::xml::Declaration {
+    ///         encoding: Some("ISO-8859-1".to_string()),
+    ///         ..Default::default()
+    ///     }),
+    ///     ..Default::default()
+    /// }, root, &mut bytes)?;
+    /// assert_eq!(
+    ///     bytes,
+    ///     b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<p>caf\xe9 &#x3B1;</p>"
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
     pub fn serialize_xml_write(
         &self,
         parameters: output::xml::Parameters,
@@ -287,8 +364,32 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         w: &mut impl Write,
         normalizer: N,
     ) -> Result<(), Error> {
+        match output_encoding(&parameters)? {
+            Some(encoding) if !encoding.is_utf8() => {
+                let mut w = EncodingWriter::new(w, encoding);
+                self.serialize_xml(parameters, node, &mut w, normalizer, Some(encoding))?;
+                w.finish()?;
+                Ok(())
+            }
+            encoding => self.serialize_xml(parameters, node, w, normalizer, encoding),
+        }
+    }
+
+    // Write XML as UTF-8, with only characters the encoding can represent.
+    fn serialize_xml<N: Normalizer>(
+        &self,
+        parameters: output::xml::Parameters,
+        node: Node,
+        w: &mut impl Write,
+        normalizer: N,
+        encoding: Option<OutputEncoding>,
+    ) -> Result<(), Error> {
+        // the prolog can't contain character references
+        let mut prolog = Vec::new();
+        let mut version = XmlVersion::Xml10;
         if let Some(declaration) = parameters.declaration {
-            declaration.serialize(w)?;
+            declaration.serialize(&mut prolog)?;
+            version = declaration.version;
         }
         if let Some(doctype) = parameters.doctype {
             // if we are in a document node, we look for the document_element,
@@ -302,8 +403,12 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
             // know it's an element now
             let name = self.node_name_ref(node)?.unwrap();
             let name = name.full_name();
-            doctype.serialize(name.as_ref(), w)?;
+            doctype.serialize(name.as_ref(), &mut prolog)?;
+        }
+        if let Some(encoding) = &encoding {
+            encoding.check(std::str::from_utf8(&prolog).unwrap())?;
         }
+        w.write_all(&prolog)?;
         let outputs = gen_outputs(self, node);
         let mut serializer = XmlSerializer::new(
             self,
@@ -314,6 +419,11 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
             },
             normalizer,
         );
+        serializer.set_version(version);
+        serializer.set_check_characters(parameters.check_characters);
+        if let Some(encoding) = encoding {
+            serializer.set_encoding(encoding);
+        }
         if let Some(indentation) = parameters.indentation {
             serializer.serialize_pretty(w, outputs, &indentation.suppress)?;
         } else {
@@ -322,6 +432,87 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         Ok(())
     }
 
+    /// Serialize node as XML string, writing the source text for everything
+    /// that's unchanged since it was parsed.
+    ///
+    /// You get the [`LexicalInfo`] with [`Xot::parse_lossless`]. Nodes that
+    /// you didn't change are written exactly as they were in the source, and
+    /// so are the unchanged namespace declarations and attributes in start
+    /// tags. Of a changed attribute only the value is written anew, so it
+    /// keeps its quotes and whitespace. Serializing the document node also
+    /// writes the XML declaration, the doctype and the whitespace around the
+    /// document element.
+    ///
+    /// Nodes you added are serialized as usual. Nodes from an entity with
+    /// markup in its replacement text are written as the entity reference as
+    /// long as none of them changes. Once one of them does, they're
+    /// serialized as usual, so the entity reference is lost, and so is the
+    /// source text of text right next to them.
+    pub fn to_string_lossless(
+        &self,
+        node: Node,
+        lexical_info: &LexicalInfo,
+    ) -> Result<String, Error> {
+        let mut buf = Vec::new();
+        self.write_lossless(node, lexical_info, &mut buf)?;
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    /// Write node as XML, writing the source text for everything that's
+    /// unchanged since it was parsed.
+    ///
+    /// See [`Xot::to_string_lossless`] for more information.
+    pub fn write_lossless(
+        &self,
+        node: Node,
+        lexical_info: &LexicalInfo,
+        w: &mut impl Write,
+    ) -> Result<(), Error> {
+        let outputs = gen_outputs(self, node);
+        LosslessSerializer::new(self, node, lexical_info).serialize(w, outputs)
+    }
+
+    /// The changes you made to a document since it was parsed, as edits of
+    /// its source text.
+    ///
+    /// You get the [`LexicalInfo`] with [`Xot::parse_lossless`], and you make
+    /// changes to the document with the usual APIs. The edits replace only
+    /// what [`Xot::to_string_lossless`] writes differently from the source,
+    /// so the comments, indentation and formatting elsewhere stay intact.
+    /// Applying the edits to the source text gives you the serialized
+    /// document.
+    ///
+    /// The edits are in order and don't overlap. Their spans are byte offsets
+    /// into the source text; you can find their lines and columns with the
+    /// [`SourceIndex`](crate::SourceIndex) of
+    /// [`LexicalInfo::span_info`].
+    ///
+    /// ```rust
+    /// use xot::{Span, TextEdit, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<doc>\n  <!-- c -->\n  <a x='1'/>\n</doc>";
+    /// let (document, lexical_info) = xot.parse_lossless(xml)?;
+    /// let doc_el = xot.document_element(document)?;
+    /// let a = xot.last_child(doc_el).and_then(|node| xot.previous_sibling(node)).unwrap();
+    /// let x = xot.add_name("x");
+    /// xot.set_attribute(a, x, "2");
+    ///
+    /// assert_eq!(
+    ///     xot.text_edits(&lexical_info)?,
+    ///     vec![TextEdit {
+    ///         span: Span::new(27, 28),
+    ///         replacement: "2".to_string()
+    ///     }]
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn text_edits(&self, lexical_info: &LexicalInfo) -> Result<Vec<TextEdit>, Error> {
+        let document = lexical_info.document();
+        let outputs = gen_outputs(self, document);
+        LosslessSerializer::new(self, document, lexical_info).edits(outputs)
+    }
+
     /// Get HTML 5 serialization API.
     ///
     /// This is a mutable calls as it needs to create a lot of new HTML names
@@ -329,7 +520,7 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
     ///
     /// If you need to generate multiple HTML 5 serializations, it's slightly
     /// more efficient not to re-create this each time.
-    pub fn html5(&mut self) -> Html5 {
+    pub fn html5(&mut self) -> Html5<'_> {
         Html5::new(self)
     }
 
@@ -348,7 +539,7 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
     /// using Xot you can guarantee that the XML is well-formed, entities and
     /// namespaces have been expanded, and you have access to Xot names using
     /// familiar Xot APIs.
-    pub fn outputs(&self, node: Node) -> impl Iterator<Item = (Node, Output)> {
+    pub fn outputs(&self, node: Node) -> impl Iterator<Item = (Node, Output<'_>)> {
         gen_outputs(self, node)
     }
 
@@ -424,3 +615,13 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         })
     }
 }
+
+// The encoding declared in the XML declaration, if any.
+fn output_encoding(parameters: &output::xml::Parameters) -> Result<Option<OutputEncoding>, Error> {
+    parameters
+        .declaration
+        .as_ref()
+        .and_then(|declaration| declaration.encoding.as_deref())
+        .map(OutputEncoding::for_label)
+        .transpose()
+}
@@ -0,0 +1,160 @@
+use crate::span::Span;
+
+/// A position in the source text as a line and column.
+///
+/// Lines and columns are 1-based. Lines are separated by `\n`, so a `\r`
+/// in front of it counts as the last character of the line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LineColumn {
+    /// The line.
+    pub line: usize,
+    /// The column, counted in characters.
+    pub column: usize,
+    /// The column, counted in UTF-8 bytes.
+    pub byte_column: usize,
+}
+
+/// An index of the source text to find line and column numbers for byte
+/// offsets such as the ones in a [`Span`].
+///
+/// You get one with the [`SpanInfo`](crate::SpanInfo) of a parsed document
+/// through [`SpanInfo::source_index`](crate::SpanInfo::source_index). When
+/// parsing fails you can create one for the source text with
+/// [`SourceIndex::new`] to locate the error.
+///
+/// ```rust
+/// use xot::{SourceIndex, Xot};
+///
+/// let mut xot = Xot::new();
+/// let xml = "<doc>\n  <é a='1' a='2'/>\n</doc>";
+/// let error = xot.parse(xml).unwrap_err();
+///
+/// let index = SourceIndex::new(xml);
+/// let position = index.line_column(error.span().start);
+/// assert_eq!(position.line, 2);
+/// assert_eq!(position.column, 12);
+/// assert_eq!(position.byte_column, 13);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SourceIndex {
+    len: usize,
+    // the offsets at which lines start, after the first one
+    line_starts: Vec<usize>,
+    // the offsets of the characters that take more than one byte, along with
+    // the total amount of bytes beyond the first of these characters up to
+    // and including this one
+    wide_chars: Vec<(usize, usize)>,
+}
+
+impl SourceIndex {
+    /// Create an index of the source text.
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = Vec::new();
+        let mut wide_chars = Vec::new();
+        let mut extra = 0;
+        for (offset, c) in source.char_indices() {
+            if c == '\n' {
+                line_starts.push(offset + 1);
+            } else if c.len_utf8() > 1 {
+                extra += c.len_utf8() - 1;
+                wide_chars.push((offset, extra));
+            }
+        }
+        SourceIndex {
+            len: source.len(),
+            line_starts,
+            wide_chars,
+        }
+    }
+
+    /// The line and column of a byte offset in the source text.
+    ///
+    /// Offsets beyond the end of the text are taken to be at the end.
+    pub fn line_column(&self, offset: usize) -> LineColumn {
+        let offset = offset.min(self.len);
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = if line == 0 {
+            0
+        } else {
+            self.line_starts[line - 1]
+        };
+        let column = self.chars_before(offset) - self.chars_before(line_start);
+        LineColumn {
+            line: line + 1,
+            column: column + 1,
+            byte_column: offset - line_start + 1,
+        }
+    }
+
+    /// The line and column of the start and the end of a span.
+    pub fn span_line_columns(&self, span: Span) -> (LineColumn, LineColumn) {
+        (self.line_column(span.start), self.line_column(span.end))
+    }
+
+    // The number of characters before a byte offset.
+    fn chars_before(&self, offset: usize) -> usize {
+        let index = self
+            .wide_chars
+            .partition_point(|(start, _)| *start < offset);
+        let extra = if index == 0 {
+            0
+        } else {
+            self.wide_chars[index - 1].1
+        };
+        offset - extra
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_column(line: usize, column: usize, byte_column: usize) -> LineColumn {
+        LineColumn {
+            line,
+            column,
+            byte_column,
+        }
+    }
+
+    #[test]
+    fn test_ascii() {
+        let index = SourceIndex::new("ab\ncd\n\nef");
+        assert_eq!(index.line_column(0), line_column(1, 1, 1));
+        assert_eq!(index.line_column(2), line_column(1, 3, 3));
+        assert_eq!(index.line_column(3), line_column(2, 1, 1));
+        assert_eq!(index.line_column(4), line_column(2, 2, 2));
+        assert_eq!(index.line_column(6), line_column(3, 1, 1));
+        assert_eq!(index.line_column(7), line_column(4, 1, 1));
+        assert_eq!(index.line_column(9), line_column(4, 3, 3));
+        // beyond the end
+        assert_eq!(index.line_column(100), line_column(4, 3, 3));
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        // é takes 2 bytes, 日 3 and 😀 4
+        let index = SourceIndex::new("é日\n😀x\ny");
+        assert_eq!(index.line_column(2), line_column(1, 2, 3));
+        assert_eq!(index.line_column(5), line_column(1, 3, 6));
+        assert_eq!(index.line_column(6), line_column(2, 1, 1));
+        assert_eq!(index.line_column(10), line_column(2, 2, 5));
+        assert_eq!(index.line_column(11), line_column(2, 3, 6));
+        assert_eq!(index.line_column(12), line_column(3, 1, 1));
+    }
+
+    #[test]
+    fn test_empty() {
+        let index = SourceIndex::new("");
+        assert_eq!(index.line_column(0), line_column(1, 1, 1));
+    }
+
+    #[test]
+    fn test_span() {
+        let index = SourceIndex::new("<a>\n  <b/>\n</a>");
+        assert_eq!(
+            index.span_line_columns(Span::new(7, 8)),
+            (line_column(2, 4, 4), line_column(2, 5, 5))
+        );
+    }
+}
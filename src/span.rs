@@ -0,0 +1,411 @@
+use std::cmp::Reverse;
+use std::sync::OnceLock;
+
+use ahash::{HashMap, HashMapExt};
+use indextree::NodeId;
+use xmlparser::StrSpan;
+
+use crate::builder::NamespaceSpans;
+use crate::id::{NameId, PrefixId};
+use crate::sourceindex::SourceIndex;
+use crate::spanmapping::SpanMapping;
+use crate::xotdata::Node;
+
+/// A span with a start and end position
+///
+/// Spans describe ranges in the source text, with the end point not inclusive,
+/// like a range. It's not a `std::ops::Range` as it's handy for a span to be
+/// `Copy`.
+///
+/// You can obtain these from a [`SpanInfo`](crate::SpanInfo). You create a
+/// [`SpanInfo`] by using
+/// [`Xot::parse_with_span_info`](crate::Xot::parse_with_span_info).
+///
+/// Positions are byte offsets. To get line and column numbers, use a
+/// [`SourceIndex`](crate::SourceIndex).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// the start position in the XML source
+    pub start: usize,
+    /// the end position in the XML source
+    pub end: usize,
+}
+
+impl Span {
+    /// Construct a new span
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub(crate) fn from_prefix_name(prefix: StrSpan<'_>, name: StrSpan<'_>) -> Self {
+        if prefix.is_empty() {
+            Self::new(name.start(), name.end())
+        } else {
+            Self::new(prefix.start(), name.end())
+        }
+    }
+
+    /// Turn a span into a range
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<xmlparser::StrSpan<'_>> for Span {
+    fn from(span: xmlparser::StrSpan) -> Self {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+impl From<&xmlparser::StrSpan<'_>> for Span {
+    fn from(span: &xmlparser::StrSpan) -> Self {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+impl From<Span> for std::ops::Range<usize> {
+    fn from(span: Span) -> Self {
+        span.range()
+    }
+}
+
+impl From<std::ops::Range<usize>> for Span {
+    fn from(range: std::ops::Range<usize>) -> Self {
+        Span {
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+type AttributeSpans = Vec<(NameId, Span, Span)>;
+
+// The spans in a start tag.
+pub(crate) struct StartTagSpans {
+    pub(crate) name: Span,
+    pub(crate) attributes: AttributeSpans,
+    pub(crate) namespaces: NamespaceSpans,
+}
+
+/// A key to use to look up span information using
+/// [`SpanInfo::get`](`crate::SpanInfo::get`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpanInfoKey {
+    /// The name part of an attribute.
+    /// In `foo:name="value"`, the `foo:name` part
+    AttributeName(Node, NameId),
+    /// The value part of an attribute.
+    /// In `foo:name="value"`, the `value` part
+    AttributeValue(Node, NameId),
+    /// The name part of a start element tag.
+    /// In `<foo:name ..>`, the `foo:name` part
+    ElementStart(Node),
+    /// The closing part of the end element tag (or a self-closing element).
+    /// In `</foo:name>`, the `</foo:name>` part, or if it is an empty element
+    /// `<foo:name/>`, the `/>` part
+    ElementEnd(Node),
+    /// Text node.
+    /// In `<foo>text</foo>`, the `text` part
+    Text(Node),
+    /// Comment node.
+    /// In `<!--comment-->`, the `comment` part
+    Comment(Node),
+    /// The target part of a processing instruction.
+    /// In `<?target content?>`, the `target` part
+    PiTarget(Node),
+    /// The content part of a processing instruction (if defined).
+    /// In `<?target content?>`, the `content` part
+    PiContent(Node),
+    /// The name part of a namespace declaration, by the prefix it declares.
+    /// In `xmlns:foo="uri"`, the `xmlns:foo` part, and in `xmlns="uri"`,
+    /// the `xmlns` part
+    NamespacePrefix(Node, PrefixId),
+    /// The value part of a namespace declaration, by the prefix it declares.
+    /// In `xmlns:foo="uri"`, the `uri` part
+    NamespaceUri(Node, PrefixId),
+    /// An entity or character reference in a text node, by the order in
+    /// which it appears in the text node, starting at 0.
+    /// In `<foo>a &amp; b &#33;</foo>`, the `&amp;` part is reference 0 and
+    /// the `&#33;` part reference 1
+    Reference(Node, usize),
+    /// The version in the XML declaration of a document node.
+    /// In `<?xml version="1.0"?>`, the `1.0` part
+    DeclarationVersion(Node),
+    /// The encoding in the XML declaration of a document node (if defined).
+    /// In `<?xml version="1.0" encoding="UTF-8"?>`, the `UTF-8` part
+    DeclarationEncoding(Node),
+    /// The standalone declaration in the XML declaration of a document node
+    /// (if defined).
+    /// In `<?xml version="1.0" standalone="yes"?>`, the `yes` part
+    DeclarationStandalone(Node),
+}
+
+/// Span information for a parsed XML document.
+///
+/// This span information is valid immediately after the parse. It becomes
+/// invalid as soon as you mutate the parsed document.
+///
+/// You obtain this by using
+/// [`Xot::parse_with_span_info`](`crate::Xot::parse_with_span_info`).
+///
+/// You use a [`SpanInfoKey`](crate::SpanInfoKey) to look up the span
+/// information.
+///
+/// The span of a CDATA section is only its text content:
+///
+/// ```text
+/// <p><![CDATA[content]]></p>
+///             ^^^^^^^
+/// ```
+///
+/// There is an exception to this. During parsing, adjacent CDATA sections and
+/// text nodes are consolidated into a single text node. This text node has the
+/// span starting with the first adjacent CDATA section or text node and ending
+/// with the last adjacent CDATA section or text node.
+///
+/// Example:
+///
+/// ```text
+/// <p>text<![CDATA[content]]>text</p>
+///    ^^^^^^^^^^^^^^^^^^^^^^^^^^^
+/// ```
+///
+/// This can lead to the slightly odd situation where only part of the CDATA
+/// marker is included in the span:
+///
+/// ```text
+/// <p>text<![CDATA[content]]>foo</p>
+///                 ^^^^^^^^^^^^^
+///```
+///
+/// In every case all text content in the adjacent CDATA and text is included
+/// in the span.
+pub struct SpanInfo {
+    // the span along with the order in which it was added
+    pub(crate) map: HashMap<SpanInfoKey, (Span, usize)>,
+    added: usize,
+    // the text node we last added a reference to, and how many references
+    // it has
+    references: Option<(Node, usize)>,
+    // the keys sorted by span, created when first needed
+    sorted: OnceLock<Vec<SortedSpan>>,
+    source_index: SourceIndex,
+}
+
+struct SortedSpan {
+    span: Span,
+    key: SpanInfoKey,
+    // the innermost span that contains this one, if any
+    parent: Option<usize>,
+}
+
+impl SpanInfo {
+    pub(crate) fn new() -> Self {
+        SpanInfo {
+            map: HashMap::new(),
+            added: 0,
+            references: None,
+            sorted: OnceLock::new(),
+            source_index: SourceIndex::default(),
+        }
+    }
+
+    /// Get span info by [`SpanInfoKey`](crate::SpanInfoKey)
+    pub fn get(&self, key: SpanInfoKey) -> Option<&Span> {
+        self.map.get(&key).map(|(span, _)| span)
+    }
+
+    /// Find the node and the part of it at a position in the source text.
+    ///
+    /// This is the reverse of [`SpanInfo::get`]: it gives you the key of the
+    /// innermost span that contains the byte offset. The key tells you both
+    /// the node and which part of it is there: the name of an element in its
+    /// start tag, its end tag, an attribute name or value, a namespace
+    /// declaration, text or a reference in it, a comment, the target or
+    /// content of a processing instruction, or a part of the XML
+    /// declaration.
+    ///
+    /// Markup that's not part of any span, such as the `<` of a start tag or
+    /// the `=` of an attribute, gives you `None`.
+    ///
+    /// All nodes that result from an entity reference with markup in its
+    /// replacement text have the span of the reference; for an offset in the
+    /// reference you get the first of these nodes.
+    ///
+    /// The first lookup sorts the spans, after which each lookup is a binary
+    /// search, so you can call this for each cursor move.
+    ///
+    /// ```rust
+    /// use xot::{SpanInfoKey, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = r#"<doc><p a="1">text</p></doc>"#;
+    /// let (document, span_info) = xot.parse_with_span_info(xml).unwrap();
+    /// let doc_el = xot.document_element(document).unwrap();
+    /// let p = xot.first_child(doc_el).unwrap();
+    /// let a = xot.name("a").unwrap();
+    ///
+    /// assert_eq!(span_info.node_at(6), Some(SpanInfoKey::ElementStart(p)));
+    /// assert_eq!(span_info.node_at(11), Some(SpanInfoKey::AttributeValue(p, a)));
+    /// assert_eq!(
+    ///     span_info.node_at(16),
+    ///     Some(SpanInfoKey::Text(xot.first_child(p).unwrap()))
+    /// );
+    /// assert_eq!(span_info.node_at(18), Some(SpanInfoKey::ElementEnd(p)));
+    /// assert_eq!(span_info.node_at(5), None);
+    /// ```
+    pub fn node_at(&self, offset: usize) -> Option<SpanInfoKey> {
+        let sorted = self.sorted.get_or_init(|| self.sort());
+        // the spans we look for contain the last span that starts at or
+        // before the offset, if it isn't that span itself
+        let index = sorted.partition_point(|sorted| sorted.span.start <= offset);
+        let mut current = index.checked_sub(1);
+        while let Some(index) = current {
+            let sorted = &sorted[index];
+            if offset < sorted.span.end {
+                return Some(sorted.key);
+            }
+            current = sorted.parent;
+        }
+        None
+    }
+
+    // Sort the spans so that spans come after the spans that contain them,
+    // and find the innermost span that contains each span.
+    fn sort(&self) -> Vec<SortedSpan> {
+        let mut spans = self
+            .map
+            .iter()
+            .map(|(key, (span, added))| (*span, *added, *key))
+            .collect::<Vec<_>>();
+        // a reference is inside the text node with the same span, and for
+        // the nodes from an entity reference the first one added wins
+        spans.sort_unstable_by_key(|(span, added, key)| {
+            (
+                span.start,
+                Reverse(span.end),
+                matches!(key, SpanInfoKey::Reference(..)),
+                Reverse(*added),
+            )
+        });
+        let mut sorted: Vec<SortedSpan> = Vec::with_capacity(spans.len());
+        // the spans that may contain the spans that follow, innermost last
+        let mut open: Vec<usize> = Vec::new();
+        for (index, (span, _, key)) in spans.into_iter().enumerate() {
+            while let Some(&parent) = open.last() {
+                let parent_span = sorted[parent].span;
+                if span.end <= parent_span.end && span.start < parent_span.end {
+                    break;
+                }
+                open.pop();
+            }
+            sorted.push(SortedSpan {
+                span,
+                key,
+                parent: open.last().copied(),
+            });
+            open.push(index);
+        }
+        sorted
+    }
+
+    /// The index of the source text, to get the line and column of a span.
+    ///
+    /// ```rust
+    /// use xot::{SpanInfoKey, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<?xml version=\"1.0\"?>\n<doc/>";
+    /// let (document, span_info) = xot.parse_with_span_info(xml).unwrap();
+    /// let doc_el = xot.document_element(document).unwrap();
+    ///
+    /// let span = span_info.get(SpanInfoKey::ElementStart(doc_el)).unwrap();
+    /// let position = span_info.source_index().line_column(span.start);
+    /// assert_eq!((position.line, position.column), (2, 2));
+    /// ```
+    pub fn source_index(&self) -> &SourceIndex {
+        &self.source_index
+    }
+
+    pub(crate) fn index_source(&mut self, xml: &str) {
+        self.source_index = SourceIndex::new(xml);
+    }
+
+    pub(crate) fn add(&mut self, key: SpanInfoKey, span: Span) {
+        self.sorted.take();
+        self.map.insert(key, (span, self.added));
+        self.added += 1;
+    }
+
+    pub(crate) fn remove(&mut self, key: SpanInfoKey) -> Option<Span> {
+        self.sorted.take();
+        self.map.remove(&key).map(|(span, _)| span)
+    }
+
+    // Remove the spans of the references in a text node.
+    pub(crate) fn remove_references(&mut self, node: Node) {
+        let mut index = 0;
+        while self.remove(SpanInfoKey::Reference(node, index)).is_some() {
+            index += 1;
+        }
+    }
+
+    // Add the span of the next reference in a text node. The text node is
+    // the last one we added text to.
+    pub(crate) fn add_reference(&mut self, node: Node, span: Span) {
+        let index = match self.references {
+            Some((last, count)) if last == node => count,
+            _ => 0,
+        };
+        self.add(SpanInfoKey::Reference(node, index), span);
+        self.references = Some((node, index + 1));
+    }
+
+    pub(crate) fn extend_text_span(&mut self, node: Node, span: Span) {
+        // if we already have span for this (text) node it, we need to store the span with that
+        // start and the given ending
+        let key = SpanInfoKey::Text(node);
+        if let Some((existing_span, _)) = self.map.get_mut(&key) {
+            self.sorted.take();
+            existing_span.end = span.end;
+        } else {
+            self.add(key, span);
+        }
+    }
+
+    pub(crate) fn add_start_tag_spans(
+        &mut self,
+        node_id: NodeId,
+        spans: StartTagSpans,
+        mapping: SpanMapping,
+    ) {
+        let node = Node::new(node_id);
+        self.add(SpanInfoKey::ElementStart(node), mapping.map(spans.name));
+        for (prefix, name_span, value_span) in spans.namespaces {
+            self.add(
+                SpanInfoKey::NamespacePrefix(node, prefix),
+                mapping.map(name_span),
+            );
+            self.add(
+                SpanInfoKey::NamespaceUri(node, prefix),
+                mapping.map(value_span),
+            );
+        }
+        for (attribute_name, name_span, value_span) in spans.attributes {
+            self.add(
+                SpanInfoKey::AttributeName(node, attribute_name),
+                mapping.map(name_span),
+            );
+            self.add(
+                SpanInfoKey::AttributeValue(node, attribute_name),
+                mapping.map(value_span),
+            );
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use xmlparser::TextPos;
+
+use crate::error::ParseError;
+use crate::span::Span;
+
+// How the spans of tokens relate to the spans in the source text.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SpanMapping {
+    // The tokens come directly from the source text.
+    Source,
+    // The tokens come from the replacement text of an entity. Everything
+    // in it is located at the entity reference.
+    Entity(Span),
+    // The tokens come from a chunk of the source text.
+    Chunk(Chunk),
+}
+
+// Where a chunk of the source text is located in the source, for parsing it
+// on its own.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Chunk {
+    // the position of the chunk in the source
+    pub(crate) position: usize,
+    // the length of the text in front of the chunk that isn't part of the
+    // source. This is only ASCII on a single line.
+    pub(crate) prefix: usize,
+    // the row and column of the position, as xmlparser reports them
+    pub(crate) row: u32,
+    pub(crate) col: u32,
+}
+
+impl Chunk {
+    fn map_position(&self, position: usize) -> usize {
+        position - self.prefix + self.position
+    }
+
+    fn map_text_pos(&self, text_pos: TextPos) -> TextPos {
+        if text_pos.row == 1 {
+            TextPos::new(self.row, self.col + text_pos.col - 1 - self.prefix as u32)
+        } else {
+            TextPos::new(self.row + text_pos.row - 1, text_pos.col)
+        }
+    }
+}
+
+impl SpanMapping {
+    pub(crate) fn map(&self, span: Span) -> Span {
+        match self {
+            SpanMapping::Source => span,
+            SpanMapping::Entity(reference) => *reference,
+            SpanMapping::Chunk(chunk) => {
+                Span::new(chunk.map_position(span.start), chunk.map_position(span.end))
+            }
+        }
+    }
+
+    pub(crate) fn map_error(&self, error: ParseError) -> ParseError {
+        match self {
+            SpanMapping::Source => error,
+            SpanMapping::Entity(reference) => error.with_span(*reference),
+            SpanMapping::Chunk(chunk) => {
+                let error = match error {
+                    ParseError::XmlParser(e, position) => ParseError::XmlParser(
+                        map_xmlparser_text_pos(e, |text_pos| chunk.map_text_pos(text_pos)),
+                        position,
+                    ),
+                    error => error,
+                };
+                let span = self.map(error.span());
+                error.with_span(span)
+            }
+        }
+    }
+}
+
+fn map_xmlparser_text_pos(
+    error: xmlparser::Error,
+    map: impl Fn(TextPos) -> TextPos,
+) -> xmlparser::Error {
+    use xmlparser::Error::*;
+    use xmlparser::StreamError;
+
+    let map_stream_error = |error: StreamError| match error {
+        StreamError::NonXmlChar(c, pos) => StreamError::NonXmlChar(c, map(pos)),
+        StreamError::InvalidChar(actual, expected, pos) => {
+            StreamError::InvalidChar(actual, expected, map(pos))
+        }
+        StreamError::InvalidCharMultiple(actual, expected, pos) => {
+            StreamError::InvalidCharMultiple(actual, expected, map(pos))
+        }
+        StreamError::InvalidQuote(c, pos) => StreamError::InvalidQuote(c, map(pos)),
+        StreamError::InvalidSpace(c, pos) => StreamError::InvalidSpace(c, map(pos)),
+        StreamError::InvalidString(expected, pos) => StreamError::InvalidString(expected, map(pos)),
+        error => error,
+    };
+    match error {
+        InvalidDeclaration(e, pos) => InvalidDeclaration(map_stream_error(e), map(pos)),
+        InvalidComment(e, pos) => InvalidComment(map_stream_error(e), map(pos)),
+        InvalidPI(e, pos) => InvalidPI(map_stream_error(e), map(pos)),
+        InvalidDoctype(e, pos) => InvalidDoctype(map_stream_error(e), map(pos)),
+        InvalidEntity(e, pos) => InvalidEntity(map_stream_error(e), map(pos)),
+        InvalidElement(e, pos) => InvalidElement(map_stream_error(e), map(pos)),
+        InvalidAttribute(e, pos) => InvalidAttribute(map_stream_error(e), map(pos)),
+        InvalidCdata(e, pos) => InvalidCdata(map_stream_error(e), map(pos)),
+        InvalidCharData(e, pos) => InvalidCharData(map_stream_error(e), map(pos)),
+        UnknownToken(pos) => UnknownToken(map(pos)),
+    }
+}
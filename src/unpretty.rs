@@ -12,6 +12,21 @@ pub(crate) fn remove_insignificant_whitespace(xot: &mut Xot, node: Node) {
     }
 }
 
+// The whitespace text children of a node if they're all insignificant. This
+// is what `remove_insignificant_whitespace` removes for the node.
+pub(crate) fn insignificant_whitespace_children(xot: &Xot, node: Node) -> Vec<Node> {
+    if in_preserve_space(xot, node)
+        || xot
+            .children(node)
+            .any(|child| is_significant_text_node(xot, child))
+    {
+        return Vec::new();
+    }
+    xot.children(node)
+        .filter(|child| xot.is_text(*child))
+        .collect()
+}
+
 fn is_whitespace(text: &str) -> bool {
     text.chars().all(|c| c.is_whitespace())
 }
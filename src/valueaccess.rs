@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::access::NodeEdge;
 use crate::xmlvalue::{
     Attribute, Comment, Element, Namespace, ProcessingInstruction, Text, Value, ValueType,
@@ -580,6 +582,26 @@ impl Xot {
     /// Text nodes, comments and processing instructions are considered to be the
     /// same if their values are the same.
     ///
+    /// Adjacent text nodes are compared as a single text, and whether text is
+    /// a CDATA section is ignored. So a CDATA section you keep with
+    /// [`ParseOptions::preserve_cdata`](crate::ParseOptions::preserve_cdata)
+    /// is the same as the text it contains:
+    ///
+    /// ```rust
+    /// use xot::{ParseOptions, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let options = ParseOptions {
+    ///     preserve_cdata: true,
+    ///     ..Default::default()
+    /// };
+    /// let root0 = xot.parse_with_options("<doc>a <![CDATA[<b>]]></doc>", &options)?;
+    /// let root1 = xot.parse("<doc>a &lt;b&gt;</doc>")?;
+    ///
+    /// assert!(xot.deep_equal(root0, root1));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    ///
     /// Compare two documents:
     ///
     /// ```rust
@@ -647,19 +669,10 @@ impl Xot {
     /// If the children are the same semantically, return true. It ignores
     /// the name and attributes of the `a` and `b` nodes themselves.
     pub fn deep_equal_children(&self, a: Node, b: Node) -> bool {
-        let mut b_children = self.children(b);
-        for a_child in self.children(a) {
-            if let Some(b_child) = b_children.next() {
-                // if the child is different, the element is different
-                if !self.deep_equal(a_child, b_child) {
-                    return false;
-                }
-            } else {
-                // we cannot find a b child for an a child
-                return false;
-            }
-        }
-        b_children.next().is_none()
+        // we skip the start edges of the nodes themselves
+        let edges_a = self.content_edges(a, |_| true).skip(1);
+        let edges_b = self.content_edges(b, |_| true).skip(1);
+        self.compare_content_edges(edges_a, edges_b, |a, b| a == b)
     }
 
     /// XPath deep equal
@@ -713,26 +726,36 @@ impl Xot {
         F: Fn(Node) -> bool,
         C: Fn(&str, &str) -> bool,
     {
-        let filter_edge = |edge: &NodeEdge| {
-            let node = match edge {
-                NodeEdge::Start(node) | NodeEdge::End(node) => *node,
-            };
-            filter(node)
-        };
+        let edges_a = self.content_edges(a, &filter);
+        let edges_b = self.content_edges(b, &filter);
+        self.compare_content_edges(edges_a, edges_b, text_compare)
+    }
 
-        let mut edges_a = self.traverse(a).filter(filter_edge);
-        let mut edges_b = self.traverse(b).filter(filter_edge);
+    fn compare_content_edges<'a, C>(
+        &self,
+        mut edges_a: impl Iterator<Item = ContentEdge<'a>>,
+        mut edges_b: impl Iterator<Item = ContentEdge<'a>>,
+        text_compare: C,
+    ) -> bool
+    where
+        C: Fn(&str, &str) -> bool,
+    {
         for edge_pair in edges_a.by_ref().zip(edges_b.by_ref()) {
             match edge_pair {
-                (NodeEdge::Start(a), NodeEdge::Start(b)) => {
+                (ContentEdge::Start(a), ContentEdge::Start(b)) => {
                     if !self.advanced_compare_value(a, b, &text_compare) {
                         return false;
                     }
                 }
-                (NodeEdge::End(_a), NodeEdge::End(_b)) => {
+                (ContentEdge::End, ContentEdge::End) => {
                     // If there is only a difference in structure, not value,
                     // the default case will fire
                 }
+                (ContentEdge::Text(a), ContentEdge::Text(b)) => {
+                    if !text_compare(&a, &b) {
+                        return false;
+                    }
+                }
                 _ => {
                     return false;
                 }
@@ -745,6 +768,55 @@ impl Xot {
         true
     }
 
+    // The edges of the traversal of a node, where adjacent text nodes are
+    // combined into a single text if some of them are CDATA sections, so
+    // that CDATA doesn't make a difference. Other adjacent text nodes, which
+    // only exist if text consolidation is off, are compared one by one.
+    fn content_edges<'a>(
+        &'a self,
+        node: Node,
+        filter: impl Fn(Node) -> bool + 'a,
+    ) -> impl Iterator<Item = ContentEdge<'a>> + 'a {
+        let mut edges = self
+            .traverse(node)
+            .filter(move |edge| match edge {
+                NodeEdge::Start(node) | NodeEdge::End(node) => filter(*node),
+            })
+            .peekable();
+        std::iter::from_fn(move || {
+            let node = match edges.next()? {
+                NodeEdge::Start(node) => node,
+                NodeEdge::End(_) => return Some(ContentEdge::End),
+            };
+            let Some(text) = self.text_str(node) else {
+                return Some(ContentEdge::Start(node));
+            };
+            // skip the end edge
+            edges.next();
+            let mut text = Cow::Borrowed(text);
+            let has_cdata = self
+                .following_siblings(node)
+                .map_while(|sibling| self.text(sibling))
+                .any(|text| text.is_cdata());
+            if !has_cdata {
+                return Some(ContentEdge::Text(text));
+            }
+            let mut last = node;
+            while let Some(NodeEdge::Start(next)) = edges.peek().copied() {
+                match self.text_str(next) {
+                    Some(next_text) if self.next_sibling(last) == Some(next) => {
+                        text.to_mut().push_str(next_text);
+                        last = next;
+                        edges.next();
+                        edges.next();
+                    }
+                    _ => break,
+                }
+            }
+            Some(ContentEdge::Text(text))
+        })
+    }
+
     pub(crate) fn advanced_compare_value<C>(&self, a: Node, b: Node, text_compare: C) -> bool
     where
         C: Fn(&str, &str) -> bool,
@@ -868,3 +940,10 @@ fn descendants_to_string(xot: &Xot, node: Node) -> String {
     }
     r
 }
+
+// An edge in the traversal of a node for the comparison of its content.
+enum ContentEdge<'a> {
+    Start(Node),
+    End,
+    Text(Cow<'a, str>),
+}
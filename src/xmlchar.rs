@@ -0,0 +1,122 @@
+// The characters XML allows in names and in character data.
+
+use crate::declaration::XmlVersion;
+use crate::error::Error;
+
+// Whether this is an XML character, which is what character references may
+// refer to.
+// https://www.w3.org/TR/xml/#NT-Char
+// https://www.w3.org/TR/xml11/#NT-Char
+pub(crate) fn is_char(c: char, version: XmlVersion) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        '\u{0}' => false,
+        '\u{1}'..='\u{1F}' => version == XmlVersion::Xml11,
+        '\u{FFFE}' | '\u{FFFF}' => false,
+        _ => true,
+    }
+}
+
+// https://www.w3.org/TR/xml/#NT-NameStartChar
+pub(crate) fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z' | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}' | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}' | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}' | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}')
+}
+
+// https://www.w3.org/TR/xml/#NT-NameChar
+pub(crate) fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+
+// A name without a colon.
+// https://www.w3.org/TR/xml-names/#NT-NCName
+pub(crate) fn is_ncname(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c != ':' && is_name_start_char(c) => chars.all(|c| c != ':' && is_name_char(c)),
+        _ => false,
+    }
+}
+
+// A name with an optional prefix.
+// https://www.w3.org/TR/xml-names/#NT-QName
+pub(crate) fn is_qname(name: &str) -> bool {
+    match name.split_once(':') {
+        Some((prefix, local_name)) => is_ncname(prefix) && is_ncname(local_name),
+        None => is_ncname(name),
+    }
+}
+
+pub(crate) fn check_ncname(name: &str) -> Result<(), Error> {
+    if is_ncname(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidNcName(name.to_string()))
+    }
+}
+
+pub(crate) fn check_qname(name: &str) -> Result<(), Error> {
+    if is_qname(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidQName(name.to_string()))
+    }
+}
+
+// Character data that only contains characters XML 1.0 allows can be
+// written in any version of XML.
+pub(crate) fn check_chars(text: &str) -> Result<(), Error> {
+    match text.chars().find(|c| !is_char(*c, XmlVersion::Xml10)) {
+        Some(c) => Err(Error::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ncname() {
+        assert!(is_ncname("a"));
+        assert!(is_ncname("_a-b.c\u{b7}1"));
+        assert!(is_ncname("\u{e9}t\u{e9}"));
+        assert!(!is_ncname(""));
+        assert!(!is_ncname("1a"));
+        assert!(!is_ncname("-a"));
+        assert!(!is_ncname("a:b"));
+        assert!(!is_ncname("a b"));
+    }
+
+    #[test]
+    fn test_qname() {
+        assert!(is_qname("a"));
+        assert!(is_qname("a:b"));
+        assert!(!is_qname(":b"));
+        assert!(!is_qname("a:"));
+        assert!(!is_qname("a:b:c"));
+    }
+
+    #[test]
+    fn test_check_chars() {
+        assert!(check_chars("a\tb\n\u{10FFFF}").is_ok());
+        assert!(matches!(
+            check_chars("a\u{0}"),
+            Err(Error::InvalidCharacter('\u{0}'))
+        ));
+        assert!(matches!(
+            check_chars("\u{1}"),
+            Err(Error::InvalidCharacter('\u{1}'))
+        ));
+        assert!(matches!(
+            check_chars("\u{FFFE}"),
+            Err(Error::InvalidCharacter('\u{FFFE}'))
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::xmlchar::{check_ncname, check_qname};
 use crate::{id::NameId, Error, NamespaceId, PrefixId, Xot};
 
 use super::owned::parse_full_name;
@@ -39,12 +40,20 @@ impl CreateName {
     /// A name given a prefix.
     ///
     /// The namespace is looked up in the provided function.
+    ///
+    /// The prefix, unless it's empty, and the local name have to be
+    /// [NCNames](https://www.w3.org/TR/xml-names/#NT-NCName); otherwise
+    /// you get an [`Error::InvalidNcName`].
     pub fn prefixed(
         xot: &mut Xot,
         prefix: &str,
         local_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<NamespaceId>,
     ) -> Result<Self, Error> {
+        if !prefix.is_empty() {
+            check_ncname(prefix)?;
+        }
+        check_ncname(local_name)?;
         let namespace =
             lookup_namespace(prefix).ok_or_else(|| Error::UnknownPrefix(prefix.to_string()))?;
         let name_id = xot.add_name_ns(local_name, namespace);
@@ -54,11 +63,16 @@ impl CreateName {
     /// Parse a fullname (with potentially a prefix) and construct a name.
     ///
     /// The namespace is looked up in the provided function.
+    ///
+    /// The full name has to be a
+    /// [QName](https://www.w3.org/TR/xml-names/#NT-QName); otherwise you get
+    /// an [`Error::InvalidQName`].
     pub fn parse_full_name(
         xot: &mut Xot,
         full_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<NamespaceId>,
     ) -> Result<Self, Error> {
+        check_qname(full_name)?;
         let (prefix, local_name) = parse_full_name(full_name);
         Self::prefixed(xot, prefix, local_name, lookup_namespace)
     }
@@ -1,3 +1,4 @@
+use crate::xmlchar::{check_ncname, check_qname};
 use crate::{Error, Xot};
 
 use super::CreateName;
@@ -91,11 +92,19 @@ impl OwnedName {
     }
 
     /// Create a new owned name from a prefix and a name.
+    ///
+    /// The prefix, unless it's empty, and the local name have to be
+    /// [NCNames](https://www.w3.org/TR/xml-names/#NT-NCName); otherwise
+    /// you get an [`Error::InvalidNcName`].
     pub fn prefixed(
         prefix: &str,
         local_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<String>,
     ) -> Result<Self, Error> {
+        if !prefix.is_empty() {
+            check_ncname(prefix)?;
+        }
+        check_ncname(local_name)?;
         let namespace =
             lookup_namespace(prefix).ok_or_else(|| Error::UnknownPrefix(prefix.to_string()))?;
         Ok(Self {
@@ -108,10 +117,15 @@ impl OwnedName {
     /// Given a fullname (with potentially a prefix), construct an XmlNameOwned
     ///
     /// This requires a function that can look up the namespace for a prefix.
+    ///
+    /// The full name has to be a
+    /// [QName](https://www.w3.org/TR/xml-names/#NT-QName); otherwise you get
+    /// an [`Error::InvalidQName`].
     pub fn parse_full_name(
         full_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<String>,
     ) -> Result<Self, Error> {
+        check_qname(full_name)?;
         let (prefix, local_name) = parse_full_name(full_name);
         Self::prefixed(prefix, local_name, lookup_namespace)
     }
@@ -4,6 +4,7 @@ use ahash::AHashMap;
 
 use crate::error::Error;
 use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::xmlchar::check_chars;
 
 /// The type of the XML node.
 ///
@@ -155,11 +156,16 @@ impl Element {
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Text {
     pub(crate) text: String,
+    pub(crate) cdata: bool,
 }
 
 impl Text {
     pub(crate) fn new(text: String) -> Self {
-        Text { text }
+        Text { text, cdata: false }
+    }
+
+    pub(crate) fn new_cdata(text: String) -> Self {
+        Text { text, cdata: true }
     }
 
     /// Get the text value.
@@ -194,6 +200,65 @@ impl Text {
     pub fn set<S: Into<String>>(&mut self, text: S) {
         self.text = text.into();
     }
+
+    /// Set the text value, checking that XML allows its characters.
+    ///
+    /// This is like [`Text::set`], but if the text contains a character that
+    /// XML doesn't allow, such as NUL, you get an [`Error::InvalidCharacter`]
+    /// and the text stays the same.
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc>Example</doc>"#)?;
+    /// let doc_el = xot.document_element(root).unwrap();
+    /// let text_node = xot.first_child(doc_el).unwrap();
+    ///
+    /// let text = xot.text_mut(text_node).unwrap();
+    /// assert!(matches!(text.try_set("a\u{1}"), Err(Error::InvalidCharacter('\u{1}'))));
+    /// text.try_set("New text")?;
+    ///
+    /// assert_eq!(xot.to_string(root).unwrap(), r#"<doc>New text</doc>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn try_set<S: Into<String>>(&mut self, text: S) -> Result<(), Error> {
+        let text = text.into();
+        check_chars(&text)?;
+        self.text = text;
+        Ok(())
+    }
+
+    /// Whether this text is a CDATA section.
+    ///
+    /// The parser only creates CDATA text if you turn this on with
+    /// [`ParseOptions::preserve_cdata`](crate::ParseOptions::preserve_cdata).
+    /// Serializing CDATA text writes a CDATA section.
+    pub fn is_cdata(&self) -> bool {
+        self.cdata
+    }
+
+    /// Set whether this text is a CDATA section.
+    ///
+    /// Text is only consolidated with adjacent text that is a CDATA section
+    /// too, or that isn't, so this doesn't merge text nodes.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc>a &lt; b</doc>"#)?;
+    /// let doc_el = xot.document_element(root).unwrap();
+    /// let text_node = xot.first_child(doc_el).unwrap();
+    ///
+    /// xot.text_mut(text_node).unwrap().set_cdata(true);
+    ///
+    /// assert_eq!(xot.to_string(root).unwrap(), r#"<doc><![CDATA[a < b]]></doc>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn set_cdata(&mut self, cdata: bool) {
+        self.cdata = cdata;
+    }
 }
 
 /// XML comment.
@@ -298,10 +363,29 @@ impl Namespace {
 }
 
 /// Represents an attribute node.
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+///
+/// Attributes with the same name and value are equal, whether they were
+/// [`specified`](Attribute::specified) or not.
+#[derive(Debug, Clone)]
 pub struct Attribute {
     pub(crate) name_id: NameId,
     pub(crate) value: String,
+    pub(crate) specified: bool,
+}
+
+impl PartialEq for Attribute {
+    fn eq(&self, other: &Self) -> bool {
+        self.name_id == other.name_id && self.value == other.value
+    }
+}
+
+impl Eq for Attribute {}
+
+impl std::hash::Hash for Attribute {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name_id.hash(state);
+        self.value.hash(state);
+    }
 }
 
 impl Attribute {
@@ -316,8 +400,20 @@ impl Attribute {
     }
 
     /// Set value
+    ///
+    /// An attribute with a value that was set is considered to be specified.
     pub fn set_value<S: Into<String>>(&mut self, value: S) {
         self.value = value.into();
+        self.specified = true;
+    }
+
+    /// Whether the attribute was specified.
+    ///
+    /// This is `false` for attributes that were added during parsing because
+    /// the DTD declares a default value for them, and `true` for all other
+    /// attributes.
+    pub fn specified(&self) -> bool {
+        self.specified
     }
 }
 
@@ -1,6 +1,12 @@
+use std::sync::Arc;
+
+use ahash::HashMap;
 use indextree::{Arena, NodeId};
 
+use crate::declaration::XmlDeclaration;
+use crate::dtd::Dtd;
 use crate::id::{Name, NameId, NameLookup, NamespaceId, NamespaceLookup, PrefixId, PrefixLookup};
+use crate::resolver::{NoResolver, Resolver};
 use crate::xmlvalue::Value;
 
 pub(crate) type XmlArena = Arena<Value>;
@@ -29,6 +35,16 @@ impl From<NodeId> for Node {
     }
 }
 
+/// Information about a document node that isn't stored in the tree itself.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocumentInfo {
+    pub(crate) declaration: Option<XmlDeclaration>,
+    pub(crate) dtd: Option<Dtd>,
+    // elements by the value of their ID attributes as found during parsing,
+    // along with the name of the ID attribute
+    pub(crate) ids: HashMap<String, (Node, NameId)>,
+}
+
 /// The `Xot` struct manages all XML tree data in your program. It lets you
 /// access and manipulate one or more XML documents and
 /// fragments, as well as unattached trees of nodes.
@@ -58,6 +74,8 @@ pub struct Xot {
     pub(crate) xml_space_id: NameId,
     pub(crate) xml_id_id: NameId,
     pub(crate) text_consolidation: bool,
+    pub(crate) documents: HashMap<Node, DocumentInfo>,
+    pub(crate) resolver: Arc<dyn Resolver>,
 }
 
 impl Xot {
@@ -84,6 +102,8 @@ impl Xot {
             xml_space_id,
             xml_id_id,
             text_consolidation: true,
+            documents: HashMap::default(),
+            resolver: Arc::new(NoResolver),
         }
     }
 
@@ -96,6 +116,14 @@ impl Xot {
     pub(crate) fn arena_mut(&mut self) -> &mut XmlArena {
         &mut self.arena
     }
+
+    pub(crate) fn document_info(&self, node: Node) -> Option<&DocumentInfo> {
+        self.documents.get(&node)
+    }
+
+    pub(crate) fn document_info_mut(&mut self, node: Node) -> &mut DocumentInfo {
+        self.documents.entry(node).or_default()
+    }
 }
 
 impl Default for Xot {
@@ -1,4 +1,4 @@
-use xot::Xot;
+use xot::{ParseOptions, Xot};
 
 #[test]
 fn test_compare_elements_same() {
@@ -84,3 +84,77 @@ fn test_compare_elements_ignore_attributes_ignorable_in_b() {
     assert!(xot.shallow_equal_ignore_attributes(a, b, &[bar]));
     assert!(!xot.shallow_equal_ignore_attributes(b, a, &[]));
 }
+
+#[test]
+fn test_compare_cdata() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+
+    let a = xot
+        .parse_with_options(r#"<a>foo<![CDATA[<bar>]]>baz</a>"#, &options)
+        .unwrap();
+    let b = xot
+        .parse_with_options(r#"<a>foo&lt;bar&gt;baz</a>"#, &options)
+        .unwrap();
+    let c = xot
+        .parse_with_options(r#"<a><![CDATA[foo<bar>baz]]></a>"#, &options)
+        .unwrap();
+    let d = xot
+        .parse_with_options(r#"<a>foo<![CDATA[<bar>]]>qux</a>"#, &options)
+        .unwrap();
+
+    assert!(xot.deep_equal(a, b));
+    assert!(xot.deep_equal(a, c));
+    assert!(xot.deep_equal(b, c));
+    assert!(!xot.deep_equal(a, d));
+
+    let a = xot.document_element(a).unwrap();
+    let b = xot.document_element(b).unwrap();
+    let d = xot.document_element(d).unwrap();
+    assert!(xot.deep_equal_children(a, b));
+    assert!(!xot.deep_equal_children(a, d));
+}
+
+#[test]
+fn test_compare_adjacent_text() {
+    let mut xot = Xot::new();
+    xot.set_text_consolidation(false);
+
+    let a = xot.parse(r#"<a>foobar<b/></a>"#).unwrap();
+    let b = xot.parse(r#"<a><b/></a>"#).unwrap();
+    let b_el = xot.document_element(b).unwrap();
+    let b_first = xot.first_child(b_el).unwrap();
+    let foo = xot.new_text("foo");
+    let bar = xot.new_text("bar");
+    xot.insert_before(b_first, foo).unwrap();
+    xot.insert_before(b_first, bar).unwrap();
+    assert_eq!(xot.children(b_el).count(), 3);
+
+    // without CDATA, adjacent text nodes are compared one by one
+    assert!(!xot.deep_equal(a, b));
+    assert!(!xot.deep_equal_children(
+        xot.document_element(a).unwrap(),
+        xot.document_element(b).unwrap()
+    ));
+    assert!(!xot.deep_equal_xpath(a, b, |a, b| a == b));
+}
+
+#[test]
+fn test_compare_adjacent_text_with_cdata() {
+    let mut xot = Xot::new();
+    xot.set_text_consolidation(false);
+
+    let a = xot.parse(r#"<a>foobarbaz</a>"#).unwrap();
+    let b = xot.parse(r#"<a/>"#).unwrap();
+    let b_el = xot.document_element(b).unwrap();
+    for (text, is_cdata) in [("foo", false), ("bar", true), ("baz", false)] {
+        let text = xot.new_text(text);
+        xot.text_mut(text).unwrap().set_cdata(is_cdata);
+        xot.append(b_el, text).unwrap();
+    }
+
+    assert!(xot.deep_equal(a, b));
+}
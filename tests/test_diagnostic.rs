@@ -0,0 +1,130 @@
+use xot::{Span, Xot};
+
+#[test]
+fn test_diagnostic_invalid_close_tag() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <a></b>\n</doc>";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    assert_eq!(
+        diagnostic.message,
+        "close tag `</b>` doesn't match the open element"
+    );
+    assert_eq!(diagnostic.span, Span::new(13, 14));
+    assert_eq!(diagnostic.start.line, 2);
+    assert_eq!(diagnostic.start.column, 8);
+    assert_eq!(diagnostic.label, "expected `</a>`");
+    assert_eq!(diagnostic.source_line, "  <a></b>");
+    assert_eq!(
+        diagnostic.to_string(),
+        r#"error: close tag `</b>` doesn't match the open element
+ --> 2:8
+  |
+2 |   <a></b>
+  |        ^ expected `</a>`
+  |
+  = help: expected `</a>` here; `<a>` was opened at 2:4"#
+    );
+}
+
+#[test]
+fn test_diagnostic_unclosed_tag() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <p>text\n</doc>";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    assert_eq!(
+        diagnostic.message,
+        "close tag `</doc>` doesn't match the open element"
+    );
+    assert_eq!(diagnostic.label, "expected `</p>`");
+
+    let xml = "<doc>\n  <p>text";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    assert_eq!(diagnostic.message, "element `p` is never closed");
+    assert_eq!(diagnostic.start.line, 2);
+    assert_eq!(diagnostic.help, vec!["close it with `</p>`"]);
+}
+
+#[test]
+fn test_diagnostic_unknown_prefix() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n <p:a/>\n</doc>";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    assert_eq!(
+        diagnostic.to_string(),
+        r#"error: namespace prefix `p` is not declared
+ --> 2:3
+  |
+2 |  <p:a/>
+  |   ^ unknown prefix
+  |
+  = help: declare it with `xmlns:p="..."`"#
+    );
+}
+
+#[test]
+fn test_diagnostic_multibyte() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <é a='1' a='2'/>\n</doc>";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    assert_eq!(
+        diagnostic.message,
+        "attribute `a` is specified more than once"
+    );
+    assert_eq!(diagnostic.start.column, 12);
+    assert_eq!(diagnostic.start.byte_column, 13);
+    // the underline is placed by characters, not bytes
+    assert!(diagnostic
+        .to_string()
+        .contains("\n  |            ^ duplicate attribute"));
+}
+
+#[test]
+fn test_diagnostic_tokenizer_error() {
+    let mut xot = Xot::new();
+    let xml = "<doc/>\n<doc/>";
+    let diagnostic = xot.parse(xml).unwrap_err().diagnostic(xml);
+    // we point at the token, not at the whitespace in front of it
+    assert_eq!(diagnostic.start.line, 2);
+    assert_eq!(diagnostic.start.column, 1);
+    assert_eq!(diagnostic.source_line, "<doc/>");
+}
+
+#[test]
+fn test_diagnostic_lenient_errors() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  a &amp b\n  <a></b>\n</doc>";
+    let (_, _, errors) = xot.parse_lenient(xml);
+    let messages = errors
+        .iter()
+        .map(|error| error.diagnostic(xml).message)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            "reference `&amp` is not closed",
+            "element `a` is never closed",
+            "close tag `</b>` doesn't match the open element",
+        ]
+    );
+}
+
+#[test]
+fn test_diagnostic_with_span_info() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <a><b/>\n  </c></a>\n</doc>";
+    let (_, span_info, errors) = xot.parse_lenient(xml);
+    assert_eq!(errors.len(), 1);
+    let diagnostic = errors[0].diagnostic_with_span_info(xml, &span_info);
+    assert_eq!(diagnostic.label, "expected `</a>`");
+    assert_eq!(
+        diagnostic.help,
+        vec!["expected `</a>` here; `<a>` was opened at 2:4"]
+    );
+    // it's the same diagnostic we get without span information
+    assert_eq!(diagnostic, errors[0].diagnostic(xml));
+
+    let xml = "<doc></doc></x>";
+    let (_, span_info, errors) = xot.parse_lenient(xml);
+    let diagnostic = errors[0].diagnostic_with_span_info(xml, &span_info);
+    assert_eq!(diagnostic.label, "no element `x` is open");
+}
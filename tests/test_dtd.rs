@@ -0,0 +1,501 @@
+use xot::{output, ParseError, SpanInfoKey, Xot};
+
+#[test]
+fn test_doctype_without_external_id() {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<!DOCTYPE note><note></note>"#).unwrap();
+    let dtd = xot.dtd(root).unwrap();
+    assert_eq!(dtd.name(), "note");
+    assert_eq!(dtd.public_id(), None);
+    assert_eq!(dtd.system_id(), None);
+    assert_eq!(dtd.internal_subset(), None);
+    assert_eq!(dtd.doc_type(), None);
+}
+
+#[test]
+fn test_doctype_system() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE note SYSTEM "Note.dtd"><note></note>"#)
+        .unwrap();
+    let dtd = xot.dtd(root).unwrap();
+    assert_eq!(dtd.name(), "note");
+    assert_eq!(dtd.public_id(), None);
+    assert_eq!(dtd.system_id(), Some("Note.dtd"));
+    assert_eq!(
+        dtd.doc_type(),
+        Some(output::xml::DocType::System {
+            system: "Note.dtd".to_string()
+        })
+    );
+}
+
+#[test]
+fn test_doctype_public() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<?xml version="1.0"?><!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"><html/>"#)
+        .unwrap();
+    let dtd = xot.dtd(root).unwrap();
+    assert_eq!(dtd.name(), "html");
+    assert_eq!(dtd.public_id(), Some("-//W3C//DTD XHTML 1.0 Strict//EN"));
+    assert_eq!(
+        dtd.system_id(),
+        Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd")
+    );
+}
+
+#[test]
+fn test_doctype_internal_subset() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE note SYSTEM "Note.dtd" [
+  <!ELEMENT note (#PCDATA)>
+  <!ATTLIST note id ID #IMPLIED>
+  <!-- a comment -->
+]><note>Hello</note>"#,
+        )
+        .unwrap();
+    let dtd = xot.dtd(root).unwrap();
+    assert_eq!(dtd.name(), "note");
+    assert_eq!(dtd.system_id(), Some("Note.dtd"));
+    assert_eq!(
+        dtd.internal_subset(),
+        Some(
+            r#"
+  <!ELEMENT note (#PCDATA)>
+  <!ATTLIST note id ID #IMPLIED>
+  <!-- a comment -->
+"#
+        )
+    );
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("Hello"));
+}
+
+#[test]
+fn test_no_doctype() {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<note/>"#).unwrap();
+    assert!(xot.dtd(root).is_none());
+    let doc_el = xot.document_element(root).unwrap();
+    assert!(xot.dtd(doc_el).is_none());
+}
+
+#[test]
+fn test_doctype_roundtrip() {
+    let mut xot = Xot::new();
+    let xml = r#"<!DOCTYPE note SYSTEM "Note.dtd">
+<note>Hello</note>"#;
+    let root = xot.parse(xml).unwrap();
+    let doctype = xot.dtd(root).unwrap().doc_type();
+    let serialized = xot
+        .serialize_xml_string(
+            output::xml::Parameters {
+                doctype,
+                ..Default::default()
+            },
+            root,
+        )
+        .unwrap();
+    assert_eq!(serialized, xml);
+}
+
+#[test]
+fn test_doctype_removed_with_document() {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<!DOCTYPE note><note/>"#).unwrap();
+    xot.remove(root).unwrap();
+    let root = xot.parse(r#"<note/>"#).unwrap();
+    assert!(xot.dtd(root).is_none());
+}
+
+#[test]
+fn test_entity_text() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY company "Example Inc.">]><doc>Hello &company;!</doc>"#)
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc>Hello Example Inc.!</doc>"
+    );
+    let doc_el = xot.document_element(root).unwrap();
+    // the text is consolidated into a single text node
+    assert_eq!(xot.children(doc_el).count(), 1);
+}
+
+#[test]
+fn test_entity_markup() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [
+<!ENTITY sig "<em>Regards</em>, &name;">
+<!ENTITY name "Bob">
+]><doc>Text &sig;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc>Text <em>Regards</em>, Bob</doc>"
+    );
+}
+
+#[test]
+fn test_entity_markup_namespaced() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ENTITY e "<x:b x:a='&v;'/>"><!ENTITY v "V">]><doc xmlns:x="http://example.com">&e;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<doc xmlns:x="http://example.com"><x:b x:a="V"/></doc>"#
+    );
+}
+
+#[test]
+fn test_entity_attribute() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "E &f;"><!ENTITY f "F">]><doc a="[&e;]"/>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="[E F]"/>"#);
+}
+
+#[test]
+fn test_entity_character_reference_becomes_markup() {
+    // example from https://www.w3.org/TR/xml/#sec-entexpand
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ENTITY example "<p>An ampersand (&#38;#38;) may be escaped numerically (&#38;#38;#38;) or with a general entity (&amp;amp;).</p>">]><doc>&example;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc><p>An ampersand (&amp;) may be escaped numerically (&amp;#38;) or with a general entity (&amp;amp;).</p></doc>"
+    );
+}
+
+#[test]
+fn test_entity_undeclared() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "E">]><doc>&f;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidEntity(ref name, _) if name == "f"));
+    assert_eq!(err.span(), (37..40).into());
+}
+
+#[test]
+fn test_entity_parameter_entity_is_not_general() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY % e "E">]><doc>&e;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidEntity(_, _)));
+}
+
+#[test]
+fn test_entity_recursive() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY a "<x>&b;</x>"><!ENTITY b "&a;">]><doc>&a;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::RecursiveEntity(_, _)));
+    // the error is reported at the reference in the document
+    assert_eq!(err.span(), (63..66).into());
+}
+
+#[test]
+fn test_entity_unbalanced() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "<b>">]><doc>&e;</b></doc>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::UnclosedTag(_)));
+    assert_eq!(err.span(), (39..42).into());
+}
+
+#[test]
+fn test_entity_closes_outer_element() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "</doc><doc>">]><doc>&e;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidCloseTag(_, _, _)));
+    assert_eq!(err.span(), (47..50).into());
+}
+
+#[test]
+fn test_internal_subset_top_level() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse("<!DOCTYPE doc [<!ENTITY e 'x'>]>\n<doc>&e;</doc>\n<!--c-->\n")
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>x</doc><!--c-->");
+
+    let err = xot
+        .parse("<!DOCTYPE doc [<!ENTITY e 'x'>]><doc/>text")
+        .unwrap_err();
+    assert!(matches!(err, ParseError::TextAtTopLevel(_)));
+    assert_eq!(err.span(), (38..42).into());
+    let err = xot
+        .parse("<!DOCTYPE doc [<!ENTITY e '<doc/>'>]><doc/>&e;")
+        .unwrap_err();
+    assert!(matches!(err, ParseError::TextAtTopLevel(_)));
+    assert_eq!(err.span(), (43..46).into());
+    let err = xot
+        .parse("<!DOCTYPE doc [<!ENTITY e 'x'>]><doc/><doc/>")
+        .unwrap_err();
+    assert!(matches!(err, ParseError::MultipleElementsAtTopLevel(_)));
+    assert_eq!(err.span(), (39..42).into());
+}
+
+#[test]
+fn test_entity_billion_laughs() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(
+            r#"<?xml version="1.0"?>
+<!DOCTYPE lolz [
+ <!ENTITY lol "lol">
+ <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+ <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+ <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+ <!ENTITY lol4 "&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;">
+ <!ENTITY lol5 "&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;">
+ <!ENTITY lol6 "&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;">
+ <!ENTITY lol7 "&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;">
+ <!ENTITY lol8 "&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;">
+ <!ENTITY lol9 "&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;">
+]>
+<lolz>&lol9;</lolz>"#,
+        )
+        .unwrap_err();
+    assert!(matches!(err, ParseError::EntityExpansionLimit(_, _)));
+}
+
+#[test]
+fn test_entity_billion_laughs_attribute() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(
+            r#"<!DOCTYPE lolz [
+ <!ENTITY lol "lol">
+ <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+ <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+ <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+ <!ENTITY lol4 "&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;">
+ <!ENTITY lol5 "&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;">
+ <!ENTITY lol6 "&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;">
+ <!ENTITY lol7 "&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;&lol6;">
+ <!ENTITY lol8 "&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;&lol7;">
+ <!ENTITY lol9 "&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;&lol8;">
+]>
+<lolz a="&lol9;"/>"#,
+        )
+        .unwrap_err();
+    assert!(matches!(err, ParseError::EntityExpansionLimit(_, _)));
+}
+
+#[test]
+fn test_entity_span_info() {
+    let mut xot = Xot::new();
+    let (root, span_info) = xot
+        .parse_with_span_info(r#"<!DOCTYPE doc [<!ENTITY e "<b>B</b>">]><doc>&e;</doc>"#)
+        .unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let b = xot.first_child(doc_el).unwrap();
+    // nodes that come from an entity are located at the entity reference
+    assert_eq!(
+        span_info.get(SpanInfoKey::ElementStart(b)),
+        Some(&(44..47).into())
+    );
+    let text = xot.first_child(b).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::Text(text)),
+        Some(&(44..47).into())
+    );
+}
+
+#[test]
+fn test_attribute_defaults() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [
+<!ATTLIST doc version CDATA #FIXED "1.0" lang CDATA "en" id ID #IMPLIED>
+<!ATTLIST item kind (a|b) "a">
+]><doc lang="nl"><item/><item kind="b"/></doc>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<doc lang="nl" version="1.0"><item kind="a"/><item kind="b"/></doc>"#
+    );
+}
+
+#[test]
+fn test_attribute_default_with_entity() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "E"><!ATTLIST doc a CDATA "&e;&#x20;&lt;">]><doc/>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="E &lt;"/>"#);
+}
+
+#[test]
+fn test_attribute_default_prefixed() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE p:doc [<!ATTLIST p:doc p:a CDATA "A">]><p:doc xmlns:p="urn:p"/>"#)
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let a = xot.add_name_ns("a", xot.namespace("urn:p").unwrap());
+    assert_eq!(xot.get_attribute(doc, a), Some("A"));
+}
+
+#[test]
+fn test_attribute_default_namespace_declaration() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ATTLIST doc xmlns CDATA #FIXED "urn:doc" xmlns:x CDATA "urn:x">]><doc><x:item/></doc>"#,
+        )
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let doc_name = xot.name_ns("doc", xot.namespace("urn:doc").unwrap());
+    assert_eq!(xot.element(doc).unwrap().name(), doc_name.unwrap());
+    let item = xot.first_child(doc).unwrap();
+    let item_name = xot.name_ns("item", xot.namespace("urn:x").unwrap());
+    assert_eq!(xot.element(item).unwrap().name(), item_name.unwrap());
+}
+
+#[test]
+fn test_attribute_default_unknown_prefix() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ATTLIST doc p:a CDATA "A">]><doc/>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::UnknownPrefix(ref prefix, _) if prefix == "p"));
+    // the span of the element, as the attribute isn't in the source
+    assert_eq!(err.span(), (46..49).into());
+}
+
+#[test]
+fn test_attribute_normalization() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ATTLIST doc tokens NMTOKENS #IMPLIED text CDATA #IMPLIED>]><doc tokens="  a&#x20;&#x20;b
+ c " text="  a  b "/>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<doc tokens="a b c" text="  a  b "/>"#
+    );
+}
+
+#[test]
+fn test_attribute_specified() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ATTLIST doc a CDATA "A" b CDATA "B">]><doc a="x"/>"#)
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let a = xot.name("a").unwrap();
+    let b = xot.name("b").unwrap();
+    let specified = |xot: &Xot, name| {
+        let node = xot.attributes(doc).get_node(name).unwrap();
+        xot.attribute_node(node).unwrap().specified()
+    };
+    assert!(specified(&xot, a));
+    assert!(!specified(&xot, b));
+    // an attribute that is set is specified
+    xot.attributes_mut(doc).insert(b, "B".to_string());
+    assert!(specified(&xot, b));
+}
+
+#[test]
+fn test_attribute_specified_not_in_equality() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ATTLIST p a CDATA "A">]><doc><p/><p a="A"/></doc>"#)
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let a = xot.name("a").unwrap();
+    let attribute = |node| {
+        let node = xot.attributes(node).get_node(a).unwrap();
+        xot.attribute_node(node).unwrap()
+    };
+    let defaulted = attribute(xot.first_child(doc).unwrap());
+    let specified = attribute(xot.last_child(doc).unwrap());
+    assert!(!defaulted.specified());
+    assert!(specified.specified());
+    assert_eq!(defaulted, specified);
+}
+
+#[test]
+fn test_attribute_default_span_info() {
+    let mut xot = Xot::new();
+    let (root, span_info) = xot
+        .parse_with_span_info(r#"<!DOCTYPE doc [<!ATTLIST doc a CDATA "A">]><doc b="B"/>"#)
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let a = xot.name("a").unwrap();
+    let b = xot.name("b").unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::AttributeValue(doc, b)),
+        Some(&(51..52).into())
+    );
+    assert_eq!(span_info.get(SpanInfoKey::AttributeValue(doc, a)), None);
+}
+
+#[test]
+fn test_attribute_list_declaration_with_greater_than() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ATTLIST doc a CDATA "x>y">]><doc/>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="x>y"/>"#);
+}
+
+#[test]
+fn test_element_by_id() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ATTLIST item key ID #IMPLIED>]><doc><item key=" one "/><item key="two"/><item key="two"/><other key="three"/></doc>"#,
+        )
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let items = xot.children(doc).collect::<Vec<_>>();
+    assert_eq!(xot.element_by_id(root, "one"), Some(items[0]));
+    // the first element with an id wins
+    assert_eq!(xot.element_by_id(items[2], "two"), Some(items[1]));
+    // the attribute isn't declared as an ID for this element
+    assert_eq!(xot.element_by_id(root, "three"), None);
+}
+
+#[test]
+fn test_element_by_xml_id() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<doc><item xml:id="one"/><item xml:id="two"/></doc>"#)
+        .unwrap();
+    let doc = xot.document_element(root).unwrap();
+    let first = xot.first_child(doc).unwrap();
+    let last = xot.last_child(doc).unwrap();
+    assert_eq!(xot.element_by_id(root, "one"), Some(first));
+    // changed and removed elements aren't found
+    xot.set_attribute(first, xot.xml_id_name(), "other");
+    assert_eq!(xot.element_by_id(root, "one"), None);
+    xot.remove(last).unwrap();
+    assert_eq!(xot.element_by_id(root, "two"), None);
+}
@@ -0,0 +1,212 @@
+use xot::{EncodingReport, Error, ParseError, ParseOptions, Xot};
+
+fn strict() -> ParseOptions {
+    ParseOptions {
+        strict_encoding: true,
+        ..Default::default()
+    }
+}
+
+fn report(bytes: &[u8]) -> EncodingReport {
+    let mut xot = Xot::new();
+    let (_, report) = xot
+        .parse_bytes_with_report(bytes, &ParseOptions::default())
+        .unwrap();
+    report
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+fn utf16be(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes())
+        .collect()
+}
+
+#[test]
+fn test_report_utf8() {
+    assert_eq!(
+        report(b"<p>\xc3\xa9</p>"),
+        EncodingReport {
+            encoding: "UTF-8",
+            bom: false,
+            replaced: false
+        }
+    );
+    assert_eq!(
+        report(b"\xef\xbb\xbf<p/>"),
+        EncodingReport {
+            encoding: "UTF-8",
+            bom: true,
+            replaced: false
+        }
+    );
+}
+
+#[test]
+fn test_report_declared() {
+    assert_eq!(
+        report(b"<?xml version='1.0' encoding='ISO-8859-1'?><p>\xe9</p>"),
+        EncodingReport {
+            encoding: "windows-1252",
+            bom: false,
+            replaced: false
+        }
+    );
+}
+
+#[test]
+fn test_report_utf16() {
+    let mut bytes = vec![0xff, 0xfe];
+    bytes.extend(utf16le(
+        "<?xml version='1.0' encoding='UTF-16'?><p>\u{e9}</p>",
+    ));
+    let mut xot = Xot::new();
+    let (root, report) = xot
+        .parse_bytes_with_report(&bytes, &ParseOptions::default())
+        .unwrap();
+    assert_eq!(
+        report,
+        EncodingReport {
+            encoding: "UTF-16LE",
+            bom: true,
+            replaced: false
+        }
+    );
+    assert_eq!(xot.to_string(root).unwrap(), "<p>\u{e9}</p>");
+}
+
+#[test]
+fn test_report_replaced() {
+    let mut xot = Xot::new();
+    let (root, report) = xot
+        .parse_bytes_with_report(b"<p>\xe9</p>", &ParseOptions::default())
+        .unwrap();
+    assert!(report.replaced);
+    assert_eq!(xot.to_string(root).unwrap(), "<p>\u{fffd}</p>");
+}
+
+#[test]
+fn test_strict_valid() {
+    let mut xot = Xot::new();
+    let documents: Vec<Vec<u8>> = vec![
+        b"<p>\xc3\xa9</p>".to_vec(),
+        b"\xef\xbb\xbf<?xml version='1.0' encoding='utf-8'?><p/>".to_vec(),
+        b"<?xml version='1.0' encoding='ISO-8859-1'?><p>\xe9</p>".to_vec(),
+        [
+            vec![0xff, 0xfe],
+            utf16le("<?xml version='1.0' encoding='UTF-16'?><p/>"),
+        ]
+        .concat(),
+        [
+            vec![0xfe, 0xff],
+            utf16be("<?xml version='1.0' encoding='UTF-16'?><p/>"),
+        ]
+        .concat(),
+        [
+            vec![0xfe, 0xff],
+            utf16be("<?xml version='1.0' encoding='UTF-16BE'?><p/>"),
+        ]
+        .concat(),
+    ];
+    for bytes in documents {
+        assert!(
+            xot.parse_bytes_with_options(&bytes, &strict()).is_ok(),
+            "{:?}",
+            bytes
+        );
+    }
+}
+
+#[test]
+fn test_strict_malformed() {
+    let mut xot = Xot::new();
+    let error = xot
+        .parse_bytes_with_options(b"<p>ab\xe9</p>", &strict())
+        .unwrap_err();
+    match error {
+        ParseError::MalformedEncoding(encoding, span) => {
+            assert_eq!(encoding, "UTF-8");
+            assert_eq!(span.start, 5);
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+#[test]
+fn test_strict_bom_contradicts_declaration() {
+    let mut xot = Xot::new();
+    let xml = "<?xml version='1.0' encoding='ISO-8859-1'?><p/>";
+    let bytes = [b"\xef\xbb\xbf", xml.as_bytes()].concat();
+    let error = xot.parse_bytes_with_options(&bytes, &strict()).unwrap_err();
+    match error {
+        ParseError::EncodingMismatch(encoding, span) => {
+            assert_eq!(encoding, "ISO-8859-1");
+            assert_eq!(&xml[span.range()], "ISO-8859-1");
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+    // without strict mode the byte order mark wins
+    assert!(xot.parse_bytes(&bytes).is_ok());
+}
+
+#[test]
+fn test_strict_utf16_declared_in_ascii() {
+    let mut xot = Xot::new();
+    let bytes = b"<?xml version='1.0' encoding='UTF-16'?><p/>";
+    let error = xot.parse_bytes_with_options(bytes, &strict()).unwrap_err();
+    assert!(matches!(error, ParseError::EncodingMismatch(encoding, _) if encoding == "UTF-16"));
+}
+
+#[test]
+fn test_strict_utf16_wrong_byte_order() {
+    let mut xot = Xot::new();
+    let bytes = [
+        vec![0xff, 0xfe],
+        utf16le("<?xml version='1.0' encoding='UTF-16BE'?><p/>"),
+    ]
+    .concat();
+    let error = xot.parse_bytes_with_options(&bytes, &strict()).unwrap_err();
+    assert!(matches!(error, ParseError::EncodingMismatch(encoding, _) if encoding == "UTF-16BE"));
+}
+
+#[test]
+fn test_strict_unknown_encoding() {
+    let mut xot = Xot::new();
+    let bytes = b"<?xml version='1.0' encoding='no-such-encoding'?><p/>";
+    let error = xot.parse_bytes_with_options(bytes, &strict()).unwrap_err();
+    let diagnostic = error.diagnostic(std::str::from_utf8(bytes).unwrap());
+    assert_eq!(
+        diagnostic.message,
+        "the encoding declaration `no-such-encoding` doesn't match the data"
+    );
+}
+
+#[test]
+fn test_strict_parse_reader() {
+    let mut xot = Xot::new();
+    let mut bytes = b"<doc>".to_vec();
+    bytes.extend("a".repeat(2000).as_bytes());
+    bytes.extend(b"\xff</doc>");
+    let error = xot
+        .parse_reader_with_options(&bytes[..], &strict())
+        .unwrap_err();
+    match error {
+        Error::Parse(ParseError::MalformedEncoding(_, span)) => assert_eq!(span.start, 2005),
+        error => panic!("unexpected error {:?}", error),
+    }
+    assert!(xot.parse_reader(&bytes[..]).is_ok());
+
+    let bytes = b"\xef\xbb\xbf<?xml version='1.0' encoding='ISO-8859-1'?><p/>";
+    let error = xot
+        .parse_reader_with_options(&bytes[..], &strict())
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::Parse(ParseError::EncodingMismatch(..))
+    ));
+}
@@ -0,0 +1,324 @@
+use std::io::Read;
+
+use xot::{Error, ParseError, ParseEvent, Span, Xot};
+
+fn events(xot: &mut Xot, xml: &str) -> Result<Vec<ParseEvent>, ParseError> {
+    xot.parse_events(xml).collect()
+}
+
+// A reader that returns at most `chunk_size` bytes at a time.
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.chunk_size.min(buf.len()).min(self.data.len());
+        buf[..size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        Ok(size)
+    }
+}
+
+fn reader_events(xot: &mut Xot, data: &[u8], chunk_size: usize) -> Result<Vec<ParseEvent>, Error> {
+    xot.parse_events_reader(ChunkedReader { data, chunk_size })
+        .collect()
+}
+
+#[test]
+fn test_events() {
+    let mut xot = Xot::new();
+    let events = events(
+        &mut xot,
+        r#"<?xml version="1.0"?><!--before--><doc a="1"><?pi data?>text<!--comment--><empty/></doc>"#,
+    )
+    .unwrap();
+    let doc = xot.name("doc").unwrap();
+    let a = xot.name("a").unwrap();
+    let empty = xot.name("empty").unwrap();
+    let pi = xot.name("pi").unwrap();
+    assert_eq!(
+        events,
+        vec![
+            ParseEvent::Comment {
+                text: "before".to_string(),
+                span: Span::new(25, 31),
+            },
+            ParseEvent::StartElement {
+                name: doc,
+                namespaces: vec![],
+                attributes: vec![(a, "1".to_string())],
+                span: Span::new(35, 38),
+            },
+            ParseEvent::ProcessingInstruction {
+                target: pi,
+                content: Some("data".to_string()),
+                span: Span::new(47, 49),
+            },
+            ParseEvent::Text {
+                text: "text".to_string(),
+                span: Span::new(56, 60),
+            },
+            ParseEvent::Comment {
+                text: "comment".to_string(),
+                span: Span::new(64, 71),
+            },
+            ParseEvent::StartElement {
+                name: empty,
+                namespaces: vec![],
+                attributes: vec![],
+                span: Span::new(75, 80),
+            },
+            ParseEvent::EndElement {
+                name: empty,
+                span: Span::new(80, 82),
+            },
+            ParseEvent::EndElement {
+                name: doc,
+                span: Span::new(82, 88),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_events_namespaces() {
+    let mut xot = Xot::new();
+    let events = events(
+        &mut xot,
+        r#"<doc xmlns="http://example.com" xmlns:p="http://example.com/p"><p:a p:b="x"/></doc>"#,
+    )
+    .unwrap();
+    let ns = xot.namespace("http://example.com").unwrap();
+    let ns_p = xot.namespace("http://example.com/p").unwrap();
+    let p = xot.prefix("p").unwrap();
+    let doc = xot.name_ns("doc", ns).unwrap();
+    let a = xot.name_ns("a", ns_p).unwrap();
+    let b = xot.name_ns("b", ns_p).unwrap();
+    assert_eq!(
+        events[0],
+        ParseEvent::StartElement {
+            name: doc,
+            namespaces: vec![(xot.empty_prefix(), ns), (p, ns_p)],
+            attributes: vec![],
+            span: Span::new(1, 4),
+        }
+    );
+    assert_eq!(
+        events[1],
+        ParseEvent::StartElement {
+            name: a,
+            namespaces: vec![],
+            attributes: vec![(b, "x".to_string())],
+            span: Span::new(64, 67),
+        }
+    );
+}
+
+#[test]
+fn test_events_text_consolidated() {
+    let mut xot = Xot::new();
+    let events = events(&mut xot, r#"<doc>a &amp; b<![CDATA[ <c> ]]>d</doc>"#).unwrap();
+    assert_eq!(
+        events[1],
+        ParseEvent::Text {
+            text: "a & b <c> d".to_string(),
+            span: Span::new(5, 32),
+        }
+    );
+    assert_eq!(events.len(), 3);
+}
+
+#[test]
+fn test_events_internal_subset() {
+    let mut xot = Xot::new();
+    let mut events = xot.parse_events(
+        r#"<!DOCTYPE doc [
+<!ENTITY greeting "hello">
+<!ATTLIST doc version CDATA "1.0">
+]>
+<doc>&greeting; world</doc>"#,
+    );
+    let start = events.next().unwrap().unwrap();
+    assert_eq!(events.dtd().unwrap().name(), "doc");
+    let text = events.next().unwrap().unwrap();
+    assert!(matches!(
+        events.next(),
+        Some(Ok(ParseEvent::EndElement { .. }))
+    ));
+    assert!(events.next().is_none());
+    drop(events);
+
+    let doc = xot.name("doc").unwrap();
+    let version = xot.name("version").unwrap();
+    assert_eq!(
+        start,
+        ParseEvent::StartElement {
+            name: doc,
+            namespaces: vec![],
+            attributes: vec![(version, "1.0".to_string())],
+            span: Span::new(82, 85),
+        }
+    );
+    assert_eq!(
+        text,
+        ParseEvent::Text {
+            text: "hello world".to_string(),
+            span: Span::new(86, 102),
+        }
+    );
+}
+
+#[test]
+fn test_events_unclosed() {
+    let mut xot = Xot::new();
+    let mut events = xot.parse_events("<doc><a></a>");
+    assert!(matches!(
+        events.next(),
+        Some(Ok(ParseEvent::StartElement { .. }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(ParseEvent::StartElement { .. }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(ParseEvent::EndElement { .. }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Err(ParseError::UnclosedTag(_)))
+    ));
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn test_events_errors_match_parse() {
+    for xml in [
+        "<doc/><doc/>",
+        "<doc/>text",
+        "",
+        "<doc></other>",
+        "<doc>&unknown;</doc>",
+        "<p:doc/>",
+        r#"<!DOCTYPE doc [<!ENTITY e "<a>">]><doc>&e;</doc>"#,
+    ] {
+        let mut xot = Xot::new();
+        let expected = xot.parse(xml).unwrap_err();
+        let error = xot.parse_events(xml).find_map(|event| event.err()).unwrap();
+        assert_eq!(format!("{:?}", error), format!("{:?}", expected), "{}", xml);
+    }
+}
+
+#[test]
+fn test_events_xot_accessible() {
+    let mut xot = Xot::new();
+    let mut events = xot.parse_events(r#"<doc xmlns:p="http://example.com"><p:a/></doc>"#);
+    events.next();
+    let Some(Ok(ParseEvent::StartElement { name, .. })) = events.next() else {
+        panic!("expected start element");
+    };
+    let (local_name, namespace) = events.xot().name_ns_str(name);
+    assert_eq!(local_name, "a");
+    assert_eq!(namespace, "http://example.com");
+}
+
+#[test]
+fn test_events_tree_untouched() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc><a/></doc>").unwrap();
+    for event in xot.parse_events("<other><b>text</b></other>") {
+        event.unwrap();
+    }
+    // stopping halfway cleans up too
+    let mut events = xot.parse_events("<other><b>text</b></other>");
+    events.next();
+    events.next();
+    drop(events);
+    assert_eq!(xot.to_string(root).unwrap(), "<doc><a/></doc>");
+}
+
+#[test]
+fn test_reader_events_match_events() {
+    for xml in [
+        r#"<?xml version="1.0"?><!--before--><doc a="1"><?pi data?>text<!--comment--><empty/></doc>"#,
+        r#"<doc xmlns="http://example.com" xmlns:p="http://example.com/p"><p:a p:b="x"/></doc>"#,
+        r#"<doc>a &amp; b<![CDATA[ <c> ]]>d</doc>"#,
+        "<!DOCTYPE doc [\n<!ENTITY greeting \"<b>hello</b>\">\n<!ATTLIST doc version CDATA \"1.0\">\n]>\n<doc>&greeting; world</doc>\n<!--after-->",
+        "<doc>\n  <a b='>'>caf\u{e9}</a>\n  <a/>\n</doc>",
+    ] {
+        let mut xot = Xot::new();
+        let expected = events(&mut xot, xml).unwrap();
+        for chunk_size in [1, 3, 1000] {
+            let events = reader_events(&mut xot, xml.as_bytes(), chunk_size).unwrap();
+            assert_eq!(events, expected, "{} in chunks of {}", xml, chunk_size);
+        }
+    }
+}
+
+#[test]
+fn test_reader_events_errors_match_parse() {
+    for xml in [
+        "<doc/><doc/>",
+        "<doc/>text",
+        "",
+        "<doc></other>",
+        "<doc><a></a>",
+        "<doc>&unknown;</doc>",
+        r#"<!DOCTYPE doc [<!ENTITY e "<a>">]><doc>&e;</doc>"#,
+    ] {
+        let mut xot = Xot::new();
+        let expected = xot.parse(xml).unwrap_err();
+        for chunk_size in [1, 1000] {
+            let error = xot
+                .parse_events_reader(ChunkedReader {
+                    data: xml.as_bytes(),
+                    chunk_size,
+                })
+                .find_map(|event| event.err())
+                .unwrap();
+            assert_eq!(
+                format!("{:?}", error),
+                format!("{:?}", Error::Parse(expected.clone())),
+                "{}",
+                xml
+            );
+        }
+    }
+}
+
+#[test]
+fn test_reader_events_encoding() {
+    let mut xot = Xot::new();
+    let data = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><doc>caf\xe9</doc>";
+    let events = reader_events(&mut xot, data, 4).unwrap();
+    assert!(matches!(&events[1], ParseEvent::Text { text, .. } if text == "caf\u{e9}"));
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("broken"))
+    }
+}
+
+#[test]
+fn test_reader_events_io_error() {
+    let mut xot = Xot::new();
+    let mut events = xot.parse_events_reader(FailingReader);
+    assert!(matches!(events.next(), Some(Err(Error::Io(message))) if message == "broken"));
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn test_reader_events_tree_untouched() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc><a/></doc>").unwrap();
+    reader_events(&mut xot, b"<other><b>text</b></other>", 2).unwrap();
+    let mut events = xot.parse_events_reader(&b"<other><b>text</b></other>"[..]);
+    events.next();
+    drop(events);
+    assert_eq!(xot.to_string(root).unwrap(), "<doc><a/></doc>");
+}
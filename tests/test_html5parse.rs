@@ -0,0 +1,141 @@
+#![cfg(feature = "html5")]
+
+use xot::Xot;
+
+const XHTML: &str = "http://www.w3.org/1999/xhtml";
+
+fn body(html: &str) -> String {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5(html);
+    let s = xot.html5().to_string(root).unwrap();
+    let start = s.find("<body>").unwrap() + "<body>".len();
+    let end = s.rfind("</body>").unwrap();
+    s[start..end].to_string()
+}
+
+#[test]
+fn test_implicit_tags() {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5("<title>T</title><p>a");
+    assert_eq!(
+        xot.html5().to_string(root).unwrap(),
+        format!(
+            "<!DOCTYPE html><html xmlns=\"{}\"><head><title>T</title></head><body><p>a</p></body></html>",
+            XHTML
+        )
+    );
+    assert_eq!(
+        body("<table><tr><td>1<td>2</table>"),
+        "<table><tbody><tr><td>1</td><td>2</td></tr></tbody></table>"
+    );
+    assert_eq!(
+        body("<ul><li>a<li>b</ul><p>c<div>d</div>"),
+        "<ul><li>a</li><li>b</li></ul><p>c</p><div>d</div>"
+    );
+}
+
+#[test]
+fn test_xhtml_namespace() {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5("<p>a</p>");
+    let xhtml = xot.add_namespace(XHTML);
+    let p = xot.add_name_ns("p", xhtml);
+    let doc_el = xot.document_element(root).unwrap();
+    let body = xot.last_child(doc_el).unwrap();
+    assert_eq!(xot.node_name(xot.first_child(body).unwrap()), Some(p));
+}
+
+#[test]
+fn test_void_elements() {
+    assert_eq!(
+        body("<p>a<br>b<img src=x></p>"),
+        "<p>a<br>b<img src=\"x\"></p>"
+    );
+    assert_eq!(body("<p>a</br>b"), "<p>a<br>b</p>");
+}
+
+#[test]
+fn test_adoption_agency() {
+    assert_eq!(
+        body("<p>a<b>b<i>c</b>d</i>e"),
+        "<p>a<b>b<i>c</i></b><i>d</i>e</p>"
+    );
+    assert_eq!(body("<b>1<p>2</b>3</p>"), "<b>1</b><p><b>2</b>3</p>");
+}
+
+#[test]
+fn test_character_references() {
+    assert_eq!(
+        body("&notin; &notit; &copy &amp; &#x41; &#128;"),
+        "\u{2209} \u{ac}it; \u{a9} &amp; A \u{20ac}"
+    );
+    assert_eq!(
+        body("<a title='&lt;&quot;'>x</a>"),
+        "<a title=\"<&quot;\">x</a>"
+    );
+}
+
+#[test]
+fn test_raw_text() {
+    assert_eq!(
+        body("<p>a</p><script>if (a < b && c) {}</script><textarea><b>&amp;</textarea>"),
+        "<p>a</p><script>if (a < b && c) {}</script><textarea>&lt;b>&amp;</textarea>"
+    );
+}
+
+#[test]
+fn test_foreign_elements() {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5(
+        "<svg viewbox='0 0 1 1'><a xlink:href='#a'><foreignobject><p>h</p></foreignobject></a></svg><math><mi>x</mi></math>",
+    );
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        format!(
+            concat!(
+                "<html xmlns=\"{}\"><head/><body>",
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 1 1\">",
+                "<a xmlns:xlink=\"http://www.w3.org/1999/xlink\" xlink:href=\"#a\">",
+                "<foreignObject><p xmlns=\"{}\">h</p></foreignObject></a></svg>",
+                "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mi>x</mi></math>",
+                "</body></html>"
+            ),
+            XHTML, XHTML
+        )
+    );
+}
+
+#[test]
+fn test_coercion() {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5("<div a:b=1 xmlns=x xmlns:c=y><!--a--b-->\u{c}</div>");
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        format!(
+            "<html xmlns=\"{}\"><head/><body><div aU00003Ab=\"1\"><!--a- -b--> </div></body></html>",
+            XHTML
+        )
+    );
+}
+
+#[test]
+fn test_template() {
+    assert_eq!(
+        body("<p>a</p><template><td>b</td></template>"),
+        "<p>a</p><template><td>b</td></template>"
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let mut xot = Xot::new();
+    let root = xot.parse_html5(
+        "<!DOCTYPE html><title>T</title><p class=a>1<b>2<i>3</b>4</i><table><td>5</table><svg><circle r=1 /></svg>",
+    );
+    let html = xot.html5().to_string(root).unwrap();
+    let again = xot.parse_html5(&html);
+    assert!(xot.deep_equal(root, again));
+    let xml = xot.to_string(root).unwrap();
+    let again = xot.parse(&xml).unwrap();
+    assert!(xot.deep_equal(root, again));
+}
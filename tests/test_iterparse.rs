@@ -0,0 +1,192 @@
+use std::io::Read;
+
+use xot::{Error, Node, Xot};
+
+fn records(count: usize) -> String {
+    let mut xml = "<records>\n".to_string();
+    for i in 0..count {
+        xml.push_str(&format!(
+            "  <record id=\"{}\"><name>Record {}</name></record>\n",
+            i, i
+        ));
+    }
+    xml.push_str("</records>\n");
+    xml
+}
+
+// A reader that returns at most `chunk_size` bytes at a time.
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.chunk_size.min(buf.len()).min(self.data.len());
+        buf[..size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        Ok(size)
+    }
+}
+
+#[test]
+fn test_iterparse_name() {
+    let xml = records(100);
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let id = xot.add_name("id");
+    let mut ids = Vec::new();
+    let document = xot
+        .iterparse(&xml, record, |xot, node| {
+            ids.push(xot.get_attribute(node, id).unwrap().to_string());
+        })
+        .unwrap();
+    let expected = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(ids, expected);
+    // the whitespace in front of each record goes with it
+    let records = xot.document_element(document).unwrap();
+    assert_eq!(xot.children(records).count(), 1);
+    assert_eq!(xot.text_content_str(records).unwrap(), "\n");
+}
+
+#[test]
+fn test_iterparse_predicate() {
+    let mut xot = Xot::new();
+    let mut names = Vec::new();
+    let document = xot
+        .iterparse(
+            r#"<doc><a keep="yes"/><b/><c keep="no">text</c></doc>"#,
+            |xot: &Xot, node: Node| {
+                let keep = xot.name("keep").unwrap();
+                xot.get_attribute(node, keep).is_some()
+            },
+            |xot, node| names.push(xot.local_name_str(xot.node_name(node).unwrap()).to_string()),
+        )
+        .unwrap();
+    assert_eq!(names, vec!["a", "c"]);
+    assert_eq!(xot.to_string(document).unwrap(), "<doc><b/></doc>");
+}
+
+#[test]
+fn test_iterparse_subtree_accessible() {
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let mut serialized = Vec::new();
+    xot.iterparse(
+        r#"<records xmlns:p="http://example.com"><record><p:a p:b="1">x &amp; y</p:a><!--c--><?pi?></record></records>"#,
+        record,
+        |xot, node| serialized.push(xot.to_string(node).unwrap()),
+    )
+    .unwrap();
+    assert_eq!(
+        serialized,
+        vec![
+            r#"<record xmlns:p="http://example.com"><p:a p:b="1">x &amp; y</p:a><!--c--><?pi?></record>"#
+        ]
+    );
+}
+
+#[test]
+fn test_iterparse_nested() {
+    let mut xot = Xot::new();
+    let item = xot.add_name("item");
+    let mut serialized = Vec::new();
+    let document = xot
+        .iterparse(
+            "<doc><item><item>inner</item>outer</item></doc>",
+            item,
+            |xot, node| serialized.push(xot.to_string(node).unwrap()),
+        )
+        .unwrap();
+    // the inner item is handed over and removed first
+    assert_eq!(serialized, vec!["<item>inner</item>", "<item>outer</item>"]);
+    assert_eq!(xot.to_string(document).unwrap(), "<doc/>");
+}
+
+#[test]
+fn test_iterparse_document_element() {
+    let mut xot = Xot::new();
+    let doc = xot.add_name("doc");
+    let mut serialized = Vec::new();
+    let document = xot
+        .iterparse("<doc><a/></doc>", doc, |xot, node| {
+            serialized.push(xot.to_string(node).unwrap())
+        })
+        .unwrap();
+    assert_eq!(serialized, vec!["<doc><a/></doc>"]);
+    // the document element remains
+    assert_eq!(xot.to_string(document).unwrap(), "<doc><a/></doc>");
+}
+
+#[test]
+fn test_iterparse_text_consolidated() {
+    let mut xot = Xot::new();
+    let b = xot.add_name("b");
+    let document = xot
+        .iterparse("<doc>a<b/>c<b/>d</doc>", b, |_, _| {})
+        .unwrap();
+    let doc = xot.document_element(document).unwrap();
+    assert_eq!(xot.children(doc).count(), 1);
+    assert_eq!(xot.text_content_str(doc), Some("acd"));
+}
+
+#[test]
+fn test_iterparse_errors_same_as_parse() {
+    for xml in [
+        "<doc><record/><record/>",
+        "<doc><record/></doc><doc/>",
+        "<doc><record/></doc>text",
+        "",
+        "<doc><record></other></doc>",
+        "<doc><record>&unknown;</record></doc>",
+        r#"<!DOCTYPE doc [<!ENTITY e "<a>">]><doc><record>&e;</record></doc>"#,
+    ] {
+        let mut xot = Xot::new();
+        let record = xot.add_name("record");
+        let expected = xot.parse(xml).unwrap_err();
+        let error = xot.iterparse(xml, record, |_, _| {}).unwrap_err();
+        assert_eq!(format!("{:?}", error), format!("{:?}", expected), "{}", xml);
+    }
+}
+
+#[test]
+fn test_iterparse_reader() {
+    let xml = records(100);
+    for chunk_size in [1, 7, 100, usize::MAX] {
+        let mut xot = Xot::new();
+        let record = xot.add_name("record");
+        let mut serialized = Vec::new();
+        let reader = ChunkedReader {
+            data: xml.as_bytes(),
+            chunk_size,
+        };
+        let document = xot
+            .iterparse_reader(reader, record, |xot, node| {
+                serialized.push(xot.to_string(node).unwrap())
+            })
+            .unwrap();
+        let expected = (0..100)
+            .map(|i| format!("<record id=\"{}\"><name>Record {}</name></record>", i, i))
+            .collect::<Vec<_>>();
+        assert_eq!(serialized, expected);
+        assert_eq!(xot.to_string(document).unwrap(), "<records>\n</records>");
+    }
+}
+
+#[test]
+fn test_iterparse_reader_error() {
+    let mut xml = records(10);
+    xml.push_str("<records/>");
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let expected = xot.parse_bytes(xml.as_bytes()).unwrap_err();
+    let mut count = 0;
+    let error = xot
+        .iterparse_reader(xml.as_bytes(), record, |_, _| count += 1)
+        .unwrap_err();
+    assert_eq!(count, 10);
+    assert_eq!(
+        format!("{:?}", error),
+        format!("{:?}", Error::Parse(expected))
+    );
+}
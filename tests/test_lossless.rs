@@ -0,0 +1,423 @@
+use rstest::rstest;
+
+use xot::{ParseOptions, Span, TextEdit, Xot};
+
+#[rstest]
+#[case("<doc/>")]
+#[case("<doc />\n")]
+#[case("<doc></doc>")]
+#[case("<doc ></doc >")]
+#[case("<?xml version=\"1.0\" encoding='UTF-8' ?>\r\n<doc/>\r\n")]
+#[case("<doc a='1'  b = \"2\"\n   c='&lt;&#x41;'/>")]
+#[case("<doc xmlns='urn:a' xmlns:p = \"urn:p\"><p:a p:b='x'/></doc>")]
+#[case("<doc>a &amp; b &#33; &gt;</doc>")]
+#[case("<doc>one<![CDATA[<two>]]>three<![CDATA[]]]]></doc>")]
+#[case("<doc>\r\n  <a>line\r\nline</a>\r\n</doc>")]
+#[case("<!--before--><?pi  data ?>\n<doc><!-- c --><?t?></doc>\n<!--after-->  ")]
+#[case("<!DOCTYPE doc [\n  <!ENTITY e \"plain\">\n  <!ATTLIST doc d CDATA 'default'>\n]>\n<doc>&e; text</doc>")]
+fn test_lossless_round_trip(#[case] xml: &str) {
+    let mut xot = Xot::new();
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        xml
+    );
+}
+
+#[test]
+fn test_lossless_element() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <a x = 'y'>text</a>\n</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot
+        .children(doc_el)
+        .find(|node| xot.is_element(*node))
+        .unwrap();
+    assert_eq!(
+        xot.to_string_lossless(a, &lexical_info).unwrap(),
+        "<a x = 'y'>text</a>"
+    );
+}
+
+#[test]
+fn test_lossless_changed_attributes() {
+    let mut xot = Xot::new();
+    let xml = "<doc  a = 'x'\n  b=\"y\"   c='z' />";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot.name("a").unwrap();
+    let b = xot.name("b").unwrap();
+    let d = xot.add_name("d");
+    xot.set_attribute(doc_el, a, "it's \"new\"");
+    xot.remove_attribute(doc_el, b);
+    xot.set_attribute(doc_el, d, "added");
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc  a = 'it&apos;s \"new\"'   c='z' d=\"added\" />"
+    );
+}
+
+#[test]
+fn test_lossless_changed_text() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <a>&lt;old&gt;</a>\n  <b>&lt;same&gt;</b>\n</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot
+        .children(doc_el)
+        .find(|node| xot.is_element(*node))
+        .unwrap();
+    let text = xot.first_child(a).unwrap();
+    xot.text_mut(text).unwrap().set("<new>");
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc>\n  <a>&lt;new&gt;</a>\n  <b>&lt;same&gt;</b>\n</doc>"
+    );
+}
+
+#[test]
+fn test_lossless_empty_elements() {
+    let mut xot = Xot::new();
+    let xml = "<doc><a /><b></b></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot
+        .children(doc_el)
+        .find(|node| xot.is_element(*node))
+        .unwrap();
+    let b = xot.next_sibling(a).unwrap();
+    xot.append_text(a, "text").unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc><a >text</a><b></b></doc>"
+    );
+    let text = xot.first_child(a).unwrap();
+    xot.remove(text).unwrap();
+    xot.append_text(b, "text").unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc><a /><b>text</b></doc>"
+    );
+}
+
+#[test]
+fn test_lossless_renamed_element() {
+    let mut xot = Xot::new();
+    let xml = "<doc><a  x='1'>text</a ></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot
+        .children(doc_el)
+        .find(|node| xot.is_element(*node))
+        .unwrap();
+    let b = xot.add_name("b");
+    xot.element_mut(a).unwrap().set_name(b);
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc><b  x='1'>text</b></doc>"
+    );
+}
+
+#[test]
+fn test_lossless_added_nodes() {
+    let mut xot = Xot::new();
+    let xml = "<doc xmlns:p='urn:p'>\n  <p:a/>\n</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let ns = xot.namespace("urn:p").unwrap();
+    let name = xot.add_name_ns("b", ns);
+    let b = xot.new_element(name);
+    xot.append_text(b, "a & b").unwrap();
+    xot.append(doc_el, b).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc xmlns:p='urn:p'>\n  <p:a/>\n<p:b>a &amp; b</p:b></doc>"
+    );
+}
+
+#[test]
+fn test_lossless_prolog_removed_comment() {
+    let mut xot = Xot::new();
+    let xml = "<?xml version=\"1.0\"?>\n<!--c-->\n<doc/>\n";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let comment = xot.first_child(document).unwrap();
+    xot.remove(comment).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<?xml version=\"1.0\"?>\n\n<doc/>\n"
+    );
+}
+
+#[test]
+fn test_lossless_entity_with_markup() {
+    let mut xot = Xot::new();
+    let xml = "<!DOCTYPE doc [<!ENTITY e \"<b>x</b>\">]><doc>a &e; b<c/></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        xml
+    );
+    assert_eq!(xot.text_edits(&lexical_info).unwrap(), vec![]);
+
+    // once a node from the entity changes, the nodes from the entity are
+    // serialized as usual
+    let doc_el = xot.document_element(document).unwrap();
+    let b = xot.children(doc_el).nth(1).unwrap();
+    xot.append_text(b, "y").unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<!DOCTYPE doc [<!ENTITY e \"<b>x</b>\">]><doc>a <b>xy</b> b<c/></doc>"
+    );
+}
+
+#[rstest]
+#[case::text_around("<!DOCTYPE doc [<!ENTITY e '<x>y</x>'>]><doc>t&e;u</doc>")]
+#[case::text_consolidated("<!DOCTYPE doc [<!ENTITY e 'a<x/>b'>]><doc>t&e;u</doc>")]
+#[case::cdata("<!DOCTYPE doc [<!ENTITY e '<x/>'>]><doc><![CDATA[<t>]]>&e;<![CDATA[<u>]]></doc>")]
+#[case::adjacent("<!DOCTYPE doc [<!ENTITY e '<x/>'>]><doc>&e;&e; &amp; &e;</doc>")]
+#[case::nested(
+    "<!DOCTYPE doc [<!ENTITY f '<y/>'><!ENTITY e '<x>&f;</x>&f;'>]><doc><a>&e;</a><!--c-->&e;</doc>"
+)]
+#[case::comment_and_pi("<!DOCTYPE doc [<!ENTITY e '<!--c--><?pi?>'>]><doc>t&e;u<v/></doc>")]
+#[case::whitespace_around("<!DOCTYPE d [<!ENTITY e '<b>x</b>'>]><d>\n  <e/>  &e; tail\n</d>")]
+fn test_lossless_entity_references_unchanged(#[case] xml: &str) {
+    let mut xot = Xot::new();
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        xml
+    );
+    assert_eq!(xot.text_edits(&lexical_info).unwrap(), vec![]);
+}
+
+#[test]
+fn test_lossless_entity_text_changed() {
+    let mut xot = Xot::new();
+    let xml = "<!DOCTYPE doc [<!ENTITY e 'a<x/>b'>]><doc a='1'>t&e;u</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+
+    // changes outside of the nodes from the entity leave its reference
+    let a = xot.name("a").unwrap();
+    xot.set_attribute(doc_el, a, "2");
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<!DOCTYPE doc [<!ENTITY e 'a<x/>b'>]><doc a='2'>t&e;u</doc>"
+    );
+
+    // the text consolidated with text from the entity is part of it
+    let last = xot.last_child(doc_el).unwrap();
+    xot.text_mut(last).unwrap().set("bv");
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<!DOCTYPE doc [<!ENTITY e 'a<x/>b'>]><doc a='2'>ta<x/>bv</doc>"
+    );
+}
+
+#[test]
+fn test_text_edits_entity_changed() {
+    let mut xot = Xot::new();
+    let xml = "<!DOCTYPE d [<!ENTITY e '<b>x</b>'>]><d>\n  <e/>  &e; tail\n</d>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let d = xot.document_element(document).unwrap();
+    let b = xot.children(d).nth(3).unwrap();
+    xot.append_text(b, "y").unwrap();
+    // only the text from the end of `<e/>` to the end tag is replaced
+    assert_eq!(
+        xot.text_edits(&lexical_info).unwrap(),
+        vec![TextEdit {
+            span: Span::new(47, 58),
+            replacement: "  <b>xy</b> tail\n".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_lossless_entity_node_moved() {
+    let mut xot = Xot::new();
+    let xml = "<!DOCTYPE doc [<!ENTITY e '<x/>'>]><doc>t&e;u<v/></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let x = xot.children(doc_el).nth(1).unwrap();
+    let v = xot.last_child(doc_el).unwrap();
+    xot.append(v, x).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<!DOCTYPE doc [<!ENTITY e '<x/>'>]><doc>tu<v><x/></v></doc>"
+    );
+}
+
+#[test]
+fn test_lossless_entity_node_on_its_own() {
+    let mut xot = Xot::new();
+    let xml = "<!DOCTYPE doc [<!ENTITY e '<x>y</x>'>]><doc>t&e;u</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let x = xot.children(doc_el).nth(1).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(x, &lexical_info).unwrap(),
+        "<x>y</x>"
+    );
+    let t = xot.first_child(doc_el).unwrap();
+    assert_eq!(xot.to_string_lossless(t, &lexical_info).unwrap(), "t");
+}
+
+#[test]
+fn test_lossless_prefix_changed() {
+    let mut xot = Xot::new();
+    let xml = "<doc xmlns:p='urn:p'><p:a/></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let p = xot.prefix("p").unwrap();
+    let q = xot.add_prefix("q");
+    let ns = xot.namespace("urn:p").unwrap();
+    xot.namespaces_mut(doc_el).remove(p);
+    xot.namespaces_mut(doc_el).insert(q, ns);
+    // the element name is written with the new prefix
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        "<doc xmlns:q=\"urn:p\"><q:a/></doc>"
+    );
+}
+
+#[test]
+fn test_lossless_fixture() {
+    let xml = std::fs::read_to_string("tests/fixtures/us-ascii.xml").unwrap();
+    let mut xot = Xot::new();
+    let (document, lexical_info) = xot.parse_lossless(&xml).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        xml
+    );
+}
+
+fn apply(source: &str, edits: &[TextEdit]) -> String {
+    let mut result = source.to_string();
+    for edit in edits.iter().rev() {
+        result.replace_range(edit.span.range(), &edit.replacement);
+    }
+    result
+}
+
+fn edit(start: usize, end: usize, replacement: &str) -> TextEdit {
+    TextEdit {
+        span: Span::new(start, end),
+        replacement: replacement.to_string(),
+    }
+}
+
+#[test]
+fn test_text_edits_unchanged() {
+    let mut xot = Xot::new();
+    let xml = "<?xml version='1.0'?>\n<doc a = 'x'><![CDATA[<>]]>&#65;<b></b></doc>\n";
+    let (_, lexical_info) = xot.parse_lossless(xml).unwrap();
+    assert_eq!(xot.text_edits(&lexical_info).unwrap(), vec![]);
+}
+
+#[test]
+fn test_text_edits() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <!-- keep -->\n  <a x = 'old' y='1'>text</a>\n  <b/>\n</doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let elements = xot
+        .children(doc_el)
+        .filter(|node| xot.is_element(*node))
+        .collect::<Vec<_>>();
+    let (a, b) = (elements[0], elements[1]);
+    let x = xot.name("x").unwrap();
+    let y = xot.name("y").unwrap();
+    xot.set_attribute(a, x, "new");
+    xot.remove_attribute(a, y);
+    let text = xot.first_child(a).unwrap();
+    xot.text_mut(text).unwrap().set("changed");
+    xot.append_text(b, "added").unwrap();
+
+    let edits = xot.text_edits(&lexical_info).unwrap();
+    assert_eq!(
+        edits,
+        vec![
+            edit(32, 35, "new"),
+            edit(36, 42, ""),
+            edit(43, 47, "changed"),
+            edit(56, 58, ">added</b>"),
+        ]
+    );
+    assert_eq!(
+        apply(xml, &edits),
+        xot.to_string_lossless(document, &lexical_info).unwrap()
+    );
+}
+
+#[test]
+fn test_text_edits_remove_and_append() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <a/>\n  <b/>\n</doc>\n";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot
+        .children(doc_el)
+        .find(|node| xot.is_element(*node))
+        .unwrap();
+    let space = xot.next_sibling(a).unwrap();
+    xot.remove(space).unwrap();
+    xot.remove(a).unwrap();
+    let c = xot.add_name("c");
+    let c = xot.new_element(c);
+    xot.append(doc_el, c).unwrap();
+
+    let edits = xot.text_edits(&lexical_info).unwrap();
+    assert_eq!(edits, vec![edit(8, 15, ""), edit(20, 20, "<c/>")]);
+    assert_eq!(apply(xml, &edits), "<doc>\n  <b/>\n<c/></doc>\n");
+}
+
+#[test]
+fn test_text_edits_moved_node() {
+    let mut xot = Xot::new();
+    let xml = "<doc><a/><b/><c/></doc>";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let c = xot.last_child(doc_el).unwrap();
+    xot.prepend(doc_el, c).unwrap();
+
+    let edits = xot.text_edits(&lexical_info).unwrap();
+    assert_eq!(apply(xml, &edits), "<doc><c/><a/><b/></doc>");
+    assert_eq!(
+        apply(xml, &edits),
+        xot.to_string_lossless(document, &lexical_info).unwrap()
+    );
+}
+
+#[test]
+fn test_text_edits_prolog() {
+    let mut xot = Xot::new();
+    let xml = "<?xml version='1.0'?>\n<!--c-->\n<doc/>\n<!--d-->";
+    let (document, lexical_info) = xot.parse_lossless(xml).unwrap();
+    let comment = xot.last_child(document).unwrap();
+    xot.remove(comment).unwrap();
+    let edits = xot.text_edits(&lexical_info).unwrap();
+    assert_eq!(edits, vec![edit(38, 46, "")]);
+}
+
+#[test]
+fn test_lossless_preserved_cdata() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+    let xml = "<doc>a <![CDATA[<b>]]> c</doc>";
+    let (document, lexical_info) = xot.parse_lossless_with_options(xml, &options).unwrap();
+    assert_eq!(
+        xot.to_string_lossless(document, &lexical_info).unwrap(),
+        xml
+    );
+    let doc_el = xot.document_element(document).unwrap();
+    let cdata = xot.children(doc_el).nth(1).unwrap();
+    xot.text_mut(cdata).unwrap().set("<d>");
+    assert_eq!(
+        xot.text_edits(&lexical_info).unwrap(),
+        vec![edit(7, 22, "<![CDATA[<d>]]>")]
+    );
+}
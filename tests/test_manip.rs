@@ -1,4 +1,4 @@
-use xot::{Error, Value, Xot};
+use xot::{Error, ParseOptions, Value, Xot};
 
 #[test]
 fn test_manipulate_text() {
@@ -1010,3 +1010,31 @@ fn test_prepend_text_with_attributes() -> Result<(), Error> {
     assert_eq!(xot.children(document_element).count(), 1);
     Ok(())
 }
+
+#[test]
+fn test_cdata_text_consolidation() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+    let doc = xot.parse_with_options(r#"<doc>a<![CDATA[b]]><x/>c</doc>"#, &options)?;
+    let doc_el = xot.document_element(doc)?;
+    let x = xot.children(doc_el).nth(2).unwrap();
+    // removing x makes the CDATA text adjacent to text, which isn't
+    // consolidated
+    xot.remove(x)?;
+    assert_eq!(xot.children(doc_el).count(), 3);
+    assert_eq!(xot.to_string(doc)?, r#"<doc>a<![CDATA[b]]>c</doc>"#);
+    // adding normal text consolidates it with normal text
+    xot.append_text(doc_el, "d")?;
+    assert_eq!(xot.children(doc_el).count(), 3);
+    // adding CDATA text consolidates it with CDATA text
+    let cdata = xot.new_text("e");
+    xot.text_mut(cdata).unwrap().set_cdata(true);
+    let b = xot.children(doc_el).nth(1).unwrap();
+    xot.insert_after(b, cdata)?;
+    assert_eq!(xot.children(doc_el).count(), 3);
+    assert_eq!(xot.to_string(doc)?, r#"<doc>a<![CDATA[be]]>cd</doc>"#);
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use xot::{output, Error, ParseOptions, Xot};
+
+fn parameters(encoding: &str) -> output::xml::Parameters {
+    output::xml::Parameters {
+        declaration: Some(output::xml::Declaration {
+            encoding: Some(encoding.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn write(xml: &str, encoding: &str) -> Result<Vec<u8>, Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(xml).unwrap();
+    let mut bytes = Vec::new();
+    xot.serialize_xml_write(parameters(encoding), root, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn declaration(encoding: &str) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"{}\"?>\n", encoding)
+}
+
+#[test]
+fn test_iso_8859_1() {
+    let bytes = write(
+        "<p a=\"\u{e9}\u{3b1}\">caf\u{e9} \u{20ac}</p>",
+        "ISO-8859-1",
+    )
+    .unwrap();
+    let mut expected = declaration("ISO-8859-1").into_bytes();
+    expected.extend(b"<p a=\"\xe9&#x3B1;\">caf\xe9 &#x20AC;</p>");
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_windows_1252() {
+    let bytes = write("<p>\u{20ac} \u{1f600}</p>", "windows-1252").unwrap();
+    let mut expected = declaration("windows-1252").into_bytes();
+    expected.extend(b"<p>\x80 &#x1F600;</p>");
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_shift_jis() {
+    let bytes = write("<p>\u{65e5}\u{672c}\u{e9}</p>", "Shift_JIS").unwrap();
+    let mut expected = declaration("Shift_JIS").into_bytes();
+    expected.extend(b"<p>\x93\xfa\x96\x7b&#xE9;</p>");
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_utf16() {
+    let bytes = write("<p>\u{e9}\u{1f600}</p>", "UTF-16").unwrap();
+    let text = declaration("UTF-16") + "<p>\u{e9}\u{1f600}</p>";
+    let mut expected = vec![0xff, 0xfe];
+    expected.extend(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+    assert_eq!(bytes, expected);
+
+    let bytes = write("<p/>", "UTF-16BE").unwrap();
+    let text = declaration("UTF-16BE") + "<p/>";
+    let mut expected = vec![0xfe, 0xff];
+    expected.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_round_trip() {
+    let xml = "<doc a=\"\u{3b1}\">\u{e9}\u{3b2}<!--\u{e9}--></doc>";
+    for encoding in ["ISO-8859-1", "windows-1252", "UTF-16", "UTF-16BE"] {
+        let bytes = write(xml, encoding).unwrap();
+        let mut xot = Xot::new();
+        let root = xot.parse_bytes(&bytes).unwrap();
+        assert_eq!(xot.to_string(root).unwrap(), xml, "{}", encoding);
+    }
+}
+
+#[test]
+fn test_cdata() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+    let root = xot
+        .parse_with_options("<p><![CDATA[a\u{3b1}b]]></p>", &options)
+        .unwrap();
+    let mut bytes = Vec::new();
+    xot.serialize_xml_write(parameters("ISO-8859-1"), root, &mut bytes)
+        .unwrap();
+    assert!(bytes.ends_with(b"<p><![CDATA[a]]>&#x3B1;<![CDATA[b]]></p>"));
+}
+
+#[test]
+fn test_unrepresentable_name() {
+    let error = write("<\u{3b1}/>", "ISO-8859-1").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::UnrepresentableCharacter('\u{3b1}', encoding) if encoding == "windows-1252"
+    ));
+    let error = write("<p \u{3b1}=\"1\"/>", "ISO-8859-1").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::UnrepresentableCharacter('\u{3b1}', _)
+    ));
+    let error = write("<p xmlns:\u{3b1}=\"urn:\u{3b1}\"/>", "ISO-8859-1").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::UnrepresentableCharacter('\u{3b1}', _)
+    ));
+}
+
+#[test]
+fn test_unrepresentable_comment_and_processing_instruction() {
+    let error = write("<p><!--\u{3b1}--></p>", "US-ASCII").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::UnrepresentableCharacter('\u{3b1}', _)
+    ));
+    let error = write("<p><?pi \u{3b1}?></p>", "US-ASCII").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::UnrepresentableCharacter('\u{3b1}', _)
+    ));
+}
+
+#[test]
+fn test_namespace_uri() {
+    let bytes = write("<p xmlns=\"urn:\u{3b1}\"/>", "US-ASCII").unwrap();
+    assert!(bytes.ends_with(b"<p xmlns=\"urn:&#x3B1;\"/>"));
+}
+
+#[test]
+fn test_unsupported_encoding() {
+    let error = write("<p/>", "no-such-encoding").unwrap_err();
+    assert!(
+        matches!(error, Error::UnsupportedEncoding(encoding) if encoding == "no-such-encoding")
+    );
+}
+
+#[test]
+fn test_string_stays_utf8() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<p>\u{e9}\u{3b1}</p>").unwrap();
+    let xml = xot
+        .serialize_xml_string(parameters("ISO-8859-1"), root)
+        .unwrap();
+    assert_eq!(xml, declaration("ISO-8859-1") + "<p>\u{e9}&#x3B1;</p>");
+    let xml = xot
+        .serialize_xml_string(parameters("UTF-16"), root)
+        .unwrap();
+    assert_eq!(xml, declaration("UTF-16") + "<p>\u{e9}\u{3b1}</p>");
+}
+
+#[test]
+fn test_pretty() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc><p>\u{3b1}</p></doc>").unwrap();
+    let parameters = output::xml::Parameters {
+        indentation: Some(Default::default()),
+        ..parameters("ISO-8859-1")
+    };
+    let mut bytes = Vec::new();
+    xot.serialize_xml_write(parameters, root, &mut bytes)
+        .unwrap();
+    assert!(bytes.ends_with(b"<doc>\n  <p>&#x3B1;</p>\n</doc>\n"));
+}
@@ -29,7 +29,7 @@ fn test_unknown_prefix() {
 
 #[test]
 fn test_unsupported_version() {
-    let xml = r#"<?xml version="1.1"?><doc/></doc>"#;
+    let xml = r#"<?xml version="1.2"?><doc/></doc>"#;
     let mut xot = Xot::new();
     let err = xot.parse(xml).unwrap_err();
     assert!(matches!(err, xot::ParseError::UnsupportedVersion { .. }));
@@ -77,33 +77,6 @@ fn test_duplicate_attribute_with_prefix() {
     assert_eq!(err.span(), (42..45).into());
 }
 
-#[test]
-fn test_invalid_dtd() {
-    let xml = r#"<!DOCTYPE note><note></note>"#;
-    let mut xot = Xot::new();
-    let err = xot.parse(xml).unwrap_err();
-    assert!(matches!(err, xot::ParseError::DtdUnsupported { .. }));
-    assert_eq!(err.span(), (0..15).into());
-}
-
-#[test]
-fn test_dtd_unsupported() {
-    let xml = r#"<!DOCTYPE note SYSTEM "Note.dtd"><note></note>"#;
-    let mut xot = Xot::new();
-    let err = xot.parse(xml).unwrap_err();
-    assert!(matches!(err, xot::ParseError::DtdUnsupported { .. }));
-    assert_eq!(err.span(), (0..33).into());
-}
-
-#[test]
-fn test_dtd_unsupported2() {
-    let xml = r#"<?xml version="1.0"?><!DOCTYPE note SYSTEM "Note.dtd"><note></note>"#;
-    let mut xot = Xot::new();
-    let err = xot.parse(xml).unwrap_err();
-    assert!(matches!(err, xot::ParseError::DtdUnsupported { .. }));
-    assert_eq!(err.span(), (21..54).into());
-}
-
 #[test]
 fn test_unclosed_tag() {
     let xml = r#"<doc>"#;
@@ -0,0 +1,232 @@
+use xot::{ParseError, Span, SpanInfoKey, Xot};
+
+fn lenient(xml: &str) -> (String, Vec<ParseError>) {
+    let mut xot = Xot::new();
+    let (document, _, errors) = xot.parse_lenient(xml);
+    (xot.to_string(document).unwrap(), errors)
+}
+
+#[test]
+fn test_lenient_well_formed() {
+    for xml in [
+        "<doc/>",
+        r#"<?xml version="1.0"?><!--before--><doc a="1"><?pi data?>text<!--comment--><empty/></doc><!--after-->"#,
+        r#"<doc xmlns="http://example.com" xmlns:p="http://example.com/p"><p:a p:b="x"/></doc>"#,
+        "<!DOCTYPE doc [<!ENTITY e \"<b>entity</b>\">]><doc>&e; &amp; &#65;</doc>",
+    ] {
+        let mut xot = Xot::new();
+        let (expected, expected_span_info) = xot.parse_with_span_info(xml).unwrap();
+        let (document, span_info, errors) = xot.parse_lenient(xml);
+        assert!(errors.is_empty(), "{}: {:?}", xml, errors);
+        assert_eq!(
+            xot.to_string(document).unwrap(),
+            xot.to_string(expected).unwrap()
+        );
+        let doc_el = xot.document_element(document).unwrap();
+        let expected_doc_el = xot.document_element(expected).unwrap();
+        assert_eq!(
+            span_info.get(SpanInfoKey::ElementEnd(doc_el)),
+            expected_span_info.get(SpanInfoKey::ElementEnd(expected_doc_el))
+        );
+    }
+}
+
+#[test]
+fn test_lenient_unclosed_at_end() {
+    let mut xot = Xot::new();
+    let (document, span_info, errors) = xot.parse_lenient("<doc><p>text");
+    assert_eq!(xot.to_string(document).unwrap(), "<doc><p>text</p></doc>");
+    assert_eq!(
+        format!("{:?}", errors),
+        format!(
+            "{:?}",
+            vec![
+                ParseError::UnclosedTag(Span::new(1, 4)),
+                ParseError::UnclosedTag(Span::new(6, 7)),
+            ]
+        )
+    );
+    let doc_el = xot.document_element(document).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::ElementEnd(doc_el)),
+        Some(&Span::new(12, 12))
+    );
+}
+
+#[test]
+fn test_lenient_unclosed_inside() {
+    let mut xot = Xot::new();
+    let (document, span_info, errors) = xot.parse_lenient("<doc><a><b>text</a><c/></doc>");
+    assert_eq!(
+        xot.to_string(document).unwrap(),
+        "<doc><a><b>text</b></a><c/></doc>"
+    );
+    assert_eq!(
+        format!("{:?}", errors),
+        format!("{:?}", vec![ParseError::UnclosedTag(Span::new(9, 10))])
+    );
+    // the unclosed element ends where its parent is closed
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot.first_child(doc_el).unwrap();
+    let b = xot.first_child(a).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::ElementEnd(b)),
+        Some(&Span::new(15, 15))
+    );
+    assert_eq!(
+        span_info.get(SpanInfoKey::ElementEnd(a)),
+        Some(&Span::new(15, 19))
+    );
+}
+
+#[test]
+fn test_lenient_close_tag_skipped() {
+    let (xml, errors) = lenient("<doc><a>text</b></a><c/></doc>");
+    assert_eq!(xml, "<doc><a>text</a><c/></doc>");
+    assert_eq!(
+        format!("{:?}", errors),
+        format!(
+            "{:?}",
+            vec![ParseError::InvalidCloseTag(
+                "".to_string(),
+                "b".to_string(),
+                Span::new(14, 15)
+            )]
+        )
+    );
+}
+
+#[test]
+fn test_lenient_unknown_prefix() {
+    let mut xot = Xot::new();
+    let (document, _, errors) = xot.parse_lenient(r#"<doc><p:a p:b="1" c="2">text</p:a></doc>"#);
+    assert_eq!(
+        xot.to_string(document).unwrap(),
+        r#"<doc><a b="1" c="2">text</a></doc>"#
+    );
+    assert_eq!(errors.len(), 3);
+    assert!(errors
+        .iter()
+        .all(|error| matches!(error, ParseError::UnknownPrefix(prefix, _) if prefix == "p")));
+    let doc_el = xot.document_element(document).unwrap();
+    let a = xot.first_child(doc_el).unwrap();
+    assert_eq!(xot.node_name(a), xot.name("a"));
+}
+
+#[test]
+fn test_lenient_unknown_entities() {
+    let mut xot = Xot::new();
+    let (document, _, errors) = xot.parse_lenient(r#"<doc a="x &foo; y">a &bar; b &#xZZ; c</doc>"#);
+    let doc_el = xot.document_element(document).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("a &bar; b &#xZZ; c"));
+    let a = xot.name("a").unwrap();
+    assert_eq!(xot.get_attribute(doc_el, a), Some("x &foo; y"));
+    assert_eq!(
+        format!("{:?}", errors),
+        format!(
+            "{:?}",
+            vec![
+                ParseError::InvalidEntity("foo".to_string(), Span::new(10, 15)),
+                ParseError::InvalidEntity("bar".to_string(), Span::new(21, 26)),
+                ParseError::InvalidEntity("xZZ".to_string(), Span::new(29, 35)),
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_lenient_unclosed_entity_reference() {
+    let (xml, errors) = lenient("<doc>a &amp b</doc>");
+    assert_eq!(xml, "<doc>a &amp;amp b</doc>");
+    assert!(matches!(&errors[..], [ParseError::UnclosedEntity(_, 7)]));
+}
+
+#[test]
+fn test_lenient_duplicate_attribute() {
+    let (xml, errors) = lenient(r#"<doc a="1" a="2" b="3"/>"#);
+    assert_eq!(xml, r#"<doc a="1" b="3"/>"#);
+    assert!(matches!(
+        &errors[..],
+        [ParseError::DuplicateAttribute(name, _)] if name == "a"
+    ));
+}
+
+#[test]
+fn test_lenient_continues_after_malformed_markup() {
+    let (xml, errors) = lenient("<doc><a b=c/><d>text</d>< <e/></doc>");
+    assert_eq!(xml, "<doc><d>text</d><e/></doc>");
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .all(|error| matches!(error, ParseError::XmlParser(..))));
+    assert_eq!(errors[0].span(), Span::new(7, 7));
+    assert_eq!(errors[1].span(), Span::new(24, 24));
+}
+
+#[test]
+fn test_lenient_top_level() {
+    let (xml, errors) = lenient("<doc/>\n<other/>");
+    assert_eq!(xml, "<doc/><other/>");
+    assert_eq!(
+        format!("{:?}", errors),
+        format!(
+            "{:?}",
+            vec![ParseError::MultipleElementsAtTopLevel(Span::new(8, 13))]
+        )
+    );
+
+    let (xml, errors) = lenient("<doc/>text<other/>");
+    assert_eq!(xml, "<doc/>text<other/>");
+    assert_eq!(
+        format!("{:?}", errors),
+        format!(
+            "{:?}",
+            vec![
+                ParseError::TextAtTopLevel(Span::new(6, 10)),
+                ParseError::MultipleElementsAtTopLevel(Span::new(11, 16)),
+            ]
+        )
+    );
+
+    let (xml, errors) = lenient("<!--only a comment-->");
+    assert_eq!(xml, "<!--only a comment-->");
+    assert!(matches!(&errors[..], [ParseError::NoElementAtTopLevel(21)]));
+}
+
+#[test]
+fn test_lenient_unbalanced_entity() {
+    let (xml, errors) = lenient(r#"<!DOCTYPE doc [<!ENTITY e "<a><b>">]><doc>&e;text</doc>"#);
+    assert_eq!(xml, "<doc><a><b/></a>text</doc>");
+    assert_eq!(
+        format!("{:?}", errors),
+        format!("{:?}", vec![ParseError::UnclosedTag(Span::new(42, 45))])
+    );
+}
+
+#[test]
+fn test_lenient_includes_strict_error() {
+    for xml in [
+        "<doc>",
+        "<doc></other>",
+        "",
+        "<doc>&unknown;</doc>",
+        "<p:doc/>",
+        r#"<doc a="1" a="2"/>"#,
+        "<doc><a b=c/></doc>",
+    ] {
+        let mut xot = Xot::new();
+        let expected = xot.parse(xml).unwrap_err();
+        let (_, _, errors) = xot.parse_lenient(xml);
+        let errors = errors
+            .iter()
+            .map(|error| format!("{:?}", error))
+            .collect::<Vec<_>>();
+        assert!(
+            errors.contains(&format!("{:?}", expected)),
+            "{}: {:?} not in {:?}",
+            xml,
+            expected,
+            errors
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use xot::{ParseError, ParseLimits, ParseOptions, Xot};
+
+fn parse(xml: &str, limits: ParseLimits) -> Result<String, ParseError> {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        limits,
+        ..Default::default()
+    };
+    let root = xot.parse_with_options(xml, &options)?;
+    Ok(xot.to_string(root).unwrap())
+}
+
+// The source text of the span of a limit error.
+fn error_text(xml: &str, limits: ParseLimits) -> (ParseError, &str) {
+    let error = parse(xml, limits).unwrap_err();
+    let span = error.span();
+    (error, &xml[span.range()])
+}
+
+#[test]
+fn test_no_limits() {
+    let xml = "<a><b><c x='1' y='2'>text</c></b></a>";
+    assert_eq!(
+        parse(xml, ParseLimits::default()).unwrap(),
+        xml.replace('\'', "\"")
+    );
+}
+
+#[test]
+fn test_max_depth() {
+    let limits = ParseLimits {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    assert!(parse("<a><b/><b><!--c--></b></a>", limits).is_ok());
+    let (error, text) = error_text("<a><b><p:c xmlns:p='p'/></b></a>", limits);
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+    assert_eq!(text, "p:c");
+}
+
+#[test]
+fn test_max_depth_entity() {
+    let limits = ParseLimits {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    let xml = r#"<!DOCTYPE a [<!ENTITY e "<b><c/></b>">]><a>&e;</a>"#;
+    let (error, text) = error_text(xml, limits);
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+    assert_eq!(text, "&e;");
+}
+
+#[test]
+fn test_max_attributes() {
+    let limits = ParseLimits {
+        max_attributes: Some(2),
+        ..Default::default()
+    };
+    assert!(parse("<a x='1' xmlns:p='p'><b x='1' y='2'/></a>", limits).is_ok());
+    let (error, text) = error_text("<a x='1' y='2' z='3'/>", limits);
+    assert!(matches!(error, ParseError::AttributeLimit(_)));
+    assert_eq!(text, "z");
+    let (error, text) = error_text("<a x='1' xmlns='n' xmlns:p='p'/>", limits);
+    assert!(matches!(error, ParseError::AttributeLimit(_)));
+    assert_eq!(text, "xmlns:p");
+}
+
+#[test]
+fn test_max_attributes_defaulted() {
+    let limits = ParseLimits {
+        max_attributes: Some(2),
+        ..Default::default()
+    };
+    let xml = "<!DOCTYPE a [<!ATTLIST a y CDATA 'y' z CDATA 'z'>]><a x='1'/>";
+    let (error, text) = error_text(xml, limits);
+    assert!(matches!(error, ParseError::AttributeLimit(_)));
+    assert_eq!(text, "a");
+}
+
+#[test]
+fn test_max_name_length() {
+    let limits = ParseLimits {
+        max_name_length: Some(5),
+        ..Default::default()
+    };
+    assert!(parse("<p:abc xmlns:p='p' p:xyz='1'><?abcde?></p:abc>", limits).is_ok());
+    let (error, text) = error_text("<doc><longer/></doc>", limits);
+    assert!(matches!(error, ParseError::NameLengthLimit(_)));
+    assert_eq!(text, "longer");
+    let (error, text) = error_text("<doc p:abcd='1' xmlns:p='p'/>", limits);
+    assert!(matches!(error, ParseError::NameLengthLimit(_)));
+    assert_eq!(text, "p:abcd");
+    let (error, text) = error_text("<doc xmlns:prefix='p'/>", limits);
+    assert!(matches!(error, ParseError::NameLengthLimit(_)));
+    assert_eq!(text, "prefix");
+    let (error, text) = error_text("<doc><?target?></doc>", limits);
+    assert!(matches!(error, ParseError::NameLengthLimit(_)));
+    assert_eq!(text, "target");
+}
+
+#[test]
+fn test_max_text_size() {
+    let limits = ParseLimits {
+        max_text_size: Some(10),
+        ..Default::default()
+    };
+    assert!(parse("<a x='12345'>12345</a>", limits).is_ok());
+    let (error, text) = error_text("<a x='12345'>12345<b>6</b></a>", limits);
+    assert!(matches!(error, ParseError::TextSizeLimit(_)));
+    assert_eq!(text, "6");
+    let (error, text) = error_text("<a><!--12345678901--></a>", limits);
+    assert!(matches!(error, ParseError::TextSizeLimit(_)));
+    assert_eq!(text, "12345678901");
+    let (error, text) = error_text("<a><![CDATA[12345678901]]></a>", limits);
+    assert!(matches!(error, ParseError::TextSizeLimit(_)));
+    assert_eq!(text, "12345678901");
+}
+
+#[test]
+fn test_max_text_size_entity() {
+    let limits = ParseLimits {
+        max_text_size: Some(10),
+        ..Default::default()
+    };
+    let xml = r#"<!DOCTYPE a [<!ENTITY e "123456">]><a>&e;&e;</a>"#;
+    let (error, text) = error_text(xml, limits);
+    assert!(matches!(error, ParseError::TextSizeLimit(_)));
+    assert_eq!(text, "&e;");
+    assert_eq!(error.span().start, xml.rfind("&e;").unwrap());
+}
+
+#[test]
+fn test_max_nodes() {
+    let limits = ParseLimits {
+        max_nodes: Some(4),
+        ..Default::default()
+    };
+    assert!(parse("<a x='1'>text<b/></a>", limits).is_ok());
+    let (error, text) = error_text("<a x='1'>text<b/><!--c--></a>", limits);
+    assert!(matches!(error, ParseError::NodeLimit(_)));
+    assert_eq!(text, "c");
+    // consolidated text is a single node
+    let xml = r#"<!DOCTYPE a [<!ENTITY e "e">]><a>t&e;t&amp;<![CDATA[t]]>t</a>"#;
+    assert!(parse(xml, limits).is_ok());
+}
+
+#[test]
+fn test_hardened() {
+    let mut xot = Xot::new();
+    let options = ParseOptions::hardened();
+    let xml = "<doc><p>Hello <b>world</b></p></doc>";
+    let root = xot.parse_with_options(xml, &options).unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), xml);
+
+    let xml = "<a>".repeat(257) + &"</a>".repeat(257);
+    let error = xot.parse_with_options(&xml, &options).unwrap_err();
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+    assert_eq!(error.span().start, 256 * 3 + 1);
+}
+
+#[test]
+fn test_limit_diagnostic() {
+    let limits = ParseLimits {
+        max_depth: Some(1),
+        ..Default::default()
+    };
+    let xml = "<doc><p/></doc>";
+    let error = parse(xml, limits).unwrap_err();
+    let diagnostic = error.diagnostic(xml);
+    assert_eq!(diagnostic.message, "element `p` is nested too deeply");
+    assert_eq!(diagnostic.label, "exceeds the maximum depth");
+}
+
+#[test]
+fn test_limits_parse_reader() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_depth: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let data = b"<doc>\n<p/></doc>";
+    let error = xot
+        .parse_reader_with_options(&data[..], &options)
+        .unwrap_err();
+    match error {
+        xot::Error::Parse(ParseError::DepthLimit(span)) => {
+            assert_eq!(&data[span.range()], b"p");
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
@@ -0,0 +1,423 @@
+use xot::{EntityExpansion, ParseError, ParseLimits, ParseOptions, PushParser, Xot};
+
+fn parse(xml: &str, options: &ParseOptions) -> Result<String, ParseError> {
+    let mut xot = Xot::new();
+    let root = xot.parse_with_options(xml, options)?;
+    Ok(xot.to_string(root).unwrap())
+}
+
+#[test]
+fn test_default_options() {
+    let xml = r#"<doc> <a xml:id=" x "/> </doc>"#;
+    assert_eq!(
+        parse(xml, &ParseOptions::default()).unwrap(),
+        r#"<doc> <a xml:id="x"/> </doc>"#
+    );
+}
+
+#[test]
+fn test_remove_insignificant_whitespace() {
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    let xml = r#"<doc>
+  <p>hello <i>world</i>  </p>
+  <pre xml:space="preserve">  <b/>  </pre>
+  <q xml:space="preserve"><r xml:space="default"> <s/> </r></q>
+</doc>"#;
+    assert_eq!(
+        parse(xml, &options).unwrap(),
+        r#"<doc><p>hello <i>world</i>  </p><pre xml:space="preserve">  <b/>  </pre><q xml:space="preserve"><r xml:space="default"><s/></r></q></doc>"#
+    );
+}
+
+#[test]
+fn test_remove_insignificant_whitespace_same_as_afterward() {
+    let xml = "<doc>\n  <a>\n    <b> </b>\n    <!--c-->\n  </a>\n  <a>x <b/> </a>\n</doc>";
+    let mut xot = Xot::new();
+    let root = xot.parse(xml).unwrap();
+    xot.remove_insignificant_whitespace(root);
+    let expected = xot.to_string(root).unwrap();
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    assert_eq!(parse(xml, &options).unwrap(), expected);
+}
+
+#[test]
+fn test_remove_insignificant_whitespace_from_entity() {
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    let xml = r#"<!DOCTYPE doc [<!ENTITY e "<a> <b/> </a>">]><doc> &e; </doc>"#;
+    assert_eq!(parse(xml, &options).unwrap(), "<doc><a><b/></a></doc>");
+}
+
+#[test]
+fn test_remove_insignificant_whitespace_keeps_text() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    let xml = "<doc> <a>text</a> </doc>";
+    let root = xot.parse_with_options(xml, &options).unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.first_child(doc_el).unwrap();
+    assert_eq!(xot.children(doc_el).count(), 1);
+    assert!(xot.is_element(a));
+    let text = xot.first_child(a).unwrap();
+    assert_eq!(xot.text_str(text), Some("text"));
+}
+
+#[test]
+fn test_remove_insignificant_whitespace_fragment() {
+    let options = ParseOptions {
+        fragment: true,
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    assert_eq!(parse(" <a/> <b/> ", &options).unwrap(), "<a/><b/>");
+    assert_eq!(parse(" <a/> text ", &options).unwrap(), " <a/> text ");
+}
+
+#[test]
+fn test_no_xml_id_normalization() {
+    let options = ParseOptions {
+        normalize_xml_id: false,
+        ..Default::default()
+    };
+    let mut xot = Xot::new();
+    let root = xot
+        .parse_with_options(r#"<doc xml:id=" a  b "/>"#, &options)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc xml:id=" a  b "/>"#);
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.element_by_id(root, " a  b "), Some(doc_el));
+}
+
+#[test]
+fn test_fragment() {
+    let options = ParseOptions {
+        fragment: true,
+        ..Default::default()
+    };
+    assert_eq!(parse("text<a/><b/>", &options).unwrap(), "text<a/><b/>");
+    assert!(parse("text<a/><b/>", &ParseOptions::default()).is_err());
+    assert!(matches!(
+        parse("<a>", &options),
+        Err(ParseError::UnclosedTag(_))
+    ));
+}
+
+#[test]
+fn test_entity_expansion_internal_only() {
+    let options = ParseOptions {
+        entity_expansion: EntityExpansion::InternalOnly,
+        ..Default::default()
+    };
+    let xml =
+        r#"<!DOCTYPE doc [<!ENTITY i "internal"><!ENTITY x SYSTEM "x.xml">]><doc>&i;&amp;</doc>"#;
+    assert_eq!(parse(xml, &options).unwrap(), "<doc>internal&amp;</doc>");
+
+    let xml = r#"<!DOCTYPE doc [<!ENTITY x SYSTEM "x.xml">]><doc>&x;</doc>"#;
+    let error = parse(xml, &options).unwrap_err();
+    match error {
+        ParseError::EntityNotAllowed(name, span) => {
+            assert_eq!(name, "x");
+            assert_eq!(&xml[span.range()], "&x;");
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+#[test]
+fn test_entity_expansion_predefined_only() {
+    let options = ParseOptions {
+        entity_expansion: EntityExpansion::PredefinedOnly,
+        ..Default::default()
+    };
+    let xml = r#"<!DOCTYPE doc [<!ENTITY i "internal">]><doc>&amp;&#65;</doc>"#;
+    assert_eq!(parse(xml, &options).unwrap(), "<doc>&amp;A</doc>");
+
+    let xml = r#"<!DOCTYPE doc [<!ENTITY i "internal">]><doc a="&i;"/>"#;
+    let error = parse(xml, &options).unwrap_err();
+    assert!(matches!(error, ParseError::EntityNotAllowed(name, _) if name == "i"));
+
+    let xml = r#"<!DOCTYPE doc [<!ENTITY i "internal">]><doc>&i;</doc>"#;
+    let error = parse(xml, &options).unwrap_err();
+    let diagnostic = error.diagnostic(xml);
+    assert_eq!(diagnostic.message, "entity `i` may not be expanded");
+}
+
+#[test]
+fn test_encoding_hint() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        encoding: Some("iso-8859-1".to_string()),
+        ..Default::default()
+    };
+    let data = b"<p>\xe9</p>";
+    let root = xot.parse_bytes_with_options(data, &options).unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<p>\u{e9}</p>");
+    let root = xot.parse_reader_with_options(&data[..], &options).unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<p>\u{e9}</p>");
+
+    // the encoding declaration takes precedence
+    let data = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><p>\xc3\xa9</p>";
+    let root = xot.parse_bytes_with_options(data, &options).unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("\u{e9}"));
+}
+
+#[test]
+fn test_parse_reader_with_options() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    let data = b"<doc>\n  <a> </a>\n</doc>";
+    let root = xot.parse_reader_with_options(&data[..], &options).unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc><a/></doc>");
+
+    let options = ParseOptions {
+        fragment: true,
+        ..Default::default()
+    };
+    let root = xot
+        .parse_reader_with_options(&b"a<b/>"[..], &options)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "a<b/>");
+}
+
+fn deep(depth: usize) -> String {
+    "<a>".repeat(depth) + &"</a>".repeat(depth)
+}
+
+#[test]
+fn test_limits_apply_to_every_entry_point() {
+    let options = ParseOptions::hardened();
+    let xml = deep(1000);
+    let mut xot = Xot::new();
+
+    let result = xot.parse_lossless_with_options(&xml, &options);
+    assert!(matches!(result, Err(ParseError::DepthLimit(_))));
+
+    let (_, _, errors) = xot.parse_lenient_with_options(&xml, &options);
+    assert!(errors
+        .iter()
+        .any(|error| matches!(error, ParseError::DepthLimit(_))));
+
+    let error = xot
+        .parse_events_with_options(&xml, &options)
+        .find_map(Result::err)
+        .unwrap();
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+
+    let error = xot
+        .parse_events_reader_with_options(xml.as_bytes(), &options)
+        .find_map(Result::err)
+        .unwrap();
+    assert!(matches!(
+        error,
+        xot::Error::Parse(ParseError::DepthLimit(_))
+    ));
+
+    let error = xot
+        .iterparse_with_options(&xml, |_: &Xot, _| false, |_, _| {}, &options)
+        .unwrap_err();
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+
+    let error = xot
+        .iterparse_reader_with_options(xml.as_bytes(), |_: &Xot, _| false, |_, _| {}, &options)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        xot::Error::Parse(ParseError::DepthLimit(_))
+    ));
+
+    let mut parser = PushParser::with_options(&mut xot, &options);
+    let error = parser.feed(xml.as_bytes()).unwrap_err();
+    assert!(matches!(error, ParseError::DepthLimit(_)));
+    drop(parser);
+
+    // below the limits these parse as usual
+    let xml = deep(10);
+    assert!(xot.parse_lossless_with_options(&xml, &options).is_ok());
+    assert!(xot.parse_lenient_with_options(&xml, &options).2.is_empty());
+    assert!(xot
+        .parse_events_with_options(&xml, &options)
+        .all(|event| event.is_ok()));
+    assert!(xot
+        .parse_events_reader_with_options(xml.as_bytes(), &options)
+        .all(|event| event.is_ok()));
+    let mut parser = PushParser::with_options(&mut xot, &options);
+    parser.feed(xml.as_bytes()).unwrap();
+    assert!(parser.finish().is_ok());
+}
+
+#[test]
+fn test_remove_insignificant_whitespace_while_streaming() {
+    let options = ParseOptions {
+        remove_insignificant_whitespace: true,
+        ..Default::default()
+    };
+    let xml = "<doc>\n  <a> </a>\n  <a>x</a>\n</doc>";
+    let mut xot = Xot::new();
+
+    let a = xot.add_name("a");
+    let mut texts = Vec::new();
+    let root = xot
+        .iterparse_with_options(
+            xml,
+            a,
+            |xot, node| texts.push(xot.to_string(node).unwrap()),
+            &options,
+        )
+        .unwrap();
+    assert_eq!(texts, ["<a/>", "<a>x</a>"]);
+    assert_eq!(xot.to_string(root).unwrap(), "<doc/>");
+
+    let mut parser = PushParser::with_options(&mut xot, &options);
+    parser.feed(xml.as_bytes()).unwrap();
+    let root = parser.finish().unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc><a/><a>x</a></doc>");
+
+    let (root, _, errors) = xot.parse_lenient_with_options(xml, &options);
+    assert!(errors.is_empty());
+    assert_eq!(xot.to_string(root).unwrap(), "<doc><a/><a>x</a></doc>");
+}
+
+const NESTED_ENTITIES: &str = r#"<!DOCTYPE doc [
+<!ENTITY e1 "x">
+<!ENTITY e2 "&e1;&e1;">
+<!ENTITY e3 "&e2;&e2;">
+<!ENTITY e4 "&e3;&e3;">
+]>
+<doc>&e4;</doc>"#;
+
+#[test]
+fn test_entity_limits() {
+    assert_eq!(
+        parse(NESTED_ENTITIES, &ParseOptions::default()).unwrap(),
+        "<doc>xxxxxxxx</doc>"
+    );
+
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_entity_depth: Some(3),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let error = parse(NESTED_ENTITIES, &options).unwrap_err();
+    assert!(matches!(error, ParseError::EntityExpansionLimit(name, _) if name == "e1"));
+
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_entity_expansion_size: Some(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let error = parse(NESTED_ENTITIES, &options).unwrap_err();
+    assert!(matches!(error, ParseError::EntityExpansionLimit(_, _)));
+
+    // without limits, entities nest as deeply as they're declared
+    let mut xml = r#"<!DOCTYPE doc [<!ENTITY e0 "x">"#.to_string();
+    for i in 1..=20 {
+        xml.push_str(&format!(r#"<!ENTITY e{} "&e{};">"#, i, i - 1));
+    }
+    xml.push_str("]><doc>&e20;</doc>");
+    assert!(matches!(
+        parse(&xml, &ParseOptions::default()),
+        Err(ParseError::EntityExpansionLimit(_, _))
+    ));
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_entity_depth: None,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(parse(&xml, &options).unwrap(), "<doc>x</doc>");
+}
+
+#[test]
+fn test_parameter_entity_limits() {
+    let xml = r#"<!DOCTYPE doc [
+<!ENTITY % p1 "<!ENTITY e 'x'>">
+<!ENTITY % p2 "&#37;p1;">
+%p2;
+]>
+<doc>&e;</doc>"#;
+    assert_eq!(
+        parse(xml, &ParseOptions::default()).unwrap(),
+        "<doc>x</doc>"
+    );
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_entity_depth: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let error = parse(xml, &options).unwrap_err();
+    assert!(matches!(error, ParseError::EntityExpansionLimit(name, _) if name == "p1"));
+}
+
+#[test]
+fn test_entity_limits_apply_to_every_entry_point() {
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_entity_depth: Some(3),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let is_limit = |error: &ParseError| matches!(error, ParseError::EntityExpansionLimit(_, _));
+    let mut xot = Xot::new();
+
+    let result = xot.parse_lossless_with_options(NESTED_ENTITIES, &options);
+    assert!(result.is_err_and(|error| is_limit(&error)));
+
+    let (_, _, errors) = xot.parse_lenient_with_options(NESTED_ENTITIES, &options);
+    assert!(errors.iter().any(is_limit));
+
+    let error = xot
+        .parse_events_with_options(NESTED_ENTITIES, &options)
+        .find_map(Result::err)
+        .unwrap();
+    assert!(is_limit(&error));
+
+    let error = xot
+        .parse_events_reader_with_options(NESTED_ENTITIES.as_bytes(), &options)
+        .find_map(Result::err)
+        .unwrap();
+    assert!(matches!(error, xot::Error::Parse(error) if is_limit(&error)));
+
+    let error = xot
+        .iterparse_with_options(NESTED_ENTITIES, |_: &Xot, _| false, |_, _| {}, &options)
+        .unwrap_err();
+    assert!(is_limit(&error));
+
+    let error = xot
+        .iterparse_reader_with_options(
+            NESTED_ENTITIES.as_bytes(),
+            |_: &Xot, _| false,
+            |_, _| {},
+            &options,
+        )
+        .unwrap_err();
+    assert!(matches!(error, xot::Error::Parse(error) if is_limit(&error)));
+
+    let mut parser = PushParser::with_options(&mut xot, &options);
+    let result = parser
+        .feed(NESTED_ENTITIES.as_bytes())
+        .and_then(|()| parser.finish().map(|_| ()));
+    assert!(result.is_err_and(|error| is_limit(&error)));
+}
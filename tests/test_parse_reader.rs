@@ -0,0 +1,153 @@
+use std::io::Read;
+
+use xot::{Error, Xot};
+
+// A reader that returns at most `chunk_size` bytes at a time.
+struct ChunkedReader<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.chunk_size.min(buf.len()).min(self.data.len());
+        buf[..size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        Ok(size)
+    }
+}
+
+fn parse_bytes(data: &[u8]) -> Result<String, String> {
+    let mut xot = Xot::new();
+    xot.parse_bytes(data)
+        .map(|root| xot.to_string(root).unwrap())
+        .map_err(|e| format!("{:?}", e))
+}
+
+fn parse_reader(data: &[u8], chunk_size: usize) -> Result<String, String> {
+    let mut xot = Xot::new();
+    let reader = ChunkedReader { data, chunk_size };
+    match xot.parse_reader(reader) {
+        Ok(root) => Ok(xot.to_string(root).unwrap()),
+        Err(Error::Parse(e)) => Err(format!("{:?}", e)),
+        Err(e) => panic!("unexpected error {:?}", e),
+    }
+}
+
+fn assert_same(data: &[u8]) {
+    assert_same_chunked(data);
+    // we only start parsing once we have enough data to detect the encoding,
+    // so we make sure the document itself arrives in chunks too
+    if data.starts_with(b"<") && !data.starts_with(b"<?xml") {
+        let mut padded = format!("<!--{}-->\n", "-x".repeat(300)).into_bytes();
+        padded.extend_from_slice(data);
+        assert_same_chunked(&padded);
+    }
+}
+
+fn assert_same_chunked(data: &[u8]) {
+    let expected = parse_bytes(data);
+    for chunk_size in [1, 2, 3, 7, 64, 1000, usize::MAX] {
+        assert_eq!(
+            parse_reader(data, chunk_size),
+            expected,
+            "chunk size {} for {}",
+            chunk_size,
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn test_parse_reader_same_as_parse_bytes() {
+    for xml in [
+        "<doc/>",
+        "<doc>text</doc>",
+        "<?xml version=\"1.0\"?>\n<!-- comment -->\n<doc a=\"1\" b='>'>\n  <p>text &amp; more</p>\n  <![CDATA[ <cdata> ]]>\n  <?pi content?>\n</doc>\n<!-- after -->\n<?after?>\n",
+        "<doc xmlns=\"http://example.com\" xmlns:p=\"http://example.com/p\"><p:a p:b=\"x\"/><c/></doc>",
+        "<!DOCTYPE doc [\n<!ENTITY e \"<b>entity</b>\">\n<!ATTLIST doc a CDATA \"default\">\n<!-- ]> -->\n]>\n<doc>&e;&e;</doc>",
+        "<!DOCTYPE doc SYSTEM \"doc.dtd\"><doc/>",
+        "<doc>é ü 日本語 😀</doc>",
+        "\u{feff}<doc/>",
+    ] {
+        assert_same(xml.as_bytes());
+    }
+}
+
+#[test]
+fn test_parse_reader_errors_same_as_parse_bytes() {
+    for xml in [
+        "",
+        "<a",
+        "<doc>",
+        "<doc>\n  <a>\n</doc>",
+        "<doc></other>",
+        "<doc/>\n<doc/>",
+        "<doc/>\n  text",
+        "text<doc/>",
+        "<doc>\n  <a b=c/>\n</doc>",
+        "<doc>\n  <a b=\"1\" b=\"2\"/>\n</doc>",
+        "<doc>\n  text &unknown; text\n</doc>",
+        "<doc>\n  text &amp text\n</doc>",
+        "<doc>\n  <p:a/>\n</doc>",
+        "<doc>\n  <!-- -- -->\n</doc>",
+        "<doc/>\n<!-- -- -->",
+        "<doc/>\n<?xml version=\"1.0\"?>",
+        "<doc>\n  <!DOCTYPE doc>\n</doc>",
+        "<?xml version=\"1.2\"?><doc/>",
+        "<!DOCTYPE doc [<!ENTITY e \"<a>\">]>\n<doc>\n  &e;\n</doc>",
+        "<!DOCTYPE doc [<!ENTITY e \"&e;\">]>\n<doc>\n  &e;\n</doc>",
+        "<!DOCTYPE doc [<!ENTITY e \"x\">\n<doc/>",
+        "<doc>\n  é <a b='1'/ > ü\n</doc>",
+        "<doc>\n  <![CDATA[ unclosed\n</doc>",
+    ] {
+        assert_same(xml.as_bytes());
+    }
+}
+
+#[test]
+fn test_parse_reader_encoding() {
+    assert_same(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>\xe9</p>");
+    // UTF-16LE with a byte order mark
+    let mut utf16 = vec![0xff, 0xfe];
+    for unit in "<doc>日本語</doc>".encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    assert_same(&utf16);
+    // invalid UTF-8 is replaced
+    assert_same(b"<p>\xff\xfe</p>");
+}
+
+#[test]
+fn test_parse_reader_large() {
+    let mut xml = "<?xml version=\"1.0\"?>\n<records>\n".to_string();
+    for i in 0..10_000 {
+        xml.push_str(&format!(
+            "  <record id=\"{}\"><name>Record {}</name><!-- {} --></record>\n",
+            i, i, i
+        ));
+    }
+    xml.push_str("  <broken>\n</records>\n");
+    let mut xot = Xot::new();
+    let expected = xot.parse_bytes(xml.as_bytes()).unwrap_err();
+    let error = xot.parse_reader(xml.as_bytes()).unwrap_err();
+    assert_eq!(
+        format!("{:?}", error),
+        format!("{:?}", Error::Parse(expected))
+    );
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("broken"))
+    }
+}
+
+#[test]
+fn test_parse_reader_io_error() {
+    let mut xot = Xot::new();
+    let error = xot.parse_reader(FailingReader).unwrap_err();
+    assert!(matches!(error, Error::Io(message) if message == "broken"));
+}
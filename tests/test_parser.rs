@@ -1,4 +1,4 @@
-use xot::{ParseError, Span, SpanInfoKey, Xot};
+use xot::{output, ParseError, ParseOptions, Span, SpanInfoKey, XmlVersion, Xot};
 
 const US_ASCII: &str = include_str!("fixtures/us-ascii.xml");
 
@@ -330,6 +330,99 @@ fn test_parse_consolidated_text_cdata_text() {
     assert_eq!(txt, "foobarbaz");
 }
 
+#[test]
+fn test_parse_xml_declaration_values() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<?xml version = '1.0' encoding="ISO-8859-1" standalone='yes' ?><doc/>"#)
+        .unwrap();
+    let declaration = xot.xml_declaration(doc).unwrap();
+    assert_eq!(declaration.version(), "1.0");
+    assert_eq!(declaration.encoding(), Some("ISO-8859-1"));
+    assert_eq!(declaration.standalone(), Some(true));
+    assert_eq!(
+        declaration.declaration(),
+        output::xml::Declaration {
+            version: XmlVersion::Xml10,
+            encoding: Some("ISO-8859-1".to_string()),
+            standalone: Some(true),
+        }
+    );
+}
+
+#[test]
+fn test_parse_xml_declaration_per_document() {
+    let mut xot = Xot::new();
+    let a = xot.parse(r#"<?xml version="1.0"?><a/>"#).unwrap();
+    let b = xot.parse(r#"<b/>"#).unwrap();
+    let declaration = xot.xml_declaration(a).unwrap();
+    assert_eq!(declaration.encoding(), None);
+    assert_eq!(declaration.standalone(), None);
+    assert!(xot.xml_declaration(b).is_none());
+    let doc_el = xot.document_element(a).unwrap();
+    assert!(xot.xml_declaration(doc_el).is_none());
+}
+
+#[test]
+fn test_parse_bytes_xml_declaration() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_bytes(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><doc>\xe9</doc>")
+        .unwrap();
+    let declaration = xot.xml_declaration(doc).unwrap();
+    assert_eq!(declaration.encoding(), Some("ISO-8859-1"));
+}
+
+#[test]
+fn test_parse_preserve_cdata() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+    let doc = xot
+        .parse_with_options(r#"<a>foo<![CDATA[bar]]><![CDATA[baz]]>qux</a>"#, &options)
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let children = xot.children(doc_el).collect::<Vec<_>>();
+    assert_eq!(children.len(), 3);
+    let texts = children
+        .iter()
+        .map(|child| {
+            let text = xot.text(*child).unwrap();
+            (text.get(), text.is_cdata())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        texts,
+        vec![("foo", false), ("barbaz", true), ("qux", false)]
+    );
+}
+
+#[test]
+fn test_parse_preserve_cdata_spans() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        preserve_cdata: true,
+        ..Default::default()
+    };
+    let (doc, lexical_info) = xot
+        .parse_lossless_with_options(r#"<a>foo<![CDATA[bar]]></a>"#, &options)
+        .unwrap();
+    let span_info = lexical_info.span_info();
+    let doc_el = xot.document_element(doc).unwrap();
+    let foo = xot.first_child(doc_el).unwrap();
+    let bar = xot.last_child(doc_el).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::Text(foo)).unwrap(),
+        &Span::new(3, 6)
+    );
+    assert_eq!(
+        span_info.get(SpanInfoKey::Text(bar)).unwrap(),
+        &Span::new(15, 18)
+    );
+}
+
 #[test]
 fn test_span_for_cdata() {
     let mut xot = Xot::new();
@@ -386,6 +479,209 @@ fn test_span_for_cdata_cdata() {
     );
 }
 
+#[test]
+fn test_node_at() {
+    let mut xot = Xot::new();
+    let xml = r#"<doc><!--c--><p a="v">text</p><?t c?><e/></doc>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let children = xot.children(doc_el).collect::<Vec<_>>();
+    let (comment, p, pi, e) = (children[0], children[1], children[2], children[3]);
+    let text = xot.first_child(p).unwrap();
+    let a = xot.name("a").unwrap();
+
+    assert_eq!(span_info.node_at(0), None);
+    assert_eq!(
+        span_info.node_at(1),
+        Some(SpanInfoKey::ElementStart(doc_el))
+    );
+    assert_eq!(
+        span_info.node_at(3),
+        Some(SpanInfoKey::ElementStart(doc_el))
+    );
+    assert_eq!(span_info.node_at(4), None);
+    assert_eq!(span_info.node_at(9), Some(SpanInfoKey::Comment(comment)));
+    assert_eq!(span_info.node_at(14), Some(SpanInfoKey::ElementStart(p)));
+    assert_eq!(
+        span_info.node_at(16),
+        Some(SpanInfoKey::AttributeName(p, a))
+    );
+    assert_eq!(span_info.node_at(17), None);
+    assert_eq!(
+        span_info.node_at(19),
+        Some(SpanInfoKey::AttributeValue(p, a))
+    );
+    assert_eq!(span_info.node_at(22), Some(SpanInfoKey::Text(text)));
+    assert_eq!(span_info.node_at(26), Some(SpanInfoKey::ElementEnd(p)));
+    assert_eq!(span_info.node_at(32), Some(SpanInfoKey::PiTarget(pi)));
+    assert_eq!(span_info.node_at(34), Some(SpanInfoKey::PiContent(pi)));
+    assert_eq!(span_info.node_at(38), Some(SpanInfoKey::ElementStart(e)));
+    assert_eq!(span_info.node_at(39), Some(SpanInfoKey::ElementEnd(e)));
+    assert_eq!(span_info.node_at(42), Some(SpanInfoKey::ElementEnd(doc_el)));
+    assert_eq!(span_info.node_at(xml.len()), None);
+}
+
+#[test]
+fn test_node_at_agrees_with_get() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><doc xmlns:x="u">
+  <x:p x:a="1" b='2'>one &amp; <![CDATA[two]]> three<!--c--></x:p>
+  <?pi content?>
+</doc>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let mut found = 0;
+    for offset in 0..xml.len() {
+        if let Some(key) = span_info.node_at(offset) {
+            let span = span_info.get(key).unwrap();
+            assert!(span.start <= offset && offset < span.end);
+            found += 1;
+        }
+    }
+    assert!(found > xml.len() / 2);
+    // every node is found somewhere
+    let doc_el = xot.document_element(doc).unwrap();
+    for node in xot.descendants(doc_el) {
+        assert!((0..xml.len()).any(|offset| span_info
+            .node_at(offset)
+            .is_some_and(|key| node_of(key) == node)));
+    }
+}
+
+fn node_of(key: SpanInfoKey) -> xot::Node {
+    match key {
+        SpanInfoKey::AttributeName(node, _)
+        | SpanInfoKey::AttributeValue(node, _)
+        | SpanInfoKey::ElementStart(node)
+        | SpanInfoKey::ElementEnd(node)
+        | SpanInfoKey::Text(node)
+        | SpanInfoKey::Comment(node)
+        | SpanInfoKey::PiTarget(node)
+        | SpanInfoKey::PiContent(node)
+        | SpanInfoKey::NamespacePrefix(node, _)
+        | SpanInfoKey::NamespaceUri(node, _)
+        | SpanInfoKey::Reference(node, _)
+        | SpanInfoKey::DeclarationVersion(node)
+        | SpanInfoKey::DeclarationEncoding(node)
+        | SpanInfoKey::DeclarationStandalone(node) => node,
+    }
+}
+
+#[test]
+fn test_span_info_namespace_declarations() {
+    let mut xot = Xot::new();
+    let xml = r#"<doc xmlns="u" xmlns:foo='v'/>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let empty = xot.empty_prefix();
+    let foo = xot.prefix("foo").unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::NamespacePrefix(doc_el, empty)),
+        Some(&Span::new(5, 10))
+    );
+    assert_eq!(
+        span_info.get(SpanInfoKey::NamespaceUri(doc_el, empty)),
+        Some(&Span::new(12, 13))
+    );
+    assert_eq!(
+        span_info.get(SpanInfoKey::NamespacePrefix(doc_el, foo)),
+        Some(&Span::new(15, 24))
+    );
+    assert_eq!(
+        span_info.get(SpanInfoKey::NamespaceUri(doc_el, foo)),
+        Some(&Span::new(26, 27))
+    );
+    assert_eq!(
+        span_info.node_at(20),
+        Some(SpanInfoKey::NamespacePrefix(doc_el, foo))
+    );
+}
+
+#[test]
+fn test_span_info_declaration() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.0" encoding='UTF-8' standalone = "yes" ?><doc/>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let part = |key| &xml[span_info.get(key).unwrap().range()];
+    assert_eq!(part(SpanInfoKey::DeclarationVersion(doc)), "1.0");
+    assert_eq!(part(SpanInfoKey::DeclarationEncoding(doc)), "UTF-8");
+    assert_eq!(part(SpanInfoKey::DeclarationStandalone(doc)), "yes");
+
+    let xml = r#"<?xml version="1.0"?><doc/>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::DeclarationVersion(doc)),
+        Some(&Span::new(15, 18))
+    );
+    assert_eq!(span_info.get(SpanInfoKey::DeclarationEncoding(doc)), None);
+    assert_eq!(span_info.get(SpanInfoKey::DeclarationStandalone(doc)), None);
+}
+
+#[test]
+fn test_span_info_references() {
+    let mut xot = Xot::new();
+    let xml = r#"<!DOCTYPE doc [<!ENTITY e "plain">]><doc>a &amp; b<![CDATA[&c;]]>&#x21;&e;</doc>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let text = xot.first_child(doc_el).unwrap();
+    assert_eq!(xot.text_str(text), Some("a & b&c;!plain"));
+    let references = (0..)
+        .map_while(|index| span_info.get(SpanInfoKey::Reference(text, index)))
+        .map(|span| &xml[span.range()])
+        .collect::<Vec<_>>();
+    assert_eq!(references, vec!["&amp;", "&#x21;", "&e;"]);
+
+    // a reference is inside the text
+    let position = xml.find("&amp;").unwrap();
+    assert_eq!(
+        span_info.node_at(position - 1),
+        Some(SpanInfoKey::Text(text))
+    );
+    assert_eq!(
+        span_info.node_at(position + 1),
+        Some(SpanInfoKey::Reference(text, 0))
+    );
+    assert_eq!(
+        span_info.node_at(position + 5),
+        Some(SpanInfoKey::Text(text))
+    );
+    let position = xml.find("&e;").unwrap();
+    assert_eq!(
+        span_info.node_at(position),
+        Some(SpanInfoKey::Reference(text, 2))
+    );
+}
+
+#[test]
+fn test_span_info_references_only_reference() {
+    let mut xot = Xot::new();
+    let xml = r#"<doc><a>&lt;</a></doc>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let text = xot.first_child(xot.first_child(doc_el).unwrap()).unwrap();
+    assert_eq!(
+        span_info.get(SpanInfoKey::Reference(text, 0)),
+        span_info.get(SpanInfoKey::Text(text))
+    );
+    assert_eq!(span_info.node_at(9), Some(SpanInfoKey::Reference(text, 0)));
+}
+
+#[test]
+fn test_node_at_entity_reference() {
+    let mut xot = Xot::new();
+    let xml = r#"<!DOCTYPE doc [<!ENTITY e "<a>x</a>">]><doc>&e;</doc>"#;
+    let (doc, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let a = xot.first_child(doc_el).unwrap();
+    let position = xml.find("&e;").unwrap();
+    // we get the first node from the entity
+    for offset in position..position + 3 {
+        assert_eq!(
+            span_info.node_at(offset),
+            Some(SpanInfoKey::ElementStart(a))
+        );
+    }
+}
+
 #[test]
 fn test_parse_should_reject_multiple_elements_in_document() {
     let mut xot = Xot::new();
@@ -0,0 +1,123 @@
+use std::sync::Mutex;
+
+use xot::{ParseError, PushParser, Xot};
+
+// Nested sections with comments in between, unlike the flat records the
+// iterparse tests use.
+fn sections(count: usize) -> String {
+    let mut xml = "<sections>\n".to_string();
+    for i in 0..count {
+        xml.push_str(&format!(
+            concat!(
+                "  <!-- section {} -->\n",
+                "  <section id=\"{}\">\n",
+                "    <title>Section {}</title>\n",
+                "    <p>One &amp; <em>two</em></p>\n",
+                "  </section>\n"
+            ),
+            i, i, i
+        ));
+    }
+    xml.push_str("</sections>\n");
+    xml
+}
+
+#[test]
+fn test_push_parser() {
+    let xml = sections(100);
+    let mut xot = Xot::new();
+    let mut parser = PushParser::new(&mut xot);
+    for chunk in xml.as_bytes().chunks(7) {
+        parser.feed(chunk).unwrap();
+    }
+    let document = parser.finish().unwrap();
+    assert_eq!(xot.to_string(document).unwrap(), xml.trim_end());
+}
+
+#[test]
+fn test_push_parser_element_closed() {
+    let xml = sections(100);
+    let closed = Mutex::new(Vec::new());
+    let mut xot = Xot::new();
+    let mut parser = PushParser::new(&mut xot);
+    parser.on_element_closed(2, |xot, node| {
+        let id = xot.name("id").unwrap();
+        closed
+            .lock()
+            .unwrap()
+            .push(xot.get_attribute(node, id).unwrap().to_string());
+    });
+    let mut closed_before_end = 0;
+    for chunk in xml.as_bytes().chunks(100) {
+        parser.feed(chunk).unwrap();
+        closed_before_end = closed.lock().unwrap().len();
+    }
+    parser.finish().unwrap();
+    // we got most sections before the document was complete
+    assert!(closed_before_end > 90);
+    let expected = (0..100).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(closed.into_inner().unwrap(), expected);
+}
+
+#[test]
+fn test_push_parser_document_element_closed() {
+    let closed = Mutex::new(Vec::new());
+    let mut xot = Xot::new();
+    let mut parser = PushParser::new(&mut xot);
+    parser.on_element_closed(1, |xot, node| {
+        closed.lock().unwrap().push(xot.to_string(node).unwrap());
+    });
+    parser.feed(b"<doc><a/>").unwrap();
+    parser.feed(b"</doc>").unwrap();
+    parser.finish().unwrap();
+    assert_eq!(closed.into_inner().unwrap(), vec!["<doc><a/></doc>"]);
+}
+
+#[test]
+fn test_push_parser_inspect_progress() {
+    let xml = sections(100);
+    let mut xot = Xot::new();
+    let mut parser = PushParser::new(&mut xot);
+    let (start, rest) = xml.as_bytes().split_at(xml.len() / 2);
+    parser.feed(start).unwrap();
+    let document = parser.document();
+    let sections = parser.xot().document_element(document).unwrap();
+    let count = parser.xot().children(sections).count();
+    assert!(count > 50);
+    parser.feed(rest).unwrap();
+    assert_eq!(parser.finish().unwrap(), document);
+}
+
+#[test]
+fn test_push_parser_error() {
+    let mut xml = sections(100);
+    xml.push_str("<sections/>");
+    let mut xot = Xot::new();
+    let expected = xot.parse_bytes(xml.as_bytes()).unwrap_err();
+
+    let mut parser = PushParser::new(&mut xot);
+    let (start, rest) = xml.as_bytes().split_at(xml.len() - 5);
+    parser.feed(start).unwrap();
+    let error = parser.feed(rest).unwrap_err();
+    assert_eq!(format!("{:?}", error), format!("{:?}", expected));
+    // the parser is stuck with the error
+    let error = parser.feed(b"more").unwrap_err();
+    assert_eq!(format!("{:?}", error), format!("{:?}", expected));
+    let error = parser.finish().unwrap_err();
+    assert_eq!(format!("{:?}", error), format!("{:?}", expected));
+}
+
+#[test]
+fn test_push_parser_error_at_finish() {
+    let mut xot = Xot::new();
+    let mut parser = PushParser::new(&mut xot);
+    parser.feed(b"<doc>").unwrap();
+    let error = parser.finish().unwrap_err();
+    assert!(matches!(error, ParseError::UnclosedTag(_)));
+}
+
+#[test]
+fn test_push_parser_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<PushParser<'static>>();
+}
@@ -0,0 +1,345 @@
+use std::path::PathBuf;
+
+use ahash::HashMap;
+
+use xot::resolver::{Catalog, ResolveError, Resolver, Resource};
+use xot::{Error, ParseError, Xot};
+
+// A resolver that resolves system ids relative to the base into resources
+// held in memory.
+#[derive(Debug, Default)]
+struct MemoryResolver {
+    resources: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    fn new(resources: &[(&str, &str)]) -> Self {
+        MemoryResolver {
+            resources: resources
+                .iter()
+                .map(|(uri, text)| (uri.to_string(), text.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn resolve(
+        &self,
+        _public_id: Option<&str>,
+        system_id: &str,
+        base: Option<&str>,
+    ) -> Result<Resource, ResolveError> {
+        let uri = match base.and_then(|base| base.rsplit_once('/')) {
+            Some((directory, _)) => format!("{}/{}", directory, system_id),
+            None => format!("mem:/{}", system_id),
+        };
+        match self.resources.get(&uri) {
+            Some(text) => Ok(Resource::new(uri, text.clone())),
+            None => Err(ResolveError::Io(format!("{} not found", uri))),
+        }
+    }
+}
+
+fn catalog() -> Catalog {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/catalog/catalog.xml");
+    Catalog::from_file(path).unwrap()
+}
+
+#[test]
+fn test_default_does_not_read_external_subset() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc>text</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>text</doc>");
+}
+
+#[test]
+fn test_default_refuses_external_entity() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e SYSTEM "e.xml">]><doc>&e;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ParseError::UnresolvedEntity(ref name, ResolveError::Refused, _) if name == "e"
+    ));
+    assert_eq!(err.span(), (48..51).into());
+}
+
+#[test]
+fn test_external_subset() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[(
+        "mem:/doc.dtd",
+        r#"<!ELEMENT doc (#PCDATA)><!ENTITY e "E">"#,
+    )]));
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc>&e;</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>E</doc>");
+}
+
+#[test]
+fn test_internal_subset_takes_precedence() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[(
+        "mem:/doc.dtd",
+        r#"<!ENTITY e "external"><!ENTITY f "F">"#,
+    )]));
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd" [<!ENTITY e "internal">]><doc>&e;&f;</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>internalF</doc>");
+}
+
+#[test]
+fn test_external_parameter_entity_relative_to_base() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[
+        (
+            "mem:/dtd/doc.dtd",
+            r#"<!ENTITY % chars SYSTEM "chars.ent"> %chars;"#,
+        ),
+        (
+            "mem:/dtd/chars.ent",
+            r#"<?xml version="1.0" encoding="UTF-8"?><!ENTITY mdash "&#x2014;">"#,
+        ),
+    ]));
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "dtd/doc.dtd"><doc>&mdash;</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>\u{2014}</doc>");
+}
+
+#[test]
+fn test_external_parameter_entity_in_internal_subset() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[(
+        "mem:/chars.ent",
+        r#"<!ENTITY mdash "&#x2014;">"#,
+    )]));
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [
+<!ENTITY % chars PUBLIC "-//Example//ENTITIES Chars//EN" "chars.ent">
+%chars;
+]><doc>&mdash;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>\u{2014}</doc>");
+    // the internal subset is retained as it was
+    assert!(xot
+        .dtd(root)
+        .unwrap()
+        .internal_subset()
+        .unwrap()
+        .contains("%chars;"));
+}
+
+#[test]
+fn test_refused_parameter_entity_skips_later_declarations() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(
+            r#"<!DOCTYPE doc [
+<!ENTITY e "E">
+<!ENTITY % chars SYSTEM "chars.ent">
+%chars;
+<!ENTITY f "F">
+]><doc>&e;&f;</doc>"#,
+        )
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidEntity(ref name, _) if name == "f"));
+}
+
+#[test]
+fn test_external_entity() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[(
+        "mem:/greeting.xml",
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\nHello, <em>world</em>!",
+    )]));
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY greeting SYSTEM "greeting.xml">]><doc>&greeting; &greeting;</doc>"#)
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc>\nHello, <em>world</em>! \nHello, <em>world</em>!</doc>"
+    );
+}
+
+#[test]
+fn test_external_entity_in_attribute() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[("mem:/e.xml", "E")]));
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e SYSTEM "e.xml">]><doc a="&e;"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidEntity(ref name, _) if name == "e"));
+}
+
+#[test]
+fn test_unparsed_entity_reference() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!NOTATION gif SYSTEM "image/gif"><!ENTITY logo SYSTEM "logo.gif" NDATA gif>]><doc>&logo;</doc>"#,
+        )
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidEntity(ref name, _) if name == "logo"));
+}
+
+#[test]
+fn test_unresolved_external_subset() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::default());
+    let err = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ParseError::UnresolvedDtd(ref system_id, ResolveError::Io(_), _) if system_id == "doc.dtd"
+    ));
+    // the span of the system id
+    assert_eq!(err.span(), (22..29).into());
+}
+
+#[test]
+fn test_error_in_external_subset() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[("mem:/doc.dtd", "<!ENTITY>")]));
+    let err = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidDtd(_)));
+    assert_eq!(err.span(), (22..29).into());
+}
+
+#[test]
+fn test_invalid_internal_subset() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e "E"> <!ENTITY e>]><doc/>"#)
+        .unwrap_err();
+    assert!(matches!(err, ParseError::InvalidDtd(_)));
+    assert_eq!(err.span(), (31..42).into());
+}
+
+#[test]
+fn test_catalog_public() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc PUBLIC "-//Example//DTD Doc V1.0//EN" "http://example.com/unknown/doc.dtd"><doc>&title;&mdash;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>Document\u{2014}</doc>");
+}
+
+#[test]
+fn test_catalog_system() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "http://example.com/dtd/system.dtd"><doc>&system;</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>System</doc>");
+}
+
+#[test]
+fn test_catalog_system_suffix() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "http://example.org/suffix.dtd"><doc>&system;</doc>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>System</doc>");
+}
+
+#[test]
+fn test_catalog_rewrite_system() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc [<!ENTITY greeting SYSTEM "http://example.com/ent/greeting.ent">]><doc>&greeting;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc>\nHello, <em>world</em>!</doc>"
+    );
+}
+
+#[test]
+fn test_catalog_next_catalog() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    let root = xot
+        .parse(
+            r#"<!DOCTYPE doc PUBLIC "-//Example//DTD Next V1.0//EN" "next.dtd"><doc>&next;</doc>"#,
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>Next</doc>");
+}
+
+#[test]
+fn test_catalog_refuses_unmapped() {
+    let mut xot = Xot::new();
+    xot.set_resolver(catalog());
+    // the external subset is not read
+    xot.parse(r#"<!DOCTYPE doc SYSTEM "http://example.com/other.dtd"><doc/>"#)
+        .unwrap();
+    let err = xot
+        .parse(r#"<!DOCTYPE doc [<!ENTITY e SYSTEM "http://example.com/e.xml">]><doc>&e;</doc>"#)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ParseError::UnresolvedEntity(_, ResolveError::Refused, _)
+    ));
+}
+
+#[test]
+fn test_catalog_lookup() {
+    let catalog = catalog();
+    let uri = catalog
+        .lookup(Some("  -//Example//DTD   Doc V1.0//EN"), None)
+        .unwrap();
+    assert!(uri.starts_with("file://"));
+    assert!(uri.ends_with("/tests/fixtures/catalog/dtd/doc.dtd"));
+    assert_eq!(
+        catalog.lookup(None, Some("http://example.com/nothing")),
+        None
+    );
+}
+
+#[test]
+fn test_catalog_prefer_system() {
+    let catalog = catalog();
+    let public_id = "-//Example//DTD System Preferred V1.0//EN";
+    assert!(catalog.lookup(Some(public_id), None).is_some());
+    // as the entry prefers system ids, it's not used if there's a system id
+    assert_eq!(catalog.lookup(Some(public_id), Some("system.dtd")), None);
+}
+
+#[test]
+fn test_catalog_missing_file() {
+    let err = Catalog::from_file("tests/fixtures/catalog/missing.xml").unwrap_err();
+    assert!(matches!(err, Error::Io(_)));
+}
+
+#[test]
+fn test_attribute_defaults_in_external_subset() {
+    let mut xot = Xot::new();
+    xot.set_resolver(MemoryResolver::new(&[(
+        "mem:/doc.dtd",
+        r#"<!ATTLIST doc a CDATA "external" b CDATA "B">"#,
+    )]));
+    let root = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd" [<!ATTLIST doc a CDATA "internal">]><doc/>"#)
+        .unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="internal" b="B"/>"#);
+}
@@ -218,3 +218,28 @@ fn test_serialize_text_fragment() {
     let serialized = xot.to_string(fragment).unwrap();
     assert_eq!(serialized, r#"text"#);
 }
+
+#[test]
+fn test_serialize_xml_parameters() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.0" standalone="no"?>
+<!DOCTYPE doc PUBLIC "-//Example//DTD Doc//EN" "doc.dtd">
+<doc/>"#;
+    let root = xot.parse(xml).unwrap();
+    let parameters = xot.xml_parameters(root);
+    assert_eq!(xot.serialize_xml_string(parameters, root).unwrap(), xml);
+}
+
+#[test]
+fn test_serialize_xml_parameters_without_prolog() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<!DOCTYPE doc [<!ELEMENT doc EMPTY>]><doc/>"#)
+        .unwrap();
+    let parameters = xot.xml_parameters(root);
+    assert_eq!(parameters, Default::default());
+    assert_eq!(
+        xot.serialize_xml_string(parameters, root).unwrap(),
+        "<doc/>"
+    );
+}
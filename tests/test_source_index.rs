@@ -0,0 +1,81 @@
+use xot::{LineColumn, SourceIndex, SpanInfoKey, Xot};
+
+fn line_column(line: usize, column: usize, byte_column: usize) -> LineColumn {
+    LineColumn {
+        line,
+        column,
+        byte_column,
+    }
+}
+
+#[test]
+fn test_span_info_source_index() {
+    let mut xot = Xot::new();
+    let xml = "<doc>\n  <é>text</é>\n  <p a='1'/>\n</doc>";
+    let (document, span_info) = xot.parse_with_span_info(xml).unwrap();
+    let doc_el = xot.document_element(document).unwrap();
+    let elements = xot
+        .children(doc_el)
+        .filter(|node| xot.is_element(*node))
+        .collect::<Vec<_>>();
+    let index = span_info.source_index();
+
+    let span = span_info
+        .get(SpanInfoKey::ElementStart(elements[0]))
+        .unwrap();
+    assert_eq!(
+        index.span_line_columns(*span),
+        (line_column(2, 4, 4), line_column(2, 5, 6))
+    );
+    let text = xot.first_child(elements[0]).unwrap();
+    let span = span_info.get(SpanInfoKey::Text(text)).unwrap();
+    assert_eq!(index.line_column(span.start), line_column(2, 6, 7));
+
+    let a = xot.name("a").unwrap();
+    let span = span_info
+        .get(SpanInfoKey::AttributeValue(elements[1], a))
+        .unwrap();
+    assert_eq!(index.line_column(span.start), line_column(3, 9, 9));
+
+    let span = span_info.get(SpanInfoKey::ElementEnd(doc_el)).unwrap();
+    assert_eq!(index.line_column(span.end), line_column(4, 7, 7));
+}
+
+#[test]
+fn test_fragment_source_index() {
+    let mut xot = Xot::new();
+    let (document, span_info) = xot.parse_fragment_with_span_info("text\n<a/>").unwrap();
+    let a = xot.last_child(document).unwrap();
+    let span = span_info.get(SpanInfoKey::ElementStart(a)).unwrap();
+    assert_eq!(
+        span_info.source_index().line_column(span.start),
+        line_column(2, 2, 2)
+    );
+}
+
+#[test]
+fn test_error_positions() {
+    let mut xot = Xot::new();
+    for (xml, expected) in [
+        // a span
+        ("<doc>\n  <a></b>\n</doc>", line_column(2, 8, 8)),
+        // a single position
+        ("<doc>\n  é &amp\n</doc>", line_column(2, 5, 6)),
+        ("<!-- only -->\n", line_column(2, 1, 1)),
+    ] {
+        let error = xot.parse(xml).unwrap_err();
+        let index = SourceIndex::new(xml);
+        assert_eq!(index.line_column(error.span().start), expected, "{}", xml);
+    }
+}
+
+#[test]
+fn test_lenient_source_index() {
+    let mut xot = Xot::new();
+    let (_, span_info, errors) = xot.parse_lenient("<doc>\n  <a>\n  <b>\n</doc>");
+    let positions = errors
+        .iter()
+        .map(|error| span_info.source_index().line_column(error.span().start))
+        .collect::<Vec<_>>();
+    assert_eq!(positions, vec![line_column(2, 4, 4), line_column(3, 4, 4)]);
+}
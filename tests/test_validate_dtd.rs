@@ -0,0 +1,193 @@
+use xot::dtd::{ContentModel, ValidationErrorKind};
+use xot::{Span, Xot};
+
+fn kinds(xml: &str) -> Vec<ValidationErrorKind> {
+    let mut xot = Xot::new();
+    let root = xot.parse(xml).unwrap();
+    xot.validate_dtd(root)
+        .into_iter()
+        .map(|error| error.kind().clone())
+        .collect()
+}
+
+const DTD: &str = r#"<!DOCTYPE doc [
+<!ELEMENT doc (head, (p | list)*)>
+<!ELEMENT head (#PCDATA)>
+<!ELEMENT p (#PCDATA | em)*>
+<!ELEMENT em (#PCDATA)>
+<!ELEMENT list (item+)>
+<!ELEMENT item ANY>
+<!ELEMENT br EMPTY>
+<!ATTLIST doc version CDATA #FIXED "1.0">
+<!ATTLIST list
+  id ID #IMPLIED
+  style (bullet | number) "bullet"
+  classes NMTOKENS #IMPLIED>
+<!ATTLIST item ref IDREF #IMPLIED refs IDREFS #IMPLIED>
+<!ATTLIST em level NMTOKEN #REQUIRED>
+]>"#;
+
+#[test]
+fn test_valid() {
+    let xml = format!(
+        r#"{}<doc>
+  <head>Title</head>
+  <p>Some <em level="1">text</em><!-- comment --></p>
+  <list id="l1" style="number" classes=" a  b ">
+    <item><br/>text<p/></item>
+    <item ref="l1" refs="l1 l2"/>
+  </list>
+  <list id="l2"><item/></list>
+</doc>"#,
+        DTD
+    );
+    assert_eq!(kinds(&xml), vec![]);
+}
+
+#[test]
+fn test_no_dtd() {
+    assert_eq!(kinds("<doc/>"), vec![ValidationErrorKind::NoDtd]);
+}
+
+#[test]
+fn test_root_element_mismatch() {
+    assert_eq!(
+        kinds(r#"<!DOCTYPE doc [<!ELEMENT other EMPTY>]><other/>"#),
+        vec![ValidationErrorKind::RootElementMismatch(
+            "doc".to_string(),
+            "other".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_undeclared_element() {
+    let xml = format!("{}<doc><head/><section/></doc>", DTD);
+    assert_eq!(
+        kinds(&xml),
+        vec![
+            ValidationErrorKind::InvalidContent("doc".to_string(), xot_content_model(&xml, "doc")),
+            ValidationErrorKind::UndeclaredElement("section".to_string()),
+        ]
+    );
+}
+
+fn xot_content_model(xml: &str, name: &str) -> ContentModel {
+    let mut xot = Xot::new();
+    let root = xot.parse(xml).unwrap();
+    xot.dtd(root).unwrap().content_model(name).unwrap().clone()
+}
+
+#[test]
+fn test_element_content() {
+    let xml = format!("{}<doc><p/><head/></doc>", DTD);
+    let errors = kinds(&xml);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].to_string(),
+        "Content of element doc doesn't match (head,(p|list)*)"
+    );
+    // text in element content
+    let xml = format!("{}<doc><head/> text </doc>", DTD);
+    assert!(matches!(
+        &kinds(&xml)[..],
+        [ValidationErrorKind::InvalidContent(name, _)] if name == "doc"
+    ));
+    // one or more
+    let xml = format!("{}<doc><head/><list/></doc>", DTD);
+    assert!(matches!(
+        &kinds(&xml)[..],
+        [ValidationErrorKind::InvalidContent(name, _)] if name == "list"
+    ));
+}
+
+#[test]
+fn test_mixed_content() {
+    let xml = format!("{}<doc><head>text<em level='1'/></head></doc>", DTD);
+    assert!(matches!(
+        &kinds(&xml)[..],
+        [ValidationErrorKind::InvalidContent(name, ContentModel::Mixed(names))] if name == "head" && names.is_empty()
+    ));
+}
+
+#[test]
+fn test_empty_content() {
+    let xml = format!(
+        "{}<doc><head/><list><item><br> </br></item></list></doc>",
+        DTD
+    );
+    assert!(matches!(
+        &kinds(&xml)[..],
+        [ValidationErrorKind::InvalidContent(name, ContentModel::Empty)] if name == "br"
+    ));
+}
+
+#[test]
+fn test_attributes() {
+    let xml = format!(
+        r#"{}<doc version="2.0" extra="x"><head/><p><em/></p><list style="square" classes="a,b"/></doc>"#,
+        DTD
+    );
+    assert_eq!(
+        kinds(&xml),
+        vec![
+            ValidationErrorKind::InvalidAttributeValue("version".to_string(), "2.0".to_string()),
+            ValidationErrorKind::UndeclaredAttribute("doc".to_string(), "extra".to_string()),
+            ValidationErrorKind::MissingAttribute("em".to_string(), "level".to_string()),
+            ValidationErrorKind::InvalidContent(
+                "list".to_string(),
+                xot_content_model(&xml, "list")
+            ),
+            ValidationErrorKind::InvalidAttributeValue("style".to_string(), "square".to_string()),
+            ValidationErrorKind::InvalidAttributeValue("classes".to_string(), "a,b".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_ids() {
+    let xml = format!(
+        r#"{}<doc><head/><list id="a"><item ref="b" refs="a c"/></list><list id="a"><item/></list></doc>"#,
+        DTD
+    );
+    assert_eq!(
+        kinds(&xml),
+        vec![
+            ValidationErrorKind::DuplicateId("a".to_string()),
+            ValidationErrorKind::UnknownIdRef("b".to_string()),
+            ValidationErrorKind::UnknownIdRef("c".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_prefixed_names() {
+    let xml = r#"<!DOCTYPE x:doc [
+<!ELEMENT x:doc (x:item*)>
+<!ELEMENT x:item EMPTY>
+<!ATTLIST x:doc xmlns:x CDATA #FIXED "urn:x" x:a CDATA #REQUIRED>
+]><x:doc x:a="A"><x:item/></x:doc>"#;
+    assert_eq!(kinds(xml), vec![]);
+}
+
+#[test]
+fn test_span_info() {
+    let mut xot = Xot::new();
+    let xml = format!(
+        r#"{}<doc><head/><list style="square"><item/></list><br/></doc>"#,
+        DTD
+    );
+    let (root, span_info) = xot.parse_with_span_info(&xml).unwrap();
+    let errors = xot.validate_dtd_with_span_info(root, &span_info);
+    assert_eq!(errors.len(), 2);
+    let start = DTD.len();
+    // the document element, as a br isn't allowed in its content
+    assert_eq!(errors[0].node(), xot.document_element(root).unwrap());
+    assert_eq!(errors[0].span(), Some(Span::new(start + 1, start + 4)));
+    // the attribute value
+    assert!(xot.is_attribute_node(errors[1].node()));
+    assert_eq!(errors[1].span(), Some(Span::new(start + 25, start + 31)));
+    // without span info
+    let errors = xot.validate_dtd(root);
+    assert_eq!(errors[0].span(), None);
+}
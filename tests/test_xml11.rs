@@ -0,0 +1,217 @@
+use xot::{output, ParseError, XmlVersion, Xot};
+
+const DECLARATION: &str = "<?xml version=\"1.1\"?>";
+
+fn parse11(xml: &str) -> Result<(Xot, xot::Node), ParseError> {
+    let mut xot = Xot::new();
+    let root = xot.parse(&format!("{}{}", DECLARATION, xml))?;
+    Ok((xot, root))
+}
+
+fn text(xml: &str) -> String {
+    let (xot, root) = parse11(xml).unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    xot.text_content_str(doc_el).unwrap().to_string()
+}
+
+#[test]
+fn test_version() {
+    let (xot, root) = parse11("<doc/>").unwrap();
+    let declaration = xot.xml_declaration(root).unwrap();
+    assert_eq!(declaration.version(), "1.1");
+    assert_eq!(declaration.xml_version(), XmlVersion::Xml11);
+    assert_eq!(declaration.declaration().version, XmlVersion::Xml11);
+}
+
+#[test]
+fn test_line_ends() {
+    assert_eq!(
+        text("<doc>a\u{85}b\u{2028}c\r\u{85}d\r\ne</doc>"),
+        "a\nb\nc\nd\ne"
+    );
+    let (mut xot, root) = parse11("<doc a='x\u{85}y\u{2028}z'/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.add_name("a");
+    assert_eq!(xot.get_attribute(doc_el, a), Some("x y z"));
+
+    // these aren't line endings in XML 1.0
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc>a\u{85}b\u{2028}c</doc>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("a\u{85}b\u{2028}c"));
+}
+
+#[test]
+fn test_line_ends_in_entity_value() {
+    let xml = "<!DOCTYPE doc [<!ENTITY e 'a\u{85}b'>]><doc>&e;</doc>";
+    assert_eq!(text(xml), "a\nb");
+}
+
+#[test]
+fn test_control_character_references() {
+    assert_eq!(text("<doc>&#x1;&#31;&#x7F;</doc>"), "\u{1}\u{1f}\u{7f}");
+    let (mut xot, root) = parse11("<doc a='&#x2;'/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.add_name("a");
+    assert_eq!(xot.get_attribute(doc_el, a), Some("\u{2}"));
+
+    // there is no way to refer to NUL
+    assert!(matches!(
+        parse11("<doc>&#0;</doc>"),
+        Err(ParseError::InvalidEntity(..))
+    ));
+    // nor to control characters in XML 1.0
+    let mut xot = Xot::new();
+    let xml = "<doc>&#x1;</doc>";
+    match xot.parse(xml) {
+        Err(ParseError::InvalidEntity(_, span)) => assert_eq!(&xml[span.range()], "&#x1;"),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_restricted_characters() {
+    let xml = format!("{}<doc>a\u{80}</doc>", DECLARATION);
+    let mut xot = Xot::new();
+    let error = xot.parse(&xml).unwrap_err();
+    match &error {
+        ParseError::RestrictedChar(c, span) => {
+            assert_eq!(*c, '\u{80}');
+            assert_eq!(&xml[span.range()], "\u{80}");
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+    let diagnostic = error.diagnostic(&xml);
+    assert_eq!(
+        diagnostic.message,
+        "control character '\\u{80}' is not allowed"
+    );
+    assert!(matches!(
+        parse11("<doc a='\u{9f}'/>"),
+        Err(ParseError::RestrictedChar('\u{9f}', _))
+    ));
+    assert!(matches!(
+        parse11("<doc><!--\u{7f}--></doc>"),
+        Err(ParseError::RestrictedChar('\u{7f}', _))
+    ));
+    // these are fine in XML 1.0
+    let mut xot = Xot::new();
+    assert!(xot.parse("<doc a='\u{9f}'>\u{80}</doc>").is_ok());
+}
+
+#[test]
+fn test_restricted_characters_from_entity() {
+    let xml = "<!DOCTYPE doc [<!ENTITY e '&#x80;&#x1;'>]><doc>&e;</doc>";
+    assert_eq!(text(xml), "\u{80}\u{1}");
+}
+
+#[test]
+fn test_names() {
+    let (xot, root) = parse11("<\u{1f600} \u{3b1}\u{b7}='1'/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(
+        xot.local_name_str(xot.node_name(doc_el).unwrap()),
+        "\u{1f600}"
+    );
+}
+
+#[test]
+fn test_prefix_undeclaration() {
+    let (xot, root) = parse11("<a xmlns:p='urn:p'><p:b><c xmlns:p=''><d/></c></p:b></a>").unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<a xmlns:p="urn:p"><p:b><c><d/></c></p:b></a>"#
+    );
+    let error = parse11("<a xmlns:p='urn:p'><b xmlns:p=''><p:c/></b></a>").unwrap_err();
+    assert!(matches!(error, ParseError::UnknownPrefix(prefix, _) if prefix == "p"));
+    let error = parse11("<a xmlns:p='urn:p'><b xmlns:p='' p:x='1'/></a>").unwrap_err();
+    assert!(matches!(error, ParseError::UnknownPrefix(prefix, _) if prefix == "p"));
+
+    // this isn't allowed in XML 1.0
+    let mut xot = Xot::new();
+    let xml = "<a xmlns:p='urn:p'><b xmlns:p=''/></a>";
+    match xot.parse(xml) {
+        Err(ParseError::PrefixUndeclaration(prefix, span)) => {
+            assert_eq!(prefix, "p");
+            assert_eq!(&xml[span.range()], "xmlns:p");
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_serialize() {
+    let xml =
+        "<a xmlns:p=\"urn:p\" x=\"&#x1;\">&#x7F;\u{85}<p:b><c xmlns:p=\"\">&#x1;</c></p:b></a>";
+    let mut xot = Xot::new();
+    let root = xot.parse(&format!("{}{}", DECLARATION, xml)).unwrap();
+    let parameters = xot.xml_parameters(root);
+    assert_eq!(
+        xot.serialize_xml_string(parameters, root).unwrap(),
+        format!(
+            "{}\n{}",
+            DECLARATION,
+            "<a xmlns:p=\"urn:p\" x=\"&#x1;\">&#x7F;\n<p:b><c xmlns:p=\"\">&#x1;</c></p:b></a>"
+        )
+    );
+    // in XML 1.0 prefixes can't be undeclared
+    assert_eq!(
+        xot.serialize_xml_string(output::xml::Parameters::default(), root)
+            .unwrap(),
+        "<a xmlns:p=\"urn:p\" x=\"\u{1}\">\u{7f}\n<p:b><c>\u{1}</c></p:b></a>"
+    );
+}
+
+#[test]
+fn test_serialize_cdata() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse("<?xml version='1.1'?><doc>a&#x1;b</doc>")
+        .unwrap();
+    let doc = xot.add_name("doc");
+    let parameters = output::xml::Parameters {
+        cdata_section_elements: vec![doc],
+        ..xot.xml_parameters(root)
+    };
+    assert_eq!(
+        xot.serialize_xml_string(parameters, root).unwrap(),
+        format!(
+            "{}\n<doc><![CDATA[a]]>&#x1;<![CDATA[b]]></doc>",
+            DECLARATION
+        )
+    );
+}
+
+#[test]
+fn test_serialize_line_ends() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    xot.append_text(doc_el, "a\u{85}b\u{2028}c").unwrap();
+    let parameters = output::xml::Parameters {
+        declaration: Some(output::xml::Declaration {
+            version: XmlVersion::Xml11,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let xml = xot.serialize_xml_string(parameters, root).unwrap();
+    assert_eq!(
+        xml,
+        format!("{}\n<doc>a&#x85;b&#x2028;c</doc>", DECLARATION)
+    );
+    let root = xot.parse(&xml).unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("a\u{85}b\u{2028}c"));
+}
+
+#[test]
+fn test_parse_lenient() {
+    let mut xot = Xot::new();
+    let xml = format!("{}<doc>\u{80}</doc>", DECLARATION);
+    let (_, _, errors) = xot.parse_lenient(&xml);
+    assert!(matches!(
+        errors[..],
+        [ParseError::RestrictedChar('\u{80}', _)]
+    ));
+}
@@ -0,0 +1,196 @@
+use xot::xmlname::{CreateName, OwnedName};
+use xot::{output, Error, XmlVersion, Xot};
+
+#[test]
+fn test_try_add_name() {
+    let mut xot = Xot::new();
+    let a = xot.try_add_name("a-b.c").unwrap();
+    assert_eq!(xot.add_name("a-b.c"), a);
+    for name in ["", "a b", "p:a", "1a", "a\u{0}"] {
+        assert!(
+            matches!(xot.try_add_name(name), Err(Error::InvalidNcName(n)) if n == name),
+            "{:?}",
+            name
+        );
+    }
+    let ns = xot.add_namespace("urn:a");
+    assert!(xot.try_add_name_ns("\u{e9}", ns).is_ok());
+    assert!(matches!(
+        xot.try_add_name_ns("a:b", ns),
+        Err(Error::InvalidNcName(_))
+    ));
+}
+
+#[test]
+fn test_try_add_prefix() {
+    let mut xot = Xot::new();
+    assert_eq!(xot.try_add_prefix("").unwrap(), xot.empty_prefix());
+    assert!(xot.try_add_prefix("p").is_ok());
+    assert!(matches!(
+        xot.try_add_prefix("p q"),
+        Err(Error::InvalidNcName(_))
+    ));
+}
+
+#[test]
+fn test_try_new_text() {
+    let mut xot = Xot::new();
+    assert!(xot.try_new_text("a\tb\u{10FFFF}").is_ok());
+    for (text, c) in [
+        ("a\u{0}", '\u{0}'),
+        ("\u{1b}", '\u{1b}'),
+        ("\u{FFFF}", '\u{FFFF}'),
+    ] {
+        assert!(matches!(
+            xot.try_new_text(text),
+            Err(Error::InvalidCharacter(invalid)) if invalid == c
+        ));
+    }
+}
+
+#[test]
+fn test_try_set_attribute() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.add_name("a");
+    xot.try_set_attribute(doc_el, a, "1").unwrap();
+    assert!(matches!(
+        xot.try_set_attribute(doc_el, a, "\u{0}"),
+        Err(Error::InvalidCharacter('\u{0}'))
+    ));
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="1"/>"#);
+}
+
+#[test]
+fn test_text_try_set() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc>a</doc>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let text = xot.text_mut(xot.first_child(doc_el).unwrap()).unwrap();
+    assert!(matches!(
+        text.try_set("\u{8}"),
+        Err(Error::InvalidCharacter('\u{8}'))
+    ));
+    text.try_set("b").unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>b</doc>");
+}
+
+#[test]
+fn test_create_name() {
+    let mut xot = Xot::new();
+    let ns = xot.add_namespace("urn:p");
+    let lookup_ns = |prefix: &str| (prefix == "p" || prefix.is_empty()).then_some(ns);
+    assert!(CreateName::prefixed(&mut xot, "p", "a", lookup_ns).is_ok());
+    assert!(CreateName::prefixed(&mut xot, "", "a", lookup_ns).is_ok());
+    assert!(matches!(
+        CreateName::prefixed(&mut xot, "p", "a b", lookup_ns),
+        Err(Error::InvalidNcName(name)) if name == "a b"
+    ));
+    assert!(matches!(
+        CreateName::prefixed(&mut xot, "p q", "a", lookup_ns),
+        Err(Error::InvalidNcName(name)) if name == "p q"
+    ));
+    assert!(matches!(
+        CreateName::prefixed(&mut xot, "x", "a", lookup_ns),
+        Err(Error::UnknownPrefix(_))
+    ));
+    assert!(CreateName::parse_full_name(&mut xot, "p:a", lookup_ns).is_ok());
+    assert!(matches!(
+        CreateName::parse_full_name(&mut xot, "p:a:b", lookup_ns),
+        Err(Error::InvalidQName(name)) if name == "p:a:b"
+    ));
+}
+
+#[test]
+fn test_owned_name() {
+    let lookup_ns =
+        |prefix: &str| (prefix == "p" || prefix.is_empty()).then(|| "urn:p".to_string());
+    assert!(OwnedName::prefixed("p", "a", lookup_ns).is_ok());
+    assert!(OwnedName::prefixed("", "a", lookup_ns).is_ok());
+    assert!(matches!(
+        OwnedName::prefixed("p", "a b", lookup_ns),
+        Err(Error::InvalidNcName(name)) if name == "a b"
+    ));
+    assert!(matches!(
+        OwnedName::prefixed("p q", "a", lookup_ns),
+        Err(Error::InvalidNcName(name)) if name == "p q"
+    ));
+    assert!(OwnedName::parse_full_name("p:a", lookup_ns).is_ok());
+    assert!(matches!(
+        OwnedName::parse_full_name("p:a:b", lookup_ns),
+        Err(Error::InvalidQName(name)) if name == "p:a:b"
+    ));
+}
+
+fn checked() -> output::xml::Parameters {
+    output::xml::Parameters {
+        check_characters: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_serialize_check_characters() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc a='1'>text<!--c--></doc>").unwrap();
+    assert!(xot.serialize_xml_string(checked(), root).is_ok());
+
+    let doc_el = xot.document_element(root).unwrap();
+    xot.append_text(doc_el, "\u{0}").unwrap();
+    // by default it's written anyway
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc a=\"1\">text<!--c-->\u{0}</doc>"
+    );
+    assert!(matches!(
+        xot.serialize_xml_string(checked(), root),
+        Err(Error::InvalidCharacter('\u{0}'))
+    ));
+}
+
+#[test]
+fn test_serialize_check_characters_attribute_and_comment() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.add_name("a");
+    xot.set_attribute(doc_el, a, "\u{1}");
+    assert!(matches!(
+        xot.serialize_xml_string(checked(), root),
+        Err(Error::InvalidCharacter('\u{1}'))
+    ));
+    xot.remove_attribute(doc_el, a);
+    let comment = xot.new_comment("\u{FFFE}");
+    xot.append(doc_el, comment).unwrap();
+    let mut buf = Vec::new();
+    assert!(matches!(
+        xot.serialize_xml_write(checked(), root, &mut buf),
+        Err(Error::InvalidCharacter('\u{FFFE}'))
+    ));
+}
+
+#[test]
+fn test_serialize_check_characters_xml11() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    xot.append_text(doc_el, "\u{1}").unwrap();
+    let parameters = output::xml::Parameters {
+        declaration: Some(output::xml::Declaration {
+            version: XmlVersion::Xml11,
+            ..Default::default()
+        }),
+        ..checked()
+    };
+    assert_eq!(
+        xot.serialize_xml_string(parameters.clone(), root).unwrap(),
+        "<?xml version=\"1.1\"?>\n<doc>&#x1;</doc>"
+    );
+    let comment = xot.new_comment("\u{1}");
+    xot.append(doc_el, comment).unwrap();
+    assert!(matches!(
+        xot.serialize_xml_string(parameters, root),
+        Err(Error::InvalidCharacter('\u{1}'))
+    ));
+}